@@ -1,7 +1,16 @@
 use clap::{App, Arg};
-use easy_fs::{BlockDevice, EasyFileSystem};
+use easy_fs::{block_cache_sync_all, BlockDevice, EasyFileSystem};
+#[cfg(test)]
+use easy_fs::{
+    block_cache_discard_all, block_cache_release, block_cache_stats, block_cached, Inode,
+    BLOCK_CACHE_SIZE, MAX_FILE_SIZE, MAX_SYMLINK_FOLLOWS, MODE_EXEC, MODE_READ, MODE_WRITE,
+    READ_AHEAD_BLOCKS,
+};
 use std::fs::{read_dir, File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
+#[cfg(test)]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -9,12 +18,18 @@ use std::sync::Mutex;
 const BLOCK_SZ: usize = 512;
 const BLOCK_NUM: usize = 131072; //64*2048
 
+/// Number of blocks read from any BlockFile
+static READ_BLOCKS: AtomicUsize = AtomicUsize::new(0);
+/// Number of blocks written to any BlockFile
+static WRITTEN_BLOCKS: AtomicUsize = AtomicUsize::new(0);
+
 /// Wrapper for turning a File into a BlockDevice
 struct BlockFile(Mutex<File>);
 
 impl BlockDevice for BlockFile {
     /// Read a block from file
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        READ_BLOCKS.fetch_add(1, Ordering::Relaxed);
         let mut file = self.0.lock().unwrap();
         file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
             .expect("Error when seeking!");
@@ -22,6 +37,7 @@ impl BlockDevice for BlockFile {
     }
     /// Write a block into file
     fn write_block(&self, block_id: usize, buf: &[u8]) {
+        WRITTEN_BLOCKS.fetch_add(1, Ordering::Relaxed);
         let mut file = self.0.lock().unwrap();
         file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
             .expect("Error when seeking!");
@@ -29,6 +45,65 @@ impl BlockDevice for BlockFile {
     }
 }
 
+/// A BlockFile that crashes right after the journal header at `crash_at` is written,
+/// losing every write from then on until it is rebooted
+#[cfg(test)]
+struct CrashFile {
+    file: BlockFile,
+    crash_at: AtomicUsize,
+    crashed: AtomicBool,
+}
+
+#[cfg(test)]
+impl BlockDevice for CrashFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.file.read_block(block_id, buf);
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        if self.crashed.load(Ordering::Relaxed) {
+            return;
+        }
+        self.file.write_block(block_id, buf);
+        if block_id == self.crash_at.load(Ordering::Relaxed) {
+            self.crashed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A BlockDevice kept in memory
+struct MemDevice(Mutex<Vec<u8>>);
+
+impl BlockDevice for MemDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let data = self.0.lock().unwrap();
+        buf.copy_from_slice(&data[block_id * BLOCK_SZ..(block_id + 1) * BLOCK_SZ]);
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut data = self.0.lock().unwrap();
+        data[block_id * BLOCK_SZ..(block_id + 1) * BLOCK_SZ].copy_from_slice(buf);
+    }
+}
+
+/// Blocks of the easy-fs image packed as `mount.img`
+const MOUNT_IMG_BLOCKS: usize = 2048;
+/// What `hello` holds in `mount.img`
+const MOUNT_IMG_HELLO: &[u8] = b"hello from mount.img\n";
+
+/// The image of a small easy-fs holding `hello`, to be mounted on a directory
+fn mount_image() -> Vec<u8> {
+    let device = Arc::new(MemDevice(Mutex::new(vec![
+        0u8;
+        MOUNT_IMG_BLOCKS * BLOCK_SZ
+    ])));
+    let efs = EasyFileSystem::create(device.clone(), MOUNT_IMG_BLOCKS as u32, 1);
+    EasyFileSystem::root_inode(&efs)
+        .create_with_data("hello", MOUNT_IMG_HELLO)
+        .unwrap();
+    block_cache_sync_all();
+    let image = device.0.lock().unwrap().clone();
+    image
+}
+
 fn main() {
     easy_fs_pack().expect("Error when packing easy-fs!");
 }
@@ -64,7 +139,7 @@ fn easy_fs_pack() -> std::io::Result<()> {
         f
     })));
     let efs = EasyFileSystem::create(block_file.clone(), BLOCK_NUM as u32, 1);
-    let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
+    let root_inode = EasyFileSystem::root_inode(&efs);
     let apps: Vec<_> = read_dir(src_path)
         .unwrap()
         .into_iter()
@@ -83,7 +158,25 @@ fn easy_fs_pack() -> std::io::Result<()> {
         let inode = root_inode.create(app.as_str()).unwrap();
         // write data to easy-fs
         inode.write_at(0, all_data.as_slice());
+        // apps are executables
+        inode.chmod(0o755);
     }
+    // an image to mount on `mnt`
+    root_inode.mkdir("mnt").unwrap();
+    // a directory to work in, such as with chdir
+    root_inode.mkdir("home").unwrap();
+    // a directory to confine a process to with chroot
+    root_inode.mkdir("jail").unwrap();
+    // a directory to set a quota on
+    root_inode.mkdir("quota").unwrap();
+    // two directories to swap, below one more
+    let swap = root_inode.mkdir("swap").unwrap();
+    swap.mkdir("a").unwrap();
+    swap.mkdir("b").unwrap();
+    let image = root_inode.create("mount.img").unwrap();
+    image.write_at(0, &mount_image());
+    // nothing is left in the cache once the packer exits
+    block_cache_sync_all();
     // list apps
     for app in root_inode.ls() {
         println!("{}", app);
@@ -99,7 +192,7 @@ fn efs_test() -> std::io::Result<()> {
             .write(true)
             .create(true)
             .open("target/fs.img")?;
-        f.set_len(BLOCK_NUM * BLOCK_SZ).unwrap();
+        f.set_len((BLOCK_NUM * BLOCK_SZ) as u64).unwrap();
         f
     })));
     EasyFileSystem::create(block_file.clone(), 4096, 1);
@@ -110,6 +203,120 @@ fn efs_test() -> std::io::Result<()> {
     for name in root_inode.ls() {
         println!("{}", name);
     }
+
+    // directory slots
+    let dir_blocks = || root_inode.read_disk_inode(|disk_inode| disk_inode.data_blocks());
+    let remove = |name: &str| {
+        let (success, clear_inode) = root_inode.unlink(name);
+        assert!(success);
+        clear_inode.unwrap().free();
+    };
+    // 102 dirents of 32 bytes take 7 blocks
+    for i in 0..100 {
+        root_inode.create(&format!("file{}", i)).unwrap();
+    }
+    assert_eq!(dir_blocks(), 7);
+    let dir_size = root_inode.read_disk_inode(|disk_inode| disk_inode.size);
+    // removed entries leave tombstones, the others stay where they are
+    for i in 10..100 {
+        remove(&format!("file{}", i));
+    }
+    assert_eq!(dir_blocks(), 7);
+    assert_eq!(root_inode.ls().len(), 12);
+    // which new ones take before the directory grows
+    for i in 10..100 {
+        root_inode.create(&format!("file{}", i)).unwrap();
+    }
+    assert_eq!(dir_blocks(), 7);
+    // removing the tail gives the blocks back
+    for i in 0..40 {
+        root_inode.create(&format!("zz{}", i)).unwrap();
+    }
+    assert_eq!(dir_blocks(), 9);
+    for i in 0..40 {
+        remove(&format!("zz{}", i));
+    }
+    assert_eq!(dir_blocks(), 7);
+    for i in 10..100 {
+        remove(&format!("file{}", i));
+    }
+    // new ones go in their place by name, taking a tombstone near it
+    for i in 3..6 {
+        remove(&format!("file{}", i));
+    }
+    assert_eq!(
+        root_inode.read_disk_inode(|disk_inode| disk_inode.size),
+        dir_size
+    );
+    root_inode.create("new0").unwrap();
+    root_inode.create("file10").unwrap();
+    assert_eq!(
+        root_inode.read_disk_inode(|disk_inode| disk_inode.size),
+        dir_size
+    );
+    assert_eq!(
+        root_inode.ls(),
+        [
+            "file0", "file1", "file10", "file2", "file6", "file7", "file8", "file9", "filea",
+            "fileb", "new0"
+        ]
+    );
+    assert!(root_inode.find("file3").is_none());
+    assert!(root_inode.find("file10").is_some());
+    // one inode in memory for a file however it is looked up, dropped once unused
+    let found = root_inode.find("file10").unwrap();
+    assert!(Arc::ptr_eq(&found, &root_inode.find("file10").unwrap()));
+    assert!(Arc::ptr_eq(&found, &root_inode.find_path("/file10").unwrap()));
+    assert!(Arc::ptr_eq(&root_inode, &root_inode.find("..").unwrap()));
+    let unused = Arc::downgrade(&found);
+    drop(found);
+    root_inode.create("file11").unwrap();
+    assert!(unused.upgrade().is_none());
+    assert!(root_inode.unlink("file11").0);
+
+    // defrag
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let probe_blocks = |inode: &Inode| {
+        inode.read_disk_inode(|disk_inode| {
+            (0..disk_inode.data_blocks())
+                .map(|i| disk_inode.get_block_id(i, &device))
+                .collect::<Vec<_>>()
+        })
+    };
+    let contiguous = |blocks: &[u32]| blocks.windows(2).all(|w| w[1] == w[0] + 1);
+    // punch 3-block holes all over the data area
+    let frag_data = |i: usize| vec![i as u8 + 1; 3 * BLOCK_SZ];
+    for i in 0..20 {
+        let inode = root_inode.create(&format!("frag{}", i)).unwrap();
+        inode.write_at(0, &frag_data(i));
+    }
+    for i in (0..20).step_by(2) {
+        remove(&format!("frag{}", i));
+    }
+    // a 16-block file, small enough for the direct blocks, grown a block at a time
+    // gets scattered over the holes
+    let grow_probe = || {
+        let probe = root_inode.create("probe").unwrap();
+        for i in 0..16 {
+            probe.write_at(i * BLOCK_SZ, &[0xffu8; BLOCK_SZ]);
+        }
+        probe
+    };
+    let probe = grow_probe();
+    assert!(!contiguous(&probe_blocks(&probe)));
+    remove("probe");
+    assert!(root_inode.defrag() > 0);
+    let probe = grow_probe();
+    assert!(contiguous(&probe_blocks(&probe)));
+    // nothing is lost on the way
+    for i in (1..20).step_by(2) {
+        let inode = root_inode.find(&format!("frag{}", i)).unwrap();
+        let mut buf = vec![0u8; 4 * BLOCK_SZ];
+        assert_eq!(inode.read_at(0, &mut buf), 3 * BLOCK_SZ);
+        assert_eq!(buf[..3 * BLOCK_SZ], frag_data(i));
+    }
+    remove("probe");
+
     let filea = root_inode.find("filea").unwrap();
     let greet_str = "Hello, world!";
     filea.write_at(0, greet_str.as_bytes());
@@ -151,5 +358,1383 @@ fn efs_test() -> std::io::Result<()> {
     random_str_test(1000 * BLOCK_SZ);
     random_str_test(2000 * BLOCK_SZ);
 
+    // defrag moves indirect blocks as well
+    let read_all = |inode: &Inode| {
+        let size = inode.read_disk_inode(|disk_inode| disk_inode.size) as usize;
+        let mut buf = vec![0u8; size];
+        assert_eq!(inode.read_at(0, &mut buf), size);
+        buf
+    };
+    let content = read_all(&filea);
+    for i in (1..20).step_by(2) {
+        remove(&format!("frag{}", i));
+    }
+    assert!(root_inode.defrag() > 0);
+    assert_eq!(read_all(&filea), content);
+
+    // pinned inodes
+    let size_of = |inode: &Inode| inode.read_disk_inode(|disk_inode| disk_inode.size);
+    let thrash = || {
+        filea.read_at(0, &mut vec![0u8; 64 * BLOCK_SZ]);
+    };
+    // not sharing its inode block with filea, which stays in the cache while being read
+    let pinned = root_inode.create("pinned").unwrap();
+    assert!(pinned.pin());
+    thrash();
+    let reads = READ_BLOCKS.load(Ordering::Relaxed);
+    assert_eq!(size_of(&pinned), 0);
+    assert_eq!(READ_BLOCKS.load(Ordering::Relaxed), reads);
+    // once unpinned, it gets substituted like any other block
+    pinned.unpin();
+    thrash();
+    let reads = READ_BLOCKS.load(Ordering::Relaxed);
+    size_of(&pinned);
+    assert_eq!(READ_BLOCKS.load(Ordering::Relaxed), reads + 1);
+
+    // snapshots
+    let free_blocks = || efs.lock().free_data_blocks();
+    let snapped = root_inode.create("snapped").unwrap();
+    snapped.write_at(0, &[b'a'; 3 * BLOCK_SZ]);
+    let free_before = free_blocks();
+    let snapshot = snapped.snapshot();
+    // the first write to a block held by the snapshot gets a new block, later ones do not
+    snapped.write_at(BLOCK_SZ, &[b'b'; 10]);
+    snapped.write_at(BLOCK_SZ + 10, &[b'b'; 10]);
+    assert_eq!(free_blocks(), free_before - 1);
+    snapped.clear();
+    snapped.write_at(0, &[b'c'; 5 * BLOCK_SZ]);
+    let mut buf = vec![0u8; 4 * BLOCK_SZ];
+    assert_eq!(snapshot.read_at(0, &mut buf), 3 * BLOCK_SZ);
+    assert!(buf[..3 * BLOCK_SZ].iter().all(|&b| b == b'a'));
+    assert_eq!(snapshot.read_at(BLOCK_SZ + 100, &mut buf[..10]), 10);
+    assert_eq!(buf[..10], [b'a'; 10]);
+    assert_eq!(snapped.read_at(0, &mut buf), 4 * BLOCK_SZ);
+    assert!(buf.iter().all(|&b| b == b'c'));
+    // blocks only the snapshot held are freed with it
+    drop(snapshot);
+    assert_eq!(free_blocks(), free_before - 2);
+    remove("snapped");
+
+    // draining the head moves the rest to the front in place
+    let drained = root_inode.create("drained").unwrap();
+    let data: Vec<u8> = (0..3 * BLOCK_SZ + 100).map(|i| (i % 251) as u8).collect();
+    drained.write_at(0, &data);
+    let snapshot = drained.snapshot();
+    let free_before = free_blocks();
+    let mut head = [0u8; BLOCK_SZ + 10];
+    assert_eq!(drained.drain(&mut head), Some(head.len()));
+    assert_eq!(head[..], data[..head.len()]);
+    assert_eq!(read_all(&drained), data[head.len()..]);
+    // the 3 blocks written are copied from the snapshot first, the last one stays with it
+    assert_eq!(free_blocks(), free_before - 3);
+    let mut buf = vec![0u8; data.len()];
+    assert_eq!(snapshot.read_at(0, &mut buf), data.len());
+    assert_eq!(buf, data);
+    drop(snapshot);
+    assert_eq!(free_blocks(), free_before + 1);
+    remove("drained");
+    // a hole at the front that can not get its blocks leaves the file as it was
+    let holed = root_inode.create("holed").unwrap();
+    holed.write_at(2 * BLOCK_SZ, b"tail");
+    assert!(root_inode.set_quota(u32::MAX));
+    let used = root_inode.quota().unwrap().used;
+    assert!(root_inode.set_quota(used));
+    let mut head = [1u8; 4];
+    assert_eq!(holed.drain(&mut head), None);
+    assert_eq!(size_of(&holed) as usize, 2 * BLOCK_SZ + 4);
+    assert!(root_inode.set_quota(0));
+    assert_eq!(holed.drain(&mut head), Some(4));
+    assert_eq!(head, [0u8; 4]);
+    assert_eq!(read_all(&holed)[2 * BLOCK_SZ - 4..], *b"tail");
+    remove("holed");
+    // copies of the blocks a snapshot holds need room in the quotas as well
+    let snapped = root_inode.create("snapped").unwrap();
+    snapped.write_at(0, &[b'a'; 2 * BLOCK_SZ]);
+    let snapshot = snapped.snapshot();
+    assert!(root_inode.set_quota(u32::MAX));
+    let used = root_inode.quota().unwrap().used;
+    assert!(root_inode.set_quota(used));
+    assert_eq!(snapped.write_at(0, b"b"), 0);
+    assert!(!snapped.truncate(BLOCK_SZ as u32 + 1));
+    assert_eq!(read_all(&snapped), [b'a'; 2 * BLOCK_SZ]);
+    assert!(root_inode.set_quota(0));
+    assert_eq!(snapped.write_at(0, b"b"), 1);
+    drop(snapshot);
+    remove("snapped");
+
+    // directory cycles
+    assert_eq!(root_inode.check_cycles(true), 0);
+    let nlink_of = |inode: &Inode| inode.read_disk_inode(|disk_inode| disk_inode.nlink);
+    // a raw dirent `name` at the end of directory `dir` to `inode`, counted as a link to it
+    let link_raw = |dir: &Inode, name: &str, inode: &Inode| {
+        let mut dirent = [0u8; 32];
+        dirent[..name.len()].copy_from_slice(name.as_bytes());
+        dirent[28..].copy_from_slice(&inode.inode_id().to_ne_bytes());
+        let dir_size = size_of(dir) as usize;
+        assert_eq!(dir.write_at(dir_size, &dirent), dirent.len());
+        inode.modify_disk_inode(|disk_inode| disk_inode.nlink += 1);
+    };
+    // "zloop" back to the root directory, at the end where the dirents sorted by name put it
+    let root_nlink = nlink_of(&root_inode);
+    let dir_size = size_of(&root_inode) as usize;
+    link_raw(&root_inode, "zloop", &root_inode);
+    assert_eq!(root_inode.check_cycles(false), 1);
+    assert!(root_inode.ls().iter().any(|name| name == "zloop"));
+    assert_eq!(root_inode.check_cycles(true), 1);
+    assert_eq!(root_inode.check_cycles(false), 0);
+    assert!(!root_inode.ls().iter().any(|name| name == "zloop"));
+    assert_eq!(size_of(&root_inode) as usize, dir_size);
+    assert_eq!(nlink_of(&root_inode), root_nlink);
+    // a second link to a directory elsewhere makes no loop and stays
+    let cyc_a = root_inode.mkdir("cyc_a").unwrap();
+    let cyc_b = root_inode.mkdir("cyc_b").unwrap();
+    link_raw(&cyc_b, "alias", &cyc_a);
+    assert_eq!(root_inode.check_cycles(true), 0);
+    assert!(root_inode.find_path("cyc_b/alias").unwrap().is_same(&cyc_a));
+    assert_eq!(nlink_of(&cyc_a), 2);
+    // one below it back up to it loops, whichever link it is reached by
+    let sub = cyc_a.mkdir("sub").unwrap();
+    link_raw(&sub, "up", &cyc_a);
+    assert_eq!(root_inode.check_cycles(false), 1);
+    assert_eq!(root_inode.check_cycles(true), 1);
+    assert_eq!(root_inode.check_cycles(false), 0);
+    assert!(root_inode.find_path("cyc_a/sub/up").is_none());
+    assert!(root_inode.find_path("cyc_b/alias/sub").is_some());
+    assert_eq!(nlink_of(&cyc_a), 2);
+    assert!(cyc_a.unlink("sub").0);
+    assert!(cyc_b.unlink("alias").0);
+    assert_eq!(nlink_of(&cyc_a), 1);
+    assert!(root_inode.unlink("cyc_a").0);
+    assert!(root_inode.unlink("cyc_b").0);
+
+    // preallocation and sealing
+    let sealed = root_inode.create("sealed").unwrap();
+    let free_before = free_blocks();
+    sealed.fallocate(10 * BLOCK_SZ);
+    assert_eq!(size_of(&sealed) as usize, 10 * BLOCK_SZ);
+    assert_eq!(free_blocks(), free_before - 10);
+    sealed.write_at(0, &[b's'; 3 * BLOCK_SZ]);
+    assert_eq!(sealed.seal_size(), 3 * BLOCK_SZ);
+    assert_eq!(free_blocks(), free_before - 3);
+    // without preallocated blocks there is nothing to cut
+    assert_eq!(sealed.seal_size(), 3 * BLOCK_SZ);
+    // writes past the written end move it, and the cut keeps partial blocks
+    sealed.fallocate(20 * BLOCK_SZ);
+    sealed.write_at(5 * BLOCK_SZ, &[b's'; 100]);
+    assert_eq!(sealed.seal_size(), 5 * BLOCK_SZ + 100);
+    assert_eq!(free_blocks(), free_before - 6);
+    let mut buf = vec![0u8; 6 * BLOCK_SZ];
+    assert_eq!(sealed.read_at(0, &mut buf), 5 * BLOCK_SZ + 100);
+    assert!(buf[3 * BLOCK_SZ..5 * BLOCK_SZ].iter().all(|&b| b == 0));
+    remove("sealed");
+    // extending backs every new block and zeroes the old bytes past the end
+    let extended = root_inode.create("extended").unwrap();
+    extended.write_at(0, &[b'e'; 300]);
+    assert!(extended.truncate(100));
+    let free_before = free_blocks();
+    assert_eq!(extended.extend(2 * BLOCK_SZ), Some(100));
+    assert_eq!(size_of(&extended) as usize, 100 + 2 * BLOCK_SZ);
+    assert_eq!(free_blocks(), free_before - 2);
+    let mut buf = vec![0xffu8; 3 * BLOCK_SZ];
+    assert_eq!(extended.read_at(0, &mut buf), 100 + 2 * BLOCK_SZ);
+    assert!(buf[..100].iter().all(|&b| b == b'e'));
+    assert!(buf[100..100 + 2 * BLOCK_SZ].iter().all(|&b| b == 0));
+    remove("extended");
+
+    // swapping contents
+    let swap_a = root_inode.create("swap_a").unwrap();
+    let swap_b = root_inode.create("swap_b").unwrap();
+    swap_a.write_at(0, &[b'a'; 30 * BLOCK_SZ]);
+    swap_b.write_at(0, b"bbb");
+    assert!(swap_a.swap_data(&swap_b));
+    let swap_a = root_inode.find("swap_a").unwrap();
+    let swap_b = root_inode.find("swap_b").unwrap();
+    assert_eq!(size_of(&swap_a), 3);
+    assert_eq!(size_of(&swap_b) as usize, 30 * BLOCK_SZ);
+    let mut buf = vec![0u8; 31 * BLOCK_SZ];
+    assert_eq!(swap_a.read_at(0, &mut buf), 3);
+    assert_eq!(&buf[..3], b"bbb");
+    assert_eq!(swap_b.read_at(0, &mut buf), 30 * BLOCK_SZ);
+    assert!(buf[..30 * BLOCK_SZ].iter().all(|&b| b == b'a'));
+    // an inode can not be swapped with itself, nor a file with a directory
+    assert!(!swap_a.swap_data(&swap_a));
+    assert!(!swap_a.swap_data(&root_inode));
+    remove("swap_a");
+    remove("swap_b");
+
+    // linking over a name
+    let link_src = root_inode.create("link_src").unwrap();
+    let link_dst = root_inode.create("link_dst").unwrap();
+    link_src.write_at(0, b"src");
+    link_dst.write_at(0, &[b'd'; 4 * BLOCK_SZ]);
+    let free_before = free_blocks();
+    let free_inodes_before = efs.lock().free_inodes();
+    let (success, clear_inode) = root_inode.link_replace("link_src", "link_dst");
+    assert!(success);
+    // the replaced inode had no other link, it and its blocks come back once freed
+    clear_inode.unwrap().free();
+    assert_eq!(free_blocks(), free_before + 4);
+    assert_eq!(efs.lock().free_inodes(), free_inodes_before + 1);
+    let link_dst = root_inode.find("link_dst").unwrap();
+    assert_eq!(link_dst.inode_id(), link_src.inode_id());
+    assert_eq!(nlink_of(&link_src), 2);
+    // linking over a name of the same inode or to a new name frees nothing
+    let (success, clear_inode) = root_inode.link_replace("link_src", "link_dst");
+    assert!(success && clear_inode.is_none());
+    let (success, clear_inode) = root_inode.link_replace("link_src", "link_new");
+    assert!(success && clear_inode.is_none());
+    assert_eq!(nlink_of(&link_src), 3);
+    assert!(!root_inode.link_replace("link_missing", "link_dst").0);
+    for name in ["link_dst", "link_new"] {
+        let (success, clear_inode) = root_inode.unlink(name);
+        assert!(success && clear_inode.is_none());
+    }
+    remove("link_src");
+
+    // appending
+    let log = root_inode.create("log").unwrap();
+    assert_eq!(log.append(b"first"), Some(0));
+    assert_eq!(log.append(&[b'x'; BLOCK_SZ]), Some(5));
+    assert_eq!(log.append(b"last"), Some(5 + BLOCK_SZ));
+    assert_eq!(size_of(&log) as usize, 9 + BLOCK_SZ);
+    let mut buf = [0u8; 4];
+    assert_eq!(log.read_at(5 + BLOCK_SZ, &mut buf), 4);
+    assert_eq!(&buf, b"last");
+    // the tail block held by a snapshot is copied before appending to it
+    let snapshot = log.snapshot();
+    log.append(b"more");
+    let mut buf = [0u8; 8];
+    assert_eq!(snapshot.read_at(5 + BLOCK_SZ, &mut buf), 4);
+    assert_eq!(&buf[..4], b"last");
+    drop(snapshot);
+    remove("log");
+    // small appends are written back together, and read back before they are
+    let log = root_inode.create("log").unwrap();
+    let written = WRITTEN_BLOCKS.load(Ordering::Relaxed);
+    for i in 0..1000 {
+        assert_eq!(log.append(&[b'a' + (i % 26) as u8]), Some(i));
+    }
+    let mut buf = [0u8; 1000];
+    assert_eq!(log.read_at(0, &mut buf), 1000);
+    assert!((0..1000).all(|i| buf[i] == b'a' + (i % 26) as u8));
+    log.sync();
+    assert!(WRITTEN_BLOCKS.load(Ordering::Relaxed) - written < 50);
+    assert_eq!(read_all(&log), buf);
+    remove("log");
+    // the count is kept for the file, not for one handle of it,
+    // and a block's worth writes back that file alone
+    let log = root_inode.create("log").unwrap();
+    let other = root_inode.create("other").unwrap();
+    assert_eq!(other.write_at(0, b"other"), 5);
+    assert_eq!(log.append(&[b'l'; 300]), Some(0));
+    drop(log);
+    efs.lock().evict_inodes();
+    let log = root_inode.find("log").unwrap();
+    assert_eq!(log.append(&[b'l'; 300]), Some(300));
+    assert_eq!(log.sync(), 0);
+    assert!(other.sync() > 0);
+    remove("log");
+    remove("other");
+
+    // ring files
+    assert!(root_inode.create_ring("ring", 0).is_none());
+    let ring = root_inode.create_ring("ring", 3000).unwrap();
+    assert!(root_inode.create_ring("ring", 3000).is_none());
+    assert!(ring.is_ring() && !root_inode.is_ring());
+    let data: Vec<u8> = (0..8000u32).map(|i| (i % 251) as u8).collect();
+    // wrap around in pieces not lined up with blocks or the capacity
+    for piece in data.chunks(700) {
+        assert_eq!(ring.ring_write(piece), piece.len());
+    }
+    assert_eq!(size_of(&ring), 3000);
+    let mut buf = vec![0u8; 4000];
+    assert_eq!(ring.ring_read(&mut buf[..1000]), 1000);
+    assert_eq!(ring.ring_read(&mut buf[1000..]), 2000);
+    assert_eq!(&buf[..3000], &data[5000..]);
+    assert_eq!(ring.ring_read(&mut buf), 0);
+    // one write longer than the ring keeps its tail
+    assert_eq!(ring.ring_write(&data), data.len());
+    assert_eq!(ring.ring_read(&mut buf), 3000);
+    assert_eq!(&buf[..3000], &data[5000..]);
+    ring.ring_write(b"stale");
+    assert!(!ring.truncate(10));
+    assert!(ring.truncate(0));
+    assert_eq!(ring.ring_read(&mut buf), 0);
+    assert_eq!(size_of(&ring), 3000);
+    remove("ring");
+
+    // compare and rename
+    root_inode.create_with_data("cas", b"free").unwrap();
+    root_inode.create("cas_taken").unwrap();
+    assert_eq!(
+        root_inode.cas_rename("cas", b"held", "cas_won"),
+        Some(false)
+    );
+    assert_eq!(
+        root_inode.cas_rename("cas", b"free!", "cas_won"),
+        Some(false)
+    );
+    assert_eq!(
+        root_inode.cas_rename("cas_missing", b"free", "cas_won"),
+        Some(false)
+    );
+    assert_eq!(root_inode.cas_rename("cas", b"free", "cas_taken"), None);
+    assert_eq!(root_inode.cas_rename("cas", b"free", "cas_won"), Some(true));
+    assert!(root_inode.find("cas").is_none());
+    assert_eq!(
+        root_inode.cas_rename("cas", b"free", "cas_again"),
+        Some(false)
+    );
+    remove("cas_won");
+    remove("cas_taken");
+
+    // unlinking the last link frees the inode and its blocks,
+    // the directory may take or give back one of its own meanwhile
+    let free_before = free_blocks() + dir_blocks();
+    let free_inodes_before = efs.lock().free_inodes();
+    let big = root_inode.create("unlink_big").unwrap();
+    assert_eq!(big.write_at(0, &[b'u'; 40 * BLOCK_SZ]), 40 * BLOCK_SZ);
+    assert!(free_blocks() + dir_blocks() < free_before - 40);
+    assert_eq!(efs.lock().free_inodes(), free_inodes_before - 1);
+    remove("unlink_big");
+    assert_eq!(free_blocks() + dir_blocks(), free_before);
+    assert_eq!(efs.lock().free_inodes(), free_inodes_before);
+    // the freed inode is handed out again
+    root_inode.create("unlink_again").unwrap();
+    assert_eq!(efs.lock().free_inodes(), free_inodes_before - 1);
+    remove("unlink_again");
+
+    // directory quotas
+    assert!(root_inode.set_quota(u32::MAX));
+    // the files already there count toward a new quota
+    let used = root_inode.quota().unwrap().used;
+    assert!(used > 0);
+    assert!(root_inode.set_quota(used + 4));
+    let quota_file = root_inode.create("quota_file").unwrap();
+    assert_eq!(quota_file.write_at(0, &[b'q'; 4 * BLOCK_SZ]), 4 * BLOCK_SZ);
+    assert_eq!(root_inode.quota().unwrap().used, used + 4);
+    // one more block goes over, and nothing is written
+    assert_eq!(quota_file.write_at(4 * BLOCK_SZ, b"over"), 0);
+    assert_eq!(size_of(&quota_file) as usize, 4 * BLOCK_SZ);
+    assert_eq!(quota_file.append(b"over"), None);
+    assert!(!quota_file.fallocate(5 * BLOCK_SZ));
+    assert_eq!(quota_file.extend(BLOCK_SZ), None);
+    assert_eq!(size_of(&quota_file) as usize, 4 * BLOCK_SZ);
+    assert!(root_inode.create_with_data("quota_more", b"x").is_none());
+    assert_eq!(root_inode.quota().unwrap().used, used + 4);
+    // the blocks already there can still be written
+    assert_eq!(quota_file.write_at(0, b"in place"), 8);
+    // freed blocks make room again
+    quota_file.clear();
+    assert_eq!(root_inode.quota().unwrap().used, used);
+    assert_eq!(quota_file.write_at(0, b"again"), 5);
+    assert!(!quota_file.set_quota(1));
+    assert!(root_inode.set_quota(0));
+    assert!(root_inode.quota().is_none());
+    remove("quota_file");
+    // a directory that can not grow over the quota keeps every name it had
+    let full = root_inode.mkdir("full").unwrap();
+    // with `.` and `..`, 16 dirents of 32 bytes fill a block
+    for i in 0..14 {
+        full.create(&format!("f{}", i)).unwrap();
+    }
+    assert_eq!(size_of(&full) as usize, BLOCK_SZ);
+    root_inode.create("outside").unwrap();
+    assert!(root_inode.set_quota(u32::MAX));
+    let used = root_inode.quota().unwrap().used;
+    assert!(root_inode.set_quota(used));
+    let free_inodes_before = efs.lock().free_inodes();
+    assert_eq!(root_inode.rename("full/f0", "full/renamed"), -1);
+    assert_eq!(root_inode.rename("outside", "full/outside"), -1);
+    assert!(root_inode.find("outside").is_some());
+    assert_eq!(full.cas_rename("f0", b"", "renamed"), None);
+    assert!(
+        !full.modify_disk_inode(|disk_inode| full.rename_dir_entry(disk_inode, "f0", "renamed"))
+    );
+    assert!(full
+        .modify_disk_inode(|disk_inode| full.copy_dir_entry(disk_inode, "f0", "copied"))
+        .is_none());
+    assert!(root_inode.link("full/f0", "full/linked").is_none());
+    assert!(!full.link_replace("f0", "replaced").0);
+    // nothing new shows up, and its inode is given back
+    assert!(full.create("new").is_none());
+    assert!(full.mkdir("new").is_none());
+    assert!(full.create_with_data("new", b"x").is_none());
+    assert!(full.create_ring("new", 8).is_none());
+    assert_eq!(efs.lock().free_inodes(), free_inodes_before);
+    assert_eq!(root_inode.quota().unwrap().used, used);
+    let names = full.ls();
+    assert_eq!(names.len(), 14);
+    assert!(names.iter().any(|name| name == "f0"));
+    assert_eq!(nlink_of(&full.find("f0").unwrap()), 1);
+    assert!(root_inode.set_quota(0));
+    for i in 0..14 {
+        let (success, clear_inode) = full.unlink(&format!("f{}", i));
+        assert!(success);
+        clear_inode.unwrap().free();
+    }
+    remove("full");
+    remove("outside");
+    // a quota on a subdirectory holds the files under it, however deep
+    let quota_dir = root_inode.mkdir("quota_dir").unwrap();
+    let inner = quota_dir.mkdir("inner").unwrap();
+    let inner_file = inner.create("inner_file").unwrap();
+    assert_eq!(inner_file.write_at(0, &[b'i'; BLOCK_SZ]), BLOCK_SZ);
+    assert!(quota_dir.set_quota(4));
+    // the dirents of `inner` and the block of `inner_file`
+    assert_eq!(quota_dir.quota().unwrap().used, 2);
+    assert_eq!(
+        inner_file.write_at(BLOCK_SZ, &[b'i'; 2 * BLOCK_SZ]),
+        2 * BLOCK_SZ
+    );
+    assert_eq!(quota_dir.quota().unwrap().used, 4);
+    assert_eq!(inner_file.write_at(3 * BLOCK_SZ, b"over"), 0);
+    assert!(quota_dir.create_with_data("more", b"x").is_none());
+    // while writes outside it go on
+    let outside = root_inode.create("quota_outside").unwrap();
+    assert_eq!(outside.write_at(0, &[b'o'; 8 * BLOCK_SZ]), 8 * BLOCK_SZ);
+    // and the quota above it counts its blocks as well
+    assert!(root_inode.set_quota(u32::MAX));
+    let root_used = root_inode.quota().unwrap().used;
+    inner_file.clear();
+    assert_eq!(quota_dir.quota().unwrap().used, 1);
+    assert_eq!(root_inode.quota().unwrap().used, root_used - 3);
+    // a file moved out takes its blocks along, one moved in must fit
+    assert_eq!(inner_file.write_at(0, &[b'i'; 2 * BLOCK_SZ]), 2 * BLOCK_SZ);
+    assert_eq!(quota_dir.quota().unwrap().used, 3);
+    assert_eq!(root_inode.rename("quota_dir/inner/inner_file", "moved"), 0);
+    assert_eq!(quota_dir.quota().unwrap().used, 1);
+    assert_eq!(root_inode.rename("quota_outside", "quota_dir/outside"), -1);
+    assert!(root_inode.find("quota_outside").is_some());
+    assert_eq!(root_inode.rename("moved", "quota_dir/moved"), 0);
+    assert_eq!(quota_dir.quota().unwrap().used, 3);
+    assert_eq!(root_inode.quota().unwrap().used, root_used - 1);
+    // lifted, only the quota above is left
+    assert!(quota_dir.set_quota(0));
+    assert!(quota_dir.quota().is_none());
+    assert_eq!(
+        inner_file.write_at(2 * BLOCK_SZ, &[b'i'; 4 * BLOCK_SZ]),
+        4 * BLOCK_SZ
+    );
+    assert_eq!(root_inode.quota().unwrap().used, root_used + 3);
+    assert!(root_inode.set_quota(0));
+    for name in ["moved", "inner"] {
+        let (success, clear_inode) = quota_dir.unlink(name);
+        assert!(success);
+        clear_inode.unwrap().free();
+    }
+    remove("quota_dir");
+    remove("quota_outside");
+
+    // subdirectories
+    let id_of = |name: &str, dir: &Inode| dir.find(name).unwrap().inode_id();
+    // the root is its own parent
+    assert_eq!(id_of(".", &root_inode), 0);
+    assert_eq!(id_of("..", &root_inode), 0);
+    let names = root_inode.ls();
+    assert!(!names.iter().any(|name| name == "." || name == ".."));
+    let a = root_inode.mkdir("a").unwrap();
+    assert!(root_inode.mkdir("a").is_none());
+    assert!(root_inode.create("a").is_none());
+    let b = a.mkdir("b").unwrap();
+    let c = b.mkdir("c").unwrap();
+    c.create("leaf").unwrap();
+    assert_eq!(a.ls(), ["b"]);
+    assert_eq!(b.ls(), ["c"]);
+    assert_eq!(c.ls(), ["leaf"]);
+    // walk down by names and back up by `..`
+    let found = a.find("b").unwrap().find("c").unwrap();
+    assert_eq!(found.inode_id(), c.inode_id());
+    assert!(found.read_disk_inode(|disk_inode| disk_inode.is_dir()));
+    assert_eq!(id_of(".", &c), c.inode_id());
+    assert_eq!(id_of("..", &c), b.inode_id());
+    assert_eq!(id_of("..", &b), a.inode_id());
+    assert_eq!(id_of("..", &a), 0);
+    assert_eq!(c.path().unwrap(), "/a/b/c");
+    assert_eq!(root_inode.path().unwrap(), "/");
+    // `.` and `..` stay put, and a directory goes only once it is empty
+    let unlink = |dir: &Inode, name: &str| {
+        let (success, clear_inode) = dir.unlink(name);
+        if let Some(inode) = clear_inode {
+            inode.free();
+        }
+        success
+    };
+    assert!(!unlink(&c, "."));
+    assert!(!unlink(&c, ".."));
+    assert!(!a.modify_disk_inode(|disk_inode| a.rename_dir_entry(disk_inode, ".", "dot")));
+    assert!(!unlink(&b, "c"));
+    assert_eq!(root_inode.check_cycles(false), 0);
+    // a directory never swaps with one under it
+    assert!(!a.swap_data(&c));
+    assert!(!c.swap_data(&a));
+    // swapped directories keep their own `.` and `..`
+    let d = root_inode.mkdir("d").unwrap();
+    d.create("d_file").unwrap();
+    assert!(a.swap_data(&d));
+    assert_eq!(a.ls(), ["d_file"]);
+    assert_eq!(d.ls(), ["b"]);
+    assert_eq!(id_of(".", &a), a.inode_id());
+    assert_eq!(id_of(".", &d), d.inode_id());
+    assert_eq!(id_of("..", &d), 0);
+    assert_eq!(id_of("..", &b), d.inode_id());
+    assert_eq!(c.path().unwrap(), "/d/b/c");
+    assert!(unlink(&c, "leaf"));
+    assert!(unlink(&b, "c"));
+    // gone from its parent, there is no path to it
+    assert!(c.path().is_none());
+    assert!(unlink(&d, "b"));
+    assert!(unlink(&a, "d_file"));
+    assert!(unlink(&root_inode, "a"));
+    assert!(unlink(&root_inode, "d"));
+
+    // paths
+    let a = root_inode.mkdir("a").unwrap();
+    let b = a.mkdir("b").unwrap();
+    let file = b.create("file").unwrap();
+    let path_id = |dir: &Inode, path: &str| dir.find_path(path).map(|inode| inode.inode_id());
+    assert_eq!(path_id(&root_inode, "/"), Some(0));
+    assert_eq!(path_id(&root_inode, "a/b/file"), Some(file.inode_id()));
+    assert_eq!(path_id(&root_inode, "/a/b/file"), Some(file.inode_id()));
+    assert_eq!(path_id(&root_inode, "//a///b/file"), Some(file.inode_id()));
+    assert_eq!(path_id(&root_inode, "/a/b/"), Some(b.inode_id()));
+    // relative paths start at the directory, absolute ones at the root
+    assert_eq!(path_id(&b, "file"), Some(file.inode_id()));
+    assert_eq!(path_id(&b, "/a"), Some(a.inode_id()));
+    assert_eq!(path_id(&b, "./../b/./file"), Some(file.inode_id()));
+    assert_eq!(path_id(&b, "../../.."), Some(0));
+    // missing components, files used as directories and empty paths
+    assert_eq!(path_id(&root_inode, ""), None);
+    assert_eq!(path_id(&root_inode, "/a/missing/file"), None);
+    assert_eq!(path_id(&root_inode, "/a/b/missing"), None);
+    assert_eq!(path_id(&root_inode, "/a/b/file/"), None);
+    assert_eq!(path_id(&root_inode, "/a/b/file/x"), None);
+    assert_eq!(path_id(&root_inode, "/a/b/file/.."), None);
+    assert_eq!(path_id(&file, "x"), None);
+    // under another root, nothing above it is reached
+    let under_id =
+        |dir: &Inode, path: &str| dir.find_path_under(&a, path).map(|inode| inode.inode_id());
+    assert_eq!(under_id(&b, "/b/file"), Some(file.inode_id()));
+    assert_eq!(under_id(&b, "../../.."), Some(a.inode_id()));
+    assert_eq!(under_id(&b, "/../a"), None);
+    assert_eq!(under_id(&a, "../b/file"), Some(file.inode_id()));
+    assert!(b.symlink("up", "/../b").is_some());
+    assert_eq!(under_id(&b, "up/file"), Some(file.inode_id()));
+    assert!(a.link_under(&a, "/b/file", "/../linked").is_some());
+    assert_eq!(path_id(&a, "linked"), Some(file.inode_id()));
+    assert_eq!(b.path_under(&a).unwrap(), "/b");
+    assert_eq!(a.path_under(&a).unwrap(), "/");
+    assert!(root_inode.path_under(&a).is_none());
+    assert!(unlink(&a, "linked"));
+    assert!(unlink(&b, "up"));
+    assert!(unlink(&b, "file"));
+    assert!(unlink(&a, "b"));
+    assert!(unlink(&root_inode, "a"));
+
+    // timestamps, from a clock that moves on every time it is read
+    static TICKS: AtomicUsize = AtomicUsize::new(0);
+    easy_fs::set_clock(|| TICKS.fetch_add(1, Ordering::Relaxed) as u64 + 1);
+    let times = |inode: &Inode| {
+        inode.read_disk_inode(|disk_inode| (disk_inode.atime, disk_inode.mtime, disk_inode.ctime))
+    };
+    let file = root_inode.create("timed").unwrap();
+    let (atime, mtime, ctime) = times(&file);
+    assert!(mtime > 0);
+    assert_eq!((atime, ctime), (mtime, mtime));
+    // writing moves mtime and ctime, not atime
+    assert_eq!(file.write_at(0, b"hello"), 5);
+    let (written_atime, written_mtime, written_ctime) = times(&file);
+    assert!(written_mtime > mtime);
+    assert_eq!(written_ctime, written_mtime);
+    assert_eq!(written_atime, atime);
+    // reading moves only atime
+    let mut buf = [0u8; 5];
+    assert_eq!(file.read_at(0, &mut buf), 5);
+    let (read_atime, read_mtime, read_ctime) = times(&file);
+    assert!(read_atime > written_mtime);
+    assert_eq!((read_mtime, read_ctime), (written_mtime, written_ctime));
+    // a new link moves only ctime
+    assert!(root_inode.link_replace("timed", "timed_link").0);
+    let (linked_atime, linked_mtime, linked_ctime) = times(&file);
+    assert!(linked_ctime > read_atime);
+    assert_eq!((linked_atime, linked_mtime), (read_atime, read_mtime));
+    assert!(unlink(&root_inode, "timed"));
+    assert!(unlink(&root_inode, "timed_link"));
+
+    // truncate
+    let file = root_inode.create("truncated").unwrap();
+    let free_before = free_blocks();
+    let data: Vec<u8> = (0..160 * BLOCK_SZ).map(|i| (i % 251) as u8).collect();
+    assert_eq!(file.write_at(0, &data), data.len());
+    // down from the doubly indirect blocks to the middle of a direct one
+    let cut = 10 * BLOCK_SZ + 100;
+    assert!(file.truncate(cut as u32));
+    assert_eq!(size_of(&file), cut as u32);
+    assert_eq!(free_blocks(), free_before - 11);
+    let mut buf = vec![0u8; data.len()];
+    assert_eq!(file.read_at(0, &mut buf), cut);
+    assert_eq!(&buf[..cut], &data[..cut]);
+    // up past the direct blocks, the old bytes of the cut block do not come back,
+    // and the part grown is a hole
+    let grown = 30 * BLOCK_SZ;
+    assert!(file.truncate(grown as u32));
+    assert_eq!(free_blocks(), free_before - 11);
+    assert_eq!(file.read_at(0, &mut buf), grown);
+    assert_eq!(&buf[..cut], &data[..cut]);
+    assert!(buf[cut..grown].iter().all(|&b| b == 0));
+    // a snapshot keeps the bytes cut off in a block it shares
+    let snapshot = file.snapshot();
+    assert!(file.truncate(50));
+    let mut snapped_buf = [0u8; 100];
+    assert_eq!(snapshot.read_at(0, &mut snapped_buf), 100);
+    assert_eq!(&snapped_buf[..], &data[..100]);
+    drop(snapshot);
+    assert!(file.truncate(0));
+    assert_eq!(free_blocks(), free_before);
+    assert!(unlink(&root_inode, "truncated"));
+
+    // contiguous allocation
+    // a file written in one go skips one-block holes for a run of its own
+    for i in 0..20 {
+        let inode = root_inode.create(&format!("hole{}", i)).unwrap();
+        assert_eq!(inode.write_at(0, &[1u8; BLOCK_SZ]), BLOCK_SZ);
+    }
+    for i in (0..20).step_by(2) {
+        remove(&format!("hole{}", i));
+    }
+    let run = root_inode.create("run").unwrap();
+    assert_eq!(run.write_at(0, &[2u8; 16 * BLOCK_SZ]), 16 * BLOCK_SZ);
+    assert!(contiguous(&probe_blocks(&run)));
+    // so does one growing into the indirect blocks
+    assert!(run.truncate(0));
+    assert_eq!(run.write_at(0, &[3u8; 40 * BLOCK_SZ]), 40 * BLOCK_SZ);
+    assert!(contiguous(&probe_blocks(&run)[..17]));
+    assert!(contiguous(&probe_blocks(&run)[17..]));
+    remove("run");
+    for i in (1..20).step_by(2) {
+        remove(&format!("hole{}", i));
+    }
+    // with only holes left the blocks are taken from them
+    let filler = root_inode.create("filler").unwrap();
+    assert!(filler.fallocate((free_blocks() - 100) as usize * BLOCK_SZ));
+    let mut filler_size = size_of(&filler) as usize;
+    while free_blocks() > 20 {
+        filler_size += BLOCK_SZ;
+        assert!(filler.fallocate(filler_size));
+    }
+    let mut full = 0;
+    while free_blocks() > 0 {
+        // the directory may take the last block for itself
+        let inode = root_inode.create(&format!("full{}", full)).unwrap();
+        inode.write_at(0, &[1u8; BLOCK_SZ]);
+        full += 1;
+    }
+    for i in (0..full).step_by(2) {
+        remove(&format!("full{}", i));
+    }
+    let free = free_blocks();
+    assert!(free >= 8);
+    let scattered = root_inode.create("scattered").unwrap();
+    let data: Vec<u8> = (0..4 * BLOCK_SZ).map(|i| (i % 251) as u8).collect();
+    assert_eq!(scattered.write_at(0, &data), data.len());
+    assert!(!contiguous(&probe_blocks(&scattered)));
+    assert_eq!(free_blocks(), free - 4);
+    assert_eq!(read_all(&scattered), data);
+    // and a write with too few of them left takes nothing
+    let too_many = vec![4u8; (free - 3) as usize * BLOCK_SZ];
+    assert_eq!(scattered.write_at(data.len(), &too_many), 0);
+    assert_eq!(free_blocks(), free - 4);
+    assert_eq!(size_of(&scattered), data.len() as u32);
+    remove("scattered");
+    remove("filler");
+    for i in (1..full).step_by(2) {
+        remove(&format!("full{}", i));
+    }
+
+    // rename
+    let a = root_inode.mkdir("a").unwrap();
+    let b = a.mkdir("b").unwrap();
+    let file = root_inode.create_with_data("file", b"moved").unwrap();
+    // in the same directory
+    assert_eq!(root_inode.rename("file", "renamed"), 0);
+    assert!(root_inode.find("file").is_none());
+    assert_eq!(path_id(&root_inode, "renamed"), Some(file.inode_id()));
+    // across directories, relative to current directory or from the root
+    assert_eq!(root_inode.rename("renamed", "a/b/file"), 0);
+    assert!(root_inode.find("renamed").is_none());
+    assert_eq!(path_id(&root_inode, "/a/b/file"), Some(file.inode_id()));
+    assert_eq!(b.rename("file", "/a/file"), 0);
+    assert_eq!(a.ls(), ["b", "file"]);
+    assert_eq!(file.state(), (file.inode_id(), 1, true));
+    // a moved directory follows its new parent up
+    assert_eq!(root_inode.rename("a/b", "b"), 0);
+    assert_eq!(id_of("..", &b), 0);
+    assert_eq!(path_id(&b, ".."), Some(0));
+    // a file replaces a file, which goes away with its last link
+    let old = b.create_with_data("old", b"replaced").unwrap();
+    let free_inodes = efs.lock().free_inodes();
+    assert_eq!(a.rename("file", "/b/old"), 0);
+    assert_eq!(efs.lock().free_inodes(), free_inodes + 1);
+    assert_eq!(path_id(&root_inode, "b/old"), Some(file.inode_id()));
+    assert_ne!(file.inode_id(), old.inode_id());
+    // a directory replaces only an empty directory, and never a file
+    assert_eq!(root_inode.rename("b", "a"), 0);
+    assert_eq!(path_id(&root_inode, "a"), Some(b.inode_id()));
+    let c = root_inode.mkdir("c").unwrap();
+    assert_eq!(root_inode.rename("c", "a"), -1);
+    assert_eq!(root_inode.rename("c", "a/old"), -1);
+    assert_eq!(root_inode.rename("a/old", "c"), -1);
+    // a directory never moves under itself
+    c.mkdir("d").unwrap();
+    assert_eq!(root_inode.rename("c", "c/d/c"), -1);
+    assert_eq!(root_inode.rename("c", "c/c"), -1);
+    assert_eq!(root_inode.check_cycles(false), 0);
+    // missing names, dots and the name in place
+    assert_eq!(root_inode.rename("missing", "x"), -1);
+    assert_eq!(root_inode.rename("c/missing/x", "x"), -1);
+    assert_eq!(root_inode.rename("a/old", "a/old/x"), -1);
+    assert_eq!(root_inode.rename("c/..", "x"), -1);
+    assert_eq!(root_inode.rename("/", "x"), -1);
+    assert_eq!(root_inode.rename("a/old", "a/old"), 0);
+    assert!(unlink(&b, "old"));
+    assert!(unlink(&c, "d"));
+    assert!(unlink(&root_inode, "c"));
+    assert!(unlink(&root_inode, "a"));
+
+    // link
+    let free_inodes = efs.lock().free_inodes();
+    let a = root_inode.mkdir("a").unwrap();
+    let b = root_inode.mkdir("b").unwrap();
+    let x = a.create_with_data("x", b"linked").unwrap();
+    let y = root_inode.link("/a/x", "/b/y").unwrap();
+    assert_eq!(y.inode_id(), x.inode_id());
+    assert_eq!(x.state(), (x.inode_id(), 2, true));
+    assert_eq!(y.state(), (x.inode_id(), 2, true));
+    assert_eq!(path_id(&root_inode, "b/y"), Some(x.inode_id()));
+    assert_eq!(read_all(&y), b"linked");
+    // nothing changes when a link fails
+    assert!(a.link("x", "../b/y").is_none());
+    assert!(a.link("missing", "z").is_none());
+    assert!(a.link("x", "missing/z").is_none());
+    assert!(a.link("x", "x/z").is_none());
+    assert!(root_inode.link("a", "b/a").is_none());
+    assert!(root_inode.link("a/.", "b/z").is_none());
+    assert_eq!(x.state(), (x.inode_id(), 2, true));
+    assert_eq!(b.ls(), ["y"]);
+    // relative to current directory, also in the same directory
+    assert!(a.link("x", "z").is_some());
+    assert_eq!(x.state().1, 3);
+    assert!(unlink(&a, "z"));
+    // the file stays as long as one of its names does
+    assert!(unlink(&a, "x"));
+    assert_eq!(y.state(), (x.inode_id(), 1, true));
+    assert_eq!(read_all(&y), b"linked");
+    assert!(unlink(&b, "y"));
+    assert!(unlink(&root_inode, "a"));
+    assert!(unlink(&root_inode, "b"));
+    assert_eq!(efs.lock().free_inodes(), free_inodes);
+
+    // block cache
+    let stats = block_cache_stats();
+    let thrash = root_inode.create("thrash").unwrap();
+    let blocks = 4 * BLOCK_CACHE_SIZE;
+    let data: Vec<u8> = (0..blocks * BLOCK_SZ)
+        .map(|i| (i * 7 % 251) as u8)
+        .collect();
+    // every block written stays dirty, so a full cache writes one back to make room
+    assert_eq!(thrash.write_at(0, &data), data.len());
+    let written = block_cache_stats();
+    assert!(written.misses >= stats.misses + blocks - BLOCK_CACHE_SIZE);
+    assert!(written.dirty_evictions >= stats.dirty_evictions + blocks - BLOCK_CACHE_SIZE);
+    assert_eq!(read_all(&thrash), data);
+    // the blocks used last are still there
+    let mut buf = [0u8; BLOCK_SZ];
+    let before = block_cache_stats();
+    assert_eq!(thrash.read_at((blocks - 1) * BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert_eq!(buf[..], data[(blocks - 1) * BLOCK_SZ..]);
+    let after = block_cache_stats();
+    assert_eq!(after.misses, before.misses);
+    assert!(after.hits > before.hits);
+    remove("thrash");
+
+    // read ahead
+    let ahead = root_inode.create("ahead").unwrap();
+    let blocks = 3 * READ_AHEAD_BLOCKS as usize;
+    let data: Vec<u8> = (0..blocks * BLOCK_SZ)
+        .map(|i| (i * 3 % 253) as u8)
+        .collect();
+    assert_eq!(ahead.write_at(0, &data), data.len());
+    // written back and dropped from the cache
+    let ids = ahead.data_block_ids().unwrap();
+    let cached = |i: usize| block_cached(ids[i], &device);
+    let ahead_end = 1 + READ_AHEAD_BLOCKS as usize;
+    // the first read of the file brings the blocks after it along
+    assert_eq!(ahead.read_at(0, &mut buf[..100]), 100);
+    assert!((1..ahead_end).all(cached));
+    assert!(!cached(ahead_end));
+    // reading on finds them there, and keeps the look-ahead in front of it
+    let before = block_cache_stats();
+    for i in 1..ahead_end {
+        assert_eq!(ahead.read_at(i * BLOCK_SZ, &mut buf), BLOCK_SZ);
+        assert_eq!(buf[..], data[i * BLOCK_SZ..(i + 1) * BLOCK_SZ]);
+    }
+    assert_eq!(block_cache_stats().misses, before.misses);
+    assert!((ahead_end..2 * ahead_end - 1).all(cached));
+    // jumping around reads only what is asked for
+    block_cache_release(&device, Some(&ids));
+    assert_eq!(ahead.read_at((blocks - 2) * BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert!(!cached(blocks - 1));
+    assert_eq!(ahead.read_at(2 * BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert!(!cached(3));
+    remove("ahead");
+
+    // sparse files
+    let free_blocks = efs.lock().free_data_blocks();
+    let sparse = root_inode.create("sparse").unwrap();
+    let far = 1 << 20;
+    assert_eq!(sparse.write_at(far, b"x"), 1);
+    assert_eq!(sparse.read_disk_inode(|disk_inode| disk_inode.size), far as u32 + 1);
+    // the data block, and the two indirect blocks above it
+    assert_eq!(sparse.allocated_blocks(), 3);
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks - 3);
+    // the hole reads as zeros up to the byte written
+    let mut hole = vec![1u8; 3 * BLOCK_SZ];
+    assert_eq!(sparse.read_at(far + 1 - hole.len(), &mut hole), hole.len());
+    assert!(hole[..hole.len() - 1].iter().all(|&b| b == 0));
+    assert_eq!(hole[hole.len() - 1], b'x');
+    assert_eq!(sparse.read_at(0, &mut buf), BLOCK_SZ);
+    assert!(buf.iter().all(|&b| b == 0));
+    // a write in the hole fills only the block it falls in, and the indirect block above
+    assert_eq!(sparse.write_at(20 * BLOCK_SZ + 3, b"mid"), 3);
+    assert_eq!(sparse.allocated_blocks(), 5);
+    assert_eq!(sparse.read_at(20 * BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert_eq!(&buf[..6], b"\0\0\0mid");
+    // cutting frees only the blocks there are, extending leaves a hole
+    assert!(sparse.truncate(30 * BLOCK_SZ as u32));
+    assert_eq!(sparse.allocated_blocks(), 2);
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks - 2);
+    assert!(sparse.truncate(far as u32));
+    assert_eq!(sparse.allocated_blocks(), 2);
+    assert_eq!(sparse.read_at(far - BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert!(buf.iter().all(|&b| b == 0));
+    // a snapshot shares the blocks there are and reads the holes as zeros
+    let snapshot = sparse.snapshot();
+    assert_eq!(sparse.write_at(20 * BLOCK_SZ, b"new"), 3);
+    assert_eq!(snapshot.read_at(20 * BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert_eq!(&buf[..6], b"\0\0\0mid");
+    assert_eq!(snapshot.read_at(far - BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert!(buf.iter().all(|&b| b == 0));
+    drop(snapshot);
+    remove("sparse");
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks);
+
+    // read_dir
+    let d = root_inode.mkdir("d").unwrap();
+    let sub = d.mkdir("sub").unwrap();
+    for name in ["f0", "f1", "f2", "f3"] {
+        d.create(name).unwrap();
+    }
+    assert!(unlink(&d, "f1"));
+    // entries come by name, an offset in the middle of an entry goes on after it
+    let mut offset = 1;
+    let mut entries = Vec::new();
+    loop {
+        let (found, next) = d.read_dir(offset, 2);
+        if found.is_empty() {
+            break;
+        }
+        assert!(found.len() <= 2 && next > offset);
+        entries.extend(found);
+        offset = next;
+    }
+    let names: Vec<&str> = entries.iter().map(|(name, _, _)| name.as_str()).collect();
+    assert_eq!(names, ["..", "f0", "f2", "f3", "sub"]);
+    assert_eq!(entries[4], (String::from("sub"), sub.inode_id(), true));
+    assert!(entries[0].2 && !entries[1].2);
+    assert!(d.read_dir(offset, 2).0.is_empty());
+    for name in ["f0", "f2", "f3", "sub"] {
+        assert!(unlink(&d, name));
+    }
+    assert!(unlink(&root_inode, "d"));
+
+    // sync
+    let synced = root_inode.create("synced").unwrap();
+    let other = root_inode.create("other").unwrap();
+    // a file with no data blocks yet only has its disk inode to write
+    assert_eq!(synced.write_at(0, &[]), 0);
+    assert_eq!(synced.sync(), 1);
+    assert_eq!(synced.sync(), 0);
+    // written data waits in the cache until the file is synced
+    let data = [7u8; 3 * BLOCK_SZ];
+    let written = WRITTEN_BLOCKS.load(Ordering::Relaxed);
+    assert_eq!(synced.write_at(0, &data), data.len());
+    assert_eq!(other.write_at(0, b"other"), 5);
+    assert_eq!(WRITTEN_BLOCKS.load(Ordering::Relaxed), written);
+    // the disk inode and three data blocks, nothing of the other file
+    assert_eq!(synced.sync(), 4);
+    assert_eq!(WRITTEN_BLOCKS.load(Ordering::Relaxed), written + 4);
+    assert_eq!(synced.sync(), 0);
+    // its data block at least, the disk inode may share a block with the first
+    assert!((1..=2).contains(&other.sync()));
+    assert_eq!(read_all(&synced), data);
+    remove("synced");
+    remove("other");
+
+    // symlink
+    let free_inodes = efs.lock().free_inodes();
+    let s = root_inode.mkdir("s").unwrap();
+    s.create_with_data("target", b"pointed").unwrap();
+    let valid = s.symlink("valid", "target").unwrap();
+    assert!(valid.is_symlink());
+    assert_eq!(valid.read_link().unwrap(), "target");
+    let followed = root_inode.find_path("s/valid").unwrap();
+    assert_eq!(read_all(&followed), b"pointed");
+    let link = root_inode.find_path_nofollow("s/valid").unwrap();
+    assert!(link.is_symlink());
+    // absolute targets start at the root, links to directories lead on into them
+    s.symlink("absolute", "/s/target").unwrap();
+    assert_eq!(read_all(&s.find_path("absolute").unwrap()), b"pointed");
+    root_inode.symlink("to_s", "s/").unwrap();
+    assert_eq!(path_id(&root_inode, "to_s/valid"), path_id(&s, "target"));
+    assert_eq!(path_id(&root_inode, "to_s/"), Some(s.inode_id()));
+    assert!(s.symlink("valid", "other").is_none());
+    assert!(s.symlink("empty", "").is_none());
+    // a dangling link is listed but leads nowhere
+    s.symlink("dangling", "missing").unwrap();
+    assert!(s.ls().iter().any(|name| name == "dangling"));
+    assert!(s.find_path("dangling").is_none());
+    assert!(s.find_path_nofollow("dangling").is_some());
+    // cycles and overly long chains are given up on
+    s.symlink("self", "self").unwrap();
+    assert!(s.find_path("self").is_none());
+    s.symlink("ping", "pong").unwrap();
+    s.symlink("pong", "ping").unwrap();
+    assert!(s.find_path("ping/x").is_none());
+    let chain = |i: usize| format!("chain{}", i);
+    for i in 0..MAX_SYMLINK_FOLLOWS {
+        s.symlink(&chain(i), &chain(i + 1)).unwrap();
+    }
+    s.symlink(&chain(MAX_SYMLINK_FOLLOWS), "target").unwrap();
+    assert!(s.find_path("chain0").is_none());
+    assert_eq!(read_all(&s.find_path("chain1").unwrap()), b"pointed");
+    for name in s.ls() {
+        assert!(unlink(&s, &name));
+    }
+    assert!(unlink(&root_inode, "to_s"));
+    assert!(unlink(&root_inode, "s"));
+    assert_eq!(efs.lock().free_inodes(), free_inodes);
+
+    // permission bits, only directories the owner may search are looked into
+    let p = root_inode.mkdir("p").unwrap();
+    let file = p.create("file").unwrap();
+    assert!(file.permits(MODE_READ | MODE_WRITE));
+    assert!(!file.permits(MODE_EXEC));
+    assert!(p.permits(MODE_READ | MODE_WRITE | MODE_EXEC));
+    file.chmod(0o444);
+    assert!(file.permits(MODE_READ) && !file.permits(MODE_WRITE));
+    p.chmod(0o600);
+    assert!(root_inode.find_path("p").is_some());
+    assert!(root_inode.find_path("p/file").is_none());
+    p.chmod(0o700);
+    assert!(root_inode.find_path("p/file").is_some());
+    // the root directory is always searchable
+    root_inode.chmod(0);
+    assert!(root_inode.permits(MODE_EXEC));
+    assert!(root_inode.find_path("/p/file").is_some());
+    root_inode.chmod(0o755);
+    assert!(unlink(&p, "file"));
+    assert!(unlink(&root_inode, "p"));
+
+    // the cached free block count, right after a clear frees many blocks at once
+    let rescanned = || {
+        EasyFileSystem::open(block_file.clone())
+            .lock()
+            .free_blocks()
+    };
+    let big = root_inode.create("big").unwrap();
+    let (free, total) = efs.lock().free_blocks();
+    assert_eq!(rescanned(), (free, total));
+    assert_eq!(efs.lock().statfs().free_blocks as usize, free);
+    big.write_at(0, &vec![1u8; 300 * BLOCK_SZ]);
+    // the indirect block, the doubly indirect one and two of its children besides the data
+    assert_eq!(efs.lock().free_blocks(), (free - 304, total));
+    assert_eq!(rescanned(), (free - 304, total));
+    big.clear();
+    assert_eq!(efs.lock().free_blocks(), (free, total));
+    assert_eq!(rescanned(), (free, total));
+    remove("big");
+
+    // two files written from two threads at once, each holding only its own data lock
+    let files: Vec<Arc<Inode>> = ["con0", "con1"]
+        .iter()
+        .map(|name| root_inode.create(name).unwrap())
+        .collect();
+    let byte = |file: usize, chunk: usize| (file * 64 + chunk) as u8;
+    std::thread::scope(|scope| {
+        for (i, file) in files.iter().enumerate() {
+            scope.spawn(move || {
+                for chunk in 0..64 {
+                    let data = [byte(i, chunk); BLOCK_SZ];
+                    assert_eq!(file.write_at(chunk * BLOCK_SZ, &data), BLOCK_SZ);
+                }
+            });
+        }
+    });
+    for (i, file) in files.iter().enumerate() {
+        let data = read_all(file);
+        assert_eq!(data.len(), 64 * BLOCK_SZ);
+        for (chunk, block) in data.chunks(BLOCK_SZ).enumerate() {
+            assert!(block.iter().all(|&b| b == byte(i, chunk)));
+        }
+    }
+    remove("con0");
+    remove("con1");
+
+    // a crash after the journal is committed, before the blocks are written home
+    block_cache_sync_all();
+    block_cache_discard_all();
+    let crash_file = Arc::new(CrashFile {
+        file: BlockFile(Mutex::new({
+            let f = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open("target/crash.img")?;
+            f.set_len((2048 * BLOCK_SZ) as u64).unwrap();
+            f
+        })),
+        crash_at: AtomicUsize::new(usize::MAX),
+        crashed: AtomicBool::new(false),
+    });
+    let crash_efs = EasyFileSystem::create(crash_file.clone(), 2048, 1);
+    let crash_root = EasyFileSystem::root_inode(&crash_efs);
+    crash_root.create("kept").unwrap();
+    let info = crash_efs.lock().fs_info();
+    assert_eq!(
+        info.journal_start,
+        info.data_area_start + info.data_area_blocks
+    );
+    assert_eq!(info.journal_start + info.journal_blocks, info.total_blocks);
+    let crash_free_inodes = crash_efs.lock().free_inodes();
+    crash_file
+        .crash_at
+        .store(info.journal_start as usize, Ordering::Relaxed);
+    let lost = crash_root.mkdir("lost").unwrap();
+    assert!(crash_file.crashed.load(Ordering::Relaxed));
+    // the header is all that reached the disk, the cache goes with the crash
+    block_cache_discard_all();
+    crash_file.crash_at.store(usize::MAX, Ordering::Relaxed);
+    crash_file.crashed.store(false, Ordering::Relaxed);
+    let unreplayed = |block_id: usize| {
+        let mut buf = [0u8; BLOCK_SZ];
+        crash_file.read_block(block_id, &mut buf);
+        buf
+    };
+    let lost_id = lost.inode_id();
+    let (lost_block, _) = crash_efs.lock().get_disk_inode_pos(lost_id);
+    let before_replay = unreplayed(lost_block as usize);
+    // opening replays the log, the directory comes back whole
+    let crash_efs = EasyFileSystem::open(crash_file.clone());
+    let crash_root = EasyFileSystem::root_inode(&crash_efs);
+    block_cache_sync_all();
+    assert_ne!(unreplayed(lost_block as usize), before_replay);
+    assert!(crash_root.find("kept").is_some());
+    let lost = crash_root.find("lost").unwrap();
+    assert_eq!(id_of(".", &lost), lost.inode_id());
+    assert_eq!(id_of("..", &lost), 0);
+    assert_eq!(crash_efs.lock().free_inodes(), crash_free_inodes - 1);
+    // and the log is cleared once its blocks are home
+    assert_eq!(unreplayed(info.journal_start as usize), [0u8; BLOCK_SZ]);
+    block_cache_discard_all();
+
+    // a write past the size quota of a file stops short at it
+    let capped = root_inode.create("capped").unwrap();
+    assert_eq!(capped.write_at(0, &[1u8; 600]), 600);
+    assert!(!capped.set_size_quota(599));
+    assert!(capped.set_size_quota(1000));
+    assert_eq!(capped.size_quota(), 1000);
+    assert_eq!(capped.write_at(600, &[2u8; 600]), 400);
+    assert_eq!(capped.write_at(1000, &[3u8; 1]), 0);
+    assert_eq!(capped.write_at(2000, &[3u8; 1]), 0);
+    assert!(!capped.truncate(1001));
+    assert!(capped.append(&[4u8; 1]).is_none());
+    let data = read_all(&capped);
+    assert_eq!(data.len(), 1000);
+    assert!(data[600..].iter().all(|&b| b == 2));
+    assert!(capped.set_size_quota(0));
+    assert_eq!(capped.append(&[4u8; 1]), Some(1000));
+    // directories have block quotas instead
+    assert!(!root_inode.set_size_quota(1000));
+    remove("capped");
+
+    // a filesystem mounted on a directory
+    let mnt = root_inode.mkdir("mnt").unwrap();
+    let image = || -> Arc<dyn BlockDevice> { Arc::new(MemDevice(Mutex::new(mount_image()))) };
+    let blank: Arc<dyn BlockDevice> = Arc::new(MemDevice(Mutex::new(vec![0u8; 64 * BLOCK_SZ])));
+    assert!(!mnt.mount(blank));
+    assert!(!root_inode.mount(image()));
+    let device = image();
+    assert!(mnt.mount(device.clone()));
+    assert!(!mnt.mount(image()));
+    let hello = root_inode.find_path("/mnt/hello").unwrap();
+    assert_eq!(read_all(&hello), MOUNT_IMG_HELLO);
+    // `..` at its root goes back to the directory holding the mount point
+    assert!(root_inode.find_path("mnt/../mnt/hello").is_some());
+    let mounted = root_inode.find_path("mnt").unwrap();
+    assert_eq!(mounted.inode_id(), 0);
+    assert_eq!(mounted.path().unwrap(), "/mnt");
+    assert!(mounted.find_path("../filea").is_some());
+    assert!(mounted.find_path("/mnt/hello").is_some());
+    mounted.create_with_data("note", b"note").unwrap();
+    // files never move or link across filesystems
+    assert_eq!(root_inode.rename("mnt/hello", "hello"), -1);
+    assert!(root_inode.link("filea", "mnt/filea").is_none());
+    assert_eq!(root_inode.rename("mnt/note", "mnt/note2"), 0);
+    // busy while a file on it is held, and the mount point stays
+    assert!(!mounted.umount());
+    assert!(!root_inode.unlink("mnt").0);
+    drop(hello);
+    assert!(mounted.umount());
+    assert!(!mounted.umount());
+    drop(mounted);
+    assert!(root_inode.find_path("mnt/hello").is_none());
+    // what was written is there when it is mounted again
+    assert!(mnt.mount(device));
+    let mounted = root_inode.find_path("mnt").unwrap();
+    assert_eq!(read_all(&mounted.find("note2").unwrap()), b"note");
+    assert!(mounted.umount());
+    drop(mounted);
+    remove("mnt");
+
+    // the files of a directory stay close to it, on inodes and on data blocks,
+    // on a filesystem of its own with room left
+    let device: Arc<dyn BlockDevice> = Arc::new(MemDevice(Mutex::new(vec![0u8; 8192 * BLOCK_SZ])));
+    let spread_efs = EasyFileSystem::create(device.clone(), 8192, 1);
+    let spread_root = EasyFileSystem::root_inode(&spread_efs);
+    for i in 0..200 {
+        spread_root
+            .create_with_data(&format!("spread{}", i), b"s")
+            .unwrap();
+    }
+    // where the data before the directory ends
+    let spread_end = spread_root
+        .find("spread199")
+        .unwrap()
+        .data_block_ids()
+        .unwrap()[0];
+    let cluster = spread_root.mkdir("cluster").unwrap();
+    // holes all over before the directory
+    for i in (0..200).step_by(2) {
+        unlink(&spread_root, &format!("spread{}", i));
+    }
+    let files: Vec<_> = (0..50)
+        .map(|i| cluster.create_with_data(&format!("f{}", i), b"c").unwrap())
+        .collect();
+    let ids: Vec<_> = files.iter().map(|file| file.inode_id()).collect();
+    assert!(ids
+        .iter()
+        .all(|&id| id > cluster.inode_id() && id < cluster.inode_id() + 64));
+    let blocks: Vec<_> = files
+        .iter()
+        .map(|file| file.data_block_ids().unwrap()[0])
+        .collect();
+    assert!(blocks
+        .iter()
+        .all(|&block| block > spread_end && block < spread_end + 64));
+    drop((files, cluster, spread_root, spread_efs));
+    block_cache_release(&device, None);
+
+    // a file fragmented by another one growing along with it, moved to one run
+    let frag = root_inode.create("frag").unwrap();
+    let filler = root_inode.create("filler").unwrap();
+    let frag_blocks = 24;
+    let frag_data: Vec<u8> = (0..frag_blocks * BLOCK_SZ)
+        .map(|i| (i / BLOCK_SZ) as u8)
+        .collect();
+    for i in 0..frag_blocks {
+        let range = i * BLOCK_SZ..(i + 1) * BLOCK_SZ;
+        frag.write_at(range.start, &frag_data[range.clone()]);
+        filler.write_at(range.start, &frag_data[range]);
+    }
+    // in order and at most one indirect block in between
+    let in_one_run = |ids: &[usize]| {
+        ids.windows(2).all(|pair| pair[1] > pair[0]) && ids[ids.len() - 1] - ids[0] <= ids.len()
+    };
+    assert!(!in_one_run(&frag.data_block_ids().unwrap()));
+    drop(filler);
+    remove("filler");
+    assert_eq!(frag.defragment(), Some(frag_blocks + 1));
+    assert!(in_one_run(&frag.data_block_ids().unwrap()));
+    assert_eq!(read_all(&frag), frag_data);
+    assert_eq!(frag.defragment(), Some(0));
+    assert_eq!(root_inode.defragment(), None);
+    drop(frag);
+    remove("frag");
+
+    // a large directory, kept sorted through inserts and deletes in any order
+    let big = root_inode.mkdir("big").unwrap();
+    let count = 2000;
+    let name_of = |i: usize| format!("e{}", i);
+    // 7919 is prime to 2000, so every entry is created once, in scattered order
+    for i in (0..count).map(|i| i * 7919 % count) {
+        big.create(&name_of(i)).unwrap();
+    }
+    for i in (0..count).step_by(3) {
+        assert!(unlink(&big, &name_of(i)));
+    }
+    // and half of those come back
+    for i in (0..count).step_by(6) {
+        big.create(&name_of(i)).unwrap();
+    }
+    let present = |i: usize| i % 3 != 0 || i % 6 == 0;
+    for i in 0..count {
+        assert_eq!(big.find(&name_of(i)).is_some(), present(i), "{}", i);
+    }
+    assert!(big.find("e").is_none() && big.find("e2000").is_none());
+    assert_eq!(id_of("..", &big), 0);
+    let names = big.ls();
+    assert_eq!(names.len(), (0..count).filter(|&i| present(i)).count());
+    assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+    // a lookup reads a handful of entries, not all of them
+    let before = block_cache_stats();
+    assert!(big.find(&name_of(count - 1)).is_some());
+    let after = block_cache_stats();
+    assert!(after.hits + after.misses - before.hits - before.misses < 100);
+    for name in names {
+        assert!(unlink(&big, &name));
+    }
+    assert_eq!(big.read_disk_inode(|disk_inode| disk_inode.size), 2 * 32);
+    assert!(unlink(&root_inode, "big"));
+    assert_eq!(efs.lock().free_inodes(), free_inodes);
+
+    // triple indirect blocks, on a filesystem large enough to hold them
+    let efs = EasyFileSystem::create(block_file.clone(), BLOCK_NUM as u32, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let free_blocks = || efs.lock().free_data_blocks();
+    let byte_at = |inode: &Inode, offset: usize| {
+        let mut buf = [0u8; 1];
+        assert_eq!(inode.read_at(offset, &mut buf), 1);
+        buf[0]
+    };
+    // the first block reached through indirect1, indirect2 and indirect3
+    let bounds = [17, 17 + 128, 17 + 128 + 128 * 128];
+    // a one-block hole before the file for defrag to fill
+    for name in ["hole", "kept"] {
+        root_inode.create(name).unwrap().write_at(0, &[1u8; BLOCK_SZ]);
+    }
+    let big = root_inode.create("big").unwrap();
+    assert!(unlink(&root_inode, "hole"));
+    let free_before = free_blocks();
+    let used = || free_before - free_blocks();
+    // a byte reachable only through indirect3, under its second indirect2 and indirect1 blocks
+    let deep = (bounds[2] + 128 * 128 + 128) * BLOCK_SZ + 7;
+    // every block up to it allocated, not left a hole
+    assert!(big.fallocate(deep + 1));
+    // a write across each threshold lands on both sides of it
+    for (i, &bound) in bounds.iter().enumerate() {
+        let offset = bound * BLOCK_SZ - 1;
+        assert_eq!(big.write_at(offset, &[i as u8 + 1, i as u8 + 11]), 2);
+    }
+    assert_eq!(big.write_at(deep, &[42]), 1);
+    let size = size_of(&big) as usize;
+    assert_eq!(size, deep + 1);
+    // indirect1, indirect2 with 128 indirect1 blocks under it,
+    // indirect3 with 2 indirect2 blocks and 130 indirect1 blocks under them
+    let data_blocks = (size + BLOCK_SZ - 1) / BLOCK_SZ;
+    assert_eq!(used(), (data_blocks + 1 + 1 + 128 + 1 + 2 + 130) as u32);
+    for (i, &bound) in bounds.iter().enumerate() {
+        let offset = bound * BLOCK_SZ;
+        assert_eq!(byte_at(&big, offset - 1), i as u8 + 1);
+        assert_eq!(byte_at(&big, offset), i as u8 + 11);
+        assert_eq!(byte_at(&big, offset + 1), 0);
+    }
+    assert_eq!(byte_at(&big, deep), 42);
+    assert_eq!(byte_at(&big, deep - BLOCK_SZ), 0);
+    // the last data block moves into the hole and is still found
+    assert!(root_inode.defrag() > 0);
+    assert_eq!(byte_at(&big, deep), 42);
+    // cut down to a single block under indirect3, then below each threshold in turn
+    assert!(big.truncate((bounds[2] * BLOCK_SZ + 1) as u32));
+    assert_eq!(used(), (bounds[2] + 1 + 1 + 1 + 128 + 1 + 1 + 1) as u32);
+    assert_eq!(byte_at(&big, bounds[2] * BLOCK_SZ), 13);
+    assert!(big.truncate((bounds[2] * BLOCK_SZ) as u32));
+    assert_eq!(used(), (bounds[2] + 1 + 1 + 128) as u32);
+    assert!(big.truncate((bounds[1] * BLOCK_SZ) as u32));
+    assert_eq!(used(), (bounds[1] + 1) as u32);
+    assert!(big.truncate((bounds[0] * BLOCK_SZ) as u32));
+    assert_eq!(used(), bounds[0] as u32);
+    assert_eq!(byte_at(&big, bounds[0] * BLOCK_SZ - 1), 1);
+    // and back up past all of them at once as a hole, the cut bytes reading as zeros
+    assert!(big.truncate(size as u32));
+    assert_eq!(used(), bounds[0] as u32);
+    assert_eq!(byte_at(&big, bounds[1] * BLOCK_SZ), 0);
+    assert_eq!(byte_at(&big, deep), 0);
+    // nothing grows past the last block indirect3 can reach
+    assert!(!big.truncate(MAX_FILE_SIZE as u32 + 1));
+    assert_eq!(big.write_at(MAX_FILE_SIZE, &[1]), 0);
+    assert_eq!(size_of(&big) as usize, size);
+    assert!(unlink(&root_inode, "big"));
+    assert_eq!(free_blocks(), free_before);
+
+    // data blocks of a few blocks of the device each, on filesystems of their own
+    for block_size in [1024, 4096] {
+        let per_block = block_size / BLOCK_SZ;
+        let device: Arc<dyn BlockDevice> =
+            Arc::new(MemDevice(Mutex::new(vec![0u8; 4096 * BLOCK_SZ])));
+        let sized_efs = EasyFileSystem::create_with_block_size(device.clone(), 4096, 1, block_size);
+        let stat = sized_efs.lock().statfs();
+        assert_eq!(stat.block_size as usize, block_size);
+        let info = sized_efs.lock().fs_info();
+        assert_eq!(info.block_size as usize, block_size);
+        assert_eq!(
+            stat.total_blocks as usize,
+            info.data_area_blocks as usize / per_block
+        );
+        let sized_root = EasyFileSystem::root_inode(&sized_efs);
+        let file = sized_root.create("sized").unwrap();
+        let free = sized_efs.lock().free_data_blocks();
+        // a byte takes a whole data block, a run of blocks of the device
+        assert_eq!(file.write_at(0, b"x"), 1);
+        assert_eq!(sized_efs.lock().free_data_blocks(), free - 1);
+        let ids = file.data_block_ids().unwrap();
+        assert_eq!(ids.len(), per_block);
+        assert!(ids.windows(2).all(|pair| pair[1] == pair[0] + 1));
+        // past a hole, under the first indirect1 block of indirect2
+        let deep = (17 + block_size / 4) * block_size + 3;
+        let data: Vec<u8> = (0..3 * block_size).map(|i| (i % 251) as u8).collect();
+        assert_eq!(file.write_at(deep, &data), data.len());
+        // indirect2, the indirect1 under it and the 4 data blocks the write spans
+        assert_eq!(sized_efs.lock().free_data_blocks(), free - 1 - 2 - 4);
+        assert_eq!(byte_at(&file, 17 * block_size), 0);
+        // a snapshot keeps the old data while the file is written over
+        let snapshot = file.snapshot();
+        assert_eq!(file.write_at(deep, &vec![0xffu8; data.len()]), data.len());
+        let mut buf = vec![0u8; data.len()];
+        assert_eq!(snapshot.read_at(deep, &mut buf), data.len());
+        assert_eq!(buf, data);
+        drop(snapshot);
+        assert_eq!(file.write_at(deep, &data), data.len());
+        // read back after opening it again
+        drop((file, sized_root, sized_efs));
+        block_cache_sync_all();
+        block_cache_release(&device, None);
+        let sized_efs = EasyFileSystem::open(device.clone());
+        assert_eq!(sized_efs.lock().statfs().block_size as usize, block_size);
+        assert_eq!(sized_efs.lock().free_data_blocks(), free - 1 - 2 - 4);
+        let sized_root = EasyFileSystem::root_inode(&sized_efs);
+        let file = sized_root.find("sized").unwrap();
+        let content = read_all(&file);
+        assert_eq!(content[0], b'x');
+        assert_eq!(&content[deep..], &data[..]);
+        assert!(content[1..deep].iter().all(|&byte| byte == 0));
+        // and the blocks all come back
+        drop(file);
+        assert!(unlink(&sized_root, "sized"));
+        assert_eq!(sized_efs.lock().free_data_blocks(), free);
+        drop((sized_root, sized_efs));
+        block_cache_release(&device, None);
+    }
+
     Ok(())
 }
+
@@ -53,6 +53,103 @@ impl Bitmap {
         }
         None
     }
+    /// Allocate a new block below `limit` from a block device, the first free one
+    /// at or after `hint`, wrapping around to the start if there is none past it
+    pub fn alloc_near(
+        &self,
+        block_device: &Arc<dyn BlockDevice>,
+        hint: usize,
+        limit: usize,
+    ) -> Option<usize> {
+        let limit = limit.min(self.maximum());
+        let hint = if hint < limit { hint } else { 0 };
+        self.alloc_between(block_device, hint, limit)
+            .or_else(|| self.alloc_between(block_device, 0, hint))
+    }
+    /// Allocate the first free block in `start..end` from a block device
+    fn alloc_between(
+        &self,
+        block_device: &Arc<dyn BlockDevice>,
+        start: usize,
+        end: usize,
+    ) -> Option<usize> {
+        let mut bit = start;
+        while bit < end {
+            let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+            // the bits of this word from `bit` on and below `end`
+            let len = (64 - inner_pos).min(end - bit);
+            let mask = (u64::MAX >> (64 - len)) << inner_pos;
+            let free = get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
+                .lock()
+                .read(0, |bitmap_block: &BitmapBlock| {
+                    !bitmap_block[bits64_pos] & mask
+                });
+            if free != 0 {
+                let bit = bit - inner_pos + free.trailing_zeros() as usize;
+                self.set(block_device, bit);
+                return Some(bit);
+            }
+            bit += len;
+        }
+        None
+    }
+    /// Allocate `n` consecutive blocks from `from` on and below `limit` from a block device,
+    /// return the first of them, None if there is no long enough run of free blocks
+    pub fn alloc_run(
+        &self,
+        block_device: &Arc<dyn BlockDevice>,
+        n: usize,
+        from: usize,
+        limit: usize,
+    ) -> Option<usize> {
+        let limit = limit.min(self.maximum());
+        let mut start = 0;
+        let mut len = 0;
+        'scan: for block_id in from / BLOCK_BITS..self.blocks {
+            let bitmap_block =
+                get_block_cache(block_id + self.start_block_id, Arc::clone(block_device))
+                    .lock()
+                    .read(0, |bitmap_block: &BitmapBlock| *bitmap_block);
+            for (bits64_pos, bits64) in bitmap_block.iter().enumerate() {
+                if *bits64 == u64::MAX {
+                    len = 0;
+                    continue;
+                }
+                for inner_pos in 0..64 {
+                    let bit = block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos;
+                    if bit >= limit {
+                        break 'scan;
+                    }
+                    if bit < from {
+                        continue;
+                    }
+                    if bits64 & (1u64 << inner_pos) != 0 {
+                        len = 0;
+                        continue;
+                    }
+                    if len == 0 {
+                        start = bit;
+                    }
+                    len += 1;
+                    if len == n {
+                        (start..start + n).for_each(|bit| self.set(block_device, bit));
+                        return Some(start);
+                    }
+                }
+            }
+        }
+        None
+    }
+    /// Mark a free block allocated
+    fn set(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
+            .lock()
+            .modify(0, |bitmap_block: &mut BitmapBlock| {
+                assert!(bitmap_block[bits64_pos] & (1u64 << inner_pos) == 0);
+                bitmap_block[bits64_pos] |= 1u64 << inner_pos;
+            });
+    }
     /// Deallocate a block
     pub fn dealloc(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) {
         let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
@@ -63,6 +160,30 @@ impl Bitmap {
                 bitmap_block[bits64_pos] &= !(1u64 << inner_pos);
             });
     }
+    /// Whether a bit is allocated
+    pub fn is_allocated(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) -> bool {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
+            .lock()
+            .read(0, |bitmap_block: &BitmapBlock| {
+                bitmap_block[bits64_pos] & (1u64 << inner_pos) > 0
+            })
+    }
+    /// Get the number of allocated blocks
+    pub fn allocated(&self, block_device: &Arc<dyn BlockDevice>) -> usize {
+        (0..self.blocks)
+            .map(|block_id| {
+                get_block_cache(block_id + self.start_block_id, Arc::clone(block_device))
+                    .lock()
+                    .read(0, |bitmap_block: &BitmapBlock| {
+                        bitmap_block
+                            .iter()
+                            .map(|bits64| bits64.count_ones() as usize)
+                            .sum::<usize>()
+                    })
+            })
+            .sum()
+    }
     /// Get the max number of allocatable blocks
     pub fn maximum(&self) -> usize {
         self.blocks * BLOCK_BITS
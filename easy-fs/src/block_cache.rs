@@ -1,16 +1,24 @@
 use super::{
     BLOCK_SZ,
     BlockDevice,
+    CountedMutex,
+    Journal,
+    BLOCK_CACHE_LOCK_COUNTER,
 };
-use alloc::collections::VecDeque;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
 use alloc::sync::Arc;
 use lazy_static::*;
 use spin::Mutex;
 
+/// Block data aligned for the `u64` fields of the structures read from it
+#[repr(C, align(8))]
+struct BlockData([u8; BLOCK_SZ]);
+
 /// Cached block inside memory
 pub struct BlockCache {
     /// cached block data
-    cache: [u8; BLOCK_SZ],
+    cache: BlockData,
     /// underlying block id
     block_id: usize,
     /// underlying block device
@@ -25,8 +33,8 @@ impl BlockCache {
         block_id: usize,
         block_device: Arc<dyn BlockDevice>
     ) -> Self {
-        let mut cache = [0u8; BLOCK_SZ];
-        block_device.read_block(block_id, &mut cache);
+        let mut cache = BlockData([0u8; BLOCK_SZ]);
+        block_device.read_block(block_id, &mut cache.0);
         Self {
             cache,
             block_id,
@@ -36,7 +44,7 @@ impl BlockCache {
     }
     /// Get the address of an offset inside the cached block data
     fn addr_of_offset(&self, offset: usize) -> usize {
-        &self.cache[offset] as *const _ as usize
+        &self.cache.0[offset] as *const _ as usize
     }
 
     pub fn get_ref<T>(&self, offset: usize) -> &T where T: Sized {
@@ -65,7 +73,7 @@ impl BlockCache {
     pub fn sync(&mut self) {
         if self.modified {
             self.modified = false;
-            self.block_device.write_block(self.block_id, &self.cache);
+            self.block_device.write_block(self.block_id, &self.cache.0);
         }
     }
 }
@@ -77,45 +85,174 @@ impl Drop for BlockCache {
 }
 
 /// Use a block cache of 16 blocks
-const BLOCK_CACHE_SIZE: usize = 16;
+pub const BLOCK_CACHE_SIZE: usize = 16;
+/// At most half of the cache can be pinned, the rest is left for substitution
+const MAX_PINNED_BLOCKS: usize = BLOCK_CACHE_SIZE / 2;
+
+/// How well the block cache did since boot
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BlockCacheStats {
+    /// lookups of a block already in the cache
+    pub hits: usize,
+    /// lookups that read the block from the device
+    pub misses: usize,
+    /// blocks substituted while dirty, written back first
+    pub dirty_evictions: usize,
+}
+
+/// Where a cached block lives: the address of its device, then its block id,
+/// so that filesystems on different devices share the cache
+type BlockKey = (usize, usize);
+
+fn block_key(block_id: usize, block_device: &Arc<dyn BlockDevice>) -> BlockKey {
+    (Arc::as_ptr(block_device) as *const u8 as usize, block_id)
+}
 
 pub struct BlockCacheManager {
-    queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+    /// least recently used first
+    queue: VecDeque<(BlockKey, Arc<CountedMutex<BlockCache>>)>,
+    /// pin count of blocks that must stay in the cache
+    pins: BTreeMap<BlockKey, usize>,
+    stats: BlockCacheStats,
+    /// log of the metadata operation in progress, whose dirty blocks
+    /// are held back from their homes until it commits
+    journal: Option<Journal>,
+    /// how deep operations are nested in each other, only the outermost one commits
+    journal_depth: usize,
 }
 
 impl BlockCacheManager {
     pub fn new() -> Self {
-        Self { queue: VecDeque::new() }
+        Self {
+            queue: VecDeque::new(),
+            pins: BTreeMap::new(),
+            stats: BlockCacheStats::default(),
+            journal: None,
+            journal_depth: 0,
+        }
     }
 
     pub fn get_block_cache(
         &mut self,
         block_id: usize,
         block_device: Arc<dyn BlockDevice>,
-    ) -> Arc<Mutex<BlockCache>> {
-        if let Some(pair) = self.queue
+    ) -> Arc<CountedMutex<BlockCache>> {
+        let key = block_key(block_id, &block_device);
+        if let Some(idx) = self.queue
             .iter()
-            .find(|pair| pair.0 == block_id) {
-                Arc::clone(&pair.1)
-        } else {
-            // substitute
-            if self.queue.len() == BLOCK_CACHE_SIZE {
-                // from front to tail
-                if let Some((idx, _)) = self.queue
-                    .iter()
-                    .enumerate()
-                    .find(|(_, pair)| Arc::strong_count(&pair.1) == 1) {
-                    self.queue.drain(idx..=idx);
-                } else {
-                    panic!("Run out of BlockCache!");
-                }
+            .position(|pair| pair.0 == key) {
+            self.stats.hits += 1;
+            // move it to the tail as the most recently used
+            let pair = self.queue.remove(idx).unwrap();
+            let block_cache = Arc::clone(&pair.1);
+            self.queue.push_back(pair);
+            return block_cache;
+        }
+        self.stats.misses += 1;
+        if self.queue.len() == BLOCK_CACHE_SIZE {
+            self.evict();
+        }
+        // load block into mem and push back
+        let block_cache = Arc::new(CountedMutex::new(
+            BlockCache::new(block_id, Arc::clone(&block_device)),
+            &BLOCK_CACHE_LOCK_COUNTER,
+        ));
+        self.queue.push_back((key, Arc::clone(&block_cache)));
+        block_cache
+    }
+
+    /// Drop the least recently used block no one holds or pinned, a clean one if
+    /// there is any, else a dirty one which is written back first. While an operation
+    /// is journaled, its dirty blocks are committed before any of them is written back.
+    fn evict(&mut self) {
+        if self.journal.is_some() && self.evictable(true).is_none() {
+            self.commit_journal();
+        }
+        let Some(idx) = self.evictable(true).or_else(|| self.evictable(false)) else {
+            panic!("Run out of BlockCache!");
+        };
+        let (_, block_cache) = self.queue.remove(idx).unwrap();
+        let mut block_cache = block_cache.lock();
+        if block_cache.modified {
+            self.stats.dirty_evictions += 1;
+            block_cache.sync();
+        }
+    }
+
+    /// The least recently used block no one holds or pinned, only a clean one if `clean`
+    fn evictable(&self, clean: bool) -> Option<usize> {
+        // no one else has a reference, so locking it never waits
+        self.queue.iter().position(|pair| {
+            Arc::strong_count(&pair.1) == 1
+                && !self.pins.contains_key(&pair.0)
+                && !(clean && pair.1.lock().modified)
+        })
+    }
+
+    /// Log the dirty blocks no one holds on the device of the log,
+    /// then write them home and clear the log
+    fn commit_journal(&mut self) {
+        let Some(journal) = &self.journal else { return; };
+        let device = block_key(0, journal.block_device()).0;
+        let mut dirty: Vec<_> = self.queue
+            .iter()
+            .filter(|((owner, _), cache)| *owner == device && Arc::strong_count(cache) == 1)
+            .map(|((_, block_id), cache)| (*block_id, cache.lock()))
+            .filter(|(_, cache)| cache.modified)
+            .collect();
+        if dirty.is_empty() {
+            return;
+        }
+        let blocks: Vec<_> = dirty
+            .iter()
+            .map(|(block_id, cache)| (*block_id, &cache.cache.0))
+            .collect();
+        journal.commit(&blocks);
+        for (_, cache) in dirty.iter_mut() {
+            cache.sync();
+        }
+        journal.clear();
+    }
+
+    /// Load a block expected to be read soon unless it is cached already, neither a hit
+    /// nor a miss. Only a clean block no one holds makes room for it, a block read
+    /// ahead is not worth a write back.
+    fn prefetch(&mut self, block_id: usize, block_device: Arc<dyn BlockDevice>) {
+        let key = block_key(block_id, &block_device);
+        if self.queue.iter().any(|pair| pair.0 == key) {
+            return;
+        }
+        if self.queue.len() == BLOCK_CACHE_SIZE {
+            let Some(idx) = self.evictable(true) else { return; };
+            self.queue.remove(idx);
+        }
+        let block_cache = Arc::new(CountedMutex::new(
+            BlockCache::new(block_id, block_device),
+            &BLOCK_CACHE_LOCK_COUNTER,
+        ));
+        self.queue.push_back((key, block_cache));
+    }
+
+    /// Load a block and keep it in the cache until it is unpinned as many times
+    pub fn pin_block(&mut self, block_id: usize, block_device: Arc<dyn BlockDevice>) -> bool {
+        let key = block_key(block_id, &block_device);
+        if !self.pins.contains_key(&key) && self.pins.len() == MAX_PINNED_BLOCKS {
+            return false;
+        }
+        self.get_block_cache(block_id, block_device);
+        *self.pins.entry(key).or_insert(0) += 1;
+        true
+    }
+
+    /// Drop one pin of a block, it can be substituted again once no pin is left
+    pub fn unpin_block(&mut self, block_id: usize, block_device: &Arc<dyn BlockDevice>) {
+        let key = block_key(block_id, block_device);
+        if let Some(count) = self.pins.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                self.pins.remove(&key);
             }
-            // load block into mem and push back
-            let block_cache = Arc::new(Mutex::new(
-                BlockCache::new(block_id, Arc::clone(&block_device))
-            ));
-            self.queue.push_back((block_id, Arc::clone(&block_cache)));
-            block_cache
         }
     }
 }
@@ -131,10 +268,85 @@ lazy_static! {
 pub fn get_block_cache(
     block_id: usize,
     block_device: Arc<dyn BlockDevice>
-) -> Arc<Mutex<BlockCache>> {
+) -> Arc<CountedMutex<BlockCache>> {
     BLOCK_CACHE_MANAGER.lock().get_block_cache(block_id, block_device)
 }
 
+/// Read a block into the cache ahead of its use, see [`BlockCacheManager::prefetch`]
+pub fn prefetch_block_cache(block_id: usize, block_device: Arc<dyn BlockDevice>) {
+    BLOCK_CACHE_MANAGER.lock().prefetch(block_id, block_device)
+}
+
+/// Whether a block is in the cache, without using it
+pub fn block_cached(block_id: usize, block_device: &Arc<dyn BlockDevice>) -> bool {
+    let key = block_key(block_id, block_device);
+    BLOCK_CACHE_MANAGER.lock().queue.iter().any(|pair| pair.0 == key)
+}
+
+/// Hits and misses of the block cache since boot
+pub fn block_cache_stats() -> BlockCacheStats {
+    BLOCK_CACHE_MANAGER.lock().stats
+}
+
+/// Pin a block in the block cache, fail if too many blocks are pinned
+pub fn pin_block_cache(block_id: usize, block_device: Arc<dyn BlockDevice>) -> bool {
+    BLOCK_CACHE_MANAGER.lock().pin_block(block_id, block_device)
+}
+
+/// Unpin a block pinned by [`pin_block_cache`]
+pub fn unpin_block_cache(block_id: usize, block_device: &Arc<dyn BlockDevice>) {
+    BLOCK_CACHE_MANAGER
+        .lock()
+        .unpin_block(block_id, block_device)
+}
+
+/// Start a journaled operation, dirty blocks stay in the cache until it ends
+pub fn journal_begin(journal: Journal) {
+    let mut manager = BLOCK_CACHE_MANAGER.lock();
+    manager.journal_depth += 1;
+    if manager.journal.is_none() {
+        manager.journal = Some(journal);
+    }
+}
+
+/// End a journaled operation, committing its blocks if it is the outermost one
+pub fn journal_end() {
+    let mut manager = BLOCK_CACHE_MANAGER.lock();
+    manager.journal_depth -= 1;
+    if manager.journal_depth == 0 {
+        manager.commit_journal();
+        manager.journal = None;
+    }
+}
+
+/// Drop every cached block without writing it back, as a crash loses them
+pub fn block_cache_discard_all() {
+    let mut manager = BLOCK_CACHE_MANAGER.lock();
+    for (_, cache) in manager.queue.drain(..) {
+        cache.lock().modified = false;
+    }
+    manager.pins.clear();
+}
+
+/// Sync the cached ones of `block_ids` on `block_device` to it,
+/// return the number of them that were dirty
+pub fn block_cache_sync(block_ids: &[usize], block_device: &Arc<dyn BlockDevice>) -> usize {
+    let manager = BLOCK_CACHE_MANAGER.lock();
+    let device = block_key(0, block_device).0;
+    let mut synced = 0;
+    for ((owner, block_id), cache) in manager.queue.iter() {
+        if *owner != device || !block_ids.contains(block_id) {
+            continue;
+        }
+        let mut cache = cache.lock();
+        if cache.modified {
+            cache.sync();
+            synced += 1;
+        }
+    }
+    synced
+}
+
 /// Sync all block cache to block device
 pub fn block_cache_sync_all() {
     let manager = BLOCK_CACHE_MANAGER.lock();
@@ -142,3 +354,19 @@ pub fn block_cache_sync_all() {
         cache.lock().sync();
     }
 }
+
+/// Write back and drop the cached blocks of `block_device`, only the ones of `block_ids`
+/// if given, so that the device is read afresh. Blocks held or pinned are kept.
+pub fn block_cache_release(block_device: &Arc<dyn BlockDevice>, block_ids: Option<&[usize]>) {
+    let mut manager = BLOCK_CACHE_MANAGER.lock();
+    let device = block_key(0, block_device).0;
+    let manager = &mut *manager;
+    let pins = &manager.pins;
+    // dropping the last reference writes a dirty block back
+    manager.queue.retain(|(key, cache)| {
+        key.0 != device
+            || block_ids.map_or(false, |ids| !ids.contains(&key.1))
+            || Arc::strong_count(cache) > 1
+            || pins.contains_key(key)
+    });
+}
@@ -1,14 +1,24 @@
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::sync::Arc;
-use spin::Mutex;
+use alloc::vec::Vec;
 use super::{
     BlockDevice,
+    CountedMutex,
+    FS_LOCK_COUNTER,
+    BlockPtr,
     Bitmap,
     SuperBlock,
     DiskInode,
     DiskInodeType,
     Inode,
+    InodeLock,
+    Journal,
+    Transaction,
+    JOURNAL_BLOCKS,
     get_block_cache,
     block_cache_sync_all,
+    has_mounts,
+    is_valid_block_size,
 };
 use crate::BLOCK_SZ;
 
@@ -19,26 +29,128 @@ pub struct EasyFileSystem {
     pub data_bitmap: Bitmap,
     inode_area_start_block: u32,
     data_area_start_block: u32,
+    /// number of data blocks in the data area
+    data_area_blocks: u32,
+    /// size of a data block, a multiple of the [`BLOCK_SZ`] of the device
+    block_size: usize,
+    /// number of free data blocks, kept up to date by every allocation and deallocation
+    /// so that the data bitmap is scanned only once, when the filesystem is opened
+    free_data: u32,
+    /// number of snapshots holding each shared data block
+    shared_blocks: BTreeMap<u32, usize>,
+    /// shared data blocks no inode points to anymore, freed with the last snapshot
+    orphan_blocks: BTreeSet<u32>,
+    /// end of the written data of each inode with blocks preallocated past it
+    written_ends: BTreeMap<u32, u32>,
+    /// bytes appended to each inode since its appends were last written back,
+    /// kept here so that every open of the file counts toward the same total
+    unsynced_appends: BTreeMap<u32, usize>,
+    /// data block near which the first data of a new inode goes, by inode id
+    data_hints: BTreeMap<u32, u32>,
+    /// quota of each directory that has one, by inode id
+    quotas: BTreeMap<u32, DirQuota>,
+    /// the directory with a quota each inode under one is charged to, by inode id,
+    /// an inode missing here is charged to the root
+    quota_dirs: BTreeMap<u32, u32>,
+    /// log of metadata operations, right after the data area
+    journal: Journal,
+    /// the inode of each file in use, by inode id, so that a file has one
+    /// however many times it is looked up
+    inodes: BTreeMap<u32, Arc<Inode>>,
 }
 
-/// A data block of block size
+/// Id of the root directory
+const ROOT_INODE_ID: u32 = 0;
+
+/// Data blocks the files under a directory may use, and how many they do
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DirQuota {
+    /// max number of blocks, 0 for no limit
+    pub limit: u32,
+    /// blocks in use, index blocks included
+    pub used: u32,
+}
+
+/// A block of the device, all or part of a data block
 type DataBlock = [u8; BLOCK_SZ];
 
+/// Geometry of a filesystem as recorded in its super block,
+/// the areas in blocks of the device of [`BLOCK_SZ`] bytes
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FsInfo {
+    /// size of a data block in bytes
+    pub block_size: u32,
+    /// number of blocks on the device
+    pub total_blocks: u32,
+    /// max number of inodes
+    pub inode_num: u32,
+    /// first block of the inode bitmap
+    pub inode_bitmap_start: u32,
+    pub inode_bitmap_blocks: u32,
+    /// first block of the inode area
+    pub inode_area_start: u32,
+    pub inode_area_blocks: u32,
+    /// first block of the data bitmap
+    pub data_bitmap_start: u32,
+    pub data_bitmap_blocks: u32,
+    /// first block of the data area
+    pub data_area_start: u32,
+    pub data_area_blocks: u32,
+    /// first block of the journal
+    pub journal_start: u32,
+    pub journal_blocks: u32,
+}
+
+/// Usage of a filesystem
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StatFs {
+    /// size of a block in bytes
+    pub block_size: u32,
+    /// number of data blocks
+    pub total_blocks: u32,
+    /// number of free data blocks
+    pub free_blocks: u32,
+    /// max number of inodes
+    pub total_inodes: u32,
+    /// number of free inodes
+    pub free_inodes: u32,
+}
+
 impl EasyFileSystem {
-    /// Create a filesystem from a block device
+    /// Create a filesystem from a block device, with data blocks of [`BLOCK_SZ`]
     pub fn create(
         block_device: Arc<dyn BlockDevice>,
         total_blocks: u32,
         inode_bitmap_blocks: u32,
-    ) -> Arc<Mutex<Self>> {
+    ) -> Arc<CountedMutex<Self>> {
+        Self::create_with_block_size(block_device, total_blocks, inode_bitmap_blocks, BLOCK_SZ)
+    }
+    /// Create a filesystem from a block device, with data blocks of `block_size` bytes,
+    /// which must pass [`is_valid_block_size`]. Larger ones take fewer indirect blocks
+    /// and bitmap bits for a large file, at the cost of more room for a small one.
+    pub fn create_with_block_size(
+        block_device: Arc<dyn BlockDevice>,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+        block_size: usize,
+    ) -> Arc<CountedMutex<Self>> {
+        assert!(is_valid_block_size(block_size));
+        // blocks of the device in a data block
+        let per_block = (block_size / BLOCK_SZ) as u32;
         // calculate block size of areas & create bitmaps
         let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize);
         let inode_num = inode_bitmap.maximum();
         let inode_area_blocks =
             ((inode_num * core::mem::size_of::<DiskInode>() + BLOCK_SZ - 1) / BLOCK_SZ) as u32;
         let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
-        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
-        let data_bitmap_blocks = (data_total_blocks + 4096) / 4097;
+        let data_total_blocks = total_blocks - 1 - inode_total_blocks - JOURNAL_BLOCKS;
+        // a block of the bitmap covers 4096 data blocks
+        let bitmap_covers = 4096 * per_block;
+        let data_bitmap_blocks = (data_total_blocks + bitmap_covers) / (bitmap_covers + 1);
+        // what is left at the end, too little for a data block, is never used
         let data_area_blocks = data_total_blocks - data_bitmap_blocks;
         let data_bitmap = Bitmap::new(
             (1 + inode_bitmap_blocks + inode_area_blocks) as usize,
@@ -50,6 +162,21 @@ impl EasyFileSystem {
             data_bitmap,
             inode_area_start_block: 1 + inode_bitmap_blocks,
             data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            data_area_blocks: data_area_blocks / per_block,
+            block_size,
+            free_data: data_area_blocks / per_block,
+            shared_blocks: BTreeMap::new(),
+            orphan_blocks: BTreeSet::new(),
+            written_ends: BTreeMap::new(),
+            unsynced_appends: BTreeMap::new(),
+            data_hints: BTreeMap::new(),
+            quotas: BTreeMap::new(),
+            quota_dirs: BTreeMap::new(),
+            journal: Journal::new(
+                (total_blocks - JOURNAL_BLOCKS) as usize,
+                Arc::clone(&block_device),
+            ),
+            inodes: BTreeMap::new(),
         };
         // clear all blocks
         for i in 0..total_blocks {
@@ -72,34 +199,48 @@ impl EasyFileSystem {
                 inode_area_blocks,
                 data_bitmap_blocks,
                 data_area_blocks,
+                JOURNAL_BLOCKS,
+                block_size as u32,
             );
         });
         // write back immediately
         // create a inode for root node "/"
-        assert_eq!(efs.alloc_inode(), 0);
-        let (root_inode_block_id, root_inode_offset) = efs.get_disk_inode_pos(0);
+        assert_eq!(efs.alloc_inode(), ROOT_INODE_ID);
+        let (root_inode_block_id, root_inode_offset) = efs.get_disk_inode_pos(ROOT_INODE_ID);
         get_block_cache(
             root_inode_block_id as usize,
             Arc::clone(&block_device)
         )
         .lock()
         .modify(root_inode_offset, |disk_inode: &mut DiskInode| {
-            disk_inode.initialize(DiskInodeType::Directory);
+            disk_inode.initialize(DiskInodeType::Directory, block_size);
         });
         block_cache_sync_all();
-        Arc::new(Mutex::new(efs))
+        let efs = Arc::new(CountedMutex::new(efs, &FS_LOCK_COUNTER));
+        Self::root_inode(&efs).init_root();
+        efs
+    }
+    /// Whether the block device holds an easy-fs, to be opened by [`EasyFileSystem::open`]
+    pub fn is_valid(block_device: &Arc<dyn BlockDevice>) -> bool {
+        get_block_cache(0, Arc::clone(block_device))
+            .lock()
+            .read(0, |super_block: &SuperBlock| super_block.is_valid())
     }
     /// Open a block device as a filesystem
-    pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self>> {
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<CountedMutex<Self>> {
         // read SuperBlock
-        get_block_cache(0, Arc::clone(&block_device))
+        let mut efs = get_block_cache(0, Arc::clone(&block_device))
             .lock()
             .read(0, |super_block: &SuperBlock| {
                 assert!(super_block.is_valid(), "Error loading EFS!");
                 let inode_total_blocks =
                     super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
-                let efs = Self {
-                    block_device,
+                let data_area_start_block =
+                    1 + inode_total_blocks + super_block.data_bitmap_blocks;
+                let block_size = super_block.block_size as usize;
+                let per_block = (block_size / BLOCK_SZ) as u32;
+                Self {
+                    block_device: Arc::clone(&block_device),
                     inode_bitmap: Bitmap::new(
                         1,
                         super_block.inode_bitmap_blocks as usize
@@ -109,23 +250,92 @@ impl EasyFileSystem {
                         super_block.data_bitmap_blocks as usize,
                     ),
                     inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
-                    data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
-                };
-                Arc::new(Mutex::new(efs))
-            })
+                    data_area_start_block,
+                    data_area_blocks: super_block.data_area_blocks / per_block,
+                    block_size,
+                    free_data: 0,
+                    shared_blocks: BTreeMap::new(),
+                    orphan_blocks: BTreeSet::new(),
+                    written_ends: BTreeMap::new(),
+                    unsynced_appends: BTreeMap::new(),
+                    data_hints: BTreeMap::new(),
+                    quotas: BTreeMap::new(),
+                    quota_dirs: BTreeMap::new(),
+                    journal: Journal::new(
+                        (data_area_start_block + super_block.data_area_blocks) as usize,
+                        Arc::clone(&block_device),
+                    ),
+                    inodes: BTreeMap::new(),
+                }
+            });
+        // finish the operation a crash cut off after it was committed
+        efs.journal.replay();
+        efs.free_data = efs.data_area_blocks - efs.data_bitmap.allocated(&efs.block_device) as u32;
+        Arc::new(CountedMutex::new(efs, &FS_LOCK_COUNTER))
     }
     /// Get the root inode of the filesystem
-    pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
-        let block_device = Arc::clone(&efs.lock().block_device);
-        // acquire efs lock temporarily
-        let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
-        // release efs lock
-        Inode::new(
+    pub fn root_inode(efs: &Arc<CountedMutex<Self>>) -> Arc<Inode> {
+        efs.lock().get_inode(efs, ROOT_INODE_ID)
+    }
+    /// Get the inode `inode_id` of `efs`, which is current filesystem, the same one
+    /// for everyone who looks it up while anyone holds it. Inodes only the table
+    /// holds anymore are dropped from it on the way.
+    pub fn get_inode(&mut self, efs: &Arc<CountedMutex<Self>>, inode_id: u32) -> Arc<Inode> {
+        if let Some(inode) = self.inodes.get(&inode_id) {
+            return Arc::clone(inode);
+        }
+        self.evict_inodes();
+        let (block_id, block_offset) = self.get_disk_inode_pos(inode_id);
+        let inode = Arc::new(Inode::new(
             block_id,
             block_offset,
             Arc::clone(efs),
-            block_device,
-        )
+            Arc::clone(&self.block_device),
+        ));
+        self.inodes.insert(inode_id, Arc::clone(&inode));
+        inode
+    }
+    /// Drop the inodes no one but the table holds
+    pub fn evict_inodes(&mut self) {
+        self.inodes.retain(|_, inode| Arc::strong_count(inode) > 1);
+    }
+    /// Drop every inode from the table, which holds the filesystem through them,
+    /// once it is not used anymore
+    pub fn clear_inodes(&mut self) {
+        self.inodes.clear();
+    }
+    /// Read the filesystem geometry from the super block
+    pub fn fs_info(&self) -> FsInfo {
+        get_block_cache(0, Arc::clone(&self.block_device))
+            .lock()
+            .read(0, |super_block: &SuperBlock| {
+                let inode_bitmap_start = 1;
+                let inode_area_start = inode_bitmap_start + super_block.inode_bitmap_blocks;
+                let data_bitmap_start = inode_area_start + super_block.inode_area_blocks;
+                let data_area_start = data_bitmap_start + super_block.data_bitmap_blocks;
+                let journal_start = data_area_start + super_block.data_area_blocks;
+                FsInfo {
+                    block_size: super_block.block_size,
+                    total_blocks: super_block.total_blocks,
+                    inode_num: self.inode_bitmap.maximum() as u32,
+                    inode_bitmap_start,
+                    inode_bitmap_blocks: super_block.inode_bitmap_blocks,
+                    inode_area_start,
+                    inode_area_blocks: super_block.inode_area_blocks,
+                    data_bitmap_start,
+                    data_bitmap_blocks: super_block.data_bitmap_blocks,
+                    data_area_start,
+                    data_area_blocks: super_block.data_area_blocks,
+                    journal_start,
+                    journal_blocks: super_block.journal_blocks,
+                }
+            })
+    }
+    /// Start a metadata operation, whose block writes reach their homes all or none
+    /// once the returned transaction is committed or dropped. Nothing in it may sync
+    /// the block cache on its own.
+    pub fn transaction(&self) -> Transaction {
+        self.journal.begin()
     }
     /// Get inode by id
     pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
@@ -134,31 +344,382 @@ impl EasyFileSystem {
         let block_id = self.inode_area_start_block + inode_id / inodes_per_block;
         (block_id, (inode_id % inodes_per_block) as usize * inode_size)
     }
-    /// Get data block by id
+    /// Get inode id by the position of its disk inode
+    pub fn get_inode_id(&self, block_id: u32, block_offset: usize) -> u32 {
+        let inode_size = core::mem::size_of::<DiskInode>();
+        let inodes_per_block = (BLOCK_SZ / inode_size) as u32;
+        (block_id - self.inode_area_start_block) * inodes_per_block
+            + (block_offset / inode_size) as u32
+    }
+    /// Size of a data block
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+    /// Blocks of the device in a data block
+    pub fn per_block(&self) -> u32 {
+        (self.block_size / BLOCK_SZ) as u32
+    }
+    /// Get the first block of the device of the data block with id, its bit in the bitmap
     pub fn get_data_block_id(&self, data_block_id: u32) -> u32 {
-        self.data_area_start_block + data_block_id
+        self.data_area_start_block + data_block_id * self.per_block()
+    }
+    /// Copy the data block starting at block `from` of the device to the one at `to`
+    pub fn copy_data_block(&self, from: u32, to: u32) {
+        for i in 0..self.per_block() {
+            let mut data: DataBlock = [0; BLOCK_SZ];
+            get_block_cache((from + i) as usize, Arc::clone(&self.block_device))
+                .lock()
+                .read(0, |block: &DataBlock| data.copy_from_slice(block));
+            get_block_cache((to + i) as usize, Arc::clone(&self.block_device))
+                .lock()
+                .modify(0, |block: &mut DataBlock| block.copy_from_slice(&data));
+        }
+    }
+    /// Get the id of the data block at or around block `block_id` of the device
+    fn data_block_index(&self, block_id: u32) -> u32 {
+        block_id.saturating_sub(self.data_area_start_block) / self.per_block()
     }
     /// Allocate a new inode
     pub fn alloc_inode(&mut self) -> u32 {
         self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
     }
+    /// Allocate a new inode for an entry of directory `dir_inode_id`, the first free one
+    /// after the directory so that the inodes of a directory stay together, or any free one
+    pub fn alloc_inode_near(&mut self, dir_inode_id: u32) -> u32 {
+        let limit = self.inode_bitmap.maximum();
+        self.inode_bitmap
+            .alloc_near(&self.block_device, dir_inode_id as usize + 1, limit)
+            .unwrap() as u32
+    }
+    /// Deallocate an inode no dirent points to anymore, its data must be cleared already
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        self.unsynced_appends.remove(&inode_id);
+        self.data_hints.remove(&inode_id);
+        self.quota_dirs.remove(&inode_id);
+        self.inode_bitmap
+            .dealloc(&self.block_device, inode_id as usize);
+    }
     /// Allocate a data block
     pub fn alloc_data(&mut self) -> u32 {
-        self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block
+        let block_id = self.data_bitmap.alloc(&self.block_device).unwrap() as u32;
+        self.free_data -= 1;
+        self.get_data_block_id(block_id)
     }
-    /// Deallocate a data block
-    pub fn dealloc_data(&mut self, block_id: u32) {
-        get_block_cache(
-            block_id as usize,
-            Arc::clone(&self.block_device)
+    /// Allocate a data block, the first free one at or after block `hint`, or any free one
+    pub fn alloc_data_near(&mut self, hint: u32) -> u32 {
+        let hint = self.data_block_index(hint) as usize;
+        let limit = self.data_area_blocks as usize;
+        let block_id = self
+            .data_bitmap
+            .alloc_near(&self.block_device, hint, limit)
+            .unwrap() as u32;
+        self.free_data -= 1;
+        self.get_data_block_id(block_id)
+    }
+    /// Allocate `n` data blocks, consecutive ones if there is a long enough run of
+    /// free blocks, else wherever they are free. None if fewer than `n` blocks are free.
+    /// With a `near` block, runs and blocks from it on are preferred.
+    pub fn alloc_data_contiguous(&mut self, n: usize, near: Option<u32>) -> Option<Vec<u32>> {
+        if n == 0 {
+            return Some(Vec::new());
+        }
+        if n > self.free_data_blocks() as usize {
+            return None;
+        }
+        if let Some(run) = self.alloc_data_run(n, near) {
+            return Some(run);
+        }
+        Some(match near {
+            Some(block) => (0..n).map(|_| self.alloc_data_near(block)).collect(),
+            None => (0..n).map(|_| self.alloc_data()).collect(),
+        })
+    }
+    /// Allocate `n` consecutive data blocks, the first run from block `near` on if given,
+    /// else the first one. None if there is no long enough run of free blocks.
+    pub fn alloc_data_run(&mut self, n: usize, near: Option<u32>) -> Option<Vec<u32>> {
+        let limit = self.data_area_blocks as usize;
+        let from = near.map_or(0, |block| self.data_block_index(block));
+        let start = self
+            .data_bitmap
+            .alloc_run(&self.block_device, n, from as usize, limit)
+            .or_else(|| self.data_bitmap.alloc_run(&self.block_device, n, 0, limit))?;
+        self.free_data -= n as u32;
+        Some(
+            (start..start + n)
+                .map(|i| self.get_data_block_id(i as u32))
+                .collect(),
         )
-        .lock()
-        .modify(0, |data_block: &mut DataBlock| {
-            data_block.iter_mut().for_each(|p| { *p = 0; })
-        });
+    }
+    /// Let the first data of new inode `inode_id` go near block `block_id`
+    pub fn set_data_hint(&mut self, inode_id: u32, block_id: u32) {
+        self.data_hints.insert(inode_id, block_id);
+    }
+    /// Forget where the first data of an inode was to go, return it if there was a hint
+    pub fn take_data_hint(&mut self, inode_id: u32) -> Option<u32> {
+        self.data_hints.remove(&inode_id)
+    }
+    /// Move data blocks toward the start of the data area, so that free blocks
+    /// coalesce into one run at the end. Return the number of blocks moved.
+    ///
+    /// Every block is copied first, then pointed to, and only then is the old one freed,
+    /// with a sync after each step, so a crash in between at worst leaks a block.
+    /// It stops at a block of a file someone is reading or writing.
+    pub fn defrag(&mut self) -> usize {
+        // snapshots remember where the blocks are, they must not move,
+        // and the blocks of a mounted image are used as a device under it
+        if self.free_data_blocks() == 0 || !self.shared_blocks.is_empty() || has_mounts() {
+            return 0;
+        }
+        // owner of every block in use: (inode id, where it is pointed to)
+        let mut owners: BTreeMap<u32, (u32, BlockPtr)> = BTreeMap::new();
+        for inode_id in 0..self.inode_bitmap.maximum() as u32 {
+            if !self.inode_bitmap.is_allocated(&self.block_device, inode_id as usize) {
+                continue;
+            }
+            let (block_id, block_offset) = self.get_disk_inode_pos(inode_id);
+            let ptrs = get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                .lock()
+                .read(block_offset, |disk_inode: &DiskInode| {
+                    disk_inode.block_ptrs(&self.block_device)
+                });
+            for (ptr, block) in ptrs {
+                owners.insert(block, (inode_id, ptr));
+            }
+        }
+        let mut moved = 0;
+        // move the last block in use to the first free one, until they meet
+        while let Some((&old_block, &(inode_id, ptr))) = owners.iter().next_back() {
+            let (block_id, block_offset) = self.get_disk_inode_pos(inode_id);
+            // the fs lock is held already, so the data lock is only tried
+            let data_lock = InodeLock::new(block_id as usize, block_offset, &self.block_device);
+            let Some(_data) = data_lock.try_write() else { break; };
+            let new_block = self.alloc_data();
+            if new_block > old_block {
+                self.dealloc_data(new_block);
+                break;
+            }
+            self.copy_data_block(old_block, new_block);
+            block_cache_sync_all();
+            get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                .lock()
+                .modify(block_offset, |disk_inode: &mut DiskInode| {
+                    disk_inode.set_block_ptr(ptr, new_block, &self.block_device)
+                });
+            block_cache_sync_all();
+            self.dealloc_data(old_block);
+            block_cache_sync_all();
+            // blocks pointed to by a moved indirect block are now found in the new one
+            for (_, owner_ptr) in owners.values_mut() {
+                if let BlockPtr::InIndirect(indirect, i) = *owner_ptr {
+                    if indirect == old_block {
+                        *owner_ptr = BlockPtr::InIndirect(new_block, i);
+                    }
+                }
+            }
+            owners.remove(&old_block);
+            owners.insert(new_block, (inode_id, ptr));
+            moved += 1;
+        }
+        moved
+    }
+    /// Get the number of free data blocks
+    pub fn free_data_blocks(&self) -> u32 {
+        self.free_data
+    }
+    /// Get the numbers of free and of all data blocks
+    pub fn free_blocks(&self) -> (usize, usize) {
+        (self.free_data as usize, self.data_area_blocks as usize)
+    }
+    /// Get the number of free inodes
+    pub fn free_inodes(&self) -> u32 {
+        (self.inode_bitmap.maximum() - self.inode_bitmap.allocated(&self.block_device)) as u32
+    }
+    /// Get the usage of the filesystem
+    pub fn statfs(&self) -> StatFs {
+        let (free_blocks, total_blocks) = self.free_blocks();
+        StatFs {
+            block_size: self.block_size as u32,
+            total_blocks: total_blocks as u32,
+            free_blocks: free_blocks as u32,
+            total_inodes: self.inode_bitmap.maximum() as u32,
+            free_inodes: self.free_inodes(),
+        }
+    }
+    /// Let one more snapshot hold a data block
+    pub fn share_data(&mut self, block_id: u32) {
+        *self.shared_blocks.entry(block_id).or_insert(0) += 1;
+    }
+    /// Whether any snapshot holds a data block
+    pub fn is_shared(&self, block_id: u32) -> bool {
+        self.shared_blocks.contains_key(&block_id)
+    }
+    /// Drop a snapshot's hold on a data block, and free it if no one needs it anymore
+    pub fn unshare_data(&mut self, block_id: u32) {
+        let count = self.shared_blocks.get_mut(&block_id).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.shared_blocks.remove(&block_id);
+            if self.orphan_blocks.remove(&block_id) {
+                self.dealloc_data(block_id);
+            }
+        }
+    }
+    /// Remember that the data of an inode ends at `size`, before blocks are preallocated past it
+    pub fn start_prealloc(&mut self, inode_id: u32, size: u32) {
+        self.written_ends.entry(inode_id).or_insert(size);
+    }
+    /// Move the end of the written data of an inode with preallocated blocks up to `end`
+    pub fn mark_written(&mut self, inode_id: u32, end: u32) {
+        if let Some(written_end) = self.written_ends.get_mut(&inode_id) {
+            *written_end = (*written_end).max(end);
+        }
+    }
+    /// Forget the end of the written data of an inode, return it if it had preallocated blocks
+    pub fn take_written_end(&mut self, inode_id: u32) -> Option<u32> {
+        self.written_ends.remove(&inode_id)
+    }
+    /// Let the records of the written data of two inodes follow their data when it is swapped
+    pub fn swap_written_ends(&mut self, inode_a: u32, inode_b: u32) {
+        let end_a = self.written_ends.remove(&inode_a);
+        let end_b = self.written_ends.remove(&inode_b);
+        if let Some(end) = end_a {
+            self.written_ends.insert(inode_b, end);
+        }
+        if let Some(end) = end_b {
+            self.written_ends.insert(inode_a, end);
+        }
+    }
+    /// Count `len` more bytes appended to an inode and not written back yet,
+    /// return true and start over once a block's worth of them has piled up
+    pub fn pile_append(&mut self, inode_id: u32, len: usize) -> bool {
+        let unsynced = self.unsynced_appends.entry(inode_id).or_insert(0);
+        *unsynced += len;
+        if *unsynced < BLOCK_SZ {
+            return false;
+        }
+        self.unsynced_appends.remove(&inode_id);
+        true
+    }
+    /// Forget the appends to an inode waiting to be written back, return true if there were any
+    pub fn take_unsynced_appends(&mut self, inode_id: u32) -> bool {
+        self.unsynced_appends.remove(&inode_id).is_some()
+    }
+    /// Cap the blocks the files under directory `dir_id` may use at `limit`, with `used`
+    /// of them in use already. A `limit` of 0 lifts the cap. `members` are the inodes
+    /// under it that are not under another directory with a quota below it, they are
+    /// charged to `dir_id` from now on.
+    pub fn set_quota(&mut self, dir_id: u32, limit: u32, used: u32, members: &[u32]) {
+        if limit == 0 {
+            if self.quotas.remove(&dir_id).is_some() {
+                // its members go to the quota above it
+                let above = self.parent_dir(dir_id).unwrap_or(ROOT_INODE_ID);
+                for quota_dir in self.quota_dirs.values_mut() {
+                    if *quota_dir == dir_id {
+                        *quota_dir = above;
+                    }
+                }
+                self.quota_dirs
+                    .retain(|_, quota_dir| *quota_dir != ROOT_INODE_ID);
+            }
+            return;
+        }
+        self.quotas.insert(dir_id, DirQuota { limit, used });
+        if dir_id != ROOT_INODE_ID {
+            for &inode_id in members {
+                self.quota_dirs.insert(inode_id, dir_id);
+            }
+        }
+    }
+    /// Quota of directory `dir_id`, if it has one
+    pub fn quota(&self, dir_id: u32) -> Option<DirQuota> {
+        self.quotas.get(&dir_id).copied()
+    }
+    /// The directory whose quota an inode is charged to, the nearest one above it
+    /// with a quota, or the root. None for the root itself.
+    pub fn parent_dir(&self, inode_id: u32) -> Option<u32> {
+        if inode_id == ROOT_INODE_ID {
+            None
+        } else {
+            Some(*self.quota_dirs.get(&inode_id).unwrap_or(&ROOT_INODE_ID))
+        }
+    }
+    /// The directory a new entry of directory `dir_id` is charged to
+    pub fn quota_dir_of(&self, dir_id: u32) -> u32 {
+        if self.quotas.contains_key(&dir_id) {
+            dir_id
+        } else {
+            self.parent_dir(dir_id).unwrap_or(ROOT_INODE_ID)
+        }
+    }
+    /// Charge inode `inode_id` to the quota of `quota_dir` from now on,
+    /// the blocks it holds are moved over by the caller
+    pub fn set_quota_dir(&mut self, inode_id: u32, quota_dir: u32) {
+        if quota_dir == ROOT_INODE_ID {
+            self.quota_dirs.remove(&inode_id);
+        } else {
+            self.quota_dirs.insert(inode_id, quota_dir);
+        }
+    }
+    /// Whether `blocks` more blocks of a file in directory `dir_id`
+    /// fit in the quotas of that directory and the ones above it
+    pub fn within_quota(&self, dir_id: u32, blocks: u32) -> bool {
+        let mut dir = Some(dir_id);
+        while let Some(dir_id) = dir {
+            if let Some(quota) = self.quotas.get(&dir_id) {
+                if quota.used + blocks > quota.limit {
+                    return false;
+                }
+            }
+            dir = self.parent_dir(dir_id);
+        }
+        true
+    }
+    /// Count `blocks` more blocks of inode `inode_id` against the quotas of the directories
+    /// above it, all or nothing. Return false if one of them would go over its limit.
+    pub fn charge(&mut self, inode_id: u32, blocks: u32) -> bool {
+        let Some(dir_id) = self.parent_dir(inode_id) else { return true; };
+        if !self.within_quota(dir_id, blocks) {
+            return false;
+        }
+        let mut dir = Some(dir_id);
+        while let Some(dir_id) = dir {
+            if let Some(quota) = self.quotas.get_mut(&dir_id) {
+                quota.used += blocks;
+            }
+            dir = self.parent_dir(dir_id);
+        }
+        true
+    }
+    /// Take `blocks` blocks freed by inode `inode_id` off the quotas of the directories above it
+    pub fn refund(&mut self, inode_id: u32, blocks: u32) {
+        let mut dir = self.parent_dir(inode_id);
+        while let Some(dir_id) = dir {
+            if let Some(quota) = self.quotas.get_mut(&dir_id) {
+                quota.used = quota.used.saturating_sub(blocks);
+            }
+            dir = self.parent_dir(dir_id);
+        }
+    }
+    /// Deallocate a data block, or leave it to the snapshots still holding it
+    pub fn dealloc_data(&mut self, block_id: u32) {
+        if self.is_shared(block_id) {
+            self.orphan_blocks.insert(block_id);
+            return;
+        }
+        for i in 0..self.per_block() {
+            get_block_cache(
+                (block_id + i) as usize,
+                Arc::clone(&self.block_device)
+            )
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| {
+                data_block.iter_mut().for_each(|p| { *p = 0; })
+            });
+        }
         self.data_bitmap.dealloc(
             &self.block_device,
-            (block_id - self.data_area_start_block) as usize
-        )
+            self.data_block_index(block_id) as usize
+        );
+        self.free_data += 1;
     }
 }
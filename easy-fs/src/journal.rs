@@ -0,0 +1,104 @@
+//! A write-ahead log of the blocks written by metadata operations
+//!
+//! While an operation such as create, link, unlink or rename runs inside a
+//! [`Transaction`], the block cache holds the blocks it dirties back from their
+//! homes. On commit they are written to the log first, then the log header with
+//! their homes marks it committed, and only then are they written home and the
+//! header cleared. A crash before the header is written loses the whole operation,
+//! one after it is finished by [`Journal::replay`] when the filesystem is opened.
+//!
+//! The log sits at the end of the device: a header block, then up to
+//! [`LOG_CAPACITY`] blocks. An operation dirtying more blocks than the cache
+//! holds is committed in several steps, each of them all or nothing.
+use super::{
+    block_cache_sync_all, get_block_cache, journal_begin, journal_end, BlockDevice,
+    BLOCK_CACHE_SIZE, BLOCK_SZ,
+};
+use alloc::sync::Arc;
+use core::convert::TryInto;
+
+/// Blocks one commit holds at most, there are never more dirty ones in the cache
+pub const LOG_CAPACITY: usize = BLOCK_CACHE_SIZE;
+/// The header and the log blocks
+pub const JOURNAL_BLOCKS: u32 = 1 + LOG_CAPACITY as u32;
+/// "jrnl", the first word of a committed header
+const COMMIT_MAGIC: u32 = 0x6c6e_726a;
+
+/// The log region of a filesystem
+#[derive(Clone)]
+pub struct Journal {
+    /// the header block, the log blocks follow it
+    start: usize,
+    block_device: Arc<dyn BlockDevice>,
+}
+
+impl Journal {
+    pub fn new(start: usize, block_device: Arc<dyn BlockDevice>) -> Self {
+        Self {
+            start,
+            block_device,
+        }
+    }
+    /// The device of the filesystem, the log and the homes of its blocks are on it
+    pub fn block_device(&self) -> &Arc<dyn BlockDevice> {
+        &self.block_device
+    }
+    /// Write `blocks` to the log, then the header holding their homes,
+    /// after which they are as good as written home
+    pub fn commit(&self, blocks: &[(usize, &[u8; BLOCK_SZ])]) {
+        assert!(blocks.len() <= LOG_CAPACITY);
+        let mut header = [0u8; BLOCK_SZ];
+        for (i, (home, data)) in blocks.iter().enumerate() {
+            self.block_device.write_block(self.start + 1 + i, &data[..]);
+            header[8 + 4 * i..12 + 4 * i].copy_from_slice(&(*home as u32).to_le_bytes());
+        }
+        header[..4].copy_from_slice(&COMMIT_MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&(blocks.len() as u32).to_le_bytes());
+        self.block_device.write_block(self.start, &header);
+    }
+    /// Forget the committed blocks once they are all written home
+    pub fn clear(&self) {
+        self.block_device.write_block(self.start, &[0u8; BLOCK_SZ]);
+    }
+    /// Write the blocks of a committed log home and clear it.
+    /// Return the number of blocks written, 0 if nothing was committed.
+    pub fn replay(&self) -> usize {
+        let mut header = [0u8; BLOCK_SZ];
+        self.block_device.read_block(self.start, &mut header);
+        let word = |i: usize| u32::from_le_bytes(header[4 * i..4 * i + 4].try_into().unwrap());
+        if word(0) != COMMIT_MAGIC {
+            return 0;
+        }
+        let count = (word(1) as usize).min(LOG_CAPACITY);
+        let mut data = [0u8; BLOCK_SZ];
+        for i in 0..count {
+            self.block_device.read_block(self.start + 1 + i, &mut data);
+            // through the cache, which may hold the block already
+            get_block_cache(word(2 + i) as usize, Arc::clone(&self.block_device))
+                .lock()
+                .modify(0, |block: &mut [u8; BLOCK_SZ]| block.copy_from_slice(&data));
+        }
+        block_cache_sync_all();
+        self.clear();
+        count
+    }
+    /// Start a metadata operation on the filesystem of this log
+    pub fn begin(&self) -> Transaction {
+        journal_begin(self.clone());
+        Transaction
+    }
+}
+
+/// A metadata operation in progress, committed when it is dropped
+pub struct Transaction;
+
+impl Transaction {
+    /// Commit the operation here rather than at the end of the scope
+    pub fn commit(self) {}
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        journal_end();
+    }
+}
@@ -1,25 +1,45 @@
-use super::{get_block_cache, BlockDevice, BLOCK_SZ};
+use super::{get_block_cache, now, BlockDevice, BLOCK_SZ};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::fmt::{Debug, Formatter, Result};
 
-/// Magic number for sanity check
-const EFS_MAGIC: u32 = 0x3b800001;
+/// Magic number for sanity check, bumped when the super block
+/// recorded the block size
+const EFS_MAGIC: u32 = 0x3b800004;
 /// The max number of direct inodes
-const INODE_DIRECT_COUNT: usize = 27;
+const INODE_DIRECT_COUNT: usize = 17;
 /// The max length of inode name
 const NAME_LENGTH_LIMIT: usize = 27;
-/// The max number of indirect1 inodes
-const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
-/// The max number of indirect2 inodes
-const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
-/// The upper bound of direct inode index
-const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
-/// The upper bound of indirect1 inode index
-const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
-/// The upper bound of indirect2 inode index
-#[allow(unused)]
-const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INODE_INDIRECT2_COUNT;
+/// Entries of an indirect block kept in one block of the device,
+/// a larger indirect block goes on in the blocks after it
+const DEVICE_BLOCK_ENTRIES: usize = BLOCK_SZ / 4;
+/// The largest size of a file on a filesystem of [`BLOCK_SZ`] blocks
+pub const MAX_FILE_SIZE: usize = max_file_size(BLOCK_SZ);
+
+/// The largest size of a file on a filesystem of `block_size` blocks, every block of it
+/// reached through indirect3 at most and its size kept in a `u32`
+pub const fn max_file_size(block_size: usize) -> usize {
+    let size = DiskInode::bounds(block_size)[3] * block_size;
+    if size > u32::MAX as usize {
+        u32::MAX as usize
+    } else {
+        size
+    }
+}
+
+/// The largest block size a filesystem can have
+pub const MAX_BLOCK_SZ: usize = 4096;
+/// Whether a filesystem can have blocks of `block_size` bytes: a power of two
+/// from [`BLOCK_SZ`] to [`MAX_BLOCK_SZ`], each made of whole blocks of the device
+pub fn is_valid_block_size(block_size: usize) -> bool {
+    block_size.is_power_of_two() && (BLOCK_SZ..=MAX_BLOCK_SZ).contains(&block_size)
+}
+/// Owner may read, in [`DiskInode::mode`]
+pub const MODE_READ: u16 = 0o400;
+/// Owner may write
+pub const MODE_WRITE: u16 = 0o200;
+/// Owner may execute a file, or look up names in a directory
+pub const MODE_EXEC: u16 = 0o100;
 
 /// Super block of a filesystem
 #[repr(C)]
@@ -30,6 +50,12 @@ pub struct SuperBlock {
     pub inode_area_blocks: u32,
     pub data_bitmap_blocks: u32,
     pub data_area_blocks: u32,
+    /// blocks of the journal at the end of the device
+    pub journal_blocks: u32,
+    /// size of a data block in bytes, `block_size / BLOCK_SZ` blocks of the device.
+    /// The areas, the data area too, are counted in blocks of the device, while
+    /// a bit of the data bitmap stands for a whole data block.
+    pub block_size: u32,
 }
 
 impl Debug for SuperBlock {
@@ -40,6 +66,8 @@ impl Debug for SuperBlock {
             .field("inode_area_blocks", &self.inode_area_blocks)
             .field("data_bitmap_blocks", &self.data_bitmap_blocks)
             .field("data_area_blocks", &self.data_area_blocks)
+            .field("journal_blocks", &self.journal_blocks)
+            .field("block_size", &self.block_size)
             .finish()
     }
 }
@@ -53,6 +81,8 @@ impl SuperBlock {
         inode_area_blocks: u32,
         data_bitmap_blocks: u32,
         data_area_blocks: u32,
+        journal_blocks: u32,
+        block_size: u32,
     ) {
         *self = Self {
             magic: EFS_MAGIC,
@@ -61,257 +91,479 @@ impl SuperBlock {
             inode_area_blocks,
             data_bitmap_blocks,
             data_area_blocks,
+            journal_blocks,
+            block_size,
         }
     }
     /// Check if a super block is valid using efs magic
     pub fn is_valid(&self) -> bool {
-        self.magic == EFS_MAGIC
+        self.magic == EFS_MAGIC && is_valid_block_size(self.block_size as usize)
     }
 }
 
 /// Type of a disk inode
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum DiskInodeType {
     File,
     Directory,
+    /// A file of fixed size written round and round, see [`DiskInode::ring_write`]
+    Ring,
+    /// A path to another inode kept as its data, see [`crate::Inode::symlink`]
+    Symlink,
+}
+
+/// Where a disk inode keeps the id of one of its blocks
+#[derive(Clone, Copy, Debug)]
+pub enum BlockPtr {
+    /// `direct[i]` of the disk inode
+    Direct(usize),
+    /// `indirect1` of the disk inode
+    Indirect1,
+    /// `indirect2` of the disk inode
+    Indirect2,
+    /// `indirect3` of the disk inode
+    Indirect3,
+    /// The `i`-th entry of an indirect block
+    InIndirect(u32, usize),
 }
 
-/// A indirect block
-type IndirectBlock = [u32; BLOCK_SZ / 4];
-/// A data block
+/// The part of an indirect block in one block of the device
+type IndirectBlock = [u32; DEVICE_BLOCK_ENTRIES];
+/// The part of a data block in one block of the device
 type DataBlock = [u8; BLOCK_SZ];
 
-/// A disk inode
+/// Read entry `i` of the indirect block at `block_id`
+fn read_entry(block_id: u32, i: usize, block_device: &Arc<dyn BlockDevice>) -> u32 {
+    get_block_cache(
+        block_id as usize + i / DEVICE_BLOCK_ENTRIES,
+        Arc::clone(block_device),
+    )
+    .lock()
+    .read(0, |indirect: &IndirectBlock| {
+        indirect[i % DEVICE_BLOCK_ENTRIES]
+    })
+}
+
+/// Call a function over entry `i` of the indirect block at `block_id` to modify it
+fn modify_entry<V>(
+    block_id: u32,
+    i: usize,
+    block_device: &Arc<dyn BlockDevice>,
+    f: impl FnOnce(&mut u32) -> V,
+) -> V {
+    get_block_cache(
+        block_id as usize + i / DEVICE_BLOCK_ENTRIES,
+        Arc::clone(block_device),
+    )
+    .lock()
+    .modify(0, |indirect: &mut IndirectBlock| {
+        f(&mut indirect[i % DEVICE_BLOCK_ENTRIES])
+    })
+}
+
+/// A disk inode, 128 bytes so that four of them fill a block
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DiskInode {
     pub size: u32,
     pub direct: [u32; INODE_DIRECT_COUNT],
     pub indirect1: u32,
     pub indirect2: u32,
+    pub indirect3: u32,
     /// 硬链接的数量
     pub nlink: u32,
+    /// Offset of the oldest byte of a ring
+    pub ring_start: u32,
+    /// Bytes of a ring not read yet
+    pub ring_len: u32,
+    /// Most bytes a file may grow to, 0 for no limit, see [`crate::Inode::set_size_quota`]
+    pub max_size: u32,
     type_: DiskInodeType,
+    /// log2 of the block size of the filesystem, for the inode to find its blocks
+    /// on its own. Fits in the padding after `type_`.
+    block_bits: u8,
+    /// Permission bits as in the low 9 bits of a unix mode, only those of the owner
+    /// are checked, there being no other users. Fits in the padding after `block_bits`.
+    pub mode: u16,
+    /// Last read, in microseconds like the rest of the timestamps
+    pub atime: u64,
+    /// Last change of the data
+    pub mtime: u64,
+    /// Last change of the data or the links
+    pub ctime: u64,
 }
 
 impl DiskInode {
-    /// Initialize a disk inode, as well as all direct inodes under it
-    /// indirect1 and indirect2 block are allocated only when they are needed
-    pub fn initialize(&mut self, type_: DiskInodeType) {
+    /// Initialize a disk inode on a filesystem of `block_size` blocks, as well as all
+    /// direct inodes under it
+    /// indirect1, indirect2 and indirect3 block are allocated only when they are needed
+    pub fn initialize(&mut self, type_: DiskInodeType, block_size: usize) {
         self.size = 0;
         self.direct.iter_mut().for_each(|v| *v = 0);
         self.indirect1 = 0;
         self.indirect2 = 0;
+        self.indirect3 = 0;
         self.nlink = 1;
+        self.ring_start = 0;
+        self.ring_len = 0;
+        self.max_size = 0;
+        self.mode = match type_ {
+            DiskInodeType::Directory => 0o755,
+            DiskInodeType::Symlink => 0o777,
+            DiskInodeType::File | DiskInodeType::Ring => 0o644,
+        };
         self.type_ = type_;
+        self.block_bits = block_size.trailing_zeros() as u8;
+        let now = now();
+        self.atime = now;
+        self.mtime = now;
+        self.ctime = now;
+    }
+    /// Record a change of the data
+    pub fn touch_data(&mut self) {
+        self.mtime = now();
+        self.ctime = self.mtime;
+    }
+    /// Record a change of the links
+    pub fn touch_links(&mut self) {
+        self.ctime = now();
+    }
+    /// Whether the owner may access this inode in all the ways `access` asks for,
+    /// a mask of [`MODE_READ`], [`MODE_WRITE`] and [`MODE_EXEC`]
+    pub fn permits(&self, access: u16) -> bool {
+        self.mode & access == access
+    }
+    /// Bytes that can be written at `offset` before the size quota is reached
+    pub fn room_below_quota(&self, offset: usize) -> usize {
+        if self.max_size == 0 {
+            usize::MAX
+        } else {
+            (self.max_size as usize).saturating_sub(offset)
+        }
     }
     /// Whether this inode is a directory
     pub fn is_dir(&self) -> bool {
         self.type_ == DiskInodeType::Directory
     }
-    /// Whether this inode is a file
+    /// Whether this inode is a file, rings included
     #[allow(unused)]
     pub fn is_file(&self) -> bool {
-        self.type_ == DiskInodeType::File
+        self.type_ != DiskInodeType::Directory
+    }
+    /// Whether this inode is a ring
+    pub fn is_ring(&self) -> bool {
+        self.type_ == DiskInodeType::Ring
+    }
+    /// Whether this inode is a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.type_ == DiskInodeType::Symlink
+    }
+    /// Exchange the size and all data blocks with `other`, which must be of the same type
+    pub fn swap_data(&mut self, other: &mut DiskInode) {
+        assert!(self.type_ == other.type_);
+        core::mem::swap(&mut self.size, &mut other.size);
+        core::mem::swap(&mut self.direct, &mut other.direct);
+        core::mem::swap(&mut self.indirect1, &mut other.indirect1);
+        core::mem::swap(&mut self.indirect2, &mut other.indirect2);
+        core::mem::swap(&mut self.indirect3, &mut other.indirect3);
+        core::mem::swap(&mut self.ring_start, &mut other.ring_start);
+        core::mem::swap(&mut self.ring_len, &mut other.ring_len);
+    }
+    /// Size of a data block, the block size of the filesystem
+    pub fn block_size(&self) -> usize {
+        1 << self.block_bits
+    }
+    /// The upper bounds of the inner ids of the direct blocks, and of the blocks
+    /// under indirect1 to indirect3, with `block_size` blocks
+    const fn bounds(block_size: usize) -> [usize; 4] {
+        let n = block_size / 4;
+        let direct = INODE_DIRECT_COUNT;
+        [
+            direct,
+            direct + n,
+            direct + n + n * n,
+            direct + n + n * n + n * n * n,
+        ]
     }
     /// Get the number of data blocks corresponding to size
     pub fn data_blocks(&self) -> u32 {
-        Self::_data_blocks(self.size)
+        Self::_data_blocks(self.size, self.block_size())
     }
-    fn _data_blocks(size: u32) -> u32 {
-        (size + BLOCK_SZ as u32 - 1) / BLOCK_SZ as u32
+    fn _data_blocks(size: u32, block_size: usize) -> u32 {
+        ((size as usize + block_size - 1) / block_size) as u32
     }
-    /// Get the number of data blocks required for the given size of data
-    pub fn total_blocks(size: u32) -> u32 {
-        let data_blocks = Self::_data_blocks(size) as usize;
+    /// Get the number of data blocks of `block_size` bytes required for the given size of data
+    pub fn total_blocks(size: u32, block_size: usize) -> u32 {
+        let data_blocks = Self::_data_blocks(size, block_size) as usize;
         let mut total = data_blocks;
+        // an indirect block for every started run of `covered` data blocks in `start..end`
+        let indirect_blocks = |start: usize, end: usize, covered: usize| {
+            (data_blocks.min(end).saturating_sub(start) + covered - 1) / covered
+        };
+        let n = block_size / 4;
+        let [direct, indirect1, indirect2, indirect3] = Self::bounds(block_size);
         // indirect1
-        if data_blocks > INODE_DIRECT_COUNT {
-            total += 1;
+        total += indirect_blocks(direct, indirect1, n);
+        // indirect2 and the indirect1 blocks under it
+        total += indirect_blocks(indirect1, indirect2, n * n);
+        total += indirect_blocks(indirect1, indirect2, n);
+        // indirect3 and the indirect2 and indirect1 blocks under it
+        total += indirect_blocks(indirect2, indirect3, n * n * n);
+        total += indirect_blocks(indirect2, indirect3, n * n);
+        total += indirect_blocks(indirect2, indirect3, n);
+        total as u32
+    }
+    /// Inner ids of the blocks the bytes `[offset, offset + len)` fall in
+    fn blocks_of_range(&self, offset: usize, len: usize) -> core::ops::Range<usize> {
+        if len == 0 {
+            return 0..0;
+        }
+        let block_size = self.block_size();
+        offset / block_size..(offset + len + block_size - 1) / block_size
+    }
+    /// Get the number of blocks that have to be allocated for the bytes
+    /// `[offset, offset + len)` to have blocks under them, indirect blocks included
+    pub fn blocks_missing(
+        &self,
+        offset: usize,
+        len: usize,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> u32 {
+        let range = self.blocks_of_range(offset, len);
+        let start = range.start;
+        let mut missing = 0;
+        for inner_id in range {
+            let (level, path) = self.locate(inner_id);
+            if level == 0 {
+                missing += (self.direct[path[0]] == 0) as u32;
+                continue;
+            }
+            let blocks = self.walk(level, &path, block_device);
+            // a missing indirect block is counted with the first block under it
+            for depth in 0..=level {
+                let first = inner_id == start || path[depth..level].iter().all(|&i| i == 0);
+                if blocks[depth] == 0 && first {
+                    missing += 1;
+                }
+            }
+        }
+        missing
+    }
+    /// Split a block index into its level, 0 for the direct blocks and 1 to 3 for
+    /// the blocks under indirect1 to indirect3, and the entries to follow down
+    /// from the top indirect block of that level, only the first `level` being used.
+    /// A direct block keeps its index in the first entry.
+    fn locate(&self, inner_id: usize) -> (usize, [usize; 3]) {
+        let n = self.block_size() / 4;
+        let [direct, indirect1, indirect2, indirect3] = Self::bounds(self.block_size());
+        if inner_id < direct {
+            (0, [inner_id, 0, 0])
+        } else if inner_id < indirect1 {
+            (1, [inner_id - direct, 0, 0])
+        } else if inner_id < indirect2 {
+            let i = inner_id - indirect1;
+            (2, [i / n, i % n, 0])
+        } else {
+            assert!(inner_id < indirect3);
+            let i = inner_id - indirect2;
+            (3, [i / (n * n), i / n % n, i % n])
         }
-        // indirect2
-        if data_blocks > INDIRECT1_BOUND {
-            total += 1;
-            // sub indirect1
-            total +=
-                (data_blocks - INDIRECT1_BOUND + INODE_INDIRECT1_COUNT - 1) / INODE_INDIRECT1_COUNT;
+    }
+    /// The top indirect block of a level
+    fn indirect(&self, level: usize) -> u32 {
+        match level {
+            1 => self.indirect1,
+            2 => self.indirect2,
+            _ => self.indirect3,
         }
-        total as u32
     }
-    /// Get the number of data blocks that have to be allocated given the new size of data
-    pub fn blocks_num_needed(&self, new_size: u32) -> u32 {
-        assert!(new_size >= self.size);
-        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+    fn indirect_mut(&mut self, level: usize) -> &mut u32 {
+        match level {
+            1 => &mut self.indirect1,
+            2 => &mut self.indirect2,
+            _ => &mut self.indirect3,
+        }
     }
-    /// Get id of block given inner id
+    /// Get the blocks met on the way from the top indirect block of `level`
+    /// down along `path`, the data block being the last of them, all 0 below a hole
+    fn walk(
+        &self,
+        level: usize,
+        path: &[usize; 3],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> [u32; 4] {
+        let mut blocks = [self.indirect(level), 0, 0, 0];
+        for depth in 0..level {
+            if blocks[depth] == 0 {
+                break;
+            }
+            blocks[depth + 1] = read_entry(blocks[depth], path[depth], block_device);
+        }
+        blocks
+    }
+    /// Get id of block given inner id, 0 for a hole
     pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
-        let inner_id = inner_id as usize;
-        if inner_id < INODE_DIRECT_COUNT {
-            self.direct[inner_id]
-        } else if inner_id < INDIRECT1_BOUND {
-            get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-                .lock()
-                .read(0, |indirect_block: &IndirectBlock| {
-                    indirect_block[inner_id - INODE_DIRECT_COUNT]
-                })
-        } else {
-            let last = inner_id - INDIRECT1_BOUND;
-            let indirect1 = get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
-                .lock()
-                .read(0, |indirect2: &IndirectBlock| {
-                    indirect2[last / INODE_INDIRECT1_COUNT]
-                });
-            get_block_cache(indirect1 as usize, Arc::clone(block_device))
-                .lock()
-                .read(0, |indirect1: &IndirectBlock| {
-                    indirect1[last % INODE_INDIRECT1_COUNT]
-                })
+        let (level, path) = self.locate(inner_id as usize);
+        if level == 0 {
+            return self.direct[path[0]];
+        }
+        self.walk(level, &path, block_device)[level]
+    }
+    /// Get id of the block of the device holding the `i`-th [`BLOCK_SZ`] bytes
+    /// of the data, part of a larger data block, 0 for a hole
+    fn device_block_id(&self, i: usize, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        let per_block = self.block_size() / BLOCK_SZ;
+        match self.get_block_id((i / per_block) as u32, block_device) {
+            0 => 0,
+            block_id => block_id + (i % per_block) as u32,
+        }
+    }
+    /// Get where the id of a block given inner id is kept
+    pub fn data_block_ptr(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> BlockPtr {
+        let (level, path) = self.locate(inner_id as usize);
+        if level == 0 {
+            return BlockPtr::Direct(path[0]);
         }
+        let blocks = self.walk(level, &path, block_device);
+        BlockPtr::InIndirect(blocks[level - 1], path[level - 1])
     }
-    /// Inncrease the size of current disk inode
-    pub fn increase_size(
+    /// Get all blocks in use by current disk inode, indirect blocks included,
+    /// together with where they are pointed to. Holes have no blocks.
+    pub fn block_ptrs(&self, block_device: &Arc<dyn BlockDevice>) -> Vec<(BlockPtr, u32)> {
+        let mut v: Vec<(BlockPtr, u32)> = Vec::new();
+        // the last block met at each depth, an indirect block shows up
+        // right before the first block under it
+        let mut met = [0u32; 4];
+        for inner_id in 0..self.data_blocks() as usize {
+            let (level, path) = self.locate(inner_id);
+            if level == 0 {
+                if self.direct[path[0]] != 0 {
+                    v.push((BlockPtr::Direct(path[0]), self.direct[path[0]]));
+                }
+                continue;
+            }
+            let blocks = self.walk(level, &path, block_device);
+            for depth in 0..=level {
+                if blocks[depth] == 0 {
+                    break;
+                }
+                if blocks[depth] == met[depth] {
+                    continue;
+                }
+                met[depth] = blocks[depth];
+                let ptr = match depth {
+                    0 if level == 1 => BlockPtr::Indirect1,
+                    0 if level == 2 => BlockPtr::Indirect2,
+                    0 => BlockPtr::Indirect3,
+                    _ => BlockPtr::InIndirect(blocks[depth - 1], path[depth - 1]),
+                };
+                v.push((ptr, blocks[depth]));
+            }
+        }
+        v
+    }
+    /// Point `ptr` of current disk inode to `block_id`
+    pub fn set_block_ptr(
         &mut self,
-        new_size: u32,
+        ptr: BlockPtr,
+        block_id: u32,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        match ptr {
+            BlockPtr::Direct(i) => self.direct[i] = block_id,
+            BlockPtr::Indirect1 => self.indirect1 = block_id,
+            BlockPtr::Indirect2 => self.indirect2 = block_id,
+            BlockPtr::Indirect3 => self.indirect3 = block_id,
+            BlockPtr::InIndirect(indirect, i) => {
+                modify_entry(indirect, i, block_device, |entry| *entry = block_id);
+            }
+        }
+    }
+    /// Grow current disk inode to cover the bytes `[offset, offset + len)` and give the
+    /// holes among their blocks the `new_blocks`, as many as [`DiskInode::blocks_missing`]
+    /// counts. Blocks between the old end and `offset` stay holes.
+    pub fn back_range(
+        &mut self,
+        offset: usize,
+        len: usize,
         new_blocks: Vec<u32>,
         block_device: &Arc<dyn BlockDevice>,
     ) {
-        let mut current_blocks = self.data_blocks();
-        self.size = new_size;
-        let mut total_blocks = self.data_blocks();
-        let mut new_blocks = new_blocks.into_iter();
-        // fill direct
-        while current_blocks < total_blocks.min(INODE_DIRECT_COUNT as u32) {
-            self.direct[current_blocks as usize] = new_blocks.next().unwrap();
-            current_blocks += 1;
+        if (offset + len) as u32 > self.size {
+            self.size = (offset + len) as u32;
+            self.touch_data();
         }
-        // alloc indirect1
-        if total_blocks > INODE_DIRECT_COUNT as u32 {
-            if current_blocks == INODE_DIRECT_COUNT as u32 {
-                self.indirect1 = new_blocks.next().unwrap();
+        let mut new_blocks = new_blocks.into_iter();
+        for inner_id in self.blocks_of_range(offset, len) {
+            let (level, path) = self.locate(inner_id);
+            if level == 0 {
+                if self.direct[path[0]] == 0 {
+                    self.direct[path[0]] = new_blocks.next().unwrap();
+                }
+                continue;
+            }
+            if self.indirect(level) == 0 {
+                *self.indirect_mut(level) = new_blocks.next().unwrap();
+            }
+            // a new indirect block is all zeros, a hole under every entry,
+            // until the data block itself is pointed to at the bottom
+            let mut block = self.indirect(level);
+            for &i in path[..level].iter() {
+                block = modify_entry(block, i, block_device, |entry| {
+                    if *entry == 0 {
+                        *entry = new_blocks.next().unwrap();
+                    }
+                    *entry
+                });
             }
-            current_blocks -= INODE_DIRECT_COUNT as u32;
-            total_blocks -= INODE_DIRECT_COUNT as u32;
-        } else {
-            return;
         }
-        // fill indirect1
-        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect1: &mut IndirectBlock| {
-                while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT as u32) {
-                    indirect1[current_blocks as usize] = new_blocks.next().unwrap();
-                    current_blocks += 1;
+        assert!(new_blocks.next().is_none());
+    }
+    /// Decrease the size of current disk inode
+    /// and return blocks that should be deallocated
+    pub fn decrease_size(
+        &mut self,
+        new_size: u32,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Vec<u32> {
+        assert!(new_size <= self.size);
+        let mut v: Vec<u32> = Vec::new();
+        let current_blocks = self.data_blocks() as usize;
+        let total_blocks = Self::_data_blocks(new_size, self.block_size()) as usize;
+        // from the end, so that an indirect block goes after every block under it
+        for inner_id in (total_blocks..current_blocks).rev() {
+            let (level, path) = self.locate(inner_id);
+            if level == 0 {
+                if self.direct[path[0]] != 0 {
+                    v.push(self.direct[path[0]]);
+                    self.direct[path[0]] = 0;
                 }
-            });
-        // alloc indirect2
-        if total_blocks > INODE_INDIRECT1_COUNT as u32 {
-            if current_blocks == INODE_INDIRECT1_COUNT as u32 {
-                self.indirect2 = new_blocks.next().unwrap();
+                continue;
             }
-            current_blocks -= INODE_INDIRECT1_COUNT as u32;
-            total_blocks -= INODE_INDIRECT1_COUNT as u32;
-        } else {
-            return;
-        }
-        // fill indirect2 from (a0, b0) -> (a1, b1)
-        let mut a0 = current_blocks as usize / INODE_INDIRECT1_COUNT;
-        let mut b0 = current_blocks as usize % INODE_INDIRECT1_COUNT;
-        let a1 = total_blocks as usize / INODE_INDIRECT1_COUNT;
-        let b1 = total_blocks as usize % INODE_INDIRECT1_COUNT;
-        // alloc low-level indirect1
-        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect2: &mut IndirectBlock| {
-                while (a0 < a1) || (a0 == a1 && b0 < b1) {
-                    // log::debug!("a0: {a0}, b0: {b0}, a1: {a1}, b1: {b1}");
-                    if b0 == 0 {
-                        indirect2[a0] = new_blocks.next().unwrap();
-                    }
-                    // fill current
-                    get_block_cache(indirect2[a0] as usize, Arc::clone(block_device))
-                        .lock()
-                        .modify(0, |indirect1: &mut IndirectBlock| {
-                            indirect1[b0] = new_blocks.next().unwrap();
-                        });
-                    // move to next
-                    b0 += 1;
-                    if b0 == INODE_INDIRECT1_COUNT {
-                        b0 = 0;
-                        a0 += 1;
-                    }
+            let blocks = self.walk(level, &path, block_device);
+            // an indirect block goes with the first block under it, and the entry
+            // pointing to a block goes back to a hole, for the blocks kept to grow again
+            for depth in (1..=level).rev() {
+                if blocks[depth] != 0 && path[depth..level].iter().all(|&i| i == 0) {
+                    v.push(blocks[depth]);
+                    modify_entry(blocks[depth - 1], path[depth - 1], block_device, |entry| {
+                        *entry = 0
+                    });
                 }
-            });
+            }
+            if blocks[0] != 0 && path[..level].iter().all(|&i| i == 0) {
+                v.push(blocks[0]);
+                *self.indirect_mut(level) = 0;
+            }
+        }
+        self.size = new_size;
+        self.touch_data();
+        v
     }
     /// Clear size to zero and return blocks that should be deallocated
     /// and clear the block contents to zero later
     pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
-        let mut v: Vec<u32> = Vec::new();
-        let mut data_blocks = self.data_blocks() as usize;
-        self.size = 0;
-        let mut current_blocks = 0usize;
-        // direct
-        while current_blocks < data_blocks.min(INODE_DIRECT_COUNT) {
-            v.push(self.direct[current_blocks]);
-            self.direct[current_blocks] = 0;
-            current_blocks += 1;
-        }
-        // indirect1 block
-        if data_blocks > INODE_DIRECT_COUNT {
-            v.push(self.indirect1);
-            data_blocks -= INODE_DIRECT_COUNT;
-            current_blocks = 0;
-        } else {
-            return v;
-        }
-        // indirect1
-        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect1: &mut IndirectBlock| {
-                while current_blocks < data_blocks.min(INODE_INDIRECT1_COUNT) {
-                    v.push(indirect1[current_blocks]);
-                    //indirect1[current_blocks] = 0;
-                    current_blocks += 1;
-                }
-            });
-        self.indirect1 = 0;
-        // indirect2 block
-        if data_blocks > INODE_INDIRECT1_COUNT {
-            v.push(self.indirect2);
-            data_blocks -= INODE_INDIRECT1_COUNT;
-        } else {
-            return v;
-        }
-        // indirect2
-        assert!(data_blocks <= INODE_INDIRECT2_COUNT);
-        let a1 = data_blocks / INODE_INDIRECT1_COUNT;
-        let b1 = data_blocks % INODE_INDIRECT1_COUNT;
-        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect2: &mut IndirectBlock| {
-                // full indirect1 blocks
-                indirect2.iter().take(a1).for_each(|&ti| {
-                    v.push(ti);
-                    get_block_cache(ti as usize, Arc::clone(block_device))
-                        .lock()
-                        .modify(0, |indirect1: &mut IndirectBlock| {
-                            log::debug!("[7] clear_size, indirect1:{indirect1:?}");
-                            v.extend_from_slice(indirect1);
-                        });
-                    //indirect2[i] = 0;
-                });
-                // last indirect1 block
-                if b1 > 0 {
-                    v.push(indirect2[a1]);
-                    get_block_cache(indirect2[a1] as usize, Arc::clone(block_device))
-                        .lock()
-                        .modify(0, |indirect1: &mut IndirectBlock| {
-                            indirect1.iter().take(b1).for_each(|&j| v.push(j));
-                        });
-                    //indirect2[a1] = 0;
-                }
-            });
-        self.indirect2 = 0;
-        v
+        self.decrease_size(0, block_device)
     }
     /// Read data from current disk inode
     pub fn read_at(
@@ -334,15 +586,16 @@ impl DiskInode {
             // read and update read size
             let block_read_size = end_current_block - start;
             let dst = &mut buf[read_size..read_size + block_read_size];
-            get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
-                Arc::clone(block_device),
-            )
-            .lock()
-            .read(0, |data_block: &DataBlock| {
-                let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
-                dst.copy_from_slice(src);
-            });
+            match self.device_block_id(start_block, block_device) {
+                // a hole reads as zeros
+                0 => dst.fill(0),
+                block_id => get_block_cache(block_id as usize, Arc::clone(block_device))
+                    .lock()
+                    .read(0, |data_block: &DataBlock| {
+                        let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
+                        dst.copy_from_slice(src);
+                    }),
+            }
             read_size += block_read_size;
             // move to next block
             if end_current_block == end {
@@ -354,7 +607,7 @@ impl DiskInode {
         read_size
     }
     /// Write data into current disk inode
-    /// size must be adjusted properly beforehand
+    /// size must be adjusted and the blocks backed properly beforehand
     pub fn write_at(
         &mut self,
         offset: usize,
@@ -364,6 +617,7 @@ impl DiskInode {
         let mut start = offset;
         let end = (offset + buf.len()).min(self.size as usize);
         assert!(start <= end);
+        self.touch_data();
         let mut start_block = start / BLOCK_SZ;
         let mut write_size = 0usize;
         loop {
@@ -372,16 +626,16 @@ impl DiskInode {
             end_current_block = end_current_block.min(end);
             // write and update write size
             let block_write_size = end_current_block - start;
-            get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
-                Arc::clone(block_device),
-            )
-            .lock()
-            .modify(0, |data_block: &mut DataBlock| {
-                let src = &buf[write_size..write_size + block_write_size];
-                let dst = &mut data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_write_size];
-                dst.copy_from_slice(src);
-            });
+            let block_id = self.device_block_id(start_block, block_device);
+            assert!(block_id != 0, "write to a hole");
+            get_block_cache(block_id as usize, Arc::clone(block_device))
+                .lock()
+                .modify(0, |data_block: &mut DataBlock| {
+                    let src = &buf[write_size..write_size + block_write_size];
+                    let dst =
+                        &mut data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_write_size];
+                    dst.copy_from_slice(src);
+                });
             write_size += block_write_size;
             // move to next block
             if end_current_block == end {
@@ -392,6 +646,45 @@ impl DiskInode {
         }
         write_size
     }
+    /// Write `buf` after the newest data of a ring, the size being its capacity.
+    /// Once the ring is full the oldest data is overwritten, so only the last
+    /// `size` bytes of `buf` can survive. Return the length of `buf`.
+    pub fn ring_write(&mut self, buf: &[u8], block_device: &Arc<dyn BlockDevice>) -> usize {
+        let cap = self.size as usize;
+        if cap == 0 {
+            return 0;
+        }
+        let data = &buf[buf.len().saturating_sub(cap)..];
+        let mut written = 0usize;
+        while written < data.len() {
+            let len = self.ring_len as usize;
+            let tail = (self.ring_start as usize + len) % cap;
+            let chunk = (cap - tail).min(data.len() - written);
+            self.write_at(tail, &data[written..written + chunk], block_device);
+            written += chunk;
+            // whatever did not fit pushed the oldest bytes out
+            let overwritten = (len + chunk).saturating_sub(cap);
+            self.ring_start = ((self.ring_start as usize + overwritten) % cap) as u32;
+            self.ring_len = (len + chunk).min(cap) as u32;
+        }
+        buf.len()
+    }
+    /// Read the oldest data of a ring into `buf` and drop it,
+    /// return 0 when everything written has been read
+    pub fn ring_read(&mut self, buf: &mut [u8], block_device: &Arc<dyn BlockDevice>) -> usize {
+        let cap = self.size as usize;
+        let total = buf.len().min(self.ring_len as usize);
+        let mut read = 0usize;
+        while read < total {
+            let start = self.ring_start as usize;
+            let chunk = (cap - start).min(total - read);
+            self.read_at(start, &mut buf[read..read + chunk], block_device);
+            read += chunk;
+            self.ring_start = ((start + chunk) % cap) as u32;
+            self.ring_len -= chunk as u32;
+        }
+        read
+    }
 }
 
 /// A directory entry
@@ -404,6 +697,10 @@ pub struct DirEntry {
 /// Size of a directory entry
 pub const DIRENT_SZ: usize = 32;
 
+/// Inode number of an entry whose file was unlinked. Its name stays, so the
+/// entries around it stay in order and none of them has to move.
+const TOMBSTONE: u32 = u32::MAX;
+
 impl DirEntry {
     /// Create an empty directory entry
     pub fn empty() -> Self {
@@ -438,4 +735,16 @@ impl DirEntry {
     pub fn inode_number(&self) -> u32 {
         self.inode_number
     }
+    /// Whether the entry is a free slot, a tombstone included
+    pub fn is_empty(&self) -> bool {
+        self.name[0] == 0 || self.is_tombstone()
+    }
+    /// Whether the entry is left from an unlinked file, see [`DirEntry::bury`]
+    pub fn is_tombstone(&self) -> bool {
+        self.inode_number == TOMBSTONE
+    }
+    /// Turn the entry into a tombstone, which keeps the name it had
+    pub fn bury(&mut self) {
+        self.inode_number = TOMBSTONE;
+    }
 }
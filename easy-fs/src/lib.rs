@@ -8,12 +8,36 @@ mod efs;
 mod bitmap;
 mod vfs;
 mod block_cache;
+mod snapshot;
+mod lock;
+mod clock;
+mod journal;
+mod mount;
 
 /// Use a block size of 512 bytes
 pub const BLOCK_SZ: usize = 512;
 pub use block_dev::BlockDevice;
-pub use efs::EasyFileSystem;
-pub use vfs::Inode;
+pub use efs::{DirQuota, EasyFileSystem, FsInfo, StatFs};
+pub use layout::{
+    is_valid_block_size, max_file_size, MAX_FILE_SIZE, MODE_EXEC, MODE_READ, MODE_WRITE,
+};
+pub use vfs::{Inode, MAX_SYMLINK_FOLLOWS, READ_AHEAD_BLOCKS};
+pub use snapshot::Snapshot;
+pub use lock::{lock_stats, LockHooks, LockStats};
+#[cfg(feature = "lockdep")]
+pub use lock::{set_lock_hooks, take_fs_and_inode_locks};
+pub use clock::set_clock;
+pub use block_cache::{
+    block_cache_discard_all, block_cache_release, block_cache_stats, block_cache_sync_all,
+    block_cached, BlockCacheStats, BLOCK_CACHE_SIZE,
+};
 use layout::*;
 use bitmap::Bitmap;
-use block_cache::{get_block_cache, block_cache_sync_all};
+use lock::{CountedGuard, CountedMutex, InodeLock};
+use lock::{BLOCK_CACHE_LOCK_COUNTER, FS_LOCK_COUNTER};
+use clock::now;
+use block_cache::{get_block_cache, block_cache_sync, pin_block_cache, unpin_block_cache};
+use block_cache::prefetch_block_cache;
+use block_cache::{journal_begin, journal_end};
+use journal::{Journal, Transaction, JOURNAL_BLOCKS};
+use mount::{add_mount, has_mounts, mount_point_of, mounted_on, remove_mount};
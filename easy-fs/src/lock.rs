@@ -0,0 +1,229 @@
+//! Spin locks which count how often they had to wait, and the locks of inodes
+//!
+//! Locks are taken in the order inode lock, `EasyFileSystem` lock, block cache lock,
+//! one who holds a later one only ever tries an earlier one.
+//!
+//! With the `lockdep` feature, every lock taken and given back is reported to the
+//! [`LockHooks`] a kernel sets, by the class of the lock, so that the kernel can check
+//! the order they are taken in together with its own locks.
+use super::BlockDevice;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "lockdep")]
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+use spin::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Told the class of each lock of easy-fs when it is taken and when it is given back
+pub struct LockHooks {
+    pub acquire: fn(&'static str),
+    pub release: fn(&'static str),
+}
+
+#[cfg(feature = "lockdep")]
+static LOCK_HOOKS: AtomicPtr<LockHooks> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Report the locks taken and given back to `hooks` from now on
+#[cfg(feature = "lockdep")]
+pub fn set_lock_hooks(hooks: &'static LockHooks) {
+    LOCK_HOOKS.store(hooks as *const LockHooks as *mut LockHooks, Ordering::Release);
+}
+
+fn note_acquire(_class: &'static str) {
+    #[cfg(feature = "lockdep")]
+    if let Some(hooks) = unsafe { LOCK_HOOKS.load(Ordering::Acquire).as_ref() } {
+        (hooks.acquire)(_class);
+    }
+}
+
+fn note_release(_class: &'static str) {
+    #[cfg(feature = "lockdep")]
+    if let Some(hooks) = unsafe { LOCK_HOOKS.load(Ordering::Acquire).as_ref() } {
+        (hooks.release)(_class);
+    }
+}
+
+/// A guard of a lock of class `class`, noting the lock given back when dropped
+pub struct Tracked<G> {
+    guard: G,
+    class: &'static str,
+}
+
+impl<G> Tracked<G> {
+    fn new(guard: G, class: &'static str) -> Self {
+        note_acquire(class);
+        Self { guard, class }
+    }
+}
+
+impl<G: Deref> Deref for Tracked<G> {
+    type Target = G::Target;
+    fn deref(&self) -> &G::Target {
+        &self.guard
+    }
+}
+
+impl<G: DerefMut> DerefMut for Tracked<G> {
+    fn deref_mut(&mut self) -> &mut G::Target {
+        &mut self.guard
+    }
+}
+
+impl<G> Drop for Tracked<G> {
+    fn drop(&mut self) {
+        note_release(self.class);
+    }
+}
+
+/// The guard of a [`CountedMutex`]
+pub type CountedGuard<'a, T> = Tracked<MutexGuard<'a, T>>;
+
+/// Contention counters shared by a group of locks, which are one class of lock
+pub struct LockCounter {
+    /// name of the class the locks are reported as, see [`LockHooks`]
+    class: &'static str,
+    /// acquisitions that found the lock held
+    waits: AtomicUsize,
+    /// times they spun before getting it
+    spins: AtomicUsize,
+}
+
+impl LockCounter {
+    const fn new(class: &'static str) -> Self {
+        Self {
+            class,
+            waits: AtomicUsize::new(0),
+            spins: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Counters of the `EasyFileSystem` lock
+pub static FS_LOCK_COUNTER: LockCounter = LockCounter::new("EasyFileSystem");
+/// Counters of all block cache locks together
+pub static BLOCK_CACHE_LOCK_COUNTER: LockCounter = LockCounter::new("BlockCache");
+/// Class the locks of inodes are reported as
+const INODE_LOCK_CLASS: &str = "InodeLock";
+
+/// A spin lock counting the acquisitions which had to wait into a [`LockCounter`]
+pub struct CountedMutex<T> {
+    inner: Mutex<T>,
+    counter: &'static LockCounter,
+}
+
+impl<T> CountedMutex<T> {
+    pub fn new(value: T, counter: &'static LockCounter) -> Self {
+        Self {
+            inner: Mutex::new(value),
+            counter,
+        }
+    }
+    /// Spin until the lock is free, counting the wait if it was held
+    pub fn lock(&self) -> CountedGuard<'_, T> {
+        if let Some(guard) = self.inner.try_lock() {
+            return Tracked::new(guard, self.counter.class);
+        }
+        self.counter.waits.fetch_add(1, Ordering::Relaxed);
+        loop {
+            self.counter.spins.fetch_add(1, Ordering::Relaxed);
+            core::hint::spin_loop();
+            if let Some(guard) = self.inner.try_lock() {
+                return Tracked::new(guard, self.counter.class);
+            }
+        }
+    }
+}
+
+/// Contention on the filesystem locks since boot
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LockStats {
+    pub fs_waits: usize,
+    /// there is no clock in easy-fs, so the wait is counted in spins
+    pub fs_spins: usize,
+    pub block_cache_waits: usize,
+    pub block_cache_spins: usize,
+}
+
+pub fn lock_stats() -> LockStats {
+    LockStats {
+        fs_waits: FS_LOCK_COUNTER.waits.load(Ordering::Relaxed),
+        fs_spins: FS_LOCK_COUNTER.spins.load(Ordering::Relaxed),
+        block_cache_waits: BLOCK_CACHE_LOCK_COUNTER.waits.load(Ordering::Relaxed),
+        block_cache_spins: BLOCK_CACHE_LOCK_COUNTER.spins.load(Ordering::Relaxed),
+    }
+}
+
+/// Where a disk inode is, the address of its device, block id and offset in the block
+type InodePos = (usize, usize, usize);
+
+lazy_static! {
+    /// The lock of every disk inode someone holds an [`InodeLock`] on
+    static ref INODE_LOCKS: Mutex<BTreeMap<InodePos, Arc<RwLock<()>>>> =
+        Mutex::new(BTreeMap::new());
+}
+
+/// The lock over the data of a disk inode, shared by all [`InodeLock`]s on it,
+/// so that every [`crate::Inode`] of a file takes the same one.
+/// It is forgotten once the last of them is dropped.
+pub struct InodeLock {
+    pos: InodePos,
+    lock: Arc<RwLock<()>>,
+}
+
+impl InodeLock {
+    pub fn new(block_id: usize, block_offset: usize, block_device: &Arc<dyn BlockDevice>) -> Self {
+        let pos = (
+            Arc::as_ptr(block_device) as *const u8 as usize,
+            block_id,
+            block_offset,
+        );
+        let lock = INODE_LOCKS
+            .lock()
+            .entry(pos)
+            .or_insert_with(|| Arc::new(RwLock::new(())))
+            .clone();
+        Self { pos, lock }
+    }
+}
+
+impl InodeLock {
+    pub fn read(&self) -> Tracked<RwLockReadGuard<'_, ()>> {
+        Tracked::new(self.lock.read(), INODE_LOCK_CLASS)
+    }
+    pub fn write(&self) -> Tracked<RwLockWriteGuard<'_, ()>> {
+        Tracked::new(self.lock.write(), INODE_LOCK_CLASS)
+    }
+    /// Take the lock only if it is free. A try can not deadlock, so it is not reported.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, ()>> {
+        self.lock.try_write()
+    }
+}
+
+/// Take the `EasyFileSystem` lock of a filesystem made up on the spot and the lock of
+/// an inode on `block_device`, the inode one first if `inode_first`, and let go of both.
+/// For a kernel to check its [`LockHooks`] catch the order of the two.
+#[cfg(feature = "lockdep")]
+pub fn take_fs_and_inode_locks(block_device: &Arc<dyn BlockDevice>, inode_first: bool) {
+    let fs = CountedMutex::new((), &FS_LOCK_COUNTER);
+    let inode = InodeLock::new(0, 0, block_device);
+    if inode_first {
+        let _data = inode.write();
+        let _fs = fs.lock();
+    } else {
+        let _fs = fs.lock();
+        let _data = inode.write();
+    }
+}
+
+impl Drop for InodeLock {
+    fn drop(&mut self) {
+        let mut locks = INODE_LOCKS.lock();
+        // the table and this one
+        if Arc::strong_count(&self.lock) == 2 {
+            locks.remove(&self.pos);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Filesystems grafted onto directories of other ones
+//!
+//! A mount pairs a directory, the mount point, with the root of a filesystem opened
+//! on another device. Looking a path up steps from the mount point onto that root,
+//! and `..` at the root steps back to the parent of the mount point, so the entries
+//! of the mount point itself are hidden until it is unmounted.
+use super::Inode;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+use spin::Mutex;
+
+/// A filesystem and the directory it is mounted on
+struct Mount {
+    point: Arc<Inode>,
+    root: Arc<Inode>,
+}
+
+lazy_static! {
+    /// Every filesystem mounted, in the order they were
+    static ref MOUNTS: Mutex<Vec<Mount>> = Mutex::new(Vec::new());
+}
+
+/// The root of the filesystem mounted on `dir`, if it is a mount point
+pub fn mounted_on(dir: &Inode) -> Option<Arc<Inode>> {
+    MOUNTS
+        .lock()
+        .iter()
+        .find(|mount| mount.point.is_same(dir))
+        .map(|mount| Arc::clone(&mount.root))
+}
+
+/// The directory `root` is mounted on, if it is the root of a mounted filesystem
+pub fn mount_point_of(root: &Inode) -> Option<Arc<Inode>> {
+    MOUNTS
+        .lock()
+        .iter()
+        .find(|mount| mount.root.is_same(root))
+        .map(|mount| Arc::clone(&mount.point))
+}
+
+/// Graft `root` onto `point`, false if `point` has a filesystem mounted already
+pub fn add_mount(point: Arc<Inode>, root: Arc<Inode>) -> bool {
+    let mut mounts = MOUNTS.lock();
+    if mounts.iter().any(|mount| mount.point.is_same(&point)) {
+        return false;
+    }
+    mounts.push(Mount { point, root });
+    true
+}
+
+/// Detach the filesystem whose root is `root`, false if it is not mounted
+pub fn remove_mount(root: &Inode) -> bool {
+    let mut mounts = MOUNTS.lock();
+    let Some(idx) = mounts.iter().position(|mount| mount.root.is_same(root)) else { return false; };
+    mounts.remove(idx);
+    true
+}
+
+/// Whether any filesystem is mounted
+pub fn has_mounts() -> bool {
+    !MOUNTS.lock().is_empty()
+}
@@ -0,0 +1,80 @@
+use super::{
+    block_cache_sync_all, get_block_cache, BlockDevice, CountedMutex, EasyFileSystem, BLOCK_SZ,
+};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// A read-only view of the data of an inode at some point in time
+pub struct Snapshot {
+    size: usize,
+    /// data blocks in file order, 0 for a hole, held against reuse until the snapshot is dropped
+    blocks: Vec<u32>,
+    /// size of the data blocks
+    block_size: usize,
+    fs: Arc<CountedMutex<EasyFileSystem>>,
+    block_device: Arc<dyn BlockDevice>,
+}
+
+impl Snapshot {
+    /// Create a snapshot over data blocks already shared in `fs`
+    pub(crate) fn new(
+        size: usize,
+        blocks: Vec<u32>,
+        block_size: usize,
+        fs: Arc<CountedMutex<EasyFileSystem>>,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Self {
+        Self {
+            size,
+            blocks,
+            block_size,
+            fs,
+            block_device,
+        }
+    }
+    /// Size of the data when the snapshot was taken
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    /// Read data from the snapshot
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size);
+        let mut read_size = 0usize;
+        while start < end {
+            let end_current_block = ((start / BLOCK_SZ + 1) * BLOCK_SZ).min(end);
+            let block_read_size = end_current_block - start;
+            let dst = &mut buf[read_size..read_size + block_read_size];
+            // read a block of the device at a time, a few of them may make up a data block
+            let per_block = self.block_size / BLOCK_SZ;
+            let i = start / BLOCK_SZ;
+            match self.blocks[i / per_block] {
+                // a hole reads as zeros
+                0 => dst.fill(0),
+                block_id => get_block_cache(
+                    block_id as usize + i % per_block,
+                    Arc::clone(&self.block_device),
+                )
+                .lock()
+                .read(0, |data_block: &[u8; BLOCK_SZ]| {
+                    dst.copy_from_slice(
+                        &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size],
+                    );
+                }),
+            }
+            read_size += block_read_size;
+            start = end_current_block;
+        }
+        read_size
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        let mut fs = self.fs.lock();
+        for &block_id in self.blocks.iter().filter(|&&block_id| block_id != 0) {
+            fs.unshare_data(block_id);
+        }
+        block_cache_sync_all();
+    }
+}
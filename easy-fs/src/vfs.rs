@@ -1,18 +1,54 @@
 use super::{
-    block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
-    EasyFileSystem, DIRENT_SZ,
+    add_mount, block_cache_release, block_cache_sync, block_cache_sync_all, get_block_cache,
+    has_mounts, mount_point_of, mounted_on, now, pin_block_cache, prefetch_block_cache,
+    remove_mount, unpin_block_cache,
+    BlockDevice, BlockPtr, CountedGuard, CountedMutex, DirEntry, InodeLock, DirQuota, DiskInode,
+    DiskInodeType, EasyFileSystem, FsInfo, Snapshot, StatFs, BLOCK_SZ, DIRENT_SZ, max_file_size,
+    MAX_BLOCK_SZ, MODE_EXEC,
 };
 use alloc::sync::Arc;
 use alloc::vec::Vec;
-use alloc::{collections::BTreeSet, string::String};
-use spin::{Mutex, MutexGuard};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+};
+use core::cmp::Ordering;
+use spin::Mutex;
+
+/// `.` and `..` come and go with their directory, they are never linked, renamed or unlinked
+fn is_dot_name(name: &str) -> bool {
+    name == "." || name == ".."
+}
+
+/// The order the entries of a directory are kept in: `.` and `..` first,
+/// then the others by name, so a lookup is a binary search
+fn dirent_order(name: &str) -> (u8, &str) {
+    match name {
+        "." => (0, name),
+        ".." => (1, name),
+        _ => (2, name),
+    }
+}
+
+/// Most symbolic links followed in one lookup, more are taken as a cycle
+pub const MAX_SYMLINK_FOLLOWS: usize = 40;
+
+/// Blocks read into the cache past the end of a sequential read, a quarter of the cache
+pub const READ_AHEAD_BLOCKS: u32 = 4;
 
 /// Virtual filesystem layer over easy-fs
 pub struct Inode {
     block_id: usize,
     block_offset: usize,
-    fs: Arc<Mutex<EasyFileSystem>>,
+    fs: Arc<CountedMutex<EasyFileSystem>>,
     block_device: Arc<dyn BlockDevice>,
+    /// Held over reads and changes of the data, taken before the fs lock.
+    /// Data is copied in and out with only this lock held, so different files
+    /// are read and written at the same time.
+    data_lock: InodeLock,
+    /// Inner id of the last block read, a read starting at it or the block after
+    /// it is sequential and reads ahead, one starting elsewhere does not
+    last_read: Mutex<Option<u32>>,
 }
 
 impl Inode {
@@ -20,16 +56,29 @@ impl Inode {
     pub fn new(
         block_id: u32,
         block_offset: usize,
-        fs: Arc<Mutex<EasyFileSystem>>,
+        fs: Arc<CountedMutex<EasyFileSystem>>,
         block_device: Arc<dyn BlockDevice>,
     ) -> Self {
         Self {
             block_id: block_id as usize,
             block_offset,
             fs,
+            data_lock: InodeLock::new(block_id as usize, block_offset, &block_device),
             block_device,
+            last_read: Mutex::new(None),
         }
     }
+    /// Get the inode number of current inode
+    pub fn inode_id(&self) -> u32 {
+        self.fs
+            .lock()
+            .get_inode_id(self.block_id as u32, self.block_offset)
+    }
+    /// Id of the filesystem current inode is on, told apart from the others mounted
+    /// by where it is kept in memory, so an inode is known by it and its inode number
+    pub fn fs_id(&self) -> usize {
+        Arc::as_ptr(&self.fs) as usize
+    }
     /// Call a function over a disk inode to read it
     pub fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
         get_block_cache(self.block_id, Arc::clone(&self.block_device))
@@ -42,51 +91,158 @@ impl Inode {
             .lock()
             .modify(self.block_offset, f)
     }
+    /// Keep the block holding the disk inode in the block cache
+    pub fn pin(&self) -> bool {
+        pin_block_cache(self.block_id, Arc::clone(&self.block_device))
+    }
+    /// Undo a successful [`Inode::pin`]
+    pub fn unpin(&self) {
+        unpin_block_cache(self.block_id, &self.block_device)
+    }
     /// Find inode under a disk inode by name
     fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        self.search_dirent(name, disk_inode)
+            .ok()
+            .map(|(_, inode_id)| inode_id)
+    }
+    /// Binary search the dirents under a disk inode, kept in [`dirent_order`], for `name`.
+    /// Its slot and inode if it is there, otherwise the slot it would go in,
+    /// which is that of its tombstone if it has one.
+    fn search_dirent(&self, name: &str, disk_inode: &DiskInode) -> Result<(usize, u32), usize> {
         // assert it is a directory
         assert!(disk_inode.is_dir());
-        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+        let key = dirent_order(name);
         let mut dirent = DirEntry::empty();
-        for i in 0..file_count {
+        let (mut lo, mut hi) = (0, (disk_inode.size as usize) / DIRENT_SZ);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
             assert_eq!(
-                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device,),
+                disk_inode.read_at(DIRENT_SZ * mid, dirent.as_bytes_mut(), &self.block_device),
                 DIRENT_SZ,
             );
-            if dirent.name() == name {
-                return Some(dirent.inode_number());
+            match dirent_order(dirent.name()).cmp(&key) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal if dirent.is_tombstone() => return Err(mid),
+                Ordering::Equal => return Ok((mid, dirent.inode_number())),
             }
         }
-        None
+        Err(lo)
     }
-    pub fn unlink(&self, disk_inode: &mut DiskInode, name: &str) -> (bool, Option<Arc<Inode>>) {
-        assert!(disk_inode.is_dir());
-        let fs = self.fs.lock();
-        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+    /// Point the dirent `name` under a disk inode at `inode_id`. If there is none, add it
+    /// in its place. The first tombstone from there on takes it, the dirents in between
+    /// move up by one, or failing that the last one before it, those in between move down.
+    /// Only with no tombstone at all does the directory grow. Return false if it can not,
+    /// over a quota or with the disk full, nothing is added then.
+    fn put_dirent(
+        &self,
+        disk_inode: &mut DiskInode,
+        name: &str,
+        inode_id: u32,
+        fs: &mut CountedGuard<EasyFileSystem>,
+    ) -> bool {
+        let dirent = DirEntry::new(name, inode_id);
+        let slot = match self.search_dirent(name, disk_inode) {
+            Ok((slot, _)) => slot,
+            Err(slot) => {
+                let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+                let is_tombstone = |i: usize| {
+                    let mut dirent = DirEntry::empty();
+                    disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+                    dirent.is_tombstone()
+                };
+                if let Some(free) = (slot..file_count).find(|&i| is_tombstone(i)) {
+                    for i in (slot..free).rev() {
+                        self.move_dirent(disk_inode, i, i + 1);
+                    }
+                    slot
+                } else if let Some(free) = (0..slot).rev().find(|&i| is_tombstone(i)) {
+                    for i in free + 1..slot {
+                        self.move_dirent(disk_inode, i, i - 1);
+                    }
+                    slot - 1
+                } else {
+                    let new_size = ((file_count + 1) * DIRENT_SZ) as u32;
+                    if !self.increase_size(new_size, disk_inode, fs) {
+                        return false;
+                    }
+                    for i in (slot..file_count).rev() {
+                        self.move_dirent(disk_inode, i, i + 1);
+                    }
+                    slot
+                }
+            }
+        };
+        disk_inode.write_at(DIRENT_SZ * slot, dirent.as_bytes(), &self.block_device);
+        true
+    }
+    /// Copy the dirent at slot `from` under a disk inode to slot `to`
+    fn move_dirent(&self, disk_inode: &mut DiskInode, from: usize, to: usize) {
         let mut dirent = DirEntry::empty();
-        for i in 0..file_count {
-            disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
-            if dirent.name() == name {
-                let (block_id, block_offset) = fs.get_disk_inode_pos(dirent.inode_number());
-                let inode = Arc::new(Self::new(
-                    block_id,
-                    block_offset,
-                    self.fs.clone(),
-                    self.block_device.clone(),
-                ));
-                let clear = inode.modify_disk_inode(|r_disk| {
-                    r_disk.nlink -= 1;
-                    r_disk.nlink == 0
-                });
-                let mut buf = [0; DIRENT_SZ];
-                disk_inode.read_at(DIRENT_SZ * (file_count - 1), &mut buf, &self.block_device);
-                disk_inode.write_at(DIRENT_SZ * i, &buf, &self.block_device);
-                disk_inode.size = ((file_count - 1) * DIRENT_SZ) as u32;
-                return (true, if clear { Some(inode) } else { None });
+        disk_inode.read_at(DIRENT_SZ * from, dirent.as_bytes_mut(), &self.block_device);
+        disk_inode.write_at(DIRENT_SZ * to, dirent.as_bytes(), &self.block_device);
+    }
+    /// Leave a tombstone for the dirent `name` under a disk inode. The dirents after it
+    /// stay where they are, so a [`Inode::read_dir`] going on over the directory neither
+    /// skips nor repeats one. The tombstones at the end are cut off, along with the block
+    /// freed there if any. False if there is no such dirent.
+    fn remove_dirent(
+        &self,
+        disk_inode: &mut DiskInode,
+        name: &str,
+        fs: &mut CountedGuard<EasyFileSystem>,
+    ) -> bool {
+        let Ok((slot, _)) = self.search_dirent(name, disk_inode) else { return false; };
+        let mut dirent = DirEntry::empty();
+        disk_inode.read_at(DIRENT_SZ * slot, dirent.as_bytes_mut(), &self.block_device);
+        dirent.bury();
+        disk_inode.write_at(DIRENT_SZ * slot, dirent.as_bytes(), &self.block_device);
+        let mut file_count = (disk_inode.size as usize) / DIRENT_SZ;
+        while file_count > 0 {
+            let offset = DIRENT_SZ * (file_count - 1);
+            disk_inode.read_at(offset, dirent.as_bytes_mut(), &self.block_device);
+            if !dirent.is_tombstone() {
+                break;
             }
+            file_count -= 1;
+        }
+        if file_count * DIRENT_SZ < disk_inode.size as usize {
+            self.decrease_size((file_count * DIRENT_SZ) as u32, disk_inode, fs);
+        }
+        true
+    }
+    /// Remove the dirent `name` under current directory. Return whether it was there,
+    /// and its inode if that was the last link to it. A directory goes only once it is empty.
+    pub fn unlink(&self, name: &str) -> (bool, Option<Arc<Inode>>) {
+        if is_dot_name(name) {
+            return (false, None);
+        }
+        let mut fs = self.fs.lock();
+        let _txn = fs.transaction();
+        let found = self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode));
+        let Some(inode_id) = found else { return (false, None); };
+        // the directory block is released first, the inodes may share it
+        let inode = fs.get_inode(&self.fs, inode_id);
+        // a mount point stays until the filesystem on it is unmounted
+        if mounted_on(&inode).is_some() {
+            return (false, None);
+        }
+        // only `.` and `..` may be left in a directory that goes away
+        let empty = inode
+            .read_disk_inode(|r_disk| !r_disk.is_dir() || r_disk.size as usize <= 2 * DIRENT_SZ);
+        if !empty {
+            return (false, None);
         }
-        (false, None)
+        let clear = inode.modify_disk_inode(|r_disk| {
+            r_disk.nlink -= 1;
+            r_disk.touch_links();
+            r_disk.nlink == 0
+        });
+        self.modify_disk_inode(|disk_inode| self.remove_dirent(disk_inode, name, &mut fs));
+        (true, if clear { Some(inode) } else { None })
     }
+    /// Add `new_name` under a disk inode for the inode of `old_name`, None if there is
+    /// no `old_name`, `new_name` is taken or the directory can not grow to hold it
     pub fn copy_dir_entry(
         &self,
         disk_inode: &mut DiskInode,
@@ -95,92 +251,1071 @@ impl Inode {
     ) -> Option<u32> {
         // assert it is a directory
         assert!(disk_inode.is_dir());
+        if is_dot_name(old_name) {
+            return None;
+        }
         let mut fs = self.fs.lock();
-        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
-        let mut dirent = DirEntry::empty();
-        for i in 0..file_count {
-            disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
-            if dirent.name() == old_name {
-                // append file in the dirent
-                let new_size = (file_count + 1) * DIRENT_SZ;
-                // increase size
-                self.increase_size(new_size as u32, disk_inode, &mut fs);
-                // write dirent
-                let dirent = DirEntry::new(new_name, dirent.inode_number());
-                disk_inode.write_at(
-                    file_count * DIRENT_SZ,
-                    dirent.as_bytes(),
-                    &self.block_device,
-                );
-                return Some(dirent.inode_number());
+        let inode_id = self.find_inode_id(old_name, disk_inode)?;
+        if self.find_inode_id(new_name, disk_inode).is_some() {
+            return None;
+        }
+        if !self.put_dirent(disk_inode, new_name, inode_id, &mut fs) {
+            return None;
+        }
+        Some(inode_id)
+    }
+    /// Give a dirent under a disk inode a new name, which moves it to its place in order,
+    /// fail if there is no `old_name`, `new_name` is taken or the directory can not grow
+    pub fn rename_dir_entry(
+        &self,
+        disk_inode: &mut DiskInode,
+        old_name: &str,
+        new_name: &str,
+    ) -> bool {
+        // assert it is a directory
+        assert!(disk_inode.is_dir());
+        if is_dot_name(old_name) {
+            return false;
+        }
+        let mut fs = self.fs.lock();
+        if old_name != new_name && self.find_inode_id(new_name, disk_inode).is_some() {
+            return false;
+        }
+        let Some(inode_id) = self.find_inode_id(old_name, disk_inode) else { return false; };
+        if old_name != new_name {
+            // the new name goes in before the old one goes, the file is never without a name
+            if !self.put_dirent(disk_inode, new_name, inode_id, &mut fs) {
+                return false;
+            }
+            self.remove_dirent(disk_inode, old_name, &mut fs);
+        }
+        true
+    }
+    /// Rename `old_name` under current directory to `new_name` only if the data of its
+    /// file is exactly `expected`. Return whether it was renamed, false if there is no
+    /// `old_name` or its data differs, and None if `new_name` is taken
+    /// or the directory can not grow to hold it.
+    pub fn cas_rename(&self, old_name: &str, expected: &[u8], new_name: &str) -> Option<bool> {
+        if is_dot_name(old_name) {
+            return Some(false);
+        }
+        // no one can write the file or the directory until the rename is done
+        let mut fs = self.fs.lock();
+        let txn = fs.transaction();
+        let found = self.read_disk_inode(|disk_inode| {
+            if old_name != new_name && self.find_inode_id(new_name, disk_inode).is_some() {
+                return None;
+            }
+            Some(self.find_inode_id(old_name, disk_inode))
+        })?;
+        let Some(inode_id) = found else { return Some(false); };
+        // the directory block is released first, the inodes may share it
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        let same = get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .read(block_offset, |disk_inode: &DiskInode| {
+                if disk_inode.size as usize != expected.len() {
+                    return false;
+                }
+                let mut data = alloc::vec![0u8; expected.len()];
+                disk_inode.read_at(0, &mut data, &self.block_device);
+                data == expected
+            });
+        if !same {
+            return Some(false);
+        }
+        if old_name != new_name {
+            let renamed = self.modify_disk_inode(|disk_inode| {
+                if !self.put_dirent(disk_inode, new_name, inode_id, &mut fs) {
+                    return false;
+                }
+                self.remove_dirent(disk_inode, old_name, &mut fs)
+            });
+            if !renamed {
+                return None;
+            }
+        }
+        txn.commit();
+        Some(true)
+    }
+    /// Move the entry at `old_path` to `new_path`, both resolved like [`Inode::find_path`],
+    /// replacing a file or an empty directory found there. A directory moved to another
+    /// parent gets its `..` pointed there. Return 0 on success, -1 if `old_path` is missing,
+    /// a parent is not a directory, a file and a directory would replace each other,
+    /// the directory at `new_path` is not empty, or a directory would move under itself.
+    pub fn rename(&self, old_path: &str, new_path: &str) -> isize {
+        let (result, clear) = self.rename_keep(old_path, new_path);
+        if let Some(inode) = clear {
+            inode.free();
+        }
+        result
+    }
+    /// Like [`Inode::rename`], but the inode replaced at `new_path` is returned instead
+    /// of freed if that was its last link, for the caller to clear
+    pub fn rename_keep(&self, old_path: &str, new_path: &str) -> (isize, Option<Arc<Inode>>) {
+        self.rename_keep_under(&self.top_root(), old_path, new_path)
+    }
+    /// Like [`Inode::rename_keep`], but both paths are resolved like
+    /// [`Inode::find_path_under`] with directory `root` taken as `/`
+    pub fn rename_keep_under(
+        &self,
+        root: &Inode,
+        old_path: &str,
+        new_path: &str,
+    ) -> (isize, Option<Arc<Inode>>) {
+        let Some((old_dir, old_name)) = self.parent_and_name(root, old_path) else { return (-1, None); };
+        let Some((new_dir, new_name)) = self.parent_and_name(root, new_path) else { return (-1, None); };
+        if is_dot_name(old_name) || is_dot_name(new_name) {
+            return (-1, None);
+        }
+        // entries only move within one filesystem
+        if !Arc::ptr_eq(&old_dir.fs, &new_dir.fs) {
+            return (-1, None);
+        }
+        old_dir.move_entry(&old_dir, old_name, &new_dir, new_name)
+    }
+    /// The rest of [`Inode::rename`] once both parents are found,
+    /// on the filesystem of current inode which holds them
+    fn move_entry(
+        &self,
+        old_dir: &Inode,
+        old_name: &str,
+        new_dir: &Inode,
+        new_name: &str,
+    ) -> (isize, Option<Arc<Inode>>) {
+        let mut fs = self.fs.lock();
+        let txn = fs.transaction();
+        let old_dir_id = fs.get_inode_id(old_dir.block_id as u32, old_dir.block_offset);
+        let new_dir_id = fs.get_inode_id(new_dir.block_id as u32, new_dir.block_offset);
+        let Some(source) = self.entry_id(&fs, old_dir_id, old_name) else { return (-1, None); };
+        let is_dir = |inode_id: u32| {
+            let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+            get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                .lock()
+                .read(block_offset, |disk_inode: &DiskInode| disk_inode.is_dir())
+        };
+        let source_is_dir = is_dir(source);
+        let target = self.entry_id(&fs, new_dir_id, new_name);
+        if let Some(target_id) = target {
+            if target_id == source {
+                return (0, None);
+            }
+            if is_dir(target_id) != source_is_dir
+                || source_is_dir && !self.child_ids(&fs, target_id).is_empty()
+            {
+                return (-1, None);
             }
         }
-        None
+        // a directory never goes under itself
+        if source_is_dir && self.is_above(&fs, source, new_dir_id) {
+            return (-1, None);
+        }
+        // its blocks go to the quota of the new directory
+        let Some(old_quota_dir) = fs.parent_dir(source) else { return (-1, None); };
+        let new_quota_dir = fs.quota_dir_of(new_dir_id);
+        if !self.move_quota(&mut fs, source, new_quota_dir) {
+            return (-1, None);
+        }
+        // the new entry is written before the old one goes, so the inode is never unnamed
+        let put = new_dir.modify_disk_inode(|disk_inode| {
+            new_dir.put_dirent(disk_inode, new_name, source, &mut fs)
+        });
+        if !put {
+            self.move_quota(&mut fs, source, old_quota_dir);
+            return (-1, None);
+        }
+        old_dir
+            .modify_disk_inode(|disk_inode| old_dir.remove_dirent(disk_inode, old_name, &mut fs));
+        if source_is_dir && old_dir_id != new_dir_id {
+            self.repoint_entry(&fs, source, "..", new_dir_id);
+        }
+        let mut inode_of = |inode_id: u32| fs.get_inode(&self.fs, inode_id);
+        inode_of(source).modify_disk_inode(|disk_inode| disk_inode.touch_links());
+        let clear = target.map(inode_of).filter(|inode| {
+            inode.modify_disk_inode(|disk_inode| {
+                disk_inode.nlink -= 1;
+                disk_inode.touch_links();
+                disk_inode.nlink == 0
+            })
+        });
+        txn.commit();
+        (0, clear)
+    }
+    /// Add `new_path` as another name of the file at `old_path`, both resolved like
+    /// [`Inode::find_path`], and return the file. None if `old_path` is missing or
+    /// a directory, a parent is not a directory, or `new_path` exists.
+    pub fn link(&self, old_path: &str, new_path: &str) -> Option<Arc<Inode>> {
+        self.link_under(&self.top_root(), old_path, new_path)
+    }
+    /// Like [`Inode::link`], but both paths are resolved like
+    /// [`Inode::find_path_under`] with directory `root` taken as `/`
+    pub fn link_under(&self, root: &Inode, old_path: &str, new_path: &str) -> Option<Arc<Inode>> {
+        let (old_dir, old_name) = self.parent_and_name(root, old_path)?;
+        let (new_dir, new_name) = self.parent_and_name(root, new_path)?;
+        if is_dot_name(old_name) || is_dot_name(new_name) {
+            return None;
+        }
+        // a file is linked only within its filesystem
+        if !Arc::ptr_eq(&old_dir.fs, &new_dir.fs) {
+            return None;
+        }
+        old_dir.link_entry(&old_dir, old_name, &new_dir, new_name)
+    }
+    /// The rest of [`Inode::link`] once both parents are found,
+    /// on the filesystem of current inode which holds them
+    fn link_entry(
+        &self,
+        old_dir: &Inode,
+        old_name: &str,
+        new_dir: &Inode,
+        new_name: &str,
+    ) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        let txn = fs.transaction();
+        let old_dir_id = fs.get_inode_id(old_dir.block_id as u32, old_dir.block_offset);
+        let new_dir_id = fs.get_inode_id(new_dir.block_id as u32, new_dir.block_offset);
+        let source = self.entry_id(&fs, old_dir_id, old_name)?;
+        if self.entry_id(&fs, new_dir_id, new_name).is_some() {
+            return None;
+        }
+        let inode = fs.get_inode(&self.fs, source);
+        if inode.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+            return None;
+        }
+        let put = new_dir.modify_disk_inode(|disk_inode| {
+            new_dir.put_dirent(disk_inode, new_name, source, &mut fs)
+        });
+        if !put {
+            return None;
+        }
+        inode.modify_disk_inode(|disk_inode| {
+            disk_inode.nlink += 1;
+            disk_inode.touch_links();
+        });
+        txn.commit();
+        Some(inode)
+    }
+    /// The directory holding the last component of `path`, resolved like
+    /// [`Inode::find_path_under`], and the name of that component
+    fn parent_and_name<'a>(&self, root: &Inode, path: &'a str) -> Option<(Arc<Inode>, &'a str)> {
+        let (dir_path, name) = match path.rfind('/') {
+            Some(i) => (&path[..=i], &path[i + 1..]),
+            None => (".", path),
+        };
+        if name.is_empty() {
+            return None;
+        }
+        let dir = self.find_path_under(root, dir_path)?;
+        if !dir.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+            return None;
+        }
+        Some((dir, name))
+    }
+    /// Point `new_name` under current directory at the inode of `old_name`, even if
+    /// `new_name` exists. Return whether it succeeded, and the inode `new_name` named
+    /// before if that was its last link, for the caller to clear.
+    pub fn link_replace(&self, old_name: &str, new_name: &str) -> (bool, Option<Arc<Inode>>) {
+        if is_dot_name(old_name) || is_dot_name(new_name) {
+            return (false, None);
+        }
+        let mut fs = self.fs.lock();
+        let txn = fs.transaction();
+        let linked = self.modify_disk_inode(|disk_inode| {
+            assert!(disk_inode.is_dir());
+            let source = self.find_inode_id(old_name, disk_inode)?;
+            let replaced = self.find_inode_id(new_name, disk_inode);
+            // a single dirent write if it is there, readers see either the old target or the new one
+            if !self.put_dirent(disk_inode, new_name, source, &mut fs) {
+                return None;
+            }
+            Some((source, replaced))
+        });
+        let Some((source, replaced)) = linked else { return (false, None); };
+        if replaced == Some(source) {
+            return (true, None);
+        }
+        // the directory block is released first, the inodes may share it
+        let mut inode_of = |inode_id: u32| fs.get_inode(&self.fs, inode_id);
+        inode_of(source).modify_disk_inode(|disk_inode| {
+            disk_inode.nlink += 1;
+            disk_inode.touch_links();
+        });
+        let clear = replaced.map(inode_of).filter(|inode| {
+            inode.modify_disk_inode(|disk_inode| {
+                disk_inode.nlink -= 1;
+                disk_inode.touch_links();
+                disk_inode.nlink == 0
+            })
+        });
+        txn.commit();
+        (true, clear)
+    }
+    /// Exchange the contents of current inode and `other` in one step, names, link counts
+    /// and types stay where they are. Fail if they are the same inode, of different types,
+    /// on different filesystems or charged to different directory quotas.
+    pub fn swap_data(&self, other: &Inode) -> bool {
+        let my_pos = (self.block_id, self.block_offset);
+        let other_pos = (other.block_id, other.block_offset);
+        if my_pos == other_pos || !Arc::ptr_eq(&self.fs, &other.fs) {
+            return false;
+        }
+        // in the order of the disk inodes, so that two swaps never wait on each other
+        let (first, second) = if my_pos < other_pos {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let _first = first.data_lock.write();
+        let _second = second.data_lock.write();
+        let mut fs = self.fs.lock();
+        let txn = fs.transaction();
+        let my_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let other_id = fs.get_inode_id(other.block_id as u32, other.block_offset);
+        // a directory swapped with one under it would end up under itself
+        if self.is_above(&fs, my_id, other_id) || self.is_above(&fs, other_id, my_id) {
+            return false;
+        }
+        // the blocks would move between quotas
+        if fs.parent_dir(my_id) != fs.parent_dir(other_id)
+            || fs.quota(my_id).is_some()
+            || fs.quota(other_id).is_some()
+        {
+            return false;
+        }
+        // the two disk inodes may share a block, so never hold both of them at once
+        let mut theirs = other.read_disk_inode(|disk_inode| disk_inode.clone());
+        let swapped = self.modify_disk_inode(|disk_inode| {
+            if disk_inode.is_dir() != theirs.is_dir() || disk_inode.is_ring() != theirs.is_ring() {
+                return false;
+            }
+            disk_inode.swap_data(&mut theirs);
+            true
+        });
+        if !swapped {
+            return false;
+        }
+        let is_dir = theirs.is_dir();
+        other.modify_disk_inode(|disk_inode| *disk_inode = theirs);
+        fs.swap_written_ends(my_id, other_id);
+        if is_dir {
+            // the entries moved, but each directory keeps its own `.` and `..`
+            let my_parent = self.entry_id(&fs, other_id, "..");
+            let other_parent = self.entry_id(&fs, my_id, "..");
+            for (dir_id, parent) in [(my_id, my_parent), (other_id, other_parent)] {
+                self.repoint_entry(&fs, dir_id, ".", dir_id);
+                if let Some(parent) = parent {
+                    self.repoint_entry(&fs, dir_id, "..", parent);
+                }
+                for child_id in self.child_ids(&fs, dir_id) {
+                    if self.entry_id(&fs, child_id, "..").is_some() {
+                        self.repoint_entry(&fs, child_id, "..", dir_id);
+                    }
+                }
+            }
+        }
+        txn.commit();
+        true
+    }
+    /// Whether directory `dir_id` is `inode_id` or met walking `..` up from it
+    fn is_above(&self, fs: &EasyFileSystem, dir_id: u32, inode_id: u32) -> bool {
+        let mut seen = BTreeSet::new();
+        let mut inode_id = inode_id;
+        while seen.insert(inode_id) {
+            if inode_id == dir_id {
+                return true;
+            }
+            let Some(parent_id) = self.entry_id(fs, inode_id, "..") else { break; };
+            inode_id = parent_id;
+        }
+        false
+    }
+    /// Inode the entry `name` of directory `dir_id` points to,
+    /// None if there is no such entry or `dir_id` is not a directory
+    fn entry_id(&self, fs: &EasyFileSystem, dir_id: u32, name: &str) -> Option<u32> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(dir_id);
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .read(block_offset, |disk_inode: &DiskInode| {
+                if !disk_inode.is_dir() {
+                    return None;
+                }
+                self.find_inode_id(name, disk_inode)
+            })
+    }
+    /// Point the entry `name` of directory `dir_id` at `inode_id` in place
+    fn repoint_entry(&self, fs: &EasyFileSystem, dir_id: u32, name: &str, inode_id: u32) {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(dir_id);
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(block_offset, |disk_inode: &mut DiskInode| {
+                if let Ok((slot, _)) = self.search_dirent(name, disk_inode) {
+                    let dirent = DirEntry::new(name, inode_id);
+                    disk_inode.write_at(DIRENT_SZ * slot, dirent.as_bytes(), &self.block_device);
+                }
+            });
+    }
+    /// The dirents naming directories under directory `dir_id`, other than `.` and `..`
+    fn subdirs(&self, fs: &mut CountedGuard<EasyFileSystem>, dir_id: u32) -> Vec<(String, u32)> {
+        let dir = fs.get_inode(&self.fs, dir_id);
+        // collect the dirents first, the inodes they name may share this block
+        let dirents: Vec<(String, u32)> = dir.read_disk_inode(|disk_inode| {
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            let mut dirent = DirEntry::empty();
+            (0..file_count)
+                .filter_map(|i| {
+                    disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+                    (!dirent.is_empty() && !is_dot_name(dirent.name()))
+                        .then(|| (String::from(dirent.name()), dirent.inode_number()))
+                })
+                .collect()
+        });
+        dirents
+            .into_iter()
+            .filter(|&(_, inode_id)| {
+                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+                get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                    .lock()
+                    .read(block_offset, |disk_inode: &DiskInode| disk_inode.is_dir())
+            })
+            .collect()
+    }
+    /// Walk the directory tree under current inode for links back to a directory
+    /// on the way down to them, which would make a recursive walk loop. Another link
+    /// to a directory seen before elsewhere is no cycle, it is left alone and not
+    /// walked again. Return how many there are, and remove them if `remove` is set.
+    pub fn check_cycles(&self, remove: bool) -> usize {
+        let mut fs = self.fs.lock();
+        let txn = fs.transaction();
+        let root_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let mut visited: BTreeSet<u32> = BTreeSet::new();
+        visited.insert(root_id);
+        // the directories from current one down to the one walked now,
+        // each with its subdirectories still to look at
+        let mut path = alloc::vec![(root_id, self.subdirs(&mut fs, root_id))];
+        let mut found = 0;
+        while let Some((dir_id, dirents)) = path.last_mut() {
+            let dir_id = *dir_id;
+            let Some((name, inode_id)) = dirents.pop() else {
+                path.pop();
+                continue;
+            };
+            if visited.insert(inode_id) {
+                let dirents = self.subdirs(&mut fs, inode_id);
+                path.push((inode_id, dirents));
+                continue;
+            }
+            if !path.iter().any(|&(id, _)| id == inode_id) {
+                continue;
+            }
+            found += 1;
+            if remove {
+                // the directory is still reachable by the link it was walked down
+                let dir = fs.get_inode(&self.fs, dir_id);
+                dir.modify_disk_inode(|disk_inode| dir.remove_dirent(disk_inode, &name, &mut fs));
+                fs.get_inode(&self.fs, inode_id)
+                    .modify_disk_inode(|disk_inode| {
+                        disk_inode.nlink -= 1;
+                        disk_inode.touch_links();
+                    });
+            }
+        }
+        txn.commit();
+        found
     }
     /// Find inode under current inode by name
     pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))
+            .map(|inode_id| fs.get_inode(&self.fs, inode_id))
+    }
+
+    /// Find inode by a `/` separated path, from the root of the filesystem if it starts
+    /// with `/` and from current directory otherwise. `.` and `..` are followed like any
+    /// other entry and empty components are skipped. Mount points are crossed both ways,
+    /// and the root is the one of the filesystem all others are mounted under. Symbolic links are followed, the
+    /// target of one relative to the directory holding it. Return None if a component is
+    /// missing, if a file is used as a directory, if a path ending in `/` names a file,
+    /// or if more than [`MAX_SYMLINK_FOLLOWS`] links are followed, as in a cycle of them.
+    pub fn find_path(&self, path: &str) -> Option<Arc<Inode>> {
+        self.resolve(&self.top_root(), path, true, &mut 0)
+    }
+    /// Like [`Inode::find_path`], but a symbolic link at the end of `path` is not followed
+    pub fn find_path_nofollow(&self, path: &str) -> Option<Arc<Inode>> {
+        self.resolve(&self.top_root(), path, false, &mut 0)
+    }
+    /// Like [`Inode::find_path`], but with directory `root` taken as `/`. Absolute paths
+    /// and the targets of absolute links start there and `..` at it stays there, so
+    /// nothing outside `root` is reached from current directory if that is under it.
+    pub fn find_path_under(&self, root: &Inode, path: &str) -> Option<Arc<Inode>> {
+        self.resolve(root, path, true, &mut 0)
+    }
+    /// Like [`Inode::find_path_under`], but a symbolic link at the end of `path` is not followed
+    pub fn find_path_nofollow_under(&self, root: &Inode, path: &str) -> Option<Arc<Inode>> {
+        self.resolve(root, path, false, &mut 0)
+    }
+    /// The root of the filesystem current inode is on, or of the one it is mounted under
+    fn top_root(&self) -> Arc<Inode> {
+        let mut root = EasyFileSystem::root_inode(&self.fs);
+        while let Some(point) = mount_point_of(&root) {
+            root = EasyFileSystem::root_inode(&point.fs);
+        }
+        root
+    }
+    /// Walk `path` from current directory with `root` as `/`,
+    /// `follows` counts the links followed on the way
+    fn resolve(
+        &self,
+        root: &Inode,
+        path: &str,
+        follow_last: bool,
+        follows: &mut usize,
+    ) -> Option<Arc<Inode>> {
+        if path.is_empty() {
+            return None;
+        }
+        let mut inode = if path.starts_with('/') {
+            root.shared()
+        } else {
+            self.shared()
+        };
+        let mut names = path.split('/').filter(|name| !name.is_empty()).peekable();
+        while let Some(name) = names.next() {
+            // names are looked up only in directories the owner may search
+            if !inode
+                .read_disk_inode(|disk_inode| disk_inode.is_dir() && disk_inode.permits(MODE_EXEC))
+            {
+                return None;
+            }
+            let mut dir = inode;
+            // nothing is above the root, even if it is a directory deep down
+            if name == ".." && dir.is_same(root) {
+                inode = dir;
+                continue;
+            }
+            // `..` at the root of a mounted filesystem is the parent of its mount point
+            if name == ".." {
+                while let Some(point) = mount_point_of(&dir) {
+                    dir = point;
+                }
+            }
+            inode = dir.find(name)?;
+            if let Some(root) = mounted_on(&inode) {
+                inode = root;
+            }
+            // a path ending in `/` wants the directory a last link points to
+            let follow = follow_last || names.peek().is_some() || path.ends_with('/');
+            if follow && inode.is_symlink() {
+                *follows += 1;
+                if *follows > MAX_SYMLINK_FOLLOWS {
+                    return None;
+                }
+                inode = dir.resolve(root, &inode.read_link()?, true, follows)?;
+            }
+        }
+        if path.ends_with('/') && !inode.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+            return None;
+        }
+        Some(inode)
+    }
+
+    /// Whether `other` is the same inode of the same filesystem as current inode
+    pub fn is_same(&self, other: &Inode) -> bool {
+        Arc::ptr_eq(&self.fs, &other.fs)
+            && self.block_id == other.block_id
+            && self.block_offset == other.block_offset
+    }
+    /// The inode of current file everyone who looks it up gets
+    pub(crate) fn shared(&self) -> Arc<Inode> {
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        fs.get_inode(&self.fs, inode_id)
+    }
+    /// Mount the easy-fs on `block_device` on current directory, paths through it go to
+    /// the root of that filesystem until it is unmounted. Fail if `block_device` holds
+    /// no easy-fs, or current inode is not a directory, is the root of a filesystem,
+    /// or has a filesystem mounted on it already.
+    pub fn mount(&self, block_device: Arc<dyn BlockDevice>) -> bool {
+        if !self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+            || self.inode_id() == 0
+            || mounted_on(self).is_some()
+        {
+            return false;
+        }
+        if !EasyFileSystem::is_valid(&block_device) {
+            block_cache_release(&block_device, None);
+            return false;
+        }
+        let efs = EasyFileSystem::open(block_device);
+        add_mount(self.shared(), EasyFileSystem::root_inode(&efs))
+    }
+    /// Unmount the filesystem whose root is current inode and write all its cached
+    /// blocks back. Fail if it is not mounted, or any inode of it other than current one
+    /// is in use, as by an open file or a filesystem mounted in it.
+    pub fn umount(&self) -> bool {
+        let mut fs = self.fs.lock();
+        // each inode of it in the table holds it, only the root may be left
+        fs.evict_inodes();
+        if Arc::strong_count(&self.fs) > 1 || !remove_mount(self) {
+            return false;
+        }
+        // it goes with the last one holding its root
+        fs.clear_inodes();
+        drop(fs);
+        block_cache_release(&self.block_device, None);
+        true
+    }
+    /// The blocks holding the data of current file in order, for it to be used as the
+    /// blocks of a device, written back and dropped from the block cache so that they
+    /// are read and written on the device directly from then on. None if it is not a
+    /// plain file, or a block is a hole or shared with a snapshot or another file.
+    pub fn data_block_ids(&self) -> Option<Vec<usize>> {
         let fs = self.fs.lock();
+        let block_ids = self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_file() {
+                return None;
+            }
+            let per_block = disk_inode.block_size() / BLOCK_SZ;
+            let blocks = (0..disk_inode.data_blocks())
+                .map(|inner_id| disk_inode.get_block_id(inner_id, &self.block_device))
+                .map(|block_id| {
+                    (block_id != 0 && !fs.is_shared(block_id)).then(|| block_id as usize)
+                })
+                .collect::<Option<Vec<_>>>()?;
+            // a larger data block is a run of blocks of the device
+            Some(
+                blocks
+                    .into_iter()
+                    .flat_map(|block_id| block_id..block_id + per_block)
+                    .collect::<Vec<_>>(),
+            )
+        })?;
+        drop(fs);
+        block_cache_release(&self.block_device, Some(&block_ids));
+        Some(block_ids)
+    }
+
+    /// Find the dirent pointing to `inode` under current inode
+    fn find_dirent(&self, inode: &Inode, fs: &EasyFileSystem) -> Option<DirEntry> {
         self.read_disk_inode(|disk_inode| {
-            self.find_inode_id(name, disk_inode).map(|inode_id| {
-                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
-                Arc::new(Self::new(
-                    block_id,
-                    block_offset,
-                    self.fs.clone(),
-                    self.block_device.clone(),
-                ))
-            })
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            let mut dirent = DirEntry::empty();
+            for i in 0..file_count {
+                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+                if dirent.is_empty() {
+                    continue;
+                }
+                let (block_id, block_offset) = fs.get_disk_inode_pos(dirent.inode_number());
+                if inode.block_id == (block_id as usize) && inode.block_offset == block_offset {
+                    return Some(dirent);
+                }
+            }
+            None
         })
     }
 
-    // inode_id, nlink, file
-    pub fn state(&self, root_inode: &Self) -> (u32, u32, bool) {
+    /// Whether the owner may access current inode in all the ways `access` asks for,
+    /// see [`DiskInode::permits`]
+    pub fn permits(&self, access: u16) -> bool {
+        self.read_disk_inode(|disk_inode| disk_inode.permits(access))
+    }
+    /// Set the permission bits of current inode to the low 9 bits of `mode`.
+    /// The root directory keeps the exec bit of its owner, every path can start there.
+    pub fn chmod(&self, mode: u16) {
         let fs = self.fs.lock();
-        let inode_id = root_inode
-            .read_disk_inode(|roo_disk_inode| {
-                let file_count = (roo_disk_inode.size as usize) / DIRENT_SZ;
-                let mut dirent = DirEntry::empty();
-                for i in 0..file_count {
-                    roo_disk_inode.read_at(
-                        DIRENT_SZ * i,
-                        dirent.as_bytes_mut(),
-                        &self.block_device,
-                    );
-                    let inode_id = dirent.inode_number();
-                    let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
-                    if self.block_id == (block_id as usize) && self.block_offset == block_offset {
-                        return Some(inode_id);
-                    }
-                }
-                None
-            })
-            .unwrap();
+        let txn = fs.transaction();
+        let is_root = fs.get_inode_id(self.block_id as u32, self.block_offset) == 0;
+        self.modify_disk_inode(|disk_inode| {
+            disk_inode.mode = mode & 0o777;
+            if is_root {
+                disk_inode.mode |= MODE_EXEC;
+            }
+            disk_inode.ctime = now();
+        });
+        txn.commit();
+    }
+    /// Keep current file from growing past `bytes`, 0 lifts the limit. Writes past it
+    /// stop short, other ways of growing the file fail. Return false if current inode is
+    /// not a plain file or is larger than `bytes` already, it is not cut down to it.
+    pub fn set_size_quota(&self, bytes: u32) -> bool {
+        let _data = self.data_lock.write();
+        let fs = self.fs.lock();
+        let txn = fs.transaction();
+        let done = self.modify_disk_inode(|disk_inode| {
+            if !disk_inode.is_file() || disk_inode.is_ring() || disk_inode.is_symlink() {
+                return false;
+            }
+            if bytes != 0 && bytes < disk_inode.size {
+                return false;
+            }
+            disk_inode.max_size = bytes;
+            disk_inode.ctime = now();
+            true
+        });
+        txn.commit();
+        done
+    }
+    /// The size quota of current file, 0 if it has none
+    pub fn size_quota(&self) -> u32 {
+        self.read_disk_inode(|disk_inode| disk_inode.max_size)
+    }
+    // inode_id, nlink, file
+    pub fn state(&self) -> (u32, u32, bool) {
+        let inode_id = self.inode_id();
         let (nlink, file) =
             self.read_disk_inode(|disk_inode| (disk_inode.nlink, disk_inode.is_file()));
         (inode_id, nlink, file)
     }
 
-    /// Increase the size of a disk inode
+    /// Read at most `max` entries of current directory, from byte `offset` of it as
+    /// [`Inode::read_at`] sees it, skipping empty slots. Return them as (name, inode number,
+    /// whether it is a directory), and the offset to go on from.
+    pub fn read_dir(&self, offset: usize, max: usize) -> (Vec<(String, u32, bool)>, usize) {
+        let fs = self.fs.lock();
+        let (found, next) = self.read_disk_inode(|disk_inode| {
+            assert!(disk_inode.is_dir());
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            let mut dirent = DirEntry::empty();
+            let mut found = Vec::new();
+            // an offset inside an entry goes on from the next one
+            let mut slot = (offset + DIRENT_SZ - 1) / DIRENT_SZ;
+            while slot < file_count && found.len() < max {
+                disk_inode.read_at(DIRENT_SZ * slot, dirent.as_bytes_mut(), &self.block_device);
+                if !dirent.is_empty() {
+                    found.push((String::from(dirent.name()), dirent.inode_number()));
+                }
+                slot += 1;
+            }
+            (found, slot * DIRENT_SZ)
+        });
+        // the directory block is released first, the inodes may share it
+        let entries = found
+            .into_iter()
+            .map(|(name, inode_id)| {
+                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+                let is_dir = get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                    .lock()
+                    .read(block_offset, |disk_inode: &DiskInode| disk_inode.is_dir());
+                (name, inode_id, is_dir)
+            })
+            .collect();
+        (entries, next)
+    }
+
+    /// Find a name of `inode` under current inode, any one of them if it has hard links
+    pub fn find_name(&self, inode: &Inode) -> Option<String> {
+        let fs = self.fs.lock();
+        self.find_dirent(inode, &fs)
+            .map(|dirent| String::from(dirent.name()))
+    }
+    /// Absolute path of current directory, found by walking `..` up to the root.
+    /// None if it or a directory above it is not linked anymore.
+    pub fn path(&self) -> Option<String> {
+        self.path_under(&self.top_root())
+    }
+    /// Like [`Inode::path`], but relative to directory `root` taken as `/`.
+    /// None also if current directory is not under `root`.
+    pub fn path_under(&self, root: &Inode) -> Option<String> {
+        let mut names: Vec<String> = Vec::new();
+        let mut dir = self.shared();
+        loop {
+            if dir.is_same(root) {
+                break;
+            }
+            if dir.inode_id() == 0 {
+                // the root of a mounted filesystem goes by the name of its mount point
+                match mount_point_of(&dir) {
+                    Some(point) => dir = point,
+                    None => return None,
+                }
+                continue;
+            }
+            let parent = dir.find("..")?;
+            names.push(parent.find_name(&dir)?);
+            dir = parent;
+        }
+        if names.is_empty() {
+            return Some(String::from("/"));
+        }
+        Some(names.iter().rev().fold(String::new(), |path, name| path + "/" + name))
+    }
+
+    /// Pack data blocks of the whole filesystem toward the start of the device,
+    /// return the number of blocks moved
+    pub fn defrag(&self) -> usize {
+        self.fs.lock().defrag()
+    }
+
+    /// Move the blocks of current file, indirect blocks included, to one run of free
+    /// blocks in the order they are read. Return the number of blocks moved, 0 if they
+    /// are in order already, None if it is not a file, a block of it is shared with a
+    /// snapshot, an image may be mounted from it or there is no long enough run.
+    ///
+    /// The copies reach the device before anything points to them, then the pointers
+    /// are switched before the old blocks are freed in one transaction, so a crash in
+    /// between at worst leaks the copies.
+    pub fn defragment(&self) -> Option<usize> {
+        let _data = self.data_lock.write();
+        let mut fs = self.fs.lock();
+        let ptrs = self.read_disk_inode(|disk_inode| {
+            disk_inode
+                .is_file()
+                .then(|| disk_inode.block_ptrs(&self.block_device))
+        })?;
+        if has_mounts() || ptrs.iter().any(|&(_, block)| fs.is_shared(block)) {
+            return None;
+        }
+        if ptrs.windows(2).all(|pair| pair[1].1 == pair[0].1 + 1) {
+            return Some(0);
+        }
+        let new_blocks = fs.alloc_data_run(ptrs.len(), None)?;
+        let mut moved: BTreeMap<u32, u32> = BTreeMap::new();
+        for (&(_, old_block), &new_block) in ptrs.iter().zip(new_blocks.iter()) {
+            fs.copy_data_block(old_block, new_block);
+            moved.insert(old_block, new_block);
+        }
+        block_cache_sync_all();
+        let txn = fs.transaction();
+        self.modify_disk_inode(|disk_inode| {
+            for &(ptr, old_block) in ptrs.iter() {
+                // an entry of a moved indirect block is set in its copy
+                let ptr = match ptr {
+                    BlockPtr::InIndirect(indirect, i) => BlockPtr::InIndirect(moved[&indirect], i),
+                    ptr => ptr,
+                };
+                disk_inode.set_block_ptr(ptr, moved[&old_block], &self.block_device);
+            }
+        });
+        for &(_, old_block) in ptrs.iter() {
+            fs.dealloc_data(old_block);
+        }
+        txn.commit();
+        Some(ptrs.len())
+    }
+
+    /// Geometry of the filesystem this inode lives on
+    pub fn fs_info(&self) -> FsInfo {
+        self.fs.lock().fs_info()
+    }
+    /// Get the usage of the filesystem current inode is on
+    pub fn statfs(&self) -> StatFs {
+        self.fs.lock().statfs()
+    }
+
+    /// Cap the data blocks the files under current directory may use at `limit`,
+    /// 0 lifts the cap. The blocks the files use already count toward it, and toward
+    /// the quotas above it as before. Return false if current inode is not a directory.
+    pub fn set_quota(&self, limit: u32) -> bool {
+        let mut fs = self.fs.lock();
+        if !self.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+            return false;
+        }
+        let dir_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let (used, members) = self.quota_members(&fs, dir_id);
+        fs.set_quota(dir_id, limit, used, &members);
+        true
+    }
+    /// Charge inode `inode_id` and the inodes under it to the quota of directory
+    /// `quota_dir`, taking their blocks off the quotas they were charged to.
+    /// Return false, leaving them where they were, if the blocks do not fit.
+    fn move_quota(&self, fs: &mut EasyFileSystem, inode_id: u32, quota_dir: u32) -> bool {
+        let Some(old_quota_dir) = fs.parent_dir(inode_id) else { return false; };
+        if old_quota_dir == quota_dir {
+            return true;
+        }
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        let (mut blocks, is_dir) =
+            get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                .lock()
+                .read(block_offset, |disk_inode: &DiskInode| {
+                    let ptrs = disk_inode.block_ptrs(&self.block_device);
+                    (ptrs.len() as u32, disk_inode.is_dir())
+                });
+        let mut members = Vec::new();
+        if is_dir {
+            let (used, under) = self.quota_members(fs, inode_id);
+            blocks += used;
+            // those under a directory with its own quota stay charged to it
+            if fs.quota(inode_id).is_none() {
+                members = under;
+            }
+        }
+        fs.refund(inode_id, blocks);
+        fs.set_quota_dir(inode_id, quota_dir);
+        if !fs.charge(inode_id, blocks) {
+            // the blocks were just refunded there, they fit again
+            fs.set_quota_dir(inode_id, old_quota_dir);
+            fs.charge(inode_id, blocks);
+            return false;
+        }
+        for member in members {
+            fs.set_quota_dir(member, quota_dir);
+        }
+        true
+    }
+    /// Blocks used under directory `dir_id`, and the inodes under it that are
+    /// not under another directory with a quota below it
+    fn quota_members(&self, fs: &EasyFileSystem, dir_id: u32) -> (u32, Vec<u32>) {
+        // hard links of a file hold its blocks once
+        let mut seen = BTreeSet::new();
+        let mut dirs = alloc::vec![(dir_id, true)];
+        let mut used = 0;
+        let mut members = Vec::new();
+        while let Some((dir_id, in_group)) = dirs.pop() {
+            for inode_id in self.child_ids(fs, dir_id) {
+                if !seen.insert(inode_id) {
+                    continue;
+                }
+                if in_group {
+                    members.push(inode_id);
+                }
+                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+                // holes take no blocks
+                let (blocks, is_dir) =
+                    get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                        .lock()
+                        .read(block_offset, |disk_inode: &DiskInode| {
+                            let ptrs = disk_inode.block_ptrs(&self.block_device);
+                            (ptrs.len() as u32, disk_inode.is_dir())
+                        });
+                used += blocks;
+                if is_dir {
+                    dirs.push((inode_id, in_group && fs.quota(inode_id).is_none()));
+                }
+            }
+        }
+        (used, members)
+    }
+    /// Inodes directory `dir_id` has entries for, besides `.` and `..`
+    fn child_ids(&self, fs: &EasyFileSystem, dir_id: u32) -> Vec<u32> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(dir_id);
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .read(block_offset, |disk_inode: &DiskInode| {
+                let mut dirent = DirEntry::empty();
+                (0..(disk_inode.size as usize) / DIRENT_SZ)
+                    .filter_map(|i| {
+                        disk_inode.read_at(
+                            DIRENT_SZ * i,
+                            dirent.as_bytes_mut(),
+                            &self.block_device,
+                        );
+                        (!dirent.is_empty() && !is_dot_name(dirent.name()))
+                            .then(|| dirent.inode_number())
+                    })
+                    .collect()
+            })
+    }
+    /// Quota of current directory, if it has one
+    pub fn quota(&self) -> Option<DirQuota> {
+        let fs = self.fs.lock();
+        fs.quota(fs.get_inode_id(self.block_id as u32, self.block_offset))
+    }
+
+    /// Increase the size of a disk inode with blocks under all of the new part,
+    /// see [`Inode::back_range`]
     fn increase_size(
         &self,
         new_size: u32,
         disk_inode: &mut DiskInode,
-        fs: &mut MutexGuard<EasyFileSystem>,
-    ) {
-        let old = disk_inode.size;
-        if new_size < disk_inode.size {
-            return;
+        fs: &mut CountedGuard<EasyFileSystem>,
+    ) -> bool {
+        let size = disk_inode.size as usize;
+        let len = (new_size as usize).saturating_sub(size);
+        self.back_range(size, len, disk_inode, fs)
+    }
+    /// Grow a disk inode to cover the bytes `[offset, offset + len)` and give the blocks
+    /// they fall in that are holes a block, on a run of consecutive blocks if there is one.
+    /// The blocks skipped over on the way from the old end are left holes.
+    /// False if the blocks needed do not fit in the quotas of the directories above it
+    /// or on the disk, or if the end is past [`max_file_size`] or the size quota.
+    fn back_range(
+        &self,
+        offset: usize,
+        len: usize,
+        disk_inode: &mut DiskInode,
+        fs: &mut CountedGuard<EasyFileSystem>,
+    ) -> bool {
+        let end = offset + len;
+        if end > max_file_size(disk_inode.block_size()) || disk_inode.room_below_quota(0) < end {
+            return false;
         }
-        let blocks_needed = disk_inode.blocks_num_needed(new_size);
-        let mut v: Vec<u32> = Vec::new();
-        for _ in 0..blocks_needed {
-            v.push(fs.alloc_data());
+        let blocks_needed = disk_inode.blocks_missing(offset, len, &self.block_device);
+        let mut v = Vec::new();
+        if blocks_needed > 0 {
+            let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+            if !fs.charge(inode_id, blocks_needed) {
+                return false;
+            }
+            // new blocks go right after the ones before them, and the first blocks
+            // of a new inode near those of its directory
+            let first = (offset / disk_inode.block_size()) as u32;
+            let near = match disk_inode.data_blocks() {
+                0 => fs.take_data_hint(inode_id),
+                _ if first == 0 => None,
+                _ => match disk_inode.get_block_id(first - 1, &self.block_device) {
+                    0 => None,
+                    block_id => Some(block_id + fs.per_block()),
+                },
+            };
+            let Some(blocks) = fs.alloc_data_contiguous(blocks_needed as usize, near) else {
+                fs.refund(inode_id, blocks_needed);
+                return false;
+            };
+            v = blocks;
+        }
+        disk_inode.back_range(offset, len, v, &self.block_device);
+        true
+    }
+    /// Decrease the size of a disk inode
+    fn decrease_size(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut CountedGuard<EasyFileSystem>,
+    ) {
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let data_blocks_dealloc = disk_inode.decrease_size(new_size, &self.block_device);
+        fs.refund(inode_id, data_blocks_dealloc.len() as u32);
+        for data_block in data_blocks_dealloc {
+            fs.dealloc_data(data_block);
         }
-        disk_inode.increase_size(new_size, v, &self.block_device);
     }
     /// Create inode under current inode by name
     pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::File)
+    }
+    /// Create a directory under current inode by name, with `.` and `..` entries in it.
+    /// Return None if the name exists.
+    pub fn mkdir(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Directory)
+    }
+    /// Allocate an inode for a new entry of current directory close to it,
+    /// and let the first data of the entry go right after that of the directory
+    fn alloc_child(&self, fs: &mut CountedGuard<EasyFileSystem>) -> u32 {
+        let dir_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let new_inode_id = fs.alloc_inode_near(dir_id);
+        let quota_dir = fs.quota_dir_of(dir_id);
+        fs.set_quota_dir(new_inode_id, quota_dir);
+        let last_block = self.read_disk_inode(|dir_inode| {
+            let blocks = dir_inode.data_blocks();
+            (blocks > 0).then(|| dir_inode.get_block_id(blocks - 1, &self.block_device))
+        });
+        if let Some(block_id) = last_block {
+            let hint = block_id + fs.per_block();
+            fs.set_data_hint(new_inode_id, hint);
+        }
+        new_inode_id
+    }
+    /// Give back a child from [`Inode::alloc_child`] that could not be named after all
+    fn discard_child(&self, inode: &Inode, inode_id: u32, fs: &mut CountedGuard<EasyFileSystem>) {
+        inode.modify_disk_inode(|disk_inode| inode.clear_size(disk_inode, fs));
+        fs.take_written_end(inode_id);
+        fs.dealloc_inode(inode_id);
+    }
+    /// Create an inode of `type_` under current inode by name
+    fn create_inode(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
         let mut fs = self.fs.lock();
+        let txn = fs.transaction();
         let node_id = self.modify_disk_inode(|root_inode| {
             // assert it is a directory
             assert!(root_inode.is_dir());
@@ -190,41 +1325,201 @@ impl Inode {
         if node_id.is_some() {
             return None;
         }
+        let dir_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let is_dir = type_ == DiskInodeType::Directory;
+        let dir_blocks = DiskInode::total_blocks(2 * DIRENT_SZ as u32, fs.block_size());
+        if is_dir && !fs.within_quota(dir_id, dir_blocks) {
+            return None;
+        }
         // create a new file
         // alloc a inode with an indirect block
-        let new_inode_id = fs.alloc_inode();
+        let new_inode_id = self.alloc_child(&mut fs);
         // initialize inode
-        let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
-        get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
-            .lock()
-            .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-                new_inode.initialize(DiskInodeType::File);
+        let inode = fs.get_inode(&self.fs, new_inode_id);
+        let ready = inode.modify_disk_inode(|new_inode| {
+            new_inode.initialize(type_, fs.block_size());
+            // a directory is complete before it shows up in its parent
+            !is_dir || inode.init_dir(new_inode_id, dir_id, new_inode, &mut fs)
+        });
+        let named = ready
+            && self.modify_disk_inode(|root_inode| {
+                self.put_dirent(root_inode, name, new_inode_id, &mut fs)
             });
-        self.modify_disk_inode(|root_inode| {
-            // append file in the dirent
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            let new_size = (file_count + 1) * DIRENT_SZ;
-            // increase size
-            self.increase_size(new_size as u32, root_inode, &mut fs);
-            // write dirent
-            let dirent = DirEntry::new(name, new_inode_id);
-            root_inode.write_at(
-                file_count * DIRENT_SZ,
-                dirent.as_bytes(),
-                &self.block_device,
-            );
+        if !named {
+            self.discard_child(&inode, new_inode_id, &mut fs);
+            txn.commit();
+            return None;
+        }
+        txn.commit();
+        Some(inode)
+        // release efs lock automatically by compiler
+    }
+    /// Write the `.` and `..` entries of a new directory `dir_id` in `parent_id`,
+    /// false if there is no room for them
+    fn init_dir(
+        &self,
+        dir_id: u32,
+        parent_id: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut CountedGuard<EasyFileSystem>,
+    ) -> bool {
+        if !self.increase_size(2 * DIRENT_SZ as u32, disk_inode, fs) {
+            return false;
+        }
+        let dot = DirEntry::new(".", dir_id);
+        let dot_dot = DirEntry::new("..", parent_id);
+        disk_inode.write_at(0, dot.as_bytes(), &self.block_device);
+        disk_inode.write_at(DIRENT_SZ, dot_dot.as_bytes(), &self.block_device);
+        true
+    }
+    /// Give the root directory of a new filesystem its `.` and `..`, both pointing to itself
+    pub(crate) fn init_root(&self) {
+        let mut fs = self.fs.lock();
+        let root_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        self.modify_disk_inode(|disk_inode| self.init_dir(root_id, root_id, disk_inode, &mut fs));
+        block_cache_sync_all();
+    }
+    /// Create inode under current inode by name with `data` in it.
+    /// The dirent is written last, so the file never shows up half written.
+    /// Return None if the name exists or there is no room for the data.
+    pub fn create_with_data(&self, name: &str, data: &[u8]) -> Option<Arc<Inode>> {
+        self.create_inode_with_data(name, data, DiskInodeType::File)
+    }
+    /// Create a symbolic link under current inode by name, pointing to `target`, which
+    /// need not exist. Return None if the name exists, `target` is empty or there is no room.
+    pub fn symlink(&self, name: &str, target: &str) -> Option<Arc<Inode>> {
+        if target.is_empty() {
+            return None;
+        }
+        self.create_inode_with_data(name, target.as_bytes(), DiskInodeType::Symlink)
+    }
+    /// Whether current inode is a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.read_disk_inode(|disk_inode| disk_inode.is_symlink())
+    }
+    /// The target of current inode if it is a symbolic link
+    pub fn read_link(&self) -> Option<String> {
+        self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_symlink() {
+                return None;
+            }
+            let mut target = alloc::vec![0u8; disk_inode.size as usize];
+            disk_inode.read_at(0, &mut target, &self.block_device);
+            String::from_utf8(target).ok()
+        })
+    }
+    /// Create an inode of `type_` under current inode by name with `data` in it,
+    /// see [`Inode::create_with_data`]
+    fn create_inode_with_data(
+        &self,
+        name: &str,
+        data: &[u8],
+        type_: DiskInodeType,
+    ) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        let txn = fs.transaction();
+        let (exists, dir_blocks_needed) = self.read_disk_inode(|root_inode| {
+            (
+                self.find_inode_id(name, root_inode).is_some(),
+                root_inode.blocks_missing(
+                    root_inode.size as usize,
+                    DIRENT_SZ,
+                    &self.block_device,
+                ),
+            )
         });
-
-        let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
+        if exists {
+            return None;
+        }
+        // check for room first, so nothing is left behind on failure
+        let blocks_needed = DiskInode::total_blocks(data.len() as u32, fs.block_size());
+        let dir_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        if blocks_needed + dir_blocks_needed > fs.free_data_blocks()
+            || !fs.within_quota(dir_id, blocks_needed)
+        {
+            return None;
+        }
+        let new_inode_id = self.alloc_child(&mut fs);
+        let inode = fs.get_inode(&self.fs, new_inode_id);
+        inode.modify_disk_inode(|new_inode| {
+            new_inode.initialize(type_, fs.block_size());
+            inode.increase_size(data.len() as u32, new_inode, &mut fs);
+            new_inode.write_at(0, data, &self.block_device);
+        });
+        let named = self.modify_disk_inode(|root_inode| {
+            self.put_dirent(root_inode, name, new_inode_id, &mut fs)
+        });
+        if !named {
+            self.discard_child(&inode, new_inode_id, &mut fs);
+            txn.commit();
+            return None;
+        }
+        txn.commit();
+        Some(inode)
+    }
+    /// Create a ring of `capacity` bytes under current inode by name, see [`Inode::ring_write`].
+    /// Return None if the name exists, the capacity is 0 or there is no room for it.
+    pub fn create_ring(&self, name: &str, capacity: usize) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        let txn = fs.transaction();
+        let (exists, dir_blocks_needed) = self.read_disk_inode(|root_inode| {
+            (
+                self.find_inode_id(name, root_inode).is_some(),
+                root_inode.blocks_missing(
+                    root_inode.size as usize,
+                    DIRENT_SZ,
+                    &self.block_device,
+                ),
+            )
+        });
+        if exists || capacity == 0 || capacity > max_file_size(fs.block_size()) {
+            return None;
+        }
+        let blocks_needed = DiskInode::total_blocks(capacity as u32, fs.block_size());
+        let dir_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        if blocks_needed + dir_blocks_needed > fs.free_data_blocks()
+            || !fs.within_quota(dir_id, blocks_needed)
+        {
+            return None;
+        }
+        let new_inode_id = self.alloc_child(&mut fs);
+        let inode = fs.get_inode(&self.fs, new_inode_id);
+        inode.modify_disk_inode(|new_inode| {
+            new_inode.initialize(DiskInodeType::Ring, fs.block_size());
+            inode.increase_size(capacity as u32, new_inode, &mut fs);
+        });
+        let named = self.modify_disk_inode(|root_inode| {
+            self.put_dirent(root_inode, name, new_inode_id, &mut fs)
+        });
+        if !named {
+            self.discard_child(&inode, new_inode_id, &mut fs);
+            txn.commit();
+            return None;
+        }
+        txn.commit();
+        Some(inode)
+    }
+    /// Whether current inode is a ring
+    pub fn is_ring(&self) -> bool {
+        self.read_disk_inode(|disk_inode| disk_inode.is_ring())
+    }
+    /// Write `buf` to the end of a ring, overwriting the oldest data once it is full
+    pub fn ring_write(&self, buf: &[u8]) -> usize {
+        let _data = self.data_lock.write();
+        let _fs = self.fs.lock();
+        let size =
+            self.modify_disk_inode(|disk_inode| disk_inode.ring_write(buf, &self.block_device));
         block_cache_sync_all();
-        // return inode
-        Some(Arc::new(Self::new(
-            block_id,
-            block_offset,
-            self.fs.clone(),
-            self.block_device.clone(),
-        )))
-        // release efs lock automatically by compiler
+        size
+    }
+    /// Read the oldest data of a ring and drop it, 0 means there is nothing left
+    pub fn ring_read(&self, buf: &mut [u8]) -> usize {
+        let _data = self.data_lock.write();
+        let _fs = self.fs.lock();
+        let size =
+            self.modify_disk_inode(|disk_inode| disk_inode.ring_read(buf, &self.block_device));
+        block_cache_sync_all();
+        size
     }
     /// List inodes under current inode
     pub fn ls(&self) -> Vec<String> {
@@ -237,37 +1532,400 @@ impl Inode {
                     disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
                     DIRENT_SZ,
                 );
-                v.push(String::from(dirent.name()));
+                if !dirent.is_empty() && !is_dot_name(dirent.name()) {
+                    v.push(String::from(dirent.name()));
+                }
             }
             v
         })
     }
-    /// Read data from current inode
+    /// Read data from current inode, other files are read and written meanwhile
     pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
-        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
+        let _data = self.data_lock.read();
+        // a copy, so the block of the disk inode is not held while the data is read
+        let disk_inode = self.modify_disk_inode(|disk_inode| {
+            disk_inode.atime = now();
+            disk_inode.clone()
+        });
+        let read_size = disk_inode.read_at(offset, buf, &self.block_device);
+        if read_size > 0 {
+            self.read_ahead(offset, read_size, &disk_inode);
+        }
+        read_size
+    }
+    /// Note the blocks of a read of `len` bytes at `offset`, and if it goes on
+    /// from the last one or starts the file, read the blocks after it into the cache
+    fn read_ahead(&self, offset: usize, len: usize, disk_inode: &DiskInode) {
+        let first = (offset / BLOCK_SZ) as u32;
+        let last = ((offset + len - 1) / BLOCK_SZ) as u32;
+        let mut last_read = self.last_read.lock();
+        let sequential =
+            first == 0 || last_read.map_or(false, |block| first == block || first == block + 1);
+        *last_read = Some(last);
+        drop(last_read);
+        if !sequential {
+            return;
+        }
+        // counted in blocks of the device, a few of them may make up a data block
+        let per_block = (disk_inode.block_size() / BLOCK_SZ) as u32;
+        let end = (disk_inode.data_blocks() * per_block).min(last + 1 + READ_AHEAD_BLOCKS);
+        for i in last + 1..end {
+            let block_id = disk_inode.get_block_id(i / per_block, &self.block_device);
+            if block_id != 0 {
+                let block_id = (block_id + i % per_block) as usize;
+                prefetch_block_cache(block_id, Arc::clone(&self.block_device));
+            }
+        }
     }
-    /// Write data to current inode
+    /// Write data to current inode, nothing is written if the blocks it needs
+    /// do not fit in the quotas of the directories above it or on the disk.
+    /// A write going past the size quota of the file stops short at it.
+    /// The data stays in the block cache, see [`Inode::sync`].
     pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let _data = self.data_lock.write();
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        // only the blocks are got under the fs lock, the data is copied without it
+        let disk_inode = self.modify_disk_inode(|disk_inode| {
+            let len = buf.len().min(disk_inode.room_below_quota(offset));
+            if !self.unshare_range(offset, len, disk_inode, &mut fs)
+                || !self.back_range(offset, len, disk_inode, &mut fs)
+            {
+                return None;
+            }
+            disk_inode.touch_data();
+            Some((disk_inode.clone(), len))
+        });
+        let Some((mut disk_inode, len)) = disk_inode else { return 0; };
+        if len == 0 {
+            return 0;
+        }
+        fs.mark_written(inode_id, (offset + len) as u32);
+        drop(fs);
+        disk_inode.write_at(offset, &buf[..len], &self.block_device)
+    }
+    /// Number of blocks current inode has in use, indirect blocks included,
+    /// fewer than its size takes if it has holes
+    pub fn allocated_blocks(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.block_ptrs(&self.block_device).len())
+    }
+    /// Write the cached blocks of current inode back to the device: the block of its
+    /// disk inode, data blocks and indirect blocks. Return the number of them written.
+    pub fn sync(&self) -> usize {
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        // the appends waiting in the cache go out with the rest
+        fs.take_unsynced_appends(inode_id);
+        self.sync_blocks()
+    }
+    /// Write the cached blocks of current inode back to the device, the fs lock held
+    fn sync_blocks(&self) -> usize {
+        let mut block_ids: Vec<usize> = self.read_disk_inode(|disk_inode| {
+            disk_inode
+                .block_ptrs(&self.block_device)
+                .into_iter()
+                .map(|(_, block_id)| block_id as usize)
+                .collect()
+        });
+        block_ids.push(self.block_id);
+        block_cache_sync(&block_ids, &self.block_device)
+    }
+    /// Write `buf` at the end of current inode in one step,
+    /// return the offset it was written at, or None if it goes over a quota.
+    /// Small appends are combined: their blocks stay dirty in the cache until
+    /// a block's worth of them has piled up, so that a log written a few bytes
+    /// at a time does not write the same blocks to the device over and over.
+    /// Reads find them in the cache meanwhile, see [`Inode::flush_appends`].
+    pub fn append(&self, buf: &[u8]) -> Option<usize> {
+        let _data = self.data_lock.write();
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let offset = self.modify_disk_inode(|disk_inode| {
+            let offset = disk_inode.size as usize;
+            // the last block may still be held by a snapshot
+            if !self.unshare_range(offset, buf.len(), disk_inode, &mut fs)
+                || !self.back_range(offset, buf.len(), disk_inode, &mut fs)
+            {
+                return None;
+            }
+            disk_inode.write_at(offset, buf, &self.block_device);
+            Some(offset)
+        })?;
+        fs.mark_written(inode_id, (offset + buf.len()) as u32);
+        if fs.pile_append(inode_id, buf.len()) {
+            self.sync_blocks();
+        }
+        Some(offset)
+    }
+    /// Write back the appends to current inode still waiting in the cache,
+    /// done on [`Inode::sync`] and when a file open on it is closed
+    pub fn flush_appends(&self) {
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        if fs.take_unsynced_appends(inode_id) {
+            self.sync_blocks();
+        }
+    }
+    /// Preallocate zeroed blocks so that current inode is at least `size` bytes.
+    /// They do not count as written until data is written over them, see [`Inode::seal_size`].
+    /// Return false if the blocks do not fit in the quotas of the directories above it.
+    pub fn fallocate(&self, size: usize) -> bool {
+        let _data = self.data_lock.write();
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let grown = self.modify_disk_inode(|disk_inode| {
+            if size as u32 <= disk_inode.size {
+                return true;
+            }
+            let written_end = disk_inode.size;
+            if !self.increase_size(size as u32, disk_inode, &mut fs) {
+                return false;
+            }
+            fs.start_prealloc(inode_id, written_end);
+            true
+        });
+        block_cache_sync_all();
+        grown
+    }
+    /// Grow current inode by `len` zeroed bytes that count as written, with every block
+    /// they fall in backed so that writing over them later needs no new blocks.
+    /// Return the old size, or None with nothing changed if the blocks do not fit
+    /// on the disk or in the quotas of the directories above it.
+    pub fn extend(&self, len: usize) -> Option<usize> {
+        let _data = self.data_lock.write();
+        if self.is_ring() {
+            return None;
+        }
         let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let old_size = self.modify_disk_inode(|disk_inode| {
+            let old_size = disk_inode.size as usize;
+            // the last block may keep old bytes past the end, zero them
+            let block_size = disk_inode.block_size();
+            let tail = (old_size + len).min((old_size + block_size - 1) / block_size * block_size)
+                - old_size;
+            if !self.unshare_range(old_size, tail, disk_inode, &mut fs)
+                || !self.back_range(old_size, len, disk_inode, &mut fs)
+            {
+                return None;
+            }
+            disk_inode.write_at(old_size, &[0u8; MAX_BLOCK_SZ][..tail], &self.block_device);
+            disk_inode.touch_data();
+            Some(old_size)
+        })?;
+        fs.mark_written(inode_id, (old_size + len) as u32);
+        block_cache_sync_all();
+        Some(old_size)
+    }
+    /// Cut current inode right after the last byte written to it,
+    /// freeing the preallocated blocks past that, return the new size
+    pub fn seal_size(&self) -> usize {
+        let _data = self.data_lock.write();
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
         let size = self.modify_disk_inode(|disk_inode| {
-            self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs);
-            disk_inode.write_at(offset, buf, &self.block_device)
+            if let Some(end) = fs.take_written_end(inode_id) {
+                if end < disk_inode.size {
+                    self.decrease_size(end, disk_inode, &mut fs);
+                }
+            }
+            disk_inode.size as usize
         });
         block_cache_sync_all();
         size
     }
     /// Clear the data in current inode
     pub fn clear(&self) {
+        let _data = self.data_lock.write();
         let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        fs.take_written_end(inode_id);
         self.modify_disk_inode(|disk_inode| {
             log::debug!("clear disk_inode {disk_inode:?}");
-            let size = disk_inode.size;
-            let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
-            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
-            for data_block in data_blocks_dealloc.into_iter() {
-                fs.dealloc_data(data_block);
+            self.clear_size(disk_inode, &mut fs);
+        });
+        block_cache_sync_all();
+    }
+    /// Free the data and the disk inode of current inode once its last link is gone,
+    /// see [`Inode::unlink`]. The inode must not be used afterwards.
+    pub fn free(&self) {
+        // clear takes the fs lock on its own
+        self.clear();
+        let mut fs = self.fs.lock();
+        let txn = fs.transaction();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        fs.dealloc_inode(inode_id);
+        txn.commit();
+    }
+    /// Cut or extend current inode to `new_size` bytes, an extended part is a hole
+    /// that reads as zeros.
+    /// A ring can only be emptied, it keeps its capacity.
+    /// Return false if the blocks needed do not fit in the quotas of the directories above it.
+    pub fn truncate(&self, new_size: u32) -> bool {
+        let _data = self.data_lock.write();
+        if self.is_ring() {
+            if new_size != 0 {
+                return false;
+            }
+            let _fs = self.fs.lock();
+            self.modify_disk_inode(|disk_inode| {
+                disk_inode.ring_start = 0;
+                disk_inode.ring_len = 0;
+            });
+            block_cache_sync_all();
+            return true;
+        }
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let done = self.modify_disk_inode(|disk_inode| {
+            let old_size = disk_inode.size;
+            // the block holding the lower of the two ends keeps old bytes past it,
+            // zero them while they are still inside the file, unless it is a hole
+            let low = old_size.min(new_size) as usize;
+            let high = old_size.max(new_size) as usize;
+            let block_size = disk_inode.block_size();
+            let tail = high.min((low + block_size - 1) / block_size * block_size) - low;
+            let backed =
+                disk_inode.get_block_id((low / block_size) as u32, &self.block_device) != 0;
+            let zero_tail = tail > 0 && backed;
+            if zero_tail && !self.unshare_range(low, tail, disk_inode, &mut fs) {
+                return false;
             }
+            if new_size > old_size
+                && !self.back_range(new_size as usize, 0, disk_inode, &mut fs)
+            {
+                return false;
+            }
+            if zero_tail {
+                disk_inode.write_at(low, &[0u8; MAX_BLOCK_SZ][..tail], &self.block_device);
+            }
+            if new_size < old_size {
+                self.decrease_size(new_size, disk_inode, &mut fs);
+            }
+            true
         });
+        // everything up to the new size counts as written
+        if done && fs.take_written_end(inode_id).is_some() {
+            fs.start_prealloc(inode_id, new_size);
+        }
+        block_cache_sync_all();
+        done
+    }
+    /// Read the head of current inode into `buf` and cut it off,
+    /// the rest of the data moves to the front a block at a time.
+    /// No one can write between the read and the cut since the data lock is held.
+    /// Return None with nothing cut if a hole at the front can not get its blocks.
+    pub fn drain(&self, buf: &mut [u8]) -> Option<usize> {
+        let _data = self.data_lock.write();
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let read_size = self.modify_disk_inode(|disk_inode| {
+            let read_size = disk_inode.read_at(0, buf, &self.block_device);
+            if read_size == 0 {
+                return Some(0);
+            }
+            let rest = disk_inode.size as usize - read_size;
+            // the blocks the rest moves into are its own before anything moves
+            if !self.unshare_range(0, rest, disk_inode, &mut fs)
+                || !self.back_range(0, rest, disk_inode, &mut fs)
+            {
+                return None;
+            }
+            // each block lands at or before where it came from
+            let mut block = alloc::vec![0u8; disk_inode.block_size()];
+            let mut moved = 0;
+            while moved < rest {
+                let len = disk_inode.read_at(read_size + moved, &mut block, &self.block_device);
+                disk_inode.write_at(moved, &block[..len], &self.block_device);
+                moved += len;
+            }
+            self.decrease_size(rest as u32, disk_inode, &mut fs);
+            Some(read_size)
+        })?;
+        // the preallocated tail moves to the front with the rest
+        if let Some(end) = fs.take_written_end(inode_id) {
+            fs.start_prealloc(inode_id, end.saturating_sub(read_size as u32));
+        }
         block_cache_sync_all();
+        Some(read_size)
+    }
+    /// Take a frozen view of the data in current inode.
+    /// Later writes copy the blocks the snapshot holds instead of changing them.
+    pub fn snapshot(&self) -> Snapshot {
+        // no write may be copying data into the blocks about to be shared
+        let _data = self.data_lock.read();
+        let mut fs = self.fs.lock();
+        let (size, blocks) = self.read_disk_inode(|disk_inode| {
+            let blocks: Vec<u32> = (0..disk_inode.data_blocks())
+                .map(|i| disk_inode.get_block_id(i, &self.block_device))
+                .collect();
+            (disk_inode.size as usize, blocks)
+        });
+        for &block_id in blocks.iter().filter(|&&block_id| block_id != 0) {
+            fs.share_data(block_id);
+        }
+        Snapshot::new(
+            size,
+            blocks,
+            fs.block_size(),
+            self.fs.clone(),
+            self.block_device.clone(),
+        )
+    }
+    /// Give the blocks in `[offset, offset + len)` held by snapshots a private copy.
+    /// Return false with nothing copied if the copies do not fit on the disk
+    /// or in the quotas above current inode.
+    fn unshare_range(
+        &self,
+        offset: usize,
+        len: usize,
+        disk_inode: &mut DiskInode,
+        fs: &mut CountedGuard<EasyFileSystem>,
+    ) -> bool {
+        let block_size = disk_inode.block_size();
+        // past the blocks it has, nothing is shared
+        let end = (offset + len).min(disk_inode.data_blocks() as usize * block_size);
+        if offset >= end {
+            return true;
+        }
+        let shared: Vec<usize> = (offset / block_size..=(end - 1) / block_size)
+            .filter(|&inner_id| {
+                fs.is_shared(disk_inode.get_block_id(inner_id as u32, &self.block_device))
+            })
+            .collect();
+        if shared.is_empty() {
+            return true;
+        }
+        // a copy takes the place of a block counted already, but the old one stays
+        // with the snapshots, so there must be room for both
+        let copies = shared.len() as u32;
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let within_quota = match fs.parent_dir(inode_id) {
+            Some(dir_id) => fs.within_quota(dir_id, copies),
+            None => true,
+        };
+        if copies > fs.free_data_blocks() || !within_quota {
+            return false;
+        }
+        for inner_id in shared {
+            let old_block = disk_inode.get_block_id(inner_id as u32, &self.block_device);
+            let new_block = fs.alloc_data();
+            fs.copy_data_block(old_block, new_block);
+            let ptr = disk_inode.data_block_ptr(inner_id as u32, &self.block_device);
+            disk_inode.set_block_ptr(ptr, new_block, &self.block_device);
+            // stays with the snapshots
+            fs.dealloc_data(old_block);
+        }
+        true
+    }
+    /// Release all data blocks of a disk inode
+    fn clear_size(&self, disk_inode: &mut DiskInode, fs: &mut CountedGuard<EasyFileSystem>) {
+        let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        fs.refund(inode_id, data_blocks_dealloc.len() as u32);
+        for data_block in data_blocks_dealloc.into_iter() {
+            fs.dealloc_data(data_block);
+        }
     }
 }
@@ -1,18 +1,38 @@
 //! Constants used in rCore
 
 pub const USER_STACK_SIZE: usize = 4096 * 2;
+/// The most a process can grow its heap to with sbrk
+pub const USER_HEAP_SIZE: usize = 0x40_0000;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 20;
 pub const KERNEL_HEAP_SIZE: usize = 0x20_0000;
 pub const MEMORY_END: usize = 0x88000000;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
+/// A megapage, mapped by a single level-1 PTE
+pub const HUGE_PAGE_SIZE: usize = 0x20_0000;
 pub const MAX_SYSCALL_NUM: usize = 500;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
+/// Where the shared read-only clock page is mapped in user space
+pub const CLOCK_PAGE: usize = TRAP_CONTEXT - PAGE_SIZE;
+/// Most threads a process can have besides its first one, see `sys_thread_create`
+pub const MAX_THREADS: usize = 16;
+/// Where the kernel starts looking for room to map a file
+pub const FILE_MAPPING_BASE: usize = 0x4000_0000;
+/// Where the kernel starts looking for room to attach a shared memory segment
+pub const SHM_BASE: usize = 0x5000_0000;
 pub const CLOCK_FREQ: usize = 12500000;
 pub const MMIO: &[(usize, usize)] = &[
     (0x10001000, 0x1000),
 ];
 
-pub const BIG_STRIDE: usize = 10000;
\ No newline at end of file
+pub const BIG_STRIDE: usize = 10000;
+/// Timer ticks a task may run before it is preempted, unless it sets its own
+pub const DEFAULT_TIMESLICE: usize = 1;
+/// The longest time slice a task can ask for, in timer ticks
+pub const MAX_TIMESLICE: usize = 100;
+/// Number of memory nodes, a single one until there is a multi-node build
+pub const MEMORY_NODES: usize = 1;
+/// Frames reclaim frees above the low watermark before it stops
+pub const RECLAIM_GAP: usize = 16;
\ No newline at end of file
@@ -0,0 +1,136 @@
+use super::{File, Stat, StatMode};
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use crate::task::{
+    block_current_and_run_next, current_task, wakeup_task, SwitchReason, TaskControlBlock,
+};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// The largest value the counter can hold
+const MAX_COUNT: u64 = u64::MAX - 1;
+
+/// A 64-bit event counter, written to add to it and read to take from it
+pub struct EventFd {
+    /// whether a read takes one instead of the whole count
+    semaphore: bool,
+    inner: UPSafeCell<EventFdInner>,
+    /// whether to return at once instead of waiting for the counter
+    nonblock: UPSafeCell<bool>,
+}
+
+/// The counter and its waiters in 'UPSafeCell'
+struct EventFdInner {
+    count: u64,
+    /// tasks blocked reading a zero counter
+    readers: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl EventFd {
+    /// Create an event counter starting from `count`
+    pub fn new(count: u64, semaphore: bool, nonblock: bool) -> Self {
+        Self {
+            semaphore,
+            inner: unsafe {
+                UPSafeCell::new(EventFdInner {
+                    count,
+                    readers: VecDeque::new(),
+                })
+            },
+            nonblock: unsafe { UPSafeCell::new(nonblock) },
+        }
+    }
+}
+
+/// Copy a native-endian `u64` into a user buffer of at least 8 bytes
+fn write_u64(buf: UserBuffer, value: u64) {
+    for (byte_ref, byte) in buf.into_iter().zip(value.to_ne_bytes().iter()) {
+        unsafe {
+            *byte_ref = *byte;
+        }
+    }
+}
+
+/// Read a native-endian `u64` from a user buffer, `None` if the buffer is too short
+fn read_u64(buf: UserBuffer) -> Option<u64> {
+    if buf.len() < 8 {
+        return None;
+    }
+    let mut bytes = [0u8; 8];
+    for (byte, byte_ref) in bytes.iter_mut().zip(buf) {
+        *byte = unsafe { *byte_ref };
+    }
+    Some(u64::from_ne_bytes(bytes))
+}
+
+impl File for EventFd {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    /// Take the count, or one of it in semaphore mode, waiting while it is zero.
+    /// Read nothing if `buf` can not hold a `u64`.
+    fn read(&self, buf: UserBuffer) -> usize {
+        if buf.len() < 8 {
+            return 0;
+        }
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            if inner.count == 0 {
+                if self.nonblock() {
+                    return 0;
+                }
+                inner.readers.push_back(current_task().unwrap());
+                drop(inner);
+                block_current_and_run_next(SwitchReason::BlockOnFile);
+                continue;
+            }
+            let value = if self.semaphore { 1 } else { inner.count };
+            inner.count -= value;
+            write_u64(buf, value);
+            return 8;
+        }
+    }
+    /// Add a `u64` to the count and wake up the readers.
+    /// Write nothing if the count would overflow.
+    fn write(&self, buf: UserBuffer) -> usize {
+        let Some(value) = read_u64(buf) else { return 0; };
+        let mut inner = self.inner.exclusive_access();
+        if value > MAX_COUNT - inner.count {
+            return 0;
+        }
+        inner.count += value;
+        if value != 0 {
+            // every reader checks the count again, those who find it taken wait again
+            for task in inner.readers.drain(..) {
+                wakeup_task(task);
+            }
+        }
+        8
+    }
+    fn status(&self) -> Stat {
+        Stat {
+            dev: 0,
+            ino: 0,
+            mode: StatMode::NULL,
+            nlink: 1,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+            size: 0,
+            blocks: 0,
+            pad: [0; 2],
+        }
+    }
+    fn read_would_block(&self) -> bool {
+        self.inner.exclusive_access().count == 0
+    }
+    fn nonblock(&self) -> bool {
+        *self.nonblock.exclusive_access()
+    }
+    fn set_nonblock(&self, nonblock: bool) {
+        *self.nonblock.exclusive_access() = nonblock;
+    }
+}
@@ -1,11 +1,19 @@
-use super::{File, Stat, StatMode};
+use super::{seek_offset, Dirent, File, Stat, StatMode, DIRENT_NAME_LEN};
 use crate::drivers::BLOCK_DEVICE;
 use crate::mm::UserBuffer;
 use crate::sync::UPSafeCell;
+use crate::task::{
+    block_current_and_run_next, current_process, current_task, wakeup_task, SwitchReason,
+    TaskControlBlock,
+};
+use crate::timer::{add_timer, get_time_us, remove_timer};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::*;
-use easy_fs::{EasyFileSystem, Inode};
+use core::mem::size_of;
+use easy_fs::{set_clock, EasyFileSystem, Inode, MODE_READ, MODE_WRITE};
 use lazy_static::*;
 
 /// A wrapper around a filesystem inode
@@ -13,6 +21,10 @@ use lazy_static::*;
 pub struct OSInode {
     readable: bool,
     writable: bool,
+    /// every write goes to the end of the file, wherever the offset is
+    append: UPSafeCell<bool>,
+    /// the directory the file was opened in, a file has no `..` to find it by
+    parent: Option<Arc<Inode>>,
     inner: UPSafeCell<OSInodeInner>,
 }
 
@@ -20,17 +32,37 @@ pub struct OSInode {
 pub struct OSInodeInner {
     offset: usize,
     inode: Arc<Inode>,
+    /// whether the inode is pinned in the block cache through this file
+    pinned: bool,
 }
 
 impl OSInode {
     /// Construct an OS inode from a inode
-    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Self {
+    pub fn new(readable: bool, writable: bool, append: bool, inode: Arc<Inode>) -> Self {
+        OPEN_INODES
+            .exclusive_access()
+            .entry(inode_key(&inode))
+            .or_insert((0, None))
+            .0 += 1;
         Self {
             readable,
             writable,
-            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+            append: unsafe { UPSafeCell::new(append) },
+            parent: None,
+            inner: unsafe {
+                UPSafeCell::new(OSInodeInner {
+                    offset: 0,
+                    inode,
+                    pinned: false,
+                })
+            },
         }
     }
+    /// Remember directory `parent` as the one the file was opened in
+    pub fn with_parent(mut self, parent: Option<Arc<Inode>>) -> Self {
+        self.parent = parent;
+        self
+    }
     /// Read all data inside a inode into vector
     pub fn read_all(&self) -> Vec<u8> {
         let mut inner = self.inner.exclusive_access();
@@ -46,16 +78,104 @@ impl OSInode {
         }
         v
     }
+    /// Read exactly `buf.len()` bytes at the offset and move past them,
+    /// false if the file ends before
+    pub fn read_exact(&self, buf: &mut [u8]) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let len = inner.inode.read_at(inner.offset, buf);
+        inner.offset += len;
+        len == buf.len()
+    }
+    /// Write all of `data` at the offset and move past it,
+    /// false with nothing written if it does not fit
+    pub fn write_all(&self, data: &[u8]) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.inode.write_at(inner.offset, data) != data.len() {
+            return false;
+        }
+        inner.offset += data.len();
+        notify_size_waiters(&inner.inode);
+        true
+    }
+}
+
+impl Drop for OSInode {
+    fn drop(&mut self) {
+        let inner = self.inner.exclusive_access();
+        if inner.pinned {
+            inner.inode.unpin();
+        }
+        // appends still combined in the cache are written back on close
+        inner.inode.flush_appends();
+        let key = inode_key(&inner.inode);
+        let mut open_inodes = OPEN_INODES.exclusive_access();
+        let (count, unlinked) = open_inodes.get_mut(&key).unwrap();
+        *count -= 1;
+        if *count > 0 {
+            return;
+        }
+        let unlinked = unlinked.take();
+        open_inodes.remove(&key);
+        drop(open_inodes);
+        // the last file open on an inode with no links left
+        if let Some(inode) = unlinked {
+            inode.free();
+        }
+    }
+}
+
+/// An inode by the filesystem it is on and its inode id,
+/// as the filesystems mounted number their inodes each from 0
+type InodeKey = (usize, u32);
+
+/// Key of `inode` in [`OPEN_INODES`] and [`SIZE_WAITERS`]
+fn inode_key(inode: &Inode) -> InodeKey {
+    (inode.fs_id(), inode.inode_id())
+}
+
+/// Number of open files on an inode, together with the inode
+/// if its last link is gone meanwhile, to be freed once the last of them is closed
+type OpenInode = (usize, Option<Arc<Inode>>);
+
+lazy_static! {
+    /// Open files on each inode
+    static ref OPEN_INODES: UPSafeCell<BTreeMap<InodeKey, OpenInode>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// Free an inode whose last link is gone, or leave it to the last file open on it,
+/// so that reads and writes through those files go on until they are closed
+pub fn free_unlinked(inode: Arc<Inode>) {
+    let mut open_inodes = OPEN_INODES.exclusive_access();
+    if let Some((_, unlinked)) = open_inodes.get_mut(&inode_key(&inode)) {
+        *unlinked = Some(inode);
+        return;
+    }
+    drop(open_inodes);
+    inode.free();
 }
 
 lazy_static! {
     /// The root of all inodes, or '/' in short
     pub static ref ROOT_INODE: Arc<Inode> = {
+        set_clock(|| get_time_us() as u64);
         let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
-        Arc::new(EasyFileSystem::root_inode(&efs))
+        EasyFileSystem::root_inode(&efs)
     };
 }
 
+/// The directory current process takes as '/', see `sys_chroot`. The root of all
+/// inodes if the process has not changed it, or if there is no process yet.
+pub fn process_root() -> Arc<Inode> {
+    let Some(process) = current_process() else { return ROOT_INODE.clone(); };
+    let inner = process.inner_exclusive_access();
+    inner
+        .root
+        .as_ref()
+        .and_then(|dir| dir.inode())
+        .unwrap_or_else(|| ROOT_INODE.clone())
+}
+
 /// List all files in the filesystems
 pub fn list_apps() {
     println!("/**** APPS ****");
@@ -71,8 +191,16 @@ bitflags! {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
+        /// with `CREATE`, fail if the path exists already, even as a symbolic link
+        const EXCL = 1 << 7;
+        /// create the file if it does not exist, an existing one is truncated
         const CREATE = 1 << 9;
+        /// truncate an existing file to zero length
         const TRUNC = 1 << 10;
+        const NONBLOCK = 1 << 11;
+        const APPEND = 1 << 12;
+        /// fail if the last component of the path is a symbolic link
+        const NOFOLLOW = 1 << 17;
     }
 }
 
@@ -80,60 +208,192 @@ impl OpenFlags {
     /// Get the current read write permission on an inode
     /// does not check validity for simplicity
     /// returns (readable, writable)
+    /// Only `WRONLY` and `RDWR` count, the other flags do not make a file writable
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
-            (true, false)
-        } else if self.contains(Self::WRONLY) {
+        if self.contains(Self::WRONLY) {
             (false, true)
-        } else {
+        } else if self.contains(Self::RDWR) {
             (true, true)
+        } else {
+            (true, false)
         }
     }
 }
 
-/// Open a file by path
+/// Split `path` into the directory holding its last component and that component,
+/// a relative path starts at `dir` and an absolute one at `root`
+fn parent_and_name<'a>(
+    root: &Inode,
+    dir: &Arc<Inode>,
+    path: &'a str,
+) -> Option<(Arc<Inode>, &'a str)> {
+    let (parent, name) = match path.rfind('/') {
+        // keep the `/` so that "/name" looks in the root and the parent must be a directory
+        Some(pos) => (dir.find_path_under(root, &path[..=pos])?, &path[pos + 1..]),
+        None => (dir.clone(), path),
+    };
+    if name.is_empty() {
+        return None;
+    }
+    Some((parent, name))
+}
+
+/// Open a file by path, which starts at the root of current process
 pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+    open_file_at(&process_root(), name, flags)
+}
+
+/// Open a file by path, a relative one starting at directory `dir`
+/// and an absolute one at the root of current process
+pub fn open_file_at(dir: &Arc<Inode>, name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+    let root = process_root();
     let (readable, writable) = flags.read_write();
+    let append = flags.contains(OpenFlags::APPEND);
+    if flags.contains(OpenFlags::NOFOLLOW)
+        && dir
+            .find_path_nofollow_under(&root, name)
+            .is_some_and(|inode| inode.is_symlink())
+    {
+        return None;
+    }
+    if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL)
+        && dir.find_path_nofollow_under(&root, name).is_some()
+    {
+        return None;
+    }
+    // a directory is only read, its entries are never truncated or written over
+    if let Some(inode) = dir.find_path_under(&root, name) {
+        let is_dir = inode.read_disk_inode(|disk_inode| disk_inode.is_dir());
+        let truncates = flags.intersects(OpenFlags::CREATE | OpenFlags::TRUNC);
+        if is_dir && (writable || truncates) {
+            return None;
+        }
+        // the bits of the owner decide, there are no other users
+        if readable && !inode.permits(MODE_READ)
+            || (writable || truncates) && !inode.permits(MODE_WRITE)
+        {
+            return None;
+        }
+    }
+    let parent = parent_and_name(&root, dir, name).map(|(parent, _)| parent);
+    let open = |inode| {
+        Arc::new(OSInode::new(readable, writable, append, inode).with_parent(parent.clone()))
+    };
     if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = ROOT_INODE.find(name) {
+        if let Some(inode) = dir.find_path_under(&root, name) {
             // clear size
-            inode.clear();
-            Some(Arc::new(OSInode::new(readable, writable, inode)))
+            inode.truncate(0);
+            Some(open(inode))
         } else {
             // create file
-            ROOT_INODE
-                .create(name)
-                .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
+            let (parent, name) = parent_and_name(&root, dir, name)?;
+            parent.create(name).map(open)
         }
     } else {
-        ROOT_INODE.find(name).map(|inode| {
+        dir.find_path_under(&root, name).map(|inode| {
             if flags.contains(OpenFlags::TRUNC) {
-                inode.clear();
+                inode.truncate(0);
             }
-            Arc::new(OSInode::new(readable, writable, inode))
+            open(inode)
         })
     }
 }
 
+/// A task blocked until an inode grows to `min_size` bytes
+struct SizeWaiter {
+    key: InodeKey,
+    min_size: usize,
+    task: Arc<TaskControlBlock>,
+}
+
+lazy_static! {
+    /// Tasks blocked in [`wait_size`]
+    static ref SIZE_WAITERS: UPSafeCell<Vec<SizeWaiter>> = unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+/// Block current task until `inode` is at least `min_size` bytes long
+/// or `timeout_ms` milliseconds have passed.
+/// Return the size of the inode, or `None` on timeout.
+pub fn wait_size(inode: &Inode, min_size: usize, timeout_ms: usize) -> Option<usize> {
+    let expire_us = get_time_us() + timeout_ms * 1000;
+    let key = inode_key(inode);
+    loop {
+        let size = inode.read_disk_inode(|disk_inode| disk_inode.size as usize);
+        if size >= min_size {
+            return Some(size);
+        }
+        if get_time_us() >= expire_us {
+            return None;
+        }
+        let task = current_task().unwrap();
+        SIZE_WAITERS.exclusive_access().push(SizeWaiter {
+            key,
+            min_size,
+            task: task.clone(),
+        });
+        add_timer(expire_us, task.clone());
+        block_current_and_run_next(SwitchReason::BlockOnFile);
+        // woken up either by a write or by the timer, forget about the other one
+        SIZE_WAITERS
+            .exclusive_access()
+            .retain(|waiter| !Arc::ptr_eq(&waiter.task, &task));
+        remove_timer(&task);
+    }
+}
+
+/// Wake up the tasks waiting for `inode` to grow, if it is large enough now
+fn notify_size_waiters(inode: &Inode) {
+    if SIZE_WAITERS.exclusive_access().is_empty() {
+        return;
+    }
+    let key = inode_key(inode);
+    let size = inode.read_disk_inode(|disk_inode| disk_inode.size as usize);
+    SIZE_WAITERS.exclusive_access().retain(|waiter| {
+        if waiter.key == key && size >= waiter.min_size {
+            wakeup_task(waiter.task.clone());
+            false
+        } else {
+            true
+        }
+    });
+}
+
+/// Create a symbolic link at `path` pointing to `target`, which need not exist
+pub fn symlink(target: &str, path: &str) -> Option<Arc<Inode>> {
+    let root = process_root();
+    let (parent, name) = parent_and_name(&root, &root, path)?;
+    parent.symlink(name, target)
+}
+
+/// Link `new_name` to the file at `old_name`, paths start at the root of current process
 pub fn increase_nlink(old_name: &str, new_name: &str) -> Option<Arc<Inode>> {
-    ROOT_INODE
-        .modify_disk_inode(|disk_inode| ROOT_INODE.copy_dir_entry(disk_inode, old_name, new_name));
-    ROOT_INODE.find(old_name).inspect(|inode| {
-        inode.modify_disk_inode(|disk_inode| {
-            disk_inode.nlink += 1;
-        })
-    })
+    let root = process_root();
+    root.link_under(&root, old_name, new_name)
 }
 
 impl File for OSInode {
     fn status(&self) -> Stat {
-        let (ino, nlink, mode) = self.inner.exclusive_access().inode.state(&ROOT_INODE);
+        let inode = self.inner.exclusive_access().inode.clone();
+        let (ino, nlink, mode) = inode.state();
+        let (atime, mtime, ctime, size) = inode.read_disk_inode(|disk_inode| {
+            (
+                disk_inode.atime,
+                disk_inode.mtime,
+                disk_inode.ctime,
+                disk_inode.size,
+            )
+        });
         Stat {
             dev: 0,
             ino: ino as u64,
             mode: if mode { StatMode::FILE } else { StatMode::DIR },
             nlink,
-            pad: [0; 7],
+            atime,
+            mtime,
+            ctime,
+            size: size as u64,
+            blocks: inode.allocated_blocks() as u64,
+            pad: [0; 2],
         }
     }
     fn readable(&self) -> bool {
@@ -142,6 +402,12 @@ impl File for OSInode {
     fn writable(&self) -> bool {
         self.writable
     }
+    fn append_mode(&self) -> bool {
+        *self.append.exclusive_access()
+    }
+    fn set_append_mode(&self, append: bool) {
+        *self.append.exclusive_access() = append;
+    }
     fn read(&self, mut buf: UserBuffer) -> usize {
         let mut inner = self.inner.exclusive_access();
         let mut total_read_size = 0usize;
@@ -157,13 +423,127 @@ impl File for OSInode {
     }
     fn write(&self, buf: UserBuffer) -> usize {
         let mut inner = self.inner.exclusive_access();
+        if self.append_mode() {
+            // nothing else runs until the write is done, so no one can slip in behind the end
+            inner.offset = inner
+                .inode
+                .read_disk_inode(|disk_inode| disk_inode.size as usize);
+        }
         let mut total_write_size = 0usize;
         for slice in buf.buffers.iter() {
             let write_size = inner.inode.write_at(inner.offset, slice);
-            assert_eq!(write_size, slice.len());
+            // the quota of the directory is used up
+            if write_size == 0 {
+                break;
+            }
             inner.offset += write_size;
             total_write_size += write_size;
         }
+        notify_size_waiters(&inner.inode);
         total_write_size
     }
+    fn inode(&self) -> Option<Arc<Inode>> {
+        Some(self.inner.exclusive_access().inode.clone())
+    }
+    /// A directory walks `..` up to `root`, a file is looked up in the directory it was
+    /// opened in first. None once it is unlinked or moved out of that directory.
+    fn path_under(&self, root: &Inode) -> Option<String> {
+        let inode = self.inner.exclusive_access().inode.clone();
+        if inode.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+            return inode.path_under(root);
+        }
+        let parent = self.parent.as_ref()?;
+        let name = parent.find_name(&inode)?;
+        let mut path = parent.path_under(root)?;
+        if !path.ends_with('/') {
+            path.push('/');
+        }
+        path.push_str(&name);
+        Some(path)
+    }
+    fn drain(&self, buf: UserBuffer) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let mut data = alloc::vec![0u8; buf.len()];
+        let read_size = inner.inode.drain(&mut data)?;
+        for (byte, ch) in buf.into_iter().zip(data[..read_size].iter()) {
+            unsafe {
+                byte.write_volatile(*ch);
+            }
+        }
+        // the data left behind moved to the front as well
+        inner.offset = inner.offset.saturating_sub(read_size);
+        Some(read_size)
+    }
+    fn seek(&self, offset: isize, whence: usize) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let size = inner
+            .inode
+            .read_disk_inode(|disk_inode| disk_inode.size as usize);
+        inner.offset = seek_offset(inner.offset, size, offset, whence)?;
+        Some(inner.offset)
+    }
+    fn swap_offset(&self, offset: usize) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        Some(core::mem::replace(&mut inner.offset, offset))
+    }
+    fn append(&self, buf: UserBuffer) -> Option<usize> {
+        let inner = self.inner.exclusive_access();
+        let mut data = Vec::with_capacity(buf.len());
+        for slice in buf.buffers.iter() {
+            data.extend_from_slice(slice);
+        }
+        let offset = inner.inode.append(&data)?;
+        notify_size_waiters(&inner.inode);
+        Some(offset)
+    }
+    fn getdents(&self, buf: UserBuffer) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        if !inner
+            .inode
+            .read_disk_inode(|disk_inode| disk_inode.is_dir())
+        {
+            return None;
+        }
+        let (entries, next) = inner
+            .inode
+            .read_dir(inner.offset, buf.len() / size_of::<Dirent>());
+        let mut data = Vec::with_capacity(entries.len() * size_of::<Dirent>());
+        for (name, ino, is_dir) in entries {
+            let mut dirent = Dirent {
+                ino: ino as u64,
+                mode: if is_dir {
+                    StatMode::DIR
+                } else {
+                    StatMode::FILE
+                },
+                name: [0; DIRENT_NAME_LEN],
+            };
+            dirent.name[..name.len()].copy_from_slice(name.as_bytes());
+            data.extend_from_slice(unsafe {
+                core::slice::from_raw_parts(&dirent as *const _ as *const u8, size_of::<Dirent>())
+            });
+        }
+        for (byte, value) in buf.into_iter().zip(data.iter()) {
+            unsafe {
+                byte.write_volatile(*value);
+            }
+        }
+        inner.offset = next;
+        Some(data.len())
+    }
+    fn set_pinned(&self, pinned: bool) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.pinned == pinned {
+            // pinning twice through the same file is fine, unpinning twice is not
+            return pinned;
+        }
+        if pinned && !inner.inode.pin() {
+            return false;
+        }
+        if !pinned {
+            inner.inode.unpin();
+        }
+        inner.pinned = pinned;
+        true
+    }
 }
@@ -1,7 +1,15 @@
+mod eventfd;
 mod inode;
+mod mount;
+mod pipe;
+mod ringfile;
+mod snapshot;
 mod stdio;
 
 use crate::mm::UserBuffer;
+use alloc::string::String;
+use alloc::sync::Arc;
+use easy_fs::Inode;
 
 /// The common abstraction of all IO resources
 pub trait File: Send + Sync {
@@ -10,6 +18,92 @@ pub trait File: Send + Sync {
     fn read(&self, buf: UserBuffer) -> usize;
     fn write(&self, buf: UserBuffer) -> usize;
     fn status(&self) -> Stat;
+    /// The easy-fs inode behind this file, if it is a regular file
+    fn inode(&self) -> Option<Arc<Inode>> {
+        None
+    }
+    /// Path of the file with directory `root` taken as `/`, if it has one
+    fn path_under(&self, _root: &Inode) -> Option<String> {
+        None
+    }
+    /// Read the head of the file and cut it off in one step
+    fn drain(&self, _buf: UserBuffer) -> Option<usize> {
+        None
+    }
+    /// Pin or unpin the metadata of the file in the block cache
+    fn set_pinned(&self, _pinned: bool) -> bool {
+        false
+    }
+    /// Whether a write to the file would block right now
+    fn write_would_block(&self) -> bool {
+        false
+    }
+    /// Whether a read and a write would return at once right now, as (readable, writable),
+    /// false for what the file is not open for
+    fn poll_ready(&self) -> (bool, bool) {
+        (
+            self.readable() && !self.read_would_block(),
+            self.writable() && !self.write_would_block(),
+        )
+    }
+    /// Whether nothing written to the file can ever be read, like a pipe with no read end left
+    fn write_broken(&self) -> bool {
+        false
+    }
+    /// Whether a read from the file would block right now
+    fn read_would_block(&self) -> bool {
+        false
+    }
+    /// Whether reads and writes return at once instead of blocking
+    fn nonblock(&self) -> bool {
+        false
+    }
+    /// Set the non-blocking mode, ignored by files that never block
+    fn set_nonblock(&self, _nonblock: bool) {}
+    /// Whether every write goes to the end of the file, wherever the offset is
+    fn append_mode(&self) -> bool {
+        false
+    }
+    /// Set the append mode, ignored by files without an end to write at
+    fn set_append_mode(&self, _append: bool) {}
+    /// Move the offset of the file, return the new one or `None` if it can not seek
+    fn seek(&self, _offset: isize, _whence: usize) -> Option<usize> {
+        None
+    }
+    /// Set the offset of the file in one step, return the old one or `None` if it can not seek
+    fn swap_offset(&self, _offset: usize) -> Option<usize> {
+        None
+    }
+    /// Write at the end of the file in one step without moving the offset,
+    /// return where it was written or `None` if the file has no end
+    fn append(&self, _buf: UserBuffer) -> Option<usize> {
+        None
+    }
+    /// Fill `buf` with [`Dirent`]s of the directory from the offset on and move past them,
+    /// return the bytes written or `None` if it is not a directory
+    fn getdents(&self, _buf: UserBuffer) -> Option<usize> {
+        None
+    }
+}
+
+const SEEK_SET: usize = 0;
+const SEEK_CUR: usize = 1;
+const SEEK_END: usize = 2;
+
+/// Compute the offset of a seek from the current offset and the size of the file
+fn seek_offset(current: usize, size: usize, offset: isize, whence: usize) -> Option<usize> {
+    let base = match whence {
+        SEEK_SET => 0,
+        SEEK_CUR => current,
+        SEEK_END => size,
+        _ => return None,
+    };
+    let new_offset = base as isize + offset;
+    if new_offset < 0 {
+        None
+    } else {
+        Some(new_offset as usize)
+    }
 }
 
 /// The stat of a inode
@@ -24,8 +118,18 @@ pub struct Stat {
     pub mode: StatMode,
     /// number of hard links
     pub nlink: u32,
+    /// time of last access, in microseconds since boot
+    pub atime: u64,
+    /// time of last data modification
+    pub mtime: u64,
+    /// time of last status change
+    pub ctime: u64,
+    /// size in bytes, holes included
+    pub size: u64,
+    /// number of blocks in use, fewer than the size takes if the file has holes
+    pub blocks: u64,
     /// unused pad
-    pad: [u64; 7],
+    pad: [u64; 2],
 }
 
 bitflags! {
@@ -40,5 +144,26 @@ bitflags! {
     }
 }
 
+/// Max length of a name in [`Dirent`], including the trailing '\0'
+const DIRENT_NAME_LEN: usize = 28;
+
+/// A directory entry as `sys_getdents` writes it
+#[repr(C)]
+#[derive(Debug)]
+pub struct Dirent {
+    /// inode number
+    pub ino: u64,
+    /// whether a directory or a file
+    pub mode: StatMode,
+    /// name, ended by '\0'
+    pub name: [u8; DIRENT_NAME_LEN],
+}
+
+pub use easy_fs::{lock_stats, DirQuota, FsInfo, LockStats, StatFs};
+pub use eventfd::EventFd;
 pub use inode::*;
+pub use mount::{mount, umount};
+pub use pipe::make_pipe;
+pub use ringfile::RingFile;
+pub use snapshot::SnapshotFile;
 pub use stdio::{Stdin, Stdout};
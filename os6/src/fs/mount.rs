@@ -0,0 +1,57 @@
+//! Easy-fs images kept in files of the root filesystem, mounted on its directories
+use super::{open_file, process_root, File, OSInode, OpenFlags};
+use crate::drivers::BLOCK_DEVICE;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easy_fs::BlockDevice;
+
+/// An image in a file used as a block device. Block `i` of the image is the `i`th
+/// data block of the file, read and written on the device under the file directly,
+/// as the block cache is locked whenever a device is used.
+/// The file must not be written or cut down through the root filesystem while mounted.
+struct ImageDevice {
+    /// data blocks of the file on [`BLOCK_DEVICE`]
+    block_ids: Vec<usize>,
+    /// kept open, so that an image unlinked while mounted is freed only once unmounted
+    _image: Arc<OSInode>,
+}
+
+impl BlockDevice for ImageDevice {
+    /// A block past the end of the image reads as zeros
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        match self.block_ids.get(block_id) {
+            Some(&block_id) => BLOCK_DEVICE.read_block(block_id, buf),
+            None => buf.fill(0),
+        }
+    }
+    /// A write past the end of the image is dropped
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        if let Some(&block_id) = self.block_ids.get(block_id) {
+            BLOCK_DEVICE.write_block(block_id, buf);
+        }
+    }
+}
+
+/// Mount the easy-fs image in the file at `source` on the directory at `target`.
+/// Fail if `source` is not a plain file one may read and write holding an easy-fs,
+/// or `target` can not be mounted on, see [`easy_fs::Inode::mount`].
+pub fn mount(source: &str, target: &str) -> bool {
+    let Some(image) = open_file(source, OpenFlags::RDWR) else { return false; };
+    let Some(block_ids) = image.inode().and_then(|inode| inode.data_block_ids()) else {
+        return false;
+    };
+    let root = process_root();
+    let Some(dir) = root.find_path_under(&root, target) else { return false; };
+    dir.mount(Arc::new(ImageDevice {
+        block_ids,
+        _image: image,
+    }))
+}
+
+/// Unmount the filesystem mounted on the directory at `target`,
+/// fail if there is none or a file on it is still open
+pub fn umount(target: &str) -> bool {
+    let root = process_root();
+    root.find_path_under(&root, target)
+        .is_some_and(|mounted| mounted.umount())
+}
@@ -0,0 +1,89 @@
+use super::{File, Stat, StatMode};
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easy_fs::Inode;
+
+/// A file over a ring inode. Writes go after the newest data and push the oldest
+/// out once it is full, reads take the oldest data in order. The read cursor is
+/// kept in the inode, so it is shared by everyone who opens the ring.
+/// Reading an empty ring gives end of file, or `EAGAIN` in non-blocking mode.
+pub struct RingFile {
+    readable: bool,
+    writable: bool,
+    inode: Arc<Inode>,
+    nonblock: UPSafeCell<bool>,
+}
+
+impl RingFile {
+    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Self {
+        Self {
+            readable,
+            writable,
+            inode,
+            nonblock: unsafe { UPSafeCell::new(false) },
+        }
+    }
+}
+
+impl File for RingFile {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+    fn writable(&self) -> bool {
+        self.writable
+    }
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let mut total_read_size = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let read_size = self.inode.ring_read(slice);
+            total_read_size += read_size;
+            if read_size < slice.len() {
+                break;
+            }
+        }
+        total_read_size
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        // one write, so that only its last bytes survive if it is longer than the ring
+        let mut data: Vec<u8> = Vec::with_capacity(buf.len());
+        for slice in buf.buffers.iter() {
+            data.extend_from_slice(slice);
+        }
+        self.inode.ring_write(&data)
+    }
+    fn status(&self) -> Stat {
+        let (ino, nlink, _) = self.inode.state();
+        let (atime, mtime, ctime, size) = self.inode.read_disk_inode(|disk_inode| {
+            (
+                disk_inode.atime,
+                disk_inode.mtime,
+                disk_inode.ctime,
+                disk_inode.size,
+            )
+        });
+        Stat {
+            dev: 0,
+            ino: ino as u64,
+            mode: StatMode::FILE,
+            nlink,
+            atime,
+            mtime,
+            ctime,
+            size: size as u64,
+            blocks: self.inode.allocated_blocks() as u64,
+            pad: [0; 2],
+        }
+    }
+    fn read_would_block(&self) -> bool {
+        self.inode
+            .read_disk_inode(|disk_inode| disk_inode.ring_len == 0)
+    }
+    fn nonblock(&self) -> bool {
+        *self.nonblock.exclusive_access()
+    }
+    fn set_nonblock(&self, nonblock: bool) {
+        *self.nonblock.exclusive_access() = nonblock;
+    }
+}
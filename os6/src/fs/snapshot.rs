@@ -0,0 +1,71 @@
+use super::{seek_offset, File, Stat, StatMode};
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use easy_fs::{Inode, Snapshot};
+
+/// A read-only file over a snapshot of an inode
+pub struct SnapshotFile {
+    snapshot: Snapshot,
+    /// inode number of the file the snapshot was taken from
+    ino: u32,
+    offset: UPSafeCell<usize>,
+}
+
+impl SnapshotFile {
+    /// Freeze the current data of `inode`
+    pub fn new(inode: &Inode) -> Self {
+        Self {
+            snapshot: inode.snapshot(),
+            ino: inode.inode_id(),
+            offset: unsafe { UPSafeCell::new(0) },
+        }
+    }
+}
+
+impl File for SnapshotFile {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let mut offset = self.offset.exclusive_access();
+        let mut total_read_size = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let read_size = self.snapshot.read_at(*offset, slice);
+            if read_size == 0 {
+                break;
+            }
+            *offset += read_size;
+            total_read_size += read_size;
+        }
+        total_read_size
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        panic!("Cannot write to a snapshot!");
+    }
+    fn status(&self) -> Stat {
+        Stat {
+            dev: 0,
+            ino: self.ino as u64,
+            mode: StatMode::FILE,
+            nlink: 1,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+            size: self.snapshot.size() as u64,
+            blocks: 0,
+            pad: [0; 2],
+        }
+    }
+    fn seek(&self, offset: isize, whence: usize) -> Option<usize> {
+        let mut current = self.offset.exclusive_access();
+        *current = seek_offset(*current, self.snapshot.size(), offset, whence)?;
+        Some(*current)
+    }
+    fn swap_offset(&self, offset: usize) -> Option<usize> {
+        let mut current = self.offset.exclusive_access();
+        Some(core::mem::replace(&mut *current, offset))
+    }
+}
@@ -1,7 +1,7 @@
 use super::File;
 use crate::mm::UserBuffer;
 use crate::sbi::console_getchar;
-use crate::task::suspend_current_and_run_next;
+use crate::task::{suspend_current_and_run_next, SwitchReason};
 
 /// The standard input
 pub struct Stdin;
@@ -22,7 +22,7 @@ impl File for Stdin {
         loop {
             c = console_getchar();
             if c == 0 {
-                suspend_current_and_run_next();
+                suspend_current_and_run_next(SwitchReason::BlockOnFile);
                 continue;
             } else {
                 break;
@@ -65,6 +65,11 @@ pub fn rust_main() -> ! {
     println!("[kernel] Hello, world!");
     mm::init();
     mm::remap_test();
+    #[cfg(feature = "lockdep")]
+    {
+        sync::lockdep_init();
+        sync::lockdep_test();
+    }
     trap::init();
     trap::enable_timer_interrupt();
     timer::set_next_trigger();
@@ -42,19 +42,85 @@ trait FrameAllocator {
     fn dealloc(&mut self, ppn: PhysPageNum);
 }
 
+/// Frame allocator statistics for `sys_frame_stats`
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct FrameStats {
+    pub total: usize,
+    pub free: usize,
+    /// the most physically contiguous free frames
+    pub largest_free_run: usize,
+    /// allocations and frees since boot
+    pub allocs: usize,
+    pub frees: usize,
+}
+
 /// an implementation for frame allocator
 pub struct StackFrameAllocator {
     current: usize,
     end: usize,
     recycled: Vec<usize>,
+    total: usize,
+    allocs: usize,
+    frees: usize,
 }
 
 impl StackFrameAllocator {
     pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
         self.current = l.0;
         self.end = r.0;
+        self.total = self.end - self.current;
         info!("last {} Physical Frames.", self.end - self.current);
     }
+    /// Number of frames that can still be allocated
+    pub fn free_count(&self) -> usize {
+        self.end - self.current + self.recycled.len()
+    }
+    /// Length of the longest run of free frames with consecutive ppns
+    pub fn largest_free_run(&self) -> usize {
+        let mut recycled = self.recycled.clone();
+        recycled.sort_unstable();
+        let mut largest = 0;
+        let mut run = 0;
+        let mut last: Option<usize> = None;
+        for &ppn in recycled.iter() {
+            run = if last == Some(ppn.wrapping_sub(1)) {
+                run + 1
+            } else {
+                1
+            };
+            largest = largest.max(run);
+            last = Some(ppn);
+        }
+        // frames never allocated lie from `current` on, after every recycled one
+        let mut tail = self.end - self.current;
+        if last.is_some() && last == self.current.checked_sub(1) {
+            tail += run;
+        }
+        largest.max(tail)
+    }
+    /// Allocate `count` frames with consecutive ppns, the first a multiple of `align`.
+    /// They are cut from the frames never allocated, the ones skipped for the
+    /// alignment are recycled.
+    pub fn alloc_contiguous(&mut self, count: usize, align: usize) -> Option<PhysPageNum> {
+        let start = self.current.div_ceil(align) * align;
+        if start + count > self.end {
+            return None;
+        }
+        self.recycled.extend(self.current..start);
+        self.current = start + count;
+        self.allocs += count;
+        Some(start.into())
+    }
+    pub fn stats(&self) -> FrameStats {
+        FrameStats {
+            total: self.total,
+            free: self.free_count(),
+            largest_free_run: self.largest_free_run(),
+            allocs: self.allocs,
+            frees: self.frees,
+        }
+    }
 }
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
@@ -62,17 +128,22 @@ impl FrameAllocator for StackFrameAllocator {
             current: 0,
             end: 0,
             recycled: Vec::new(),
+            total: 0,
+            allocs: 0,
+            frees: 0,
         }
     }
     fn alloc(&mut self) -> Option<PhysPageNum> {
-        if let Some(ppn) = self.recycled.pop() {
-            Some(ppn.into())
+        let ppn = if let Some(ppn) = self.recycled.pop() {
+            ppn
         } else if self.current == self.end {
-            None
+            return None;
         } else {
             self.current += 1;
-            Some((self.current - 1).into())
-        }
+            self.current - 1
+        };
+        self.allocs += 1;
+        Some(ppn.into())
     }
     fn dealloc(&mut self, ppn: PhysPageNum) {
         let ppn = ppn.0;
@@ -82,6 +153,7 @@ impl FrameAllocator for StackFrameAllocator {
         }
         // recycle
         self.recycled.push(ppn);
+        self.frees += 1;
     }
 }
 
@@ -111,6 +183,28 @@ pub fn frame_alloc() -> Option<FrameTracker> {
         .map(FrameTracker::new)
 }
 
+/// Allocate `count` physically contiguous frames starting at a multiple of `align` frames
+pub fn frame_alloc_contiguous(count: usize, align: usize) -> Option<Vec<FrameTracker>> {
+    let start = FRAME_ALLOCATOR
+        .exclusive_access()
+        .alloc_contiguous(count, align)?;
+    Some(
+        (start.0..start.0 + count)
+            .map(|ppn| FrameTracker::new(ppn.into()))
+            .collect(),
+    )
+}
+
+/// Number of free frames left
+pub fn frame_free_count() -> usize {
+    FRAME_ALLOCATOR.exclusive_access().free_count()
+}
+
+/// Counters and fragmentation of the frame allocator
+pub fn frame_stats() -> FrameStats {
+    FRAME_ALLOCATOR.exclusive_access().stats()
+}
+
 /// deallocate a frame
 pub fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
@@ -1,10 +1,14 @@
 //! Implementation of [`MapArea`] and [`MemorySet`].
 
-use super::{frame_alloc, FrameTracker};
+use super::shm::ShmAttachment;
+use super::{frame_alloc, frame_alloc_contiguous, FrameTracker};
 use super::{PTEFlags, PageTable, PageTableEntry};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE};
+use crate::config::{
+    CLOCK_PAGE, HUGE_PAGE_SIZE, MEMORY_END, MMIO, PAGE_SIZE, SHM_BASE, TRAMPOLINE, TRAP_CONTEXT,
+    USER_STACK_SIZE,
+};
 use crate::sync::UPSafeCell;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
@@ -25,6 +29,9 @@ extern "C" {
     fn strampoline();
 }
 
+/// Pages in a megapage
+const HUGE_PAGE_PAGES: usize = HUGE_PAGE_SIZE / PAGE_SIZE;
+
 lazy_static! {
     /// a memory set instance through lazy_static! managing kernel space
     pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
@@ -40,6 +47,8 @@ pub fn kernel_token() -> usize {
 pub struct MemorySet {
     page_table: PageTable,
     areas: Vec<MapArea>,
+    /// pages of lazy areas which get a frame when first touched, see [`MemorySet::insert_lazy_area`]
+    lazy_ranges: Vec<VPNRange>,
 }
 
 impl MemorySet {
@@ -47,18 +56,72 @@ impl MemorySet {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            lazy_ranges: Vec::new(),
         }
     }
     pub fn token(&self) -> usize {
         self.page_table.token()
     }
-    pub fn unmap(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
-        for vpn in VPNRange::new(start_va.floor(), end_va.ceil()) {
-            if !self.page_table.unmap(vpn) {
-                return false;
+    /// Unmap `[start_va, end_va)` and free its frames, return the number of pages
+    /// that were mapped. Areas partly in the range are cut down, split in two
+    /// if the range is in the middle of one.
+    /// None if a page of the range is not in use, the pages before it are unmapped still.
+    pub fn unmap(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> Option<usize> {
+        let (start_vpn, end_vpn) = (start_va.floor(), end_va.ceil());
+        // a megapage goes as a whole or not at all
+        for vpn in [start_vpn, end_vpn] {
+            if vpn.0 % HUGE_PAGE_PAGES != 0 && self.page_table.is_huge(vpn) {
+                return None;
+            }
+        }
+        let mut vpn = start_vpn;
+        let mut unmapped = Some(0);
+        while vpn < end_vpn {
+            if self.page_table.is_huge(vpn) {
+                self.page_table.unmap_huge(vpn);
+                unmapped = unmapped.map(|n| n + HUGE_PAGE_PAGES);
+                vpn = VirtPageNum(vpn.0 + HUGE_PAGE_PAGES);
+                continue;
+            }
+            // a page of a lazy area not touched yet has nothing to unmap
+            if self.is_mapped(vpn) {
+                self.page_table.unmap(vpn);
+                unmapped = unmapped.map(|n| n + 1);
+            } else if !self.is_lazy(vpn) {
+                unmapped = None;
+                break;
+            }
+            vpn.step();
+        }
+        self.drop_lazy(start_vpn, vpn);
+        self.cut_areas(start_vpn, vpn);
+        unmapped
+    }
+    /// Take `[start, end)` out of the areas and drop their frames there
+    fn cut_areas(&mut self, start: VirtPageNum, end: VirtPageNum) {
+        if start >= end {
+            return;
+        }
+        for mut area in core::mem::take(&mut self.areas) {
+            let (l, r) = (area.vpn_range.get_start(), area.vpn_range.get_end());
+            if r <= start || end <= l {
+                self.areas.push(area);
+                continue;
+            }
+            let tail_frames = area.data_frames.split_off(&end);
+            // freed unless a fork still shares them
+            drop(area.data_frames.split_off(&start));
+            if end < r {
+                let mut tail = MapArea::from_another(&area);
+                tail.vpn_range = VPNRange::new(end, r);
+                tail.data_frames = tail_frames;
+                self.areas.push(tail);
+            }
+            if l < start {
+                area.vpn_range = VPNRange::new(l, start);
+                self.areas.push(area);
             }
         }
-        true
     }
     /// Assume that no conflicts.
     pub fn insert_framed_area(
@@ -72,6 +135,60 @@ impl MemorySet {
             None,
         )
     }
+    /// Like [`MemorySet::insert_framed_area`], but no page gets a frame before it is
+    /// touched, see [`MemorySet::fault_in_lazy`]. Fail if any page of the range is in use.
+    pub fn insert_lazy_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) -> bool {
+        let area = MapArea::new(start_va, end_va, MapType::Framed, permission);
+        if area.vpn_range.into_iter().any(|vpn| self.is_claimed(vpn)) {
+            return false;
+        }
+        self.lazy_ranges.push(area.vpn_range);
+        self.areas.push(area);
+        true
+    }
+    /// Like [`MemorySet::insert_framed_area`], but map it with megapages.
+    /// Fail with nothing mapped if the range is not megapage aligned
+    /// or there are not enough contiguous frames.
+    pub fn insert_huge_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) -> bool {
+        if !start_va.0.is_multiple_of(HUGE_PAGE_SIZE) || !end_va.0.is_multiple_of(HUGE_PAGE_SIZE) {
+            return false;
+        }
+        if VPNRange::new(start_va.floor(), end_va.ceil())
+            .into_iter()
+            .any(|vpn| self.is_lazy(vpn))
+        {
+            return false;
+        }
+        let mut area = MapArea::new(start_va, end_va, MapType::Framed, permission);
+        area.huge = true;
+        self.push(area, None)
+    }
+    /// Find the first `len` bytes of unmapped space at or after `start`
+    pub fn find_free_area(&self, start: VirtAddr, len: usize) -> VirtAddr {
+        let mut start_vpn = start.floor();
+        let page_count = len.div_ceil(PAGE_SIZE);
+        'search: loop {
+            let mut vpn = start_vpn;
+            for _ in 0..page_count {
+                if self.is_claimed(vpn) {
+                    start_vpn = VirtPageNum(vpn.0 + 1);
+                    continue 'search;
+                }
+                vpn.step();
+            }
+            return start_vpn.into();
+        }
+    }
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
         if let Some((idx, area)) = self
             .areas
@@ -83,6 +200,44 @@ impl MemorySet {
             self.areas.remove(idx);
         }
     }
+    /// Map the frames of a shared memory segment read-write at the first room for them
+    /// from [`SHM_BASE`] on, return where. None if a page can not be mapped.
+    pub fn attach_shm(&mut self, attachment: ShmAttachment) -> Option<VirtAddr> {
+        let len = attachment.frames().len() * PAGE_SIZE;
+        let start = self.find_free_area(VirtAddr(SHM_BASE), len);
+        let mut area = MapArea::new(
+            start,
+            VirtAddr(start.0 + len),
+            MapType::Framed,
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        );
+        let flags = PTEFlags::from_bits(area.map_perm.bits).unwrap();
+        for (vpn, frame) in area.vpn_range.into_iter().zip(attachment.frames()) {
+            if !self.page_table.map(vpn, frame.ppn, flags) {
+                for &vpn in area.data_frames.keys() {
+                    self.page_table.unmap(vpn);
+                }
+                return None;
+            }
+            area.data_frames.insert(vpn, Arc::clone(frame));
+        }
+        area.shm = Some(attachment);
+        self.areas.push(area);
+        Some(start)
+    }
+    /// Unmap the shared memory segment attached at `start`, false if none is
+    pub fn detach_shm(&mut self, start: VirtAddr) -> bool {
+        let Some(idx) = self
+            .areas
+            .iter()
+            .position(|area| area.shm.is_some() && area.vpn_range.get_start() == start.floor())
+        else {
+            return false;
+        };
+        let mut area = self.areas.remove(idx);
+        area.unmap(&mut self.page_table);
+        true
+    }
     fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) -> bool {
         if !map_area.map(&mut self.page_table) {
             return false;
@@ -101,6 +256,21 @@ impl MemorySet {
             PTEFlags::R | PTEFlags::X,
         );
     }
+    /// Map the shared clock page read-only for user.
+    /// Like trampoline, it is not collected by areas.
+    pub fn map_clock_page(&mut self, ppn: PhysPageNum) -> bool {
+        self.page_table.map(
+            VirtAddr::from(CLOCK_PAGE).into(),
+            ppn,
+            PTEFlags::R | PTEFlags::U,
+        )
+    }
+    /// Physical page of the clock page if it is mapped
+    pub fn clock_page(&self) -> Option<PhysPageNum> {
+        self.translate(VirtAddr::from(CLOCK_PAGE).into())
+            .filter(|pte| pte.is_valid())
+            .map(|pte| pte.ppn())
+    }
     /// Without kernel stacks.
     pub fn new_kernel() -> Self {
         let mut memory_set = Self::new_bare();
@@ -246,15 +416,47 @@ impl MemorySet {
             elf.header.pt2.entry_point() as usize,
         )
     }
-    /// Copy an identical user_space
-    pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
+    /// Copy an identical user_space. The frames of user pages are shared instead,
+    /// writable ones copy-on-write, see [`MemorySet::copy_on_write`].
+    pub fn from_existed_user(user_space: &mut MemorySet) -> MemorySet {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
+        // pages not touched yet are left for the child to touch
+        memory_set.lazy_ranges = user_space.lazy_ranges.clone();
         // copy data sections/trap_context/user_stack
         for area in user_space.areas.iter() {
+            if area.map_type == MapType::Framed
+                && area.map_perm.contains(MapPermission::U)
+                && !area.huge
+            {
+                let mut new_area = MapArea::from_another(area);
+                for (&vpn, frame) in area.data_frames.iter() {
+                    // pages unmapped on their own are left out
+                    if !user_space.translate(vpn).is_some_and(|pte| pte.is_valid()) {
+                        continue;
+                    }
+                    // a shared memory segment stays shared
+                    if area.map_perm.contains(MapPermission::W) && area.shm.is_none() {
+                        user_space.page_table.set_cow(vpn);
+                    }
+                    let pte = user_space.translate(vpn).unwrap();
+                    memory_set.page_table.map(vpn, frame.ppn, pte.flags());
+                    if pte.is_cow() {
+                        memory_set.page_table.set_cow(vpn);
+                    }
+                    new_area.data_frames.insert(vpn, frame.clone());
+                }
+                memory_set.areas.push(new_area);
+                continue;
+            }
             let new_area = MapArea::from_another(area);
-            memory_set.push(new_area, None);
+            if !memory_set.push(new_area, None) && area.huge {
+                // no contiguous frames left for megapages, copy onto pages
+                let mut new_area = MapArea::from_another(area);
+                new_area.huge = false;
+                memory_set.push(new_area, None);
+            }
             // copy data from another space
             for vpn in area.vpn_range {
                 let src_ppn = user_space.translate(vpn).unwrap().ppn();
@@ -264,6 +466,10 @@ impl MemorySet {
                     .copy_from_slice(src_ppn.get_bytes_array());
             }
         }
+        // the clock page is shared instead of copied
+        if let Some(ppn) = user_space.clock_page() {
+            memory_set.map_clock_page(ppn);
+        }
         memory_set
     }
     pub fn activate(&self) {
@@ -276,18 +482,191 @@ impl MemorySet {
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
+    /// Prefer frames of `node` for the pages in `[start_va, end_va)`,
+    /// fail if any of them is not mapped.
+    /// The policy is kept per area, so it covers all of every area the range touches.
+    pub fn set_mempolicy(&mut self, start_va: VirtAddr, end_va: VirtAddr, node: usize) -> bool {
+        let (start_vpn, end_vpn) = (start_va.floor(), end_va.ceil());
+        for vpn in VPNRange::new(start_vpn, end_vpn) {
+            if !self.is_claimed(vpn) || !self.areas.iter().any(|area| area.contains(vpn)) {
+                return false;
+            }
+        }
+        for area in self.areas.iter_mut() {
+            if area.vpn_range.get_start() < end_vpn && start_vpn < area.vpn_range.get_end() {
+                area.node = Some(node);
+            }
+        }
+        true
+    }
+    /// The node preferred for the page at `va`, if a policy is set on it
+    pub fn mempolicy(&self, va: VirtAddr) -> Option<usize> {
+        self.areas
+            .iter()
+            .find(|area| area.contains(va.floor()))
+            .and_then(|area| area.node)
+    }
+    /// Unmap a page of a framed area and free its frame, the area stays
+    pub fn drop_page(&mut self, vpn: VirtPageNum) -> bool {
+        if !self.translate(vpn).is_some_and(|pte| pte.is_valid()) {
+            return false;
+        }
+        let Some(area) = self.areas.iter_mut().find(|area| area.contains(vpn)) else { return false; };
+        if area.map_type != MapType::Framed || area.huge {
+            return false;
+        }
+        area.unmap_one(&mut self.page_table, vpn);
+        true
+    }
+    /// Give the page at `vpn` a frame of its own if it is shared copy-on-write,
+    /// false if it is not such a page or no frame is left.
+    /// The last owner of a shared frame keeps it, it only gets write access back.
+    pub fn copy_on_write(&mut self, vpn: VirtPageNum) -> bool {
+        let Some(pte) = self.translate(vpn) else { return false; };
+        if !pte.is_valid() || !pte.is_cow() {
+            return false;
+        }
+        let Some(area) = self.areas.iter_mut().find(|area| area.contains(vpn)) else { return false; };
+        let Some(frame) = area.data_frames.get_mut(&vpn) else { return false; };
+        if Arc::strong_count(frame) > 1 {
+            let Some(copy) = frame_alloc() else { return false; };
+            copy.ppn
+                .get_bytes_array()
+                .copy_from_slice(frame.ppn.get_bytes_array());
+            *frame = Arc::new(copy);
+        }
+        // a dirty page of a file mapping still has to be written back
+        let flags = PTEFlags::from_bits(area.map_perm.bits).unwrap()
+            | (pte.flags() & (PTEFlags::A | PTEFlags::D));
+        self.page_table.remap(vpn, frame.ppn, flags)
+    }
+    /// Set or clear the dirty bit of page `vpn`, return whether it was set
+    pub fn set_dirty(&mut self, vpn: VirtPageNum, dirty: bool) -> bool {
+        self.page_table.set_dirty(vpn, dirty)
+    }
+    /// Map a zeroed frame again at a page dropped by [`MemorySet::drop_page`]
+    pub fn map_page(&mut self, vpn: VirtPageNum) -> bool {
+        if self.translate(vpn).is_some_and(|pte| pte.is_valid()) {
+            return false;
+        }
+        let Some(area) = self.areas.iter_mut().find(|area| area.contains(vpn)) else { return false; };
+        area.map_type == MapType::Framed && !area.huge && area.map_one(&mut self.page_table, vpn)
+    }
+    /// Map a frame at the page `vpn` of a lazy area on its first touch,
+    /// false if it is not such a page or the area does not allow `access`
+    pub fn fault_in_lazy(&mut self, vpn: VirtPageNum, access: MapPermission) -> bool {
+        if self.is_mapped(vpn) || !self.is_lazy(vpn) {
+            return false;
+        }
+        let Some(area) = self.areas.iter_mut().find(|area| area.contains(vpn)) else { return false; };
+        area.map_perm.contains(access) && area.map_one(&mut self.page_table, vpn)
+    }
+    fn is_mapped(&self, vpn: VirtPageNum) -> bool {
+        self.translate(vpn).is_some_and(|pte| pte.is_valid())
+    }
+    /// Whether `vpn` is in a lazy area, touched or not
+    pub fn is_lazy(&self, vpn: VirtPageNum) -> bool {
+        self.lazy_ranges
+            .iter()
+            .any(|range| range.get_start() <= vpn && vpn < range.get_end())
+    }
+    /// Whether `vpn` is mapped or left to a lazy area
+    fn is_claimed(&self, vpn: VirtPageNum) -> bool {
+        self.is_mapped(vpn) || self.is_lazy(vpn)
+    }
+    /// Take `[start, end)` out of the lazy areas, its pages are not to be faulted in again
+    fn drop_lazy(&mut self, start: VirtPageNum, end: VirtPageNum) {
+        if start >= end {
+            return;
+        }
+        let mut ranges = Vec::new();
+        for range in self.lazy_ranges.drain(..) {
+            let (l, r) = (range.get_start(), range.get_end());
+            if l < start.min(r) {
+                ranges.push(VPNRange::new(l, start.min(r)));
+            }
+            if end.max(l) < r {
+                ranges.push(VPNRange::new(end.max(l), r));
+            }
+        }
+        self.lazy_ranges = ranges;
+    }
+    /// Number of user pages holding a frame, shared ones included
+    pub fn resident_pages(&self) -> usize {
+        self.areas
+            .iter()
+            .filter(|area| area.map_perm.contains(MapPermission::U))
+            .map(|area| area.data_frames.len())
+            .sum()
+    }
+    /// The user areas as `(start vpn, end vpn, permission)` by start,
+    /// what a checkpoint is restored only into an address space alike in
+    pub fn user_layout(&self) -> Vec<(usize, usize, u8)> {
+        let mut layout: Vec<_> = self
+            .areas
+            .iter()
+            .filter(|area| area.map_perm.contains(MapPermission::U))
+            .map(|area| {
+                let range = area.vpn_range;
+                (range.get_start().0, range.get_end().0, area.map_perm.bits)
+            })
+            .collect();
+        layout.sort_unstable();
+        layout
+    }
+    /// Pages of writable user areas holding a frame,
+    /// the only ones that may differ from what their areas were set up with
+    pub fn writable_user_pages(&self) -> Vec<VirtPageNum> {
+        self.areas
+            .iter()
+            .filter(|area| area.map_perm.contains(MapPermission::U | MapPermission::W))
+            .flat_map(|area| area.vpn_range)
+            .filter(|&vpn| self.is_mapped(vpn))
+            .collect()
+    }
+    /// Overwrite the page `vpn` of a writable user area with `data`, giving it a frame
+    /// of its own first. False if it is not such a page or no frame is left.
+    pub fn load_page(&mut self, vpn: VirtPageNum, data: &[u8]) -> bool {
+        if !self.areas.iter().any(|area| {
+            area.contains(vpn) && area.map_perm.contains(MapPermission::U | MapPermission::W)
+        }) {
+            return false;
+        }
+        if !self.is_mapped(vpn) && !self.fault_in_lazy(vpn, MapPermission::W) {
+            return false;
+        }
+        if self.translate(vpn).unwrap().is_cow() && !self.copy_on_write(vpn) {
+            return false;
+        }
+        let bytes = self.translate(vpn).unwrap().ppn().get_bytes_array();
+        bytes[..data.len()].copy_from_slice(data);
+        true
+    }
+    /// Free the frame of a touched page of a lazy area, it is as if never touched again.
+    /// False if it is not such a page.
+    pub fn forget_page(&mut self, vpn: VirtPageNum) -> bool {
+        self.is_lazy(vpn) && self.drop_page(vpn)
+    }
     pub fn recycle_data_pages(&mut self) {
         //*self = Self::new_bare();
         self.areas.clear();
+        self.lazy_ranges.clear();
     }
 }
 
 /// map area structure, controls a contiguous piece of virtual memory
 pub struct MapArea {
     vpn_range: VPNRange,
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// frames are shared between the memory sets of a fork until one side writes
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
     map_type: MapType,
     map_perm: MapPermission,
+    /// the memory node to take frames from, set by `set_mempolicy`
+    node: Option<usize>,
+    /// whether it is mapped with megapages, each holding contiguous frames
+    huge: bool,
+    /// the shared memory segment whose frames it maps
+    shm: Option<ShmAttachment>,
 }
 
 impl MapArea {
@@ -304,6 +683,9 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            node: None,
+            huge: false,
+            shm: None,
         }
     }
     pub fn from_another(another: &MapArea) -> Self {
@@ -312,8 +694,15 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            node: another.node,
+            huge: another.huge,
+            shm: another.shm.clone(),
         }
     }
+    /// Whether the page `vpn` is in this area
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
         let ppn: PhysPageNum;
         match self.map_type {
@@ -321,9 +710,10 @@ impl MapArea {
                 ppn = PhysPageNum(vpn.0);
             }
             MapType::Framed => {
+                // with a single memory node every frame is on the preferred one
                 let Some(frame) = frame_alloc() else { return false; };
                 ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                self.data_frames.insert(vpn, Arc::new(frame));
             }
         }
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
@@ -340,7 +730,42 @@ impl MapArea {
         }
         page_table.unmap(vpn);
     }
+    /// Map the megapage at `vpn` to newly allocated contiguous frames
+    fn map_huge_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let Some(frames) = frame_alloc_contiguous(HUGE_PAGE_PAGES, HUGE_PAGE_PAGES) else {
+            return false;
+        };
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        if !page_table.map_huge(vpn, frames[0].ppn, pte_flags) {
+            return false;
+        }
+        for (i, frame) in frames.into_iter().enumerate() {
+            self.data_frames
+                .insert(VirtPageNum(vpn.0 + i), Arc::new(frame));
+        }
+        true
+    }
+    /// Start of every megapage of a huge area
+    fn huge_pages(&self) -> impl Iterator<Item = VirtPageNum> {
+        (self.vpn_range.get_start().0..self.vpn_range.get_end().0)
+            .step_by(HUGE_PAGE_PAGES)
+            .map(VirtPageNum)
+    }
     pub fn map(&mut self, page_table: &mut PageTable) -> bool {
+        if self.huge {
+            let starts: Vec<VirtPageNum> = self.huge_pages().collect();
+            for (i, &vpn) in starts.iter().enumerate() {
+                if !self.map_huge_one(page_table, vpn) {
+                    // leave nothing behind, the range may be mapped with pages instead
+                    for &vpn in starts[..i].iter() {
+                        page_table.unmap_huge(vpn);
+                    }
+                    self.data_frames.clear();
+                    return false;
+                }
+            }
+            return true;
+        }
         for vpn in self.vpn_range {
             if !self.map_one(page_table, vpn) {
                 return false;
@@ -349,6 +774,13 @@ impl MapArea {
         true
     }
     pub fn unmap(&mut self, page_table: &mut PageTable) {
+        if self.huge {
+            for vpn in self.huge_pages().collect::<Vec<_>>() {
+                page_table.unmap_huge(vpn);
+            }
+            self.data_frames.clear();
+            return;
+        }
         for vpn in self.vpn_range {
             self.unmap_one(page_table, vpn);
         }
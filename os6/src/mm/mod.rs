@@ -11,14 +11,21 @@ mod frame_allocator;
 mod heap_allocator;
 mod memory_set;
 mod page_table;
+mod shm;
 
 pub use address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 pub use address::{StepByOne, VPNRange};
-pub use frame_allocator::{frame_alloc, frame_dealloc, FrameTracker};
+pub use frame_allocator::{frame_alloc, frame_dealloc, frame_free_count, FrameTracker};
+pub use frame_allocator::{frame_alloc_contiguous, frame_stats, FrameStats};
 pub use memory_set::{kernel_token, remap_test};
 pub use memory_set::{MapPermission, MemorySet, KERNEL_SPACE};
-pub use page_table::{translated_byte_buffer, translated_refmut, translated_str, PageTableEntry};
+pub use page_table::{
+    checked_byte_buffer, translated_byte_buffer, translated_kernel_buffer, translated_read_buffer,
+};
+pub use page_table::translated_refmut;
+pub use page_table::{translated_str, PageTableEntry};
 pub use page_table::{PTEFlags, PageTable, UserBuffer};
+pub use shm::{shm_attachment, shm_get};
 
 /// initiate heap allocator, frame allocator and kernel space
 pub fn init() {
@@ -1,6 +1,8 @@
 //! Implementation of [`PageTableEntry`] and [`PageTable`].
 
+use super::MapPermission;
 use super::{frame_alloc, FrameTracker, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+use crate::task::{copy_on_write, fault_in_lazy, fault_in_reclaimed};
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
@@ -20,6 +22,10 @@ bitflags! {
     }
 }
 
+/// Set in the RSW bits of a PTE whose frame is shared copy-on-write,
+/// the page is mapped read-only until it gets a frame of its own
+const PTE_COW: usize = 1 << 8;
+
 #[derive(Copy, Clone)]
 #[repr(C)]
 /// page table entry structure
@@ -54,6 +60,14 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+    /// Whether this entry maps memory instead of pointing to the next level table
+    pub fn is_leaf(&self) -> bool {
+        self.is_valid() && (self.readable() || self.writable() || self.executable())
+    }
+    /// Whether the frame is shared copy-on-write
+    pub fn is_cow(&self) -> bool {
+        self.bits & PTE_COW != 0
+    }
 }
 
 /// page table structure
@@ -78,15 +92,18 @@ impl PageTable {
             frames: Vec::new(),
         }
     }
-    fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
-        let mut idxs = vpn.indexes();
+    /// Find the PTE of `vpn` at `level` (2 for a page, 1 for a megapage),
+    /// creating the tables above it. None if a megapage is in the way.
+    fn find_pte_create(&mut self, vpn: VirtPageNum, level: usize) -> Option<&mut PageTableEntry> {
+        let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
-        let mut result: Option<&mut PageTableEntry> = None;
-        for (i, idx) in idxs.iter_mut().enumerate() {
+        for (i, idx) in idxs.iter().enumerate() {
             let pte = &mut ppn.get_pte_array()[*idx];
-            if i == 2 {
-                result = Some(pte);
-                break;
+            if i == level {
+                return Some(pte);
+            }
+            if pte.is_leaf() {
+                return None;
             }
             if !pte.is_valid() {
                 let frame = frame_alloc().unwrap();
@@ -95,27 +112,26 @@ impl PageTable {
             }
             ppn = pte.ppn();
         }
-        result
+        None
     }
-    fn find_pte(&self, vpn: VirtPageNum) -> Option<&PageTableEntry> {
+    /// Find the PTE mapping `vpn` and its level, which is 1 if it is in a megapage
+    fn find_pte(&self, vpn: VirtPageNum) -> Option<(&PageTableEntry, usize)> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
-        let mut result: Option<&PageTableEntry> = None;
         for (i, idx) in idxs.iter().enumerate() {
             let pte = &ppn.get_pte_array()[*idx];
-            if i == 2 {
-                result = Some(pte);
-                break;
+            if i == 2 || pte.is_leaf() {
+                return Some((pte, i));
             }
             if !pte.is_valid() {
                 return None;
             }
             ppn = pte.ppn();
         }
-        result
+        None
     }
     pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) -> bool {
-        let pte = self.find_pte_create(vpn).unwrap();
+        let Some(pte) = self.find_pte_create(vpn, 2) else { return false; };
         if pte.is_valid() {
             log::error!("vpn {:?} is mapped before mapping", vpn);
             return false;
@@ -124,7 +140,7 @@ impl PageTable {
         true
     }
     pub fn unmap(&mut self, vpn: VirtPageNum) -> bool {
-        let pte = self.find_pte_create(vpn).unwrap();
+        let Some(pte) = self.find_pte_create(vpn, 2) else { return false; };
         if !pte.is_valid() {
             log::error!("vpn {:?} is invalid before unmapping", vpn);
             return false;
@@ -132,11 +148,74 @@ impl PageTable {
         *pte = PageTableEntry::empty();
         true
     }
+    /// Map the megapage at `vpn` to the frames from `ppn` with a single level-1 PTE,
+    /// both must be megapage aligned. Fail if any page of it is mapped or has been.
+    pub fn map_huge(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) -> bool {
+        let Some(pte) = self.find_pte_create(vpn, 1) else { return false; };
+        // an empty level-0 table is left behind by pages unmapped before
+        if pte.is_valid() {
+            return false;
+        }
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        true
+    }
+    /// Remove the megapage mapping at `vpn`
+    pub fn unmap_huge(&mut self, vpn: VirtPageNum) -> bool {
+        match self.find_pte_create(vpn, 1) {
+            Some(pte) if pte.is_leaf() => {
+                *pte = PageTableEntry::empty();
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Set or clear the dirty bit of the page at `vpn`, return whether it was set.
+    /// Pages not mapped, or in a megapage, are never dirty.
+    pub fn set_dirty(&mut self, vpn: VirtPageNum, dirty: bool) -> bool {
+        if !matches!(self.find_pte(vpn), Some((pte, 2)) if pte.is_valid()) {
+            return false;
+        }
+        // the tables are all there, nothing is created
+        let pte = self.find_pte_create(vpn, 2).unwrap();
+        let was_dirty = pte.flags().contains(PTEFlags::D);
+        let mut flags = pte.flags();
+        flags.set(PTEFlags::D, dirty);
+        pte.bits = PageTableEntry::new(pte.ppn(), flags).bits | (pte.bits & PTE_COW);
+        was_dirty
+    }
+    /// Map the page at `vpn` read-only and mark its frame shared copy-on-write,
+    /// false if it is not mapped or in a megapage
+    pub fn set_cow(&mut self, vpn: VirtPageNum) -> bool {
+        if !matches!(self.find_pte(vpn), Some((pte, 2)) if pte.is_valid()) {
+            return false;
+        }
+        let pte = self.find_pte_create(vpn, 2).unwrap();
+        let flags = pte.flags() - PTEFlags::W;
+        pte.bits = PageTableEntry::new(pte.ppn(), flags).bits | PTE_COW;
+        true
+    }
+    /// Point the mapped page at `vpn` to `ppn` with `flags`, which also drops
+    /// the copy-on-write mark, false if it is not mapped or in a megapage
+    pub fn remap(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) -> bool {
+        if !matches!(self.find_pte(vpn), Some((pte, 2)) if pte.is_valid()) {
+            return false;
+        }
+        *self.find_pte_create(vpn, 2).unwrap() = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        true
+    }
+    /// Whether `vpn` is mapped by a megapage
+    pub fn is_huge(&self, vpn: VirtPageNum) -> bool {
+        matches!(self.find_pte(vpn), Some((pte, 1)) if pte.is_leaf())
+    }
+    /// The PTE of `vpn`, for a page in a megapage one for that page alone
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
-        self.find_pte(vpn).copied()
+        self.find_pte(vpn).map(|(pte, level)| match level {
+            2 => *pte,
+            _ => PageTableEntry::new(PhysPageNum(pte.ppn().0 + vpn.indexes()[2]), pte.flags()),
+        })
     }
     pub fn translate_va(&self, va: VirtAddr) -> Option<PhysAddr> {
-        self.find_pte(va.clone().floor()).map(|pte| {
+        self.translate(va.clone().floor()).map(|pte| {
             //println!("translate_va:va = {:?}", va);
             let aligned_pa: PhysAddr = pte.ppn().into();
             //println!("translate_va:pa_align = {:?}", aligned_pa);
@@ -150,15 +229,46 @@ impl PageTable {
     }
 }
 
-/// translate a pointer to a mutable u8 Vec through page table
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
-    let page_table = PageTable::from_token(token);
+/// Get the page at `vpn` ready for the kernel to read from. A page of a lazy area
+/// the task has not touched yet gets its frame, and a reclaimed page of a file mapping
+/// is read back, as no page fault does that for the kernel.
+fn prepare_read(page_table: &PageTable, token: usize, vpn: VirtPageNum) {
+    if !page_table.translate(vpn).is_some_and(|pte| pte.is_valid()) {
+        // the kernel may touch any page of user space
+        if !fault_in_lazy(token, vpn, MapPermission::U) {
+            fault_in_reclaimed(token, vpn);
+        }
+    }
+}
+
+/// Get the page at `vpn` ready for the kernel to write to. The kernel writes through
+/// its own mapping, where no page fault stops it at a page shared copy-on-write and
+/// the dirty bit is left alone.
+fn prepare_write(page_table: &mut PageTable, token: usize, vpn: VirtPageNum) {
+    prepare_read(page_table, token, vpn);
+    if page_table.translate(vpn).is_some_and(|pte| pte.is_cow()) {
+        assert!(
+            copy_on_write(token, vpn),
+            "no frame to copy a shared page into"
+        );
+    }
+    page_table.set_dirty(vpn, true);
+}
+
+/// The frames behind `[ptr, ptr + len)`, made ready for writing with `write`
+fn user_buffer(token: usize, ptr: *const u8, len: usize, write: bool) -> Vec<&'static mut [u8]> {
+    let mut page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
     let end = start + len;
     let mut v = Vec::new();
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
+        if write {
+            prepare_write(&mut page_table, token, vpn);
+        } else {
+            prepare_read(&page_table, token, vpn);
+        }
         let ppn = page_table.translate(vpn).unwrap().ppn();
         vpn.step();
         let mut end_va: VirtAddr = vpn.into();
@@ -173,11 +283,102 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
     v
 }
 
+/// Get every page of `[ptr, ptr + len)` ready for the kernel, false if one of them is
+/// not mapped for user space, or not writable by it with `write`. A page shared
+/// copy-on-write is writable if its area is, and gets its own frame then.
+fn check_user_range(
+    page_table: &mut PageTable,
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+    write: bool,
+) -> bool {
+    let start = ptr as usize;
+    let Some(end) = start.checked_add(len) else { return false; };
+    let access = if write {
+        MapPermission::W
+    } else {
+        MapPermission::R
+    };
+    let mut vpn = VirtAddr::from(start).floor();
+    while VirtAddr::from(vpn).0 < end {
+        if !page_table.translate(vpn).is_some_and(|pte| pte.is_valid())
+            && !fault_in_lazy(token, vpn, MapPermission::U | access)
+        {
+            fault_in_reclaimed(token, vpn);
+        }
+        let Some(pte) = page_table.translate(vpn) else { return false; };
+        if !pte.is_valid() || !pte.readable() || !pte.flags().contains(PTEFlags::U) {
+            return false;
+        }
+        if write && pte.is_cow() && !copy_on_write(token, vpn) {
+            return false;
+        }
+        let writable = page_table.translate(vpn).is_some_and(|pte| pte.writable());
+        if write && !writable {
+            return false;
+        }
+        vpn.step();
+    }
+    true
+}
+
+/// translate a pointer to a mutable u8 Vec through page table,
+/// `None` if any page of the range is not mapped writable for user space
+pub fn translated_byte_buffer(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+) -> Option<Vec<&'static mut [u8]>> {
+    let mut page_table = PageTable::from_token(token);
+    if !check_user_range(&mut page_table, token, ptr, len, true) {
+        return None;
+    }
+    Some(user_buffer(token, ptr, len, true))
+}
+
+/// Like [`translated_byte_buffer`] for the kernel to only read from, so a page
+/// only has to be mapped readable for user space and pages shared copy-on-write stay shared
+pub fn checked_byte_buffer(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+) -> Option<Vec<&'static mut [u8]>> {
+    let mut page_table = PageTable::from_token(token);
+    if !check_user_range(&mut page_table, token, ptr, len, false) {
+        return None;
+    }
+    // often in the address space of another task
+    Some(user_buffer(token, ptr, len, false))
+}
+
+/// The frames behind `[ptr, ptr + len)` for the kernel to write to whatever user space
+/// may do with them, such as a page of a read-only file mapping being filled
+pub fn translated_kernel_buffer(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+) -> Vec<&'static mut [u8]> {
+    user_buffer(token, ptr, len, true)
+}
+
+/// The frames behind `[ptr, ptr + len)` for the kernel to only read from
+/// whatever user space may do with them, pages shared copy-on-write stay shared
+pub fn translated_read_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static [u8]> {
+    user_buffer(token, ptr, len, false)
+        .into_iter()
+        .map(|slice| &*slice)
+        .collect()
+}
+
 pub fn translated_str(token: usize, ptr: *const u8) -> String {
     let page_table = PageTable::from_token(token);
     let mut string = String::new();
     let mut va = ptr as usize;
     loop {
+        if va == ptr as usize || VirtAddr::from(va).page_offset() == 0 {
+            prepare_read(&page_table, token, VirtAddr::from(va).floor());
+        }
         let ch: u8 = *(page_table
             .translate_va(VirtAddr::from(va))
             .unwrap()
@@ -194,8 +395,9 @@ pub fn translated_str(token: usize, ptr: *const u8) -> String {
 
 pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> &'static mut T {
     //println!("into translated_refmut!");
-    let page_table = PageTable::from_token(token);
+    let mut page_table = PageTable::from_token(token);
     let va = ptr as usize;
+    prepare_write(&mut page_table, token, VirtAddr::from(va).floor());
     //println!("translated_refmut: before translate_va");
     page_table
         .translate_va(VirtAddr::from(va))
@@ -0,0 +1,85 @@
+//! Shared memory segments, whose frames are mapped into every process attaching them
+//!
+//! A segment made by `sys_shmget` is found again by its key. Its frames are shared
+//! as they are, writes are seen by all attached, never copied on write. It goes once
+//! the last of its attachments does, by `sys_shmdt`, munmap, exec or exit, while a
+//! segment no one has attached yet stays. A fork passes the attachments on.
+
+use super::{frame_alloc, FrameTracker};
+use crate::config::PAGE_SIZE;
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// A shared memory segment
+pub struct ShmSegment {
+    id: usize,
+    key: usize,
+    frames: Vec<Arc<FrameTracker>>,
+}
+
+struct ShmTable {
+    /// segments by id
+    segments: BTreeMap<usize, Arc<ShmSegment>>,
+    next_id: usize,
+}
+
+lazy_static! {
+    static ref SHM_TABLE: UPSafeCell<ShmTable> = unsafe {
+        UPSafeCell::new(ShmTable {
+            segments: BTreeMap::new(),
+            next_id: 1,
+        })
+    };
+}
+
+/// An attachment of a segment, held by the area it is mapped at
+#[derive(Clone)]
+pub struct ShmAttachment(Arc<ShmSegment>);
+
+impl ShmAttachment {
+    /// Frames of the segment, one for each page
+    pub fn frames(&self) -> &[Arc<FrameTracker>] {
+        &self.0.frames
+    }
+}
+
+impl Drop for ShmAttachment {
+    fn drop(&mut self) {
+        let mut table = SHM_TABLE.exclusive_access();
+        // the table and this one, the last attachment goes
+        if Arc::strong_count(&self.0) == 2 {
+            table.segments.remove(&self.0.id);
+        }
+    }
+}
+
+/// The id of the segment of `key`, made with `size` bytes of zeroed frames if there is
+/// none. None if `size` is 0 for a new one or more than an existing one holds,
+/// or there are not enough frames.
+pub fn shm_get(key: usize, size: usize) -> Option<usize> {
+    let mut table = SHM_TABLE.exclusive_access();
+    if let Some(segment) = table.segments.values().find(|segment| segment.key == key) {
+        return (size <= segment.frames.len() * PAGE_SIZE).then(|| segment.id);
+    }
+    if size == 0 {
+        return None;
+    }
+    let frames = (0..size.div_ceil(PAGE_SIZE))
+        .map(|_| frame_alloc().map(Arc::new))
+        .collect::<Option<Vec<_>>>()?;
+    let id = table.next_id;
+    table.next_id += 1;
+    table
+        .segments
+        .insert(id, Arc::new(ShmSegment { id, key, frames }));
+    Some(id)
+}
+
+/// A new attachment of the segment `id`, to be mapped by [`super::MemorySet::attach_shm`]
+pub fn shm_attachment(id: usize) -> Option<ShmAttachment> {
+    let table = SHM_TABLE.exclusive_access();
+    table.segments.get(&id).cloned().map(ShmAttachment)
+}
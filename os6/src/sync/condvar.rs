@@ -0,0 +1,47 @@
+//! Condition variables for tasks waiting on each other
+
+use super::{Mutex, UPSafeCell};
+use crate::task::{
+    block_current_and_run_next, current_task, wakeup_task, SwitchReason, TaskControlBlock,
+};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// Tasks waiting for a condition guarded by a [`Mutex`]
+pub struct Condvar {
+    inner: UPSafeCell<CondvarInner>,
+}
+
+struct CondvarInner {
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(CondvarInner {
+                    wait_queue: VecDeque::new(),
+                })
+            },
+        }
+    }
+    /// Put the task waiting longest back into the ready queue, if any
+    pub fn signal(&self) {
+        if let Some(task) = self.inner.exclusive_access().wait_queue.pop_front() {
+            wakeup_task(task);
+        }
+    }
+    /// Release `mutex` and block current task until [`Condvar::signal`] picks it,
+    /// then take `mutex` again. The condition has to be checked again afterwards.
+    pub fn wait(&self, mutex: Arc<dyn Mutex>) {
+        // queued before the mutex is released, a signal in between is not lost
+        self.inner
+            .exclusive_access()
+            .wait_queue
+            .push_back(current_task().unwrap());
+        mutex.unlock();
+        block_current_and_run_next(SwitchReason::BlockOnSync);
+        mutex.lock();
+    }
+}
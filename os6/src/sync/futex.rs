@@ -0,0 +1,50 @@
+//! Futexes, tasks waiting on a word of user memory
+
+use super::UPSafeCell;
+use crate::task::{
+    block_current_and_run_next, current_task, wakeup_task, SwitchReason, TaskControlBlock,
+};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+lazy_static! {
+    /// Tasks waiting on each futex, keyed by the physical address of its word,
+    /// so that every virtual address the word is mapped at finds the same queue
+    static ref FUTEX_QUEUES: UPSafeCell<BTreeMap<usize, VecDeque<Arc<TaskControlBlock>>>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// Block current task on the futex at physical address `pa` until [`futex_wake`] picks it,
+/// if the word there still holds `expected`. Return false at once if it does not.
+pub fn futex_wait(pa: usize, expected: u32) -> bool {
+    // nothing runs between the check and the task joining the queue, a wake is not lost
+    if unsafe { (pa as *const u32).read_volatile() } != expected {
+        return false;
+    }
+    FUTEX_QUEUES
+        .exclusive_access()
+        .entry(pa)
+        .or_default()
+        .push_back(current_task().unwrap());
+    block_current_and_run_next(SwitchReason::BlockOnSync);
+    true
+}
+
+/// Put up to `n` tasks waiting longest on the futex at physical address `pa`
+/// back into the ready queue, return how many
+pub fn futex_wake(pa: usize, n: usize) -> usize {
+    let mut queues = FUTEX_QUEUES.exclusive_access();
+    let Some(queue) = queues.get_mut(&pa) else {
+        return 0;
+    };
+    let tasks: Vec<_> = (0..n).map_while(|_| queue.pop_front()).collect();
+    if queue.is_empty() {
+        queues.remove(&pa);
+    }
+    drop(queues);
+    let woken = tasks.len();
+    tasks.into_iter().for_each(wakeup_task);
+    woken
+}
@@ -0,0 +1,275 @@
+//! Lock order checking
+//!
+//! The order locks are taken in is kept as a graph. Taking a lock that closes a cycle
+//! in it is reported, as the tasks taking those locks could deadlock on each other
+//! the first time they interleave badly, even if they never have so far.
+//!
+//! The mutexes of a process are checked once it asks for it with
+//! `sys_enable_deadlock_detect`. Built with the `lockdep` feature, the kernel checks
+//! its [`super::UPSafeCell`]s too, telling them apart by the type they hold, and the
+//! spin locks of easy-fs by their class, and panics with the cycle in the message.
+
+use super::UPSafeCell;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// Locks taken while others were held, an edge `a -> b` for each `b` taken with `a` held
+pub struct LockGraph<K> {
+    edges: BTreeMap<K, BTreeSet<K>>,
+}
+
+impl<K: Ord + Copy> LockGraph<K> {
+    pub fn new() -> Self {
+        Self {
+            edges: BTreeMap::new(),
+        }
+    }
+    /// Note `next` taken while the locks in `held` are held. Return the cycle
+    /// it closes instead, from `next` round to it again, leaving the graph as it was.
+    /// An order seen before is only looked up, so checking costs little once warm.
+    pub fn acquire(&mut self, held: &[K], next: K) -> Result<(), Vec<K>> {
+        for &lock in held {
+            let after = self.edges.get(&lock);
+            if after.is_some_and(|after| after.contains(&next)) {
+                continue;
+            }
+            if let Some(mut cycle) = self.path(next, lock) {
+                cycle.push(next);
+                return Err(cycle);
+            }
+        }
+        for &lock in held {
+            self.edges.entry(lock).or_default().insert(next);
+        }
+        Ok(())
+    }
+    /// Locks along the edges from `from` to `to`, both included
+    fn path(&self, from: K, to: K) -> Option<Vec<K>> {
+        let mut came_from: BTreeMap<K, K> = BTreeMap::new();
+        let mut stack = vec![from];
+        while let Some(lock) = stack.pop() {
+            if lock == to {
+                let mut path = vec![to];
+                while *path.last().unwrap() != from {
+                    path.push(came_from[path.last().unwrap()]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &next in self.edges.get(&lock).into_iter().flatten() {
+                if next != from && !came_from.contains_key(&next) {
+                    came_from.insert(next, lock);
+                    stack.push(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The order the threads of a process took its mutexes in
+struct MutexOrder {
+    graph: LockGraph<usize>,
+    /// Ids of the mutexes each thread holds by its tid, in the order it took them
+    held: BTreeMap<usize, Vec<usize>>,
+}
+
+lazy_static! {
+    /// The mutex order of each process checking it, by pid. A process missing here
+    /// is not checked, so threads keep nothing of it.
+    static ref MUTEX_ORDERS: UPSafeCell<BTreeMap<usize, MutexOrder>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// Check the order the threads of process `pid` take its mutexes in from now on,
+/// forgetting the orders seen so far, or stop checking if `enabled` is false
+pub fn check_mutex_order(pid: usize, enabled: bool) {
+    let mut orders = MUTEX_ORDERS.exclusive_access();
+    if enabled {
+        orders.insert(
+            pid,
+            MutexOrder {
+                graph: LockGraph::new(),
+                held: BTreeMap::new(),
+            },
+        );
+    } else {
+        orders.remove(&pid);
+    }
+}
+
+/// Whether the mutex order of process `pid` is checked
+pub fn mutex_order_checked(pid: usize) -> bool {
+    MUTEX_ORDERS.exclusive_access().contains_key(&pid)
+}
+
+/// Note thread `tid` of process `pid` about to take its mutex `mutex_id`. Return the cycle
+/// that closes instead if the process is checked, taking a mutex held already is one too.
+pub fn mutex_acquire(pid: usize, tid: usize, mutex_id: usize) -> Result<(), Vec<usize>> {
+    let mut orders = MUTEX_ORDERS.exclusive_access();
+    let Some(order) = orders.get_mut(&pid) else { return Ok(()); };
+    let held = order.held.get(&tid).map_or(&[][..], |held| &held[..]);
+    order.graph.acquire(held, mutex_id)
+}
+
+/// Note thread `tid` of process `pid` took its mutex `mutex_id`
+pub fn mutex_taken(pid: usize, tid: usize, mutex_id: usize) {
+    if let Some(order) = MUTEX_ORDERS.exclusive_access().get_mut(&pid) {
+        order.held.entry(tid).or_default().push(mutex_id);
+    }
+}
+
+/// Note thread `tid` of process `pid` let go of its mutex `mutex_id`
+pub fn mutex_released(pid: usize, tid: usize, mutex_id: usize) {
+    let mut orders = MUTEX_ORDERS.exclusive_access();
+    let Some(held) = orders
+        .get_mut(&pid)
+        .and_then(|order| order.held.get_mut(&tid))
+    else {
+        return;
+    };
+    if let Some(pos) = held.iter().rposition(|id| *id == mutex_id) {
+        held.remove(pos);
+    }
+}
+
+/// Forget the mutexes thread `tid` of process `pid` holds, it exited,
+/// and the order of the process with it if `tid` was its last thread
+pub fn forget_mutex_order(pid: usize, tid: usize, last: bool) {
+    let mut orders = MUTEX_ORDERS.exclusive_access();
+    if last {
+        orders.remove(&pid);
+    } else if let Some(order) = orders.get_mut(&pid) {
+        order.held.remove(&tid);
+    }
+}
+
+#[cfg(feature = "lockdep")]
+pub use cells::{cell_acquire, cell_release, lockdep_init, lockdep_test};
+
+#[cfg(feature = "lockdep")]
+mod cells {
+    use super::LockGraph;
+    use crate::drivers::BLOCK_DEVICE;
+    use crate::sync::UPSafeCell;
+    use alloc::vec::Vec;
+    use core::cell::RefCell;
+    use easy_fs::{set_lock_hooks, take_fs_and_inode_locks, LockHooks};
+
+    /// The order of the `UPSafeCell`s, by the type they hold, and of the locks of easy-fs,
+    /// by their class, and those held now. Neither is held across a task switch,
+    /// so one list does for all tasks.
+    struct Cells {
+        graph: LockGraph<&'static str>,
+        held: Vec<&'static str>,
+        /// Set while a cycle is expected, it is kept in `caught` instead of panicking
+        expecting: bool,
+        caught: Option<Vec<&'static str>>,
+    }
+
+    /// Not an `UPSafeCell` itself, which would be checked on the way in
+    struct CellsCell(RefCell<Option<Cells>>);
+
+    unsafe impl Sync for CellsCell {}
+
+    static CELLS: CellsCell = CellsCell(RefCell::new(None));
+
+    static FS_LOCK_HOOKS: LockHooks = LockHooks {
+        acquire: cell_acquire,
+        release: cell_release,
+    };
+
+    /// Check the locks of easy-fs along with the `UPSafeCell`s
+    pub fn lockdep_init() {
+        set_lock_hooks(&FS_LOCK_HOOKS);
+    }
+
+    /// Note a `UPSafeCell` holding a `name` borrowed, or a lock of class `name` taken,
+    /// panic if that closes a cycle. Cells of one type borrowed inside each other,
+    /// as a parent and its child, are not ordered against each other, nor are locks
+    /// of one class.
+    pub fn cell_acquire(name: &'static str) {
+        // the graph allocates, and whatever the allocator borrows is not checked
+        let Ok(mut cells) = CELLS.0.try_borrow_mut() else { return; };
+        let cells = cells.get_or_insert_with(|| Cells {
+            graph: LockGraph::new(),
+            held: Vec::new(),
+            expecting: false,
+            caught: None,
+        });
+        if !cells.held.contains(&name) {
+            if let Err(cycle) = cells.graph.acquire(&cells.held, name) {
+                if !cells.expecting {
+                    panic!(
+                        "lock order cycle: {} (holding {})",
+                        cycle.join(" -> "),
+                        cells.held.join(", ")
+                    );
+                }
+                cells.caught = Some(cycle);
+            }
+        }
+        cells.held.push(name);
+    }
+
+    /// Note a `UPSafeCell` holding a `name` given back, or a lock of class `name`
+    pub fn cell_release(name: &'static str) {
+        let Ok(mut cells) = CELLS.0.try_borrow_mut() else { return; };
+        let Some(cells) = cells.as_mut() else { return; };
+        if let Some(pos) = cells.held.iter().rposition(|held| *held == name) {
+            cells.held.remove(pos);
+        }
+    }
+
+    /// Run `f`, return the first cycle it closes instead of panicking on it
+    fn catch_cycle(f: impl FnOnce()) -> Option<Vec<&'static str>> {
+        let set_expecting = |expecting| {
+            if let Some(cells) = CELLS.0.borrow_mut().as_mut() {
+                cells.expecting = expecting;
+                cells.caught = None;
+            }
+        };
+        set_expecting(true);
+        f();
+        let caught = CELLS
+            .0
+            .borrow_mut()
+            .as_mut()
+            .and_then(|cells| cells.caught.take());
+        set_expecting(false);
+        caught
+    }
+
+    struct First;
+    struct Second;
+
+    /// Take two `UPSafeCell`s, then the fs lock and an inode lock of easy-fs,
+    /// in one order as one task would and in the other as a second task would,
+    /// the second order is caught each time
+    pub fn lockdep_test() {
+        let first = unsafe { UPSafeCell::new(First) };
+        let second = unsafe { UPSafeCell::new(Second) };
+        {
+            let _first = first.exclusive_access();
+            let _second = second.exclusive_access();
+        }
+        let cycle = catch_cycle(|| {
+            let _second = second.exclusive_access();
+            let _first = first.exclusive_access();
+        })
+        .unwrap();
+        assert_eq!(cycle.len(), 3);
+        assert_eq!(cycle.first(), cycle.last());
+        // easy-fs takes an inode lock before the fs lock
+        take_fs_and_inode_locks(&BLOCK_DEVICE, true);
+        assert_eq!(
+            catch_cycle(|| take_fs_and_inode_locks(&BLOCK_DEVICE, true)),
+            None
+        );
+        let cycle = catch_cycle(|| take_fs_and_inode_locks(&BLOCK_DEVICE, false)).unwrap();
+        assert_eq!(cycle, ["InodeLock", "EasyFileSystem", "InodeLock"]);
+        info!("lockdep_test passed!");
+    }
+}
@@ -1,5 +1,18 @@
 //! Synchronization and interior mutability primitives
 
+mod condvar;
+mod futex;
+mod lockdep;
+mod mutex;
 mod up;
 
-pub use up::UPSafeCell;
+pub use condvar::Condvar;
+pub use futex::{futex_wait, futex_wake};
+pub use lockdep::{
+    check_mutex_order, forget_mutex_order, mutex_acquire, mutex_order_checked, mutex_released,
+    mutex_taken,
+};
+#[cfg(feature = "lockdep")]
+pub use lockdep::{lockdep_init, lockdep_test};
+pub use mutex::{Mutex, MutexBlocking, MutexSpin};
+pub use up::{UPRefMut, UPSafeCell};
@@ -0,0 +1,124 @@
+//! Mutexes a task can hold across system calls
+
+use super::UPSafeCell;
+use crate::task::{
+    block_current_and_run_next, current_task, suspend_current_and_run_next, wakeup_task,
+    SwitchReason, TaskControlBlock,
+};
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+/// A lock shared by the tasks holding its id
+pub trait Mutex: Sync + Send {
+    /// Wait until the lock is free and take it
+    fn lock(&self);
+    /// Give the lock back
+    fn unlock(&self);
+}
+
+/// A mutex whose waiters keep yielding the CPU until it is free
+pub struct MutexSpin {
+    locked: UPSafeCell<bool>,
+}
+
+impl MutexSpin {
+    pub fn new() -> Self {
+        Self {
+            locked: unsafe { UPSafeCell::new(false) },
+        }
+    }
+}
+
+impl Mutex for MutexSpin {
+    fn lock(&self) {
+        loop {
+            let mut locked = self.locked.exclusive_access();
+            if *locked {
+                drop(locked);
+                suspend_current_and_run_next(SwitchReason::Yield);
+                continue;
+            }
+            *locked = true;
+            return;
+        }
+    }
+    fn unlock(&self) {
+        *self.locked.exclusive_access() = false;
+    }
+}
+
+/// A mutex whose waiters sleep until it is handed to them.
+/// Its holder is scheduled with the priority of its waiters while that is higher,
+/// see [`TaskControlBlock::effective_stride`].
+pub struct MutexBlocking {
+    inner: UPSafeCell<MutexBlockingInner>,
+}
+
+struct MutexBlockingInner {
+    locked: bool,
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+    owner: Option<Weak<TaskControlBlock>>,
+}
+
+impl MutexBlocking {
+    pub fn new() -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(MutexBlockingInner {
+                    locked: false,
+                    wait_queue: VecDeque::new(),
+                    owner: None,
+                })
+            },
+        }
+    }
+    /// Tells the waiters of this mutex apart from those of others in `donors` of a task
+    fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+}
+
+impl Mutex for MutexBlocking {
+    fn lock(&self) {
+        let mut inner = self.inner.exclusive_access();
+        let task = current_task().unwrap();
+        if !inner.locked {
+            inner.locked = true;
+            inner.owner = Some(Arc::downgrade(&task));
+            return;
+        }
+        // the holder lends our priority until it lets go
+        if let Some(owner) = inner.owner.as_ref().and_then(|owner| owner.upgrade()) {
+            owner
+                .inner_exclusive_access()
+                .donors
+                .push((self.id(), task.clone()));
+        }
+        inner.wait_queue.push_back(task);
+        drop(inner);
+        // `unlock` hands the lock over, it is ours once we run again
+        block_current_and_run_next(SwitchReason::BlockOnSync);
+    }
+    fn unlock(&self) {
+        let mut inner = self.inner.exclusive_access();
+        assert!(inner.locked);
+        let id = self.id();
+        if let Some(owner) = inner.owner.take().and_then(|owner| owner.upgrade()) {
+            owner
+                .inner_exclusive_access()
+                .donors
+                .retain(|(mutex, _)| *mutex != id);
+        }
+        match inner.wait_queue.pop_front() {
+            Some(task) => {
+                // the waiters left lend their priority to the new holder instead
+                let waiters: Vec<_> = inner.wait_queue.iter().map(|w| (id, w.clone())).collect();
+                task.inner_exclusive_access().donors.extend(waiters);
+                inner.owner = Some(Arc::downgrade(&task));
+                wakeup_task(task);
+            }
+            None => inner.locked = false,
+        }
+    }
+}
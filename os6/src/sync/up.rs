@@ -1,6 +1,7 @@
 //! Uniprocessor interior mutability primitives
 
 use core::cell::{RefCell, RefMut};
+use core::ops::{Deref, DerefMut};
 
 /// Wrap a static data structure inside it so that we are
 /// able to access it without any `unsafe`.
@@ -25,7 +26,38 @@ impl<T> UPSafeCell<T> {
         }
     }
     /// Panic if the data has been borrowed.
-    pub fn exclusive_access(&self) -> RefMut<'_, T> {
-        self.inner.borrow_mut()
+    /// With the `lockdep` feature, also if borrowing it now closes a cycle
+    /// in the order cells are borrowed in, see [`super::lockdep`].
+    pub fn exclusive_access(&self) -> UPRefMut<'_, T> {
+        #[cfg(feature = "lockdep")]
+        super::lockdep::cell_acquire(core::any::type_name::<T>());
+        UPRefMut {
+            inner: self.inner.borrow_mut(),
+        }
+    }
+}
+
+/// The data of a [`UPSafeCell`] borrowed, given back when dropped
+pub struct UPRefMut<'a, T> {
+    inner: RefMut<'a, T>,
+}
+
+impl<T> Deref for UPRefMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for UPRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+#[cfg(feature = "lockdep")]
+impl<T> Drop for UPRefMut<'_, T> {
+    fn drop(&mut self) {
+        super::lockdep::cell_release(core::any::type_name::<T>());
     }
 }
@@ -1,20 +1,114 @@
 //! File and filesystem-related syscalls
 
-use crate::fs::increase_nlink;
-use crate::fs::open_file;
-use crate::fs::OpenFlags;
-use crate::fs::Stat;
-use crate::fs::ROOT_INODE;
-use crate::mm::translated_byte_buffer;
-use crate::mm::translated_refmut;
-use crate::mm::translated_str;
-use crate::mm::UserBuffer;
-use crate::task::current_task;
-use crate::task::current_user_token;
+use crate::config::{FILE_MAPPING_BASE, PAGE_SIZE};
+use crate::fs::{
+    free_unlinked, increase_nlink, lock_stats, make_pipe, mount, open_file_at, process_root,
+    symlink, umount, wait_size, DirQuota, Dirent, EventFd, File, FsInfo, LockStats, OSInode,
+    OpenFlags, RingFile, SnapshotFile, Stat, StatFs, ROOT_INODE,
+};
+use crate::mm::{
+    checked_byte_buffer, translated_byte_buffer, translated_refmut, translated_str, MapPermission,
+    UserBuffer, VirtAddr,
+};
+use crate::syscall::process::{copy_from_user, IoVec};
+use crate::task::{
+    current_process, current_user_token, suspend_current_and_run_next, FileMapping, SwitchReason,
+};
+use crate::timer::get_time_us;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::size_of;
+use easy_fs::Inode;
+
+const ENOENT: isize = 2;
+const EAGAIN: isize = 11;
+const ENOTDIR: isize = 20;
+const EISDIR: isize = 21;
+const EINVAL: isize = 22;
+const ENOSPC: isize = 28;
+const EPIPE: isize = 32;
+const ENOTEMPTY: isize = 39;
+const EDQUOT: isize = 122;
+
+/// Most buffers `sys_readv` and `sys_writev` take at once
+const IOV_MAX: usize = 1024;
+
+/// The only dirfd there is: the root directory
+const AT_FDCWD: isize = -100;
+
+/// Commands of [`sys_fcntl`]
+const F_GETFD: usize = 1;
+const F_SETFD: usize = 2;
+const F_GETFL: usize = 3;
+const F_SETFL: usize = 4;
+/// The only fd flag of `F_GETFD` and `F_SETFD`: close the fd on exec
+const FD_CLOEXEC: usize = 1;
+
+/// Flag of [`sys_close_range`]: mark the fds close-on-exec instead of closing them
+const CLOSE_RANGE_CLOEXEC: u32 = 1 << 2;
+
+/// Flag of [`sys_linkat`]: replace `new_name` if it exists
+const LINK_REPLACE: usize = 1;
+
+/// Flags of [`sys_msync`]: write back without waiting, drop the pages
+/// after writing them back, and wait for the write back
+const MS_ASYNC: usize = 1;
+const MS_INVALIDATE: usize = 2;
+const MS_SYNC: usize = 4;
+
+/// Flags of [`sys_eventfd`]: take one from the counter on each read,
+/// do not wait on reads, and close the fd on exec
+const EFD_SEMAPHORE: u32 = 1;
+const EFD_NONBLOCK: u32 = OpenFlags::NONBLOCK.bits();
+const EFD_CLOEXEC: u32 = 1 << 19;
+
+/// Accesses `sys_access` checks for, the permission bits of the owner shifted down
+const R_OK: u32 = 4;
+const W_OK: u32 = 2;
+const X_OK: u32 = 1;
+
+/// Whence of `File::seek` counting from the offset
+const SEEK_CUR: usize = 1;
 
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
-    let task = current_task().unwrap();
+    let Some(buffers) = checked_byte_buffer(token, buf, len) else { return -1; };
+    write_buffer(fd, UserBuffer::new(buffers))
+}
+
+/// Write the `iovcnt` buffers at `iov` to `fd` in order as a single write.
+/// Return the bytes written, or -1 if there are more than `IOV_MAX` buffers
+/// or one of them is not mapped readable.
+pub fn sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
+    let Some(buffer) = gather(iov, iovcnt, false) else { return -1; };
+    write_buffer(fd, buffer)
+}
+
+/// The buffers of the `iovcnt` entries at `iov` one after another, for the kernel
+/// to fill them with `write`, `None` if one of them is not mapped for that
+fn gather(iov: *const IoVec, iovcnt: usize, write: bool) -> Option<UserBuffer> {
+    if iovcnt > IOV_MAX {
+        return None;
+    }
+    let token = current_user_token();
+    let mut buffers = Vec::new();
+    for i in 0..iovcnt {
+        let iovec = copy_from_user(token, unsafe { iov.add(i) })?;
+        let base = iovec.base as *const u8;
+        // an empty buffer adds no slice, which the iterator of `UserBuffer` could not skip
+        if write {
+            buffers.extend(translated_byte_buffer(token, base, iovec.len)?);
+        } else {
+            buffers.extend(checked_byte_buffer(token, base, iovec.len)?);
+        }
+    }
+    Some(UserBuffer::new(buffers))
+}
+
+/// Write `buffer` to `fd`, shared by `sys_write` and `sys_writev`
+fn write_buffer(fd: usize, buffer: UserBuffer) -> isize {
+    let task = current_process().unwrap();
     let inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
         return -1;
@@ -23,15 +117,66 @@ pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
         let file = file.clone();
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
-        file.write(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        if !file.writable() {
+            return -1;
+        }
+        if file.write_broken() {
+            return -EPIPE;
+        }
+        if file.nonblock() && file.write_would_block() {
+            return -EAGAIN;
+        }
+        let len = buffer.len();
+        let written = file.write(buffer);
+        // a file takes none of a write only when the quota of its directory or the disk is full
+        if written == 0 && len > 0 && file.inode().is_some() {
+            return -EDQUOT;
+        }
+        written as isize
     } else {
         -1
     }
 }
 
+/// Write `len` bytes at the end of the file behind `fd` in one step,
+/// return the offset they were written at. The offset of `fd` stays where it is.
+pub fn sys_append(fd: usize, buf: *const u8, len: usize) -> isize {
+    let token = current_user_token();
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    drop(inner);
+    if !file.writable() {
+        return -1;
+    }
+    let Some(buffers) = checked_byte_buffer(token, buf, len) else { return -1; };
+    match file.append(UserBuffer::new(buffers)) {
+        Some(offset) => offset as isize,
+        None if file.inode().is_some() => -EDQUOT,
+        None => -1,
+    }
+}
+
 pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
-    let task = current_task().unwrap();
+    let Some(buffers) = translated_byte_buffer(token, buf, len) else { return -1; };
+    read_buffer(fd, UserBuffer::new(buffers))
+}
+
+/// Fill the `iovcnt` buffers at `iov` in order from `fd` as a single read.
+/// Return the bytes read, or -1 if there are more than `IOV_MAX` buffers
+/// or one of them is not mapped writable.
+pub fn sys_readv(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
+    let Some(buffer) = gather(iov, iovcnt, true) else { return -1; };
+    read_buffer(fd, buffer)
+}
+
+/// Read from `fd` into `buffer`, shared by `sys_read` and `sys_readv`
+fn read_buffer(fd: usize, buffer: UserBuffer) -> isize {
+    let task = current_process().unwrap();
     let inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
         return -1;
@@ -40,28 +185,153 @@ pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
         let file = file.clone();
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
-        file.read(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        if !file.readable() {
+            return -1;
+        }
+        if file.nonblock() && file.read_would_block() {
+            return -EAGAIN;
+        }
+        file.read(buffer) as isize
     } else {
         -1
     }
 }
 
+/// Copy up to `count` bytes from `in_fd` to `out_fd` in the kernel, a page at a time
+/// through one buffer, and move both offsets past them. Return the bytes copied,
+/// fewer than `count` if the input ends or the output fills up first.
+pub fn sys_sendfile(out_fd: usize, in_fd: usize, count: usize) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    let Some(Some(out_file)) = inner.fd_table.get(out_fd).cloned() else { return -1; };
+    let Some(Some(in_file)) = inner.fd_table.get(in_fd).cloned() else { return -1; };
+    drop(inner);
+    if !out_file.writable() || !in_file.readable() {
+        return -1;
+    }
+    let mut buffer = vec![0u8; PAGE_SIZE];
+    let mut copied = 0;
+    while copied < count {
+        let len = (count - copied).min(PAGE_SIZE);
+        // the slice lives as long as `buffer`, which outlives each read and write
+        let slice = unsafe { core::slice::from_raw_parts_mut(buffer.as_mut_ptr(), len) };
+        let read = in_file.read(UserBuffer::new(vec![slice]));
+        if read == 0 {
+            break;
+        }
+        let slice = unsafe { core::slice::from_raw_parts_mut(buffer.as_mut_ptr(), read) };
+        let written = out_file.write(UserBuffer::new(vec![slice]));
+        copied += written;
+        if written < read {
+            // give back what the output did not take, if the input can seek
+            in_file.seek(-((read - written) as isize), SEEK_CUR);
+            break;
+        }
+    }
+    copied as isize
+}
+
 pub fn sys_open(path: *const u8, flags: u32) -> isize {
-    let task = current_task().unwrap();
+    let task = current_process().unwrap();
     let token = current_user_token();
     let path = translated_str(token, path);
-    if let Some(inode) = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
+    let flags = OpenFlags::from_bits(flags).unwrap();
+    if let Some(inode) = open_file_at(&cwd(), path.as_str(), flags) {
         let mut inner = task.inner_exclusive_access();
         let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
+        match inode.inode() {
+            // a ring is never read or written at an offset
+            Some(ring) if ring.is_ring() => {
+                let (readable, writable) = flags.read_write();
+                let ring = RingFile::new(readable, writable, ring);
+                ring.set_nonblock(flags.contains(OpenFlags::NONBLOCK));
+                inner.fd_table[fd] = Some(Arc::new(ring));
+            }
+            _ => inner.fd_table[fd] = Some(inode),
+        }
         fd as isize
     } else {
         -1
     }
 }
 
+/// The directory relative paths of current process start at
+fn cwd() -> Arc<Inode> {
+    let process = current_process().unwrap();
+    let cwd = process
+        .inner_exclusive_access()
+        .cwd
+        .as_ref()
+        .and_then(|dir| dir.inode());
+    cwd.unwrap_or_else(process_root)
+}
+
+/// Make the directory at `path` the one relative paths of current process start at
+pub fn sys_chdir(path: *const u8) -> isize {
+    let path = translated_str(current_user_token(), path);
+    let Some(dir) = cwd().find_path_under(&process_root(), &path) else { return -1; };
+    if !dir.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+        return -1;
+    }
+    let dir = Arc::new(OSInode::new(true, false, false, dir));
+    current_process().unwrap().inner_exclusive_access().cwd = Some(dir);
+    0
+}
+
+/// Make the directory at `path` the one current process and its future children take
+/// as '/', and move the working directory there, so that no path leads out of it.
+/// A process that has changed its root can not change it again, which keeps it from
+/// escaping through a root deeper down with the working directory left above.
+/// -1 if the root has been changed already or `path` is not a directory.
+pub fn sys_chroot(path: *const u8) -> isize {
+    let path = translated_str(current_user_token(), path);
+    let process = current_process().unwrap();
+    if process.inner_exclusive_access().root.is_some() {
+        return -1;
+    }
+    let Some(dir) = cwd().find_path(&path) else { return -1; };
+    if !dir.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+        return -1;
+    }
+    let dir = Arc::new(OSInode::new(true, false, false, dir));
+    let mut inner = process.inner_exclusive_access();
+    inner.root = Some(dir.clone());
+    inner.cwd = Some(dir);
+    0
+}
+
+/// Write the absolute path of the working directory of current process and a `\0` to `buf`,
+/// return the length of the path. -1 if `len` bytes can not hold it, or the directory
+/// has been removed.
+pub fn sys_getcwd(buf: *mut u8, len: usize) -> isize {
+    let Some(path) = cwd().path_under(&process_root()) else { return -1; };
+    if path.len() + 1 > len {
+        return -1;
+    }
+    let token = current_user_token();
+    let mut bytes = path.bytes().chain(Some(0));
+    let Some(buffers) = translated_byte_buffer(token, buf, path.len() + 1) else { return -1; };
+    for slice in buffers {
+        slice.iter_mut().for_each(|byte| *byte = bytes.next().unwrap());
+    }
+    path.len() as isize
+}
+
+/// Create a ring file of `capacity` bytes at `path` and open it for reading and writing.
+/// Fail if `path` exists or `capacity` is 0.
+pub fn sys_ringfile_create(path: *const u8, capacity: usize) -> isize {
+    let task = current_process().unwrap();
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let Some(ring) = process_root().create_ring(path.as_str(), capacity) else { return -1; };
+    let mut inner = task.inner_exclusive_access();
+    let fd = inner.alloc_fd();
+    inner.fd_table[fd] = Some(Arc::new(RingFile::new(true, true, ring)));
+    fd as isize
+}
+
 pub fn sys_close(fd: usize) -> isize {
-    let task = current_task().unwrap();
+    let task = current_process().unwrap();
     let mut inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
         return -1;
@@ -70,13 +340,199 @@ pub fn sys_close(fd: usize) -> isize {
         return -1;
     }
     inner.fd_table[fd].take();
+    inner.cloexec_fds.remove(&fd);
+    0
+}
+
+/// The fd table is grown up to this many fds at most by [`sys_dup2`]
+const MAX_FDS: usize = 1024;
+
+/// Open `fd` again at the lowest free fd, which shares the file and its offset with it
+pub fn sys_dup(fd: usize) -> isize {
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    let new_fd = inner.alloc_fd();
+    inner.fd_table[new_fd] = Some(file);
+    new_fd as isize
+}
+
+/// Like [`sys_dup`], but at `new_fd`, which is closed first if it is open.
+/// Nothing is done if `old_fd` is open and the same as `new_fd`.
+pub fn sys_dup2(old_fd: usize, new_fd: usize) -> isize {
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if old_fd >= inner.fd_table.len() || new_fd >= MAX_FDS {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[old_fd].clone() else { return -1; };
+    if old_fd == new_fd {
+        return new_fd as isize;
+    }
+    while inner.fd_table.len() <= new_fd {
+        inner.fd_table.push(None);
+    }
+    inner.fd_table[new_fd] = Some(file);
+    // the flag belongs to the fd, not to the file
+    inner.cloexec_fds.remove(&new_fd);
+    new_fd as isize
+}
+
+/// Get or set the fd flags of `fd`, only [`FD_CLOEXEC`], which belong to the fd,
+/// or its status flags, only `O_NONBLOCK` and `O_APPEND`, which belong to the open file
+/// and so are shared with the fds dup'ed from it
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    match cmd {
+        F_GETFD => {
+            if inner.cloexec_fds.contains(&fd) {
+                FD_CLOEXEC as isize
+            } else {
+                0
+            }
+        }
+        F_SETFD => {
+            if arg & FD_CLOEXEC != 0 {
+                inner.cloexec_fds.insert(fd);
+            } else {
+                inner.cloexec_fds.remove(&fd);
+            }
+            0
+        }
+        F_GETFL => {
+            drop(inner);
+            let mut flags = OpenFlags::empty();
+            flags.set(OpenFlags::NONBLOCK, file.nonblock());
+            flags.set(OpenFlags::APPEND, file.append_mode());
+            flags.bits() as isize
+        }
+        F_SETFL => {
+            drop(inner);
+            let flags = OpenFlags::from_bits_truncate(arg as u32);
+            file.set_nonblock(flags.contains(OpenFlags::NONBLOCK));
+            file.set_append_mode(flags.contains(OpenFlags::APPEND));
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Move the offset of `fd`, return the new offset
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    drop(inner);
+    match file.seek(offset, whence) {
+        Some(offset) => offset as isize,
+        None => -1,
+    }
+}
+
+/// Set the offset of `fd` to `offset` and return the old one, as one step
+pub fn sys_lseek_swap(fd: usize, offset: usize) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    drop(inner);
+    match file.swap_offset(offset) {
+        Some(old) => old as isize,
+        None => -1,
+    }
+}
+
+/// Open a read-only, seekable view of the file behind `fd` as it is now, return its fd
+pub fn sys_snapshot_file(fd: usize) -> isize {
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(inode) = inner.fd_table[fd].as_ref().and_then(|file| file.inode()) else { return -1; };
+    let snap_fd = inner.alloc_fd();
+    inner.fd_table[snap_fd] = Some(Arc::new(SnapshotFile::new(&inode)));
+    snap_fd as isize
+}
+
+/// Create a pipe, its read end at `pipe[0]` and its write end at `pipe[1]`.
+/// Reads wait while it is empty and return 0 once every write end is closed,
+/// writes wait while it is full and fail with EPIPE once every read end is closed.
+pub fn sys_pipe(pipe: *mut usize) -> isize {
+    let task = current_process().unwrap();
+    let token = current_user_token();
+    let mut inner = task.inner_exclusive_access();
+    let (pipe_read, pipe_write) = make_pipe();
+    let read_fd = inner.alloc_fd();
+    inner.fd_table[read_fd] = Some(pipe_read);
+    let write_fd = inner.alloc_fd();
+    inner.fd_table[write_fd] = Some(pipe_write);
+    // the write may copy a shared page, which takes current TCB again
+    drop(inner);
+    *translated_refmut(token, pipe) = read_fd;
+    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd;
+    0
+}
+
+/// Open an event counter starting from `initval`, return its fd
+pub fn sys_eventfd(initval: u32, flags: u32) -> isize {
+    if flags & !(EFD_SEMAPHORE | EFD_NONBLOCK | EFD_CLOEXEC) != 0 {
+        return -1;
+    }
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let fd = inner.alloc_fd();
+    inner.fd_table[fd] = Some(Arc::new(EventFd::new(
+        initval as u64,
+        flags & EFD_SEMAPHORE != 0,
+        flags & EFD_NONBLOCK != 0,
+    )));
+    if flags & EFD_CLOEXEC != 0 {
+        inner.cloexec_fds.insert(fd);
+    }
+    fd as isize
+}
+
+/// Close every open fd in `[first, last]`, or with [`CLOSE_RANGE_CLOEXEC`]
+/// only mark them to be closed on exec
+pub fn sys_close_range(first: usize, last: usize, flags: u32) -> isize {
+    if first > last || flags & !CLOSE_RANGE_CLOEXEC != 0 {
+        return -1;
+    }
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let end = inner.fd_table.len().min(last.saturating_add(1));
+    for fd in first..end {
+        if inner.fd_table[fd].is_none() {
+            continue;
+        }
+        if flags & CLOSE_RANGE_CLOEXEC != 0 {
+            inner.cloexec_fds.insert(fd);
+        } else {
+            inner.fd_table[fd].take();
+            inner.cloexec_fds.remove(&fd);
+        }
+    }
     0
 }
 
 // YOUR JOB: 扩展 easy-fs 和内核以实现以下三个 syscall
 pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
     let st = translated_refmut(current_user_token(), st);
-    let tcb = current_task().unwrap();
+    let tcb = current_process().unwrap();
     let inner = tcb.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
         return -1;
@@ -86,26 +542,662 @@ pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
     0
 }
 
-pub fn sys_linkat(old_name: *const u8, new_name: *const u8) -> isize {
+/// Fill `buf` with entries of the directory `fd` from its offset on, as [`Dirent`]s.
+/// Return the bytes written, 0 at the end, or -1 if `fd` is not a directory
+/// or `buf` can not hold a single entry.
+pub fn sys_getdents(fd: usize, buf: *const u8, len: usize) -> isize {
+    if len < size_of::<Dirent>() {
+        return -1;
+    }
+    let token = current_user_token();
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    drop(inner);
+    if !file.readable() {
+        return -1;
+    }
+    let Some(buffers) = translated_byte_buffer(token, buf, len) else { return -1; };
+    match file.getdents(UserBuffer::new(buffers)) {
+        Some(written) => written as isize,
+        None => -1,
+    }
+}
+
+/// Link `new_name` to the inode of `old_name`. With `LINK_REPLACE` an existing
+/// `new_name` is pointed at it in one step, and freed if that was its last link.
+pub fn sys_linkat(old_name: *const u8, new_name: *const u8, flags: usize) -> isize {
+    if flags & !LINK_REPLACE != 0 {
+        return -1;
+    }
+    let token = current_user_token();
+    let old_name = translated_str(token, old_name);
+    let new_name = translated_str(token, new_name);
+    if flags & LINK_REPLACE != 0 {
+        let (success, clear_inode) = process_root().link_replace(&old_name, &new_name);
+        if let Some(inode) = clear_inode {
+            free_unlinked(inode);
+        }
+        return if success { 0 } else { -1 };
+    }
+    if increase_nlink(&old_name, &new_name).is_none() {
+        return -1;
+    }
+    0
+}
+
+/// Move `old_name` to `new_name` without touching the inode, also into another directory.
+/// An existing `new_name` is kept, as with `RENAME_NOREPLACE`.
+/// Paths start at the root directory, so both dirfds must be `AT_FDCWD`.
+pub fn sys_renameat2(
+    old_dirfd: isize,
+    old_name: *const u8,
+    new_dirfd: isize,
+    new_name: *const u8,
+) -> isize {
+    if old_dirfd != AT_FDCWD || new_dirfd != AT_FDCWD {
+        return -1;
+    }
     let token = current_user_token();
     let old_name = translated_str(token, old_name);
     let new_name = translated_str(token, new_name);
-    if ROOT_INODE.find(&new_name).is_some() || increase_nlink(&old_name, &new_name).is_none() {
+    let root = process_root();
+    let inode_id = |path: &str| {
+        root.find_path_under(&root, path)
+            .map(|inode| inode.inode_id())
+    };
+    let target = inode_id(&new_name);
+    if target.is_some() && target != inode_id(&old_name) {
         return -1;
     }
+    let (result, clear_inode) = root.rename_keep_under(&root, &old_name, &new_name);
+    if let Some(inode) = clear_inode {
+        inode.free();
+    }
+    result
+}
+
+/// Move `old_path` to `new_path`, both relative to the working directory, replacing
+/// a file or an empty directory there as `mv` does. -ENOENT if there is no `old_path`,
+/// -ENOTEMPTY if the directory at `new_path` has entries, -EINVAL if a directory would
+/// move under itself, -EISDIR or -ENOTDIR if a file and a directory would replace each
+/// other, and -1 on any other failure. A replaced file with no links left is freed
+/// once the last file open on it is closed.
+pub fn sys_rename(old_path: *const u8, new_path: *const u8) -> isize {
+    let token = current_user_token();
+    let old_path = translated_str(token, old_path);
+    let new_path = translated_str(token, new_path);
+    let root = process_root();
+    let dir = cwd();
+    let Some(source) = dir.find_path_nofollow_under(&root, &old_path) else { return -ENOENT; };
+    let is_dir = |inode: &Inode| inode.read_disk_inode(|disk_inode| disk_inode.is_dir());
+    let source_is_dir = is_dir(&source);
+    if let Some(target) = dir.find_path_nofollow_under(&root, &new_path) {
+        match (source_is_dir, is_dir(&target)) {
+            (false, true) => return -EISDIR,
+            (true, false) => return -ENOTDIR,
+            (true, true) if !Arc::ptr_eq(&source, &target) && !target.ls().is_empty() => {
+                return -ENOTEMPTY;
+            }
+            _ => {}
+        }
+    }
+    if source_is_dir {
+        let parent_path = match new_path.rfind('/') {
+            Some(i) => &new_path[..=i],
+            None => ".",
+        };
+        // walk up from the new parent, the directory must not be met on the way
+        let mut up = dir
+            .find_path_under(&root, parent_path)
+            .filter(|parent| is_dir(parent));
+        while let Some(parent) = up {
+            if Arc::ptr_eq(&parent, &source) {
+                return -EINVAL;
+            }
+            up = parent.find("..").filter(|next| !Arc::ptr_eq(next, &parent));
+        }
+    }
+    let (result, clear_inode) = dir.rename_keep_under(&root, &old_path, &new_path);
+    if let Some(inode) = clear_inode {
+        free_unlinked(inode);
+    }
+    result
+}
+
+/// Rename `name` to `new_name` only if the data of `name` is exactly the `len` bytes
+/// at `expected`, checked and renamed in one step. Return 1 if it was renamed,
+/// 0 if there is no `name` or its data differs, and -1 if `new_name` is taken.
+pub fn sys_cas_rename(
+    name: *const u8,
+    expected: *const u8,
+    len: usize,
+    new_name: *const u8,
+) -> isize {
+    let token = current_user_token();
+    let name = translated_str(token, name);
+    let new_name = translated_str(token, new_name);
+    let Some(expected) = checked_byte_buffer(token, expected, len) else { return -1; };
+    let expected: Vec<u8> = expected
+        .into_iter()
+        .flat_map(|slice| slice.iter().copied())
+        .collect();
+    match process_root().cas_rename(&name, &expected, &new_name) {
+        Some(renamed) => renamed as isize,
+        None => -1,
+    }
+}
+
+/// The directory at `path`, "/" for the root of current process
+fn find_dir(path: &str) -> Option<Arc<Inode>> {
+    let root = process_root();
+    let inode = root.find_path_under(&root, path)?;
+    if !inode.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+        return None;
+    }
+    Some(inode)
+}
+
+/// Check that `path` exists and its owner may access it in all the ways `mode` asks for,
+/// any of `R_OK`, `W_OK` and `X_OK`, or none of them only to check that it exists.
+/// Return 0 if so, -1 otherwise.
+pub fn sys_access(path: *const u8, mode: u32) -> isize {
+    let path = translated_str(current_user_token(), path);
+    if mode & !(R_OK | W_OK | X_OK) != 0 {
+        return -1;
+    }
+    let root = process_root();
+    match root.find_path_under(&root, &path) {
+        Some(inode) if inode.permits((mode as u16) << 6) => 0,
+        _ => -1,
+    }
+}
+
+/// Set the permission bits of `path` to the low 9 bits of `mode`,
+/// the root directory always stays searchable
+pub fn sys_chmod(path: *const u8, mode: u32) -> isize {
+    let path = translated_str(current_user_token(), path);
+    let root = process_root();
+    let Some(inode) = root.find_path_under(&root, &path) else { return -1; };
+    inode.chmod((mode & 0o777) as u16);
     0
 }
 
+/// Cap the data blocks the files under the directory at `path` may use at `max_blocks`,
+/// 0 lifts the cap. Writes that need more blocks fail with EDQUOT.
+/// Quotas live in memory only and are gone after a reboot.
+pub fn sys_set_dir_quota(path: *const u8, max_blocks: usize) -> isize {
+    let path = translated_str(current_user_token(), path);
+    if max_blocks > u32::MAX as usize {
+        return -1;
+    }
+    match find_dir(&path) {
+        Some(dir) if dir.set_quota(max_blocks as u32) => 0,
+        _ => -1,
+    }
+}
+
+/// Report the limit and the blocks in use of the quota of the directory at `path`,
+/// a limit of 0 means it has none
+pub fn sys_get_dir_quota(path: *const u8, quota: *mut DirQuota) -> isize {
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let Some(dir) = find_dir(&path) else { return -1; };
+    *translated_refmut(token, quota) = dir.quota().unwrap_or_default();
+    0
+}
+
+/// Exchange the entries of the directories `dir_a` and `dir_b` in one step,
+/// each keeps its own `.` and `..`
+pub fn sys_swap_dir(dir_a: *const u8, dir_b: *const u8) -> isize {
+    let token = current_user_token();
+    let dir_a = translated_str(token, dir_a);
+    let dir_b = translated_str(token, dir_b);
+    let (Some(inode_a), Some(inode_b)) = (find_dir(&dir_a), find_dir(&dir_b)) else {
+        return -1;
+    };
+    if !inode_a.swap_data(&inode_b) {
+        return -1;
+    }
+    0
+}
+
+/// Remove the dirent `name`. The file goes with its last link,
+/// or once the last file still open on it is closed.
 pub fn sys_unlinkat(name: *const u8) -> isize {
     let token = current_user_token();
     let name = translated_str(token, name);
-    let (success, clear_inode) =
-        ROOT_INODE.modify_disk_inode(|disk_inode| ROOT_INODE.unlink(disk_inode, &name));
+    let (success, clear_inode) = process_root().unlink(&name);
     if success {
         if let Some(inode) = clear_inode {
-            inode.clear();
+            free_unlinked(inode);
         }
         return 0;
     }
     -1
 }
+
+/// Create a symbolic link at `path` pointing to `target`, paths start at the root directory
+pub fn sys_symlinkat(target: *const u8, path: *const u8) -> isize {
+    let token = current_user_token();
+    let target = translated_str(token, target);
+    let path = translated_str(token, path);
+    if symlink(&target, &path).is_none() {
+        return -1;
+    }
+    0
+}
+
+/// Mount the easy-fs image in the file at `source` on the directory at `target`,
+/// paths through `target` lead into the image until it is unmounted.
+/// Fail if `source` holds no easy-fs, or `target` is not a directory, is the root
+/// of a filesystem, or has one mounted on it already.
+pub fn sys_mount(source: *const u8, target: *const u8) -> isize {
+    let token = current_user_token();
+    let source = translated_str(token, source);
+    let target = translated_str(token, target);
+    if !mount(&source, &target) {
+        return -1;
+    }
+    0
+}
+
+/// Unmount the filesystem mounted on the directory at `target`,
+/// fail if there is none or a file on it is still open
+pub fn sys_umount(target: *const u8) -> isize {
+    let target = translated_str(current_user_token(), target);
+    if !umount(&target) {
+        return -1;
+    }
+    0
+}
+
+pub fn sys_fs_info(buf: *mut FsInfo) -> isize {
+    *translated_refmut(current_user_token(), buf) = ROOT_INODE.fs_info();
+    0
+}
+
+/// Report the free and total data blocks and inodes of the filesystem
+pub fn sys_statfs(buf: *mut StatFs) -> isize {
+    *translated_refmut(current_user_token(), buf) = ROOT_INODE.statfs();
+    0
+}
+
+/// Report how often the filesystem and block cache locks had to wait since boot
+pub fn sys_lock_stats(buf: *mut LockStats) -> isize {
+    *translated_refmut(current_user_token(), buf) = lock_stats();
+    0
+}
+
+/// Pack data blocks of the filesystem together, return the number of blocks moved
+pub fn sys_fs_defrag() -> isize {
+    ROOT_INODE.defrag() as isize
+}
+
+/// Move the blocks of the file behind `fd` to one run of free blocks, return the number
+/// of blocks moved, 0 if they are in one run already, -1 if they can not be moved
+pub fn sys_defrag(fd: usize) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(inode) = inner.fd_table[fd].as_ref().and_then(|file| file.inode()) else { return -1; };
+    drop(inner);
+    inode.defragment().map_or(-1, |moved| moved as isize)
+}
+
+/// Count directory links that close a cycle, and unlink them if `remove` is set
+pub fn sys_fs_checkcycles(remove: usize) -> isize {
+    ROOT_INODE.check_cycles(remove != 0) as isize
+}
+
+/// Block until the file reaches `min_size` bytes, return its size or -EAGAIN on timeout
+pub fn sys_wait_size(fd: usize, min_size: usize, timeout_ms: usize) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(inode) = inner.fd_table[fd].as_ref().and_then(|file| file.inode()) else { return -1; };
+    // release current task TCB manually before blocking
+    drop(inner);
+    match wait_size(&inode, min_size, timeout_ms) {
+        Some(size) => size as isize,
+        None => -EAGAIN,
+    }
+}
+
+/// Read up to `len` bytes from the head of the file and cut them off atomically
+pub fn sys_drain(fd: usize, buf: *const u8, len: usize) -> isize {
+    let token = current_user_token();
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    // release current task TCB manually to avoid multi-borrow
+    drop(inner);
+    if !file.readable() || !file.writable() {
+        return -1;
+    }
+    let Some(buffers) = translated_byte_buffer(token, buf, len) else { return -1; };
+    match file.drain(UserBuffer::new(buffers)) {
+        Some(size) => size as isize,
+        None => -1,
+    }
+}
+
+/// Write the path of the file behind `fd` from the root of current process into `buf`
+/// with a trailing '\0', return its length
+pub fn sys_fd_path(fd: usize, buf: *const u8, len: usize) -> isize {
+    let token = current_user_token();
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    drop(inner);
+    // the file has been unlinked, no path leads to it
+    let Some(path) = file.path_under(&process_root()) else { return -1; };
+    if path.len() + 1 > len {
+        return -1;
+    }
+    let Some(buffers) = translated_byte_buffer(token, buf, path.len() + 1) else { return -1; };
+    let buffer = UserBuffer::new(buffers);
+    for (byte, ch) in buffer.into_iter().zip(path.bytes().chain(Some(0))) {
+        unsafe {
+            byte.write_volatile(ch);
+        }
+    }
+    path.len() as isize
+}
+
+/// Create a file that already holds `len` bytes of `data` the moment its name shows up.
+/// There are no permission bits on disk yet, so `mode` is not stored.
+pub fn sys_create_with_data(path: *const u8, data: *const u8, len: usize, _mode: u32) -> isize {
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let root = process_root();
+    if root.find(&path).is_some() {
+        return -1;
+    }
+    let Some(slices) = checked_byte_buffer(token, data, len) else { return -1; };
+    let mut buf: Vec<u8> = Vec::with_capacity(len);
+    for slice in slices {
+        buf.extend_from_slice(slice);
+    }
+    if root.create_with_data(&path, &buf).is_none() {
+        return -ENOSPC;
+    }
+    0
+}
+
+/// Keep the inode of the file behind `fd` in the block cache until it is unpinned or closed
+pub fn sys_pin_inode(fd: usize) -> isize {
+    set_pinned(fd, true)
+}
+
+/// Undo a [`sys_pin_inode`] on `fd`
+pub fn sys_unpin_inode(fd: usize) -> isize {
+    set_pinned(fd, false)
+}
+
+fn set_pinned(fd: usize, pinned: bool) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    drop(inner);
+    if file.set_pinned(pinned) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Return 1 if a write to `fd` would block right now, such as on a full pipe, else 0
+pub fn sys_write_would_block(fd: usize) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    drop(inner);
+    if !file.writable() {
+        return -1;
+    }
+    file.write_would_block() as isize
+}
+
+/// An fd [`sys_poll`] watches, as `struct pollfd`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PollFd {
+    pub fd: i32,
+    /// events of interest
+    pub events: i16,
+    /// events that happened, filled in by [`sys_poll`]
+    pub revents: i16,
+}
+
+/// Events of [`PollFd`]: it can be read, it can be written, it is not open
+const POLLIN: i16 = 0x1;
+const POLLOUT: i16 = 0x4;
+const POLLNVAL: i16 = 0x20;
+
+/// Fill in the events of the `n` [`PollFd`]s at `fds` that can happen without blocking,
+/// and return the number of fds with any. Wait until there is one, or for at most
+/// `timeout_us` unless it is negative: 0 is a pure poll, and 0 is returned when time runs out.
+/// A negative fd is skipped.
+pub fn sys_poll(fds: *mut PollFd, n: usize, timeout_us: isize) -> isize {
+    let expire_us = (timeout_us >= 0).then(|| get_time_us() + timeout_us as usize);
+    loop {
+        let token = current_user_token();
+        let mut ready = 0;
+        for i in 0..n {
+            let poll_fd = translated_refmut(token, fds.wrapping_add(i));
+            poll_fd.revents = poll_revents(poll_fd.fd, poll_fd.events);
+            if poll_fd.revents != 0 {
+                ready += 1;
+            }
+        }
+        if ready > 0 || expire_us.is_some_and(|expire_us| get_time_us() >= expire_us) {
+            return ready;
+        }
+        // nothing wakes a poller, it looks again when its turn comes
+        suspend_current_and_run_next(SwitchReason::BlockOnFile);
+    }
+}
+
+/// The events of `events` that can happen on `fd` now, [`POLLNVAL`] if it is not open
+fn poll_revents(fd: i32, events: i16) -> i16 {
+    if fd < 0 {
+        return 0;
+    }
+    let process = current_process().unwrap();
+    let inner = process.inner_exclusive_access();
+    let Some(Some(file)) = inner.fd_table.get(fd as usize).cloned() else { return POLLNVAL; };
+    drop(inner);
+    let (readable, writable) = file.poll_ready();
+    let mut revents = 0;
+    if readable {
+        revents |= POLLIN;
+    }
+    if writable {
+        revents |= POLLOUT;
+    }
+    revents & events
+}
+
+/// Grow the file behind `fd` by `len` zero bytes and map the new bytes into memory,
+/// return the address they are mapped at.
+/// What is written there goes back to the file on fsync, munmap, exec or exit.
+/// Fail with -EDQUOT, leaving the file as it was, if its new blocks do not fit.
+pub fn sys_extend_and_map(fd: usize, len: usize) -> isize {
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if len == 0 || len > u32::MAX as usize || fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    if !file.writable() {
+        return -1;
+    }
+    let Some(inode) = file.inode() else { return -1; };
+    let start = inner
+        .memory_set
+        .find_free_area(VirtAddr(FILE_MAPPING_BASE), len);
+    if !inner.memory_set.insert_framed_area(
+        start,
+        VirtAddr(start.0 + len),
+        MapPermission::R | MapPermission::W | MapPermission::U,
+    ) {
+        return -1;
+    }
+    // the whole part is backed at once or not at all, so write back can not run out of room
+    let Some(offset) = inode.extend(len) else {
+        inner.memory_set.remove_area_with_start_vpn(start.floor());
+        return -EDQUOT;
+    };
+    inner.update_max_rss();
+    inner.file_mappings.push(FileMapping {
+        start: start.0,
+        len,
+        inode,
+        offset,
+    });
+    start.0 as isize
+}
+
+/// Cut or extend the file behind `fd` to `len` bytes, an extended part reads as zeros.
+/// A ring file can only be emptied.
+pub fn sys_ftruncate(fd: usize, len: usize) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() || len > u32::MAX as usize {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    drop(inner);
+    if !file.writable() {
+        return -1;
+    }
+    let Some(inode) = file.inode() else { return -1; };
+    if inode.is_ring() && len != 0 {
+        return -1;
+    }
+    if !inode.truncate(len as u32) {
+        return -EDQUOT;
+    }
+    0
+}
+
+/// Preallocate zeroed blocks so that the file behind `fd` covers `offset + len` bytes,
+/// only mode 0 is supported
+pub fn sys_fallocate(fd: usize, mode: usize, offset: usize, len: usize) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if mode != 0 || len == 0 || fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    drop(inner);
+    if !file.writable() {
+        return -1;
+    }
+    let Some(inode) = file.inode() else { return -1; };
+    if !inode.fallocate(offset + len) {
+        return -EDQUOT;
+    }
+    0
+}
+
+/// Keep the file behind `fd` from growing past `bytes`, 0 lifts the limit.
+/// Writes past it stop short. Fail if `fd` is not a plain file open for writing,
+/// or the file is larger than `bytes` already, it is not cut down to it.
+pub fn sys_setquota(fd: usize, bytes: usize) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() || bytes > u32::MAX as usize {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    drop(inner);
+    if !file.writable() {
+        return -1;
+    }
+    let Some(inode) = file.inode() else { return -1; };
+    if inode.set_size_quota(bytes as u32) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Cut the file behind `fd` right after the last byte written to it,
+/// freeing the blocks preallocated past that, return the new size
+pub fn sys_seal_size(fd: usize) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else { return -1; };
+    drop(inner);
+    if !file.writable() {
+        return -1;
+    }
+    let Some(inode) = file.inode() else { return -1; };
+    inode.seal_size() as isize
+}
+
+/// Write the memory mapped from the file behind `fd` back to it,
+/// then the cached blocks of the file to the disk
+pub fn sys_fsync(fd: usize) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let Some(inode) = inner.fd_table[fd].as_ref().and_then(|file| file.inode()) else { return -1; };
+    let token = inner.get_user_token();
+    // inode ids repeat across mounted filesystems, so the filesystem is compared too
+    for mapping in inner.file_mappings.iter() {
+        if mapping.inode.is_same(&inode) {
+            mapping.write_back(token);
+        }
+    }
+    drop(inner);
+    inode.sync();
+    0
+}
+
+/// Write the dirty pages of the file mappings in `[start, start + len)` back
+/// to their files, with [`MS_INVALIDATE`] the pages are read from the files again.
+/// The range is widened out to whole pages, a page it touches at all is synced.
+/// The block cache writes through and there is no writeback thread to queue
+/// pages to, so [`MS_ASYNC`] is done right away like [`MS_SYNC`].
+pub fn sys_msync(start: usize, len: usize, flags: usize) -> isize {
+    if flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0
+        || flags & (MS_ASYNC | MS_SYNC) == MS_ASYNC | MS_SYNC
+    {
+        return -1;
+    }
+    let Some(end) = start.checked_add(len) else { return -1; };
+    if len == 0 {
+        return 0;
+    }
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.sync_file_mappings(start, end, flags & MS_INVALIDATE != 0);
+    0
+}
@@ -11,55 +11,265 @@
 //! submodules, and you should also implement syscalls this way.
 
 const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_SYMLINKAT: usize = 36;
+const SYSCALL_FACCESSAT: usize = 48;
+const SYSCALL_FCHMODAT: usize = 53;
 const SYSCALL_LINKAT: usize = 37;
+const SYSCALL_UMOUNT: usize = 39;
+const SYSCALL_MOUNT: usize = 40;
 const SYSCALL_OPEN: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_DUP: usize = 24;
+const SYSCALL_DUP2: usize = 444;
+const SYSCALL_CLOSE_RANGE: usize = 436;
+const SYSCALL_PIPE: usize = 59;
+const SYSCALL_EVENTFD: usize = 19;
+const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_LSEEK: usize = 62;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_READV: usize = 65;
+const SYSCALL_WRITEV: usize = 66;
 const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_GETDENTS: usize = 61;
+const SYSCALL_FSYNC: usize = 82;
+const SYSCALL_MSYNC: usize = 227;
+const SYSCALL_RENAMEAT2: usize = 276;
 const SYSCALL_EXIT: usize = 93;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_SLEEP: usize = 101;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETTID: usize = 178;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_KILLPG: usize = 457;
+const SYSCALL_VFORK: usize = 458;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
 const SYSCALL_WAITPID: usize = 260;
 const SYSCALL_SPAWN: usize = 400;
 const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_MMAP: usize = 222;
+const SYSCALL_SBRK: usize = 214;
+const SYSCALL_SHMGET: usize = 194;
+const SYSCALL_SHMAT: usize = 196;
+const SYSCALL_SHMDT: usize = 197;
 const SYSCALL_SET_PRIORITY: usize = 140;
 const SYSCALL_TASK_INFO: usize = 410;
+const SYSCALL_FS_INFO: usize = 411;
+const SYSCALL_WAIT_SIZE: usize = 412;
+const SYSCALL_DRAIN: usize = 413;
+const SYSCALL_FD_PATH: usize = 414;
+const SYSCALL_CREATE_WITH_DATA: usize = 415;
+const SYSCALL_PS: usize = 416;
+const SYSCALL_SET_DEADLINE_SCHED: usize = 417;
+const SYSCALL_MAP_VDSO: usize = 418;
+const SYSCALL_FS_DEFRAG: usize = 419;
+const SYSCALL_PIN_INODE: usize = 420;
+const SYSCALL_UNPIN_INODE: usize = 421;
+const SYSCALL_WRITE_WOULD_BLOCK: usize = 422;
+const SYSCALL_POLL: usize = 73;
+const SYSCALL_SNAPSHOT_FILE: usize = 423;
+const SYSCALL_SWITCH_HISTORY: usize = 424;
+const SYSCALL_EXTEND_AND_MAP: usize = 425;
+const SYSCALL_FS_CHECKCYCLES: usize = 426;
+const SYSCALL_LSEEK_SWAP: usize = 427;
+const SYSCALL_SET_TIMESLICE: usize = 428;
+const SYSCALL_PROCESS_VM_READV: usize = 270;
+const SYSCALL_FTRUNCATE: usize = 46;
+const SYSCALL_FALLOCATE: usize = 47;
+const SYSCALL_SEAL_SIZE: usize = 429;
+const SYSCALL_SET_MEMPOLICY: usize = 430;
+const SYSCALL_GET_MEMPOLICY: usize = 431;
+const SYSCALL_SWAP_DIR: usize = 432;
+const SYSCALL_SET_RECLAIM_WATERMARK: usize = 433;
+const SYSCALL_GET_RECLAIM_STATS: usize = 434;
+const SYSCALL_LOCK_STATS: usize = 435;
+const SYSCALL_APPEND: usize = 437;
+const SYSCALL_RINGFILE_CREATE: usize = 438;
+const SYSCALL_CAS_RENAME: usize = 439;
+const SYSCALL_FRAME_STATS: usize = 440;
+const SYSCALL_SET_DIR_QUOTA: usize = 441;
+const SYSCALL_GET_DIR_QUOTA: usize = 442;
+const SYSCALL_GET_RSS: usize = 443;
+const SYSCALL_CHECKPOINT: usize = 445;
+const SYSCALL_RESTORE: usize = 446;
+const SYSCALL_STATFS: usize = 447;
+const SYSCALL_WAIT_STATS: usize = 448;
+const SYSCALL_SENDFILE: usize = 449;
+const SYSCALL_NANOSLEEP: usize = 450;
+const SYSCALL_SETQUOTA: usize = 451;
+const SYSCALL_RESET_SYSCALL_STATS: usize = 452;
+const SYSCALL_FUTEX_WAIT: usize = 453;
+const SYSCALL_FUTEX_WAKE: usize = 454;
+const SYSCALL_DEFRAG: usize = 455;
+const SYSCALL_RENAME: usize = 456;
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_CHROOT: usize = 51;
+const SYSCALL_THREAD_CREATE: usize = 460;
+const SYSCALL_WAITTID: usize = 462;
+const SYSCALL_MUTEX_CREATE: usize = 463;
+const SYSCALL_MUTEX_LOCK: usize = 464;
+const SYSCALL_MUTEX_UNLOCK: usize = 466;
+const SYSCALL_ENABLE_DEADLOCK_DETECT: usize = 469;
+const SYSCALL_CONDVAR_CREATE: usize = 471;
+const SYSCALL_CONDVAR_SIGNAL: usize = 472;
+const SYSCALL_CONDVAR_WAIT: usize = 473;
 
 mod fs;
 pub mod process;
+mod sync;
 
-use crate::{fs::Stat, task::add_syscall_times};
+use crate::fs::{DirQuota, FsInfo, LockStats, Stat, StatFs};
+use crate::mm::FrameStats;
+use crate::task::{add_syscall_times, ReclaimStats, SwitchRecord};
 use fs::*;
 use process::*;
+use sync::*;
 
 /// handle syscall exception with `syscall_id` and other arguments
-pub fn syscall(syscall_id: usize, args: [usize; 4]) -> isize {
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
     add_syscall_times(syscall_id);
     match syscall_id {
-        SYSCALL_LINKAT => sys_linkat(args[1] as *const u8, args[3] as *const u8),
+        SYSCALL_LINKAT => sys_linkat(args[1] as *const u8, args[3] as *const u8, args[4]),
         SYSCALL_UNLINKAT => sys_unlinkat(args[1] as *const u8),
+        SYSCALL_UMOUNT => sys_umount(args[0] as *const u8),
+        SYSCALL_MOUNT => sys_mount(args[0] as *const u8, args[1] as *const u8),
+        SYSCALL_FACCESSAT => sys_access(args[1] as *const u8, args[2] as u32),
+        SYSCALL_FCHMODAT => sys_chmod(args[1] as *const u8, args[2] as u32),
+        SYSCALL_DUP => sys_dup(args[0]),
+        SYSCALL_DUP2 => sys_dup2(args[0], args[1]),
+        SYSCALL_SYMLINKAT => sys_symlinkat(args[0] as *const u8, args[2] as *const u8),
+        SYSCALL_RENAMEAT2 => sys_renameat2(
+            args[0] as isize,
+            args[1] as *const u8,
+            args[2] as isize,
+            args[3] as *const u8,
+        ),
+        SYSCALL_RENAME => sys_rename(args[0] as *const u8, args[1] as *const u8),
+        SYSCALL_CAS_RENAME => sys_cas_rename(
+            args[0] as *const u8,
+            args[1] as *const u8,
+            args[2],
+            args[3] as *const u8,
+        ),
+        SYSCALL_SWAP_DIR => sys_swap_dir(args[0] as *const u8, args[1] as *const u8),
         SYSCALL_OPEN => sys_open(args[1] as *const u8, args[2] as u32),
         SYSCALL_CLOSE => sys_close(args[0]),
+        SYSCALL_CLOSE_RANGE => sys_close_range(args[0], args[1], args[2] as u32),
+        SYSCALL_PIPE => sys_pipe(args[0] as *mut usize),
+        SYSCALL_EVENTFD => sys_eventfd(args[0] as u32, args[1] as u32),
+        SYSCALL_FCNTL => sys_fcntl(args[0], args[1], args[2]),
+        SYSCALL_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
+        SYSCALL_LSEEK_SWAP => sys_lseek_swap(args[0], args[1]),
         SYSCALL_READ => sys_read(args[0], args[1] as *const u8, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_READV => sys_readv(args[0], args[1] as *const IoVec, args[2]),
+        SYSCALL_WRITEV => sys_writev(args[0], args[1] as *const IoVec, args[2]),
+        SYSCALL_APPEND => sys_append(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_RINGFILE_CREATE => sys_ringfile_create(args[0] as *const u8, args[1]),
         SYSCALL_FSTAT => sys_fstat(args[0], args[1] as *mut Stat),
+        SYSCALL_GETDENTS => sys_getdents(args[0], args[1] as *const u8, args[2]),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
         SYSCALL_YIELD => sys_yield(),
+        SYSCALL_SLEEP => sys_sleep(args[0]),
+        SYSCALL_NANOSLEEP => sys_nanosleep(args[0]),
         SYSCALL_GETPID => sys_getpid(),
+        SYSCALL_GETTID => sys_gettid(),
+        SYSCALL_SETPGID => sys_setpgid(args[0], args[1]),
+        SYSCALL_GETPGID => sys_getpgid(args[0]),
+        SYSCALL_KILLPG => sys_killpg(args[0], args[1]),
+        SYSCALL_THREAD_CREATE => sys_thread_create(args[0], args[1]),
+        SYSCALL_WAITTID => sys_waittid(args[0]),
         SYSCALL_FORK => sys_fork(),
+        SYSCALL_VFORK => sys_vfork(),
         SYSCALL_EXEC => sys_exec(args[0] as *const u8),
         SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
+        SYSCALL_WAIT_STATS => sys_wait_stats(
+            args[0] as isize,
+            args[1] as *mut i32,
+            args[2] as *mut Rusage,
+        ),
         SYSCALL_GET_TIME => sys_get_time(args[0] as *mut TimeVal, args[1]),
-        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2]),
+        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
         SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_SBRK => sys_sbrk(args[0] as isize),
+        SYSCALL_SHMGET => sys_shmget(args[0], args[1]),
+        SYSCALL_SHMAT => sys_shmat(args[0]),
+        SYSCALL_SHMDT => sys_shmdt(args[0]),
+        SYSCALL_CHECKPOINT => sys_checkpoint(args[0] as *const u8),
+        SYSCALL_RESTORE => sys_restore(args[0] as *const u8),
+        SYSCALL_SET_MEMPOLICY => sys_set_mempolicy(args[0], args[1], args[2]),
+        SYSCALL_GET_MEMPOLICY => sys_get_mempolicy(args[0]),
+        SYSCALL_SET_RECLAIM_WATERMARK => sys_set_reclaim_watermark(args[0]),
+        SYSCALL_GET_RECLAIM_STATS => sys_get_reclaim_stats(args[0] as *mut ReclaimStats),
+        SYSCALL_FRAME_STATS => sys_frame_stats(args[0] as *mut FrameStats),
+        SYSCALL_GET_RSS => sys_get_rss(),
+        SYSCALL_SET_DIR_QUOTA => sys_set_dir_quota(args[0] as *const u8, args[1]),
+        SYSCALL_GET_DIR_QUOTA => {
+            sys_get_dir_quota(args[0] as *const u8, args[1] as *mut DirQuota)
+        }
         SYSCALL_SET_PRIORITY => sys_set_priority(args[0] as isize),
         SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut TaskInfo),
+        SYSCALL_RESET_SYSCALL_STATS => sys_reset_syscall_stats(),
         SYSCALL_SPAWN => sys_spawn(args[0] as *const u8),
+        SYSCALL_FS_INFO => sys_fs_info(args[0] as *mut FsInfo),
+        SYSCALL_STATFS => sys_statfs(args[0] as *mut StatFs),
+        SYSCALL_SENDFILE => sys_sendfile(args[0], args[1], args[2]),
+        SYSCALL_LOCK_STATS => sys_lock_stats(args[0] as *mut LockStats),
+        SYSCALL_WAIT_SIZE => sys_wait_size(args[0], args[1], args[2]),
+        SYSCALL_DRAIN => sys_drain(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_FD_PATH => sys_fd_path(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_CREATE_WITH_DATA => sys_create_with_data(
+            args[0] as *const u8,
+            args[1] as *const u8,
+            args[2],
+            args[3] as u32,
+        ),
+        SYSCALL_PS => sys_ps(args[0] as *mut ProcInfo, args[1]),
+        SYSCALL_SET_DEADLINE_SCHED => sys_set_deadline_sched(args[0], args[1]),
+        SYSCALL_SET_TIMESLICE => sys_set_timeslice(args[0]),
+        SYSCALL_MAP_VDSO => sys_map_vdso(),
+        SYSCALL_FS_DEFRAG => sys_fs_defrag(),
+        SYSCALL_DEFRAG => sys_defrag(args[0]),
+        SYSCALL_GETCWD => sys_getcwd(args[0] as *mut u8, args[1]),
+        SYSCALL_CHDIR => sys_chdir(args[0] as *const u8),
+        SYSCALL_CHROOT => sys_chroot(args[0] as *const u8),
+        SYSCALL_FS_CHECKCYCLES => sys_fs_checkcycles(args[0]),
+        SYSCALL_PIN_INODE => sys_pin_inode(args[0]),
+        SYSCALL_UNPIN_INODE => sys_unpin_inode(args[0]),
+        SYSCALL_WRITE_WOULD_BLOCK => sys_write_would_block(args[0]),
+        SYSCALL_POLL => sys_poll(args[0] as *mut PollFd, args[1], args[2] as isize),
+        SYSCALL_SNAPSHOT_FILE => sys_snapshot_file(args[0]),
+        SYSCALL_EXTEND_AND_MAP => sys_extend_and_map(args[0], args[1]),
+        SYSCALL_FSYNC => sys_fsync(args[0]),
+        SYSCALL_MSYNC => sys_msync(args[0], args[1], args[2]),
+        SYSCALL_FTRUNCATE => sys_ftruncate(args[0], args[1]),
+        SYSCALL_FALLOCATE => sys_fallocate(args[0], args[1], args[2], args[3]),
+        SYSCALL_SEAL_SIZE => sys_seal_size(args[0]),
+        SYSCALL_SETQUOTA => sys_setquota(args[0], args[1]),
+        SYSCALL_PROCESS_VM_READV => sys_process_vm_readv(
+            args[0],
+            args[1] as *const IoVec,
+            args[2],
+            args[3] as *const IoVec,
+            args[4],
+            args[5],
+        ),
+        SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0] == 1),
+        SYSCALL_MUTEX_LOCK => sys_mutex_lock(args[0]),
+        SYSCALL_MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
+        SYSCALL_ENABLE_DEADLOCK_DETECT => sys_enable_deadlock_detect(args[0]),
+        SYSCALL_CONDVAR_CREATE => sys_condvar_create(args[0]),
+        SYSCALL_CONDVAR_SIGNAL => sys_condvar_signal(args[0]),
+        SYSCALL_CONDVAR_WAIT => sys_condvar_wait(args[0], args[1]),
+        SYSCALL_FUTEX_WAIT => sys_futex_wait(args[0], args[1] as u32),
+        SYSCALL_FUTEX_WAKE => sys_futex_wake(args[0], args[1]),
+        SYSCALL_SWITCH_HISTORY => {
+            sys_switch_history(args[0], args[1] as *mut SwitchRecord, args[2])
+        }
         _ => panic!("Unsupported syscall_id: {}", syscall_id),
     }
 }
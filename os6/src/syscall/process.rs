@@ -1,14 +1,25 @@
 //! Process management syscalls
 
-use crate::config::{BIG_STRIDE, MAX_SYSCALL_NUM};
+use crate::config::{BIG_STRIDE, CLOCK_PAGE, MAX_SYSCALL_NUM, MAX_TIMESLICE, MEMORY_NODES};
 use crate::fs::{open_file, OpenFlags};
-use crate::mm::{translated_refmut, translated_str};
+use crate::mm::{translated_kernel_buffer, translated_refmut, translated_str};
+use crate::mm::{checked_byte_buffer, translated_byte_buffer, UserBuffer, VirtAddr};
+use crate::mm::{frame_stats, shm_get, FrameStats};
 use crate::task::{
-    add_task, current_task, current_user_token, exit_current_and_run_next, get_current_task_info,
-    mmap, munmap, suspend_current_and_run_next, TaskStatus,
+    add_task, block_current_and_run_next, checkpoint, current_process, current_task, current_trap_cx, current_user_token, exit_current_and_run_next, get_current_task_info,
+    insert_into_pid2task, mmap, mmap_file, munmap, reclaim_stats, shmat, shmdt, reset_syscall_stats, restore, sbrk, remove_from_pid2task, set_reclaim_watermark,
+    suspend_current_and_run_next, DeadlineSched, ReclaimStats, SwitchReason, SwitchRecord,
+    TaskControlBlock, TaskStatus, INITPROC, PID2TCB,
 };
-use crate::timer::get_time_us;
+use crate::timer::{get_time_us, sleep_current, CLOCK_FRAME};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::{size_of, MaybeUninit};
+
+/// Flag of [`sys_mmap`]: map a file, writes through the mapping go back to it
+const MAP_SHARED: usize = 1;
+/// The write bit of the permission of [`sys_mmap`]
+const PROT_WRITE: usize = 1 << 1;
 
 #[repr(C)]
 #[derive(Debug)]
@@ -17,11 +28,39 @@ pub struct TimeVal {
     pub usec: usize,
 }
 
+/// CPU time of a reaped child for `sys_wait_stats`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Rusage {
+    /// time spent in user mode, in microseconds
+    pub utime_us: usize,
+    /// time spent in the kernel on behalf of the child, in microseconds
+    pub stime_us: usize,
+}
+
 #[derive(Clone, Copy)]
 pub struct TaskInfo {
     pub status: TaskStatus,
     pub syscall_times: [u32; MAX_SYSCALL_NUM],
     pub time: usize,
+    /// Times the task was switched in to run
+    pub schedule_count: usize,
+}
+
+/// Max length of a process name in [`ProcInfo`], including the trailing '\0'
+const PROC_NAME_LEN: usize = 32;
+
+/// A snapshot of a process for `sys_ps`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ProcInfo {
+    pub pid: usize,
+    /// 0 if the process has no parent
+    pub ppid: usize,
+    pub status: usize,
+    /// in milliseconds
+    pub cpu_time: usize,
+    pub name: [u8; PROC_NAME_LEN],
 }
 
 pub fn sys_exit(exit_code: i32) -> ! {
@@ -32,17 +71,151 @@ pub fn sys_exit(exit_code: i32) -> ! {
 
 /// current task gives up resources for other tasks
 pub fn sys_yield() -> isize {
-    suspend_current_and_run_next();
+    suspend_current_and_run_next(SwitchReason::Yield);
+    0
+}
+
+/// Block current task for `sleep_ms` milliseconds
+pub fn sys_sleep(sleep_ms: usize) -> isize {
+    sleep_current(sleep_ms * 1000);
+    0
+}
+
+/// Block current task until `get_time_us` has advanced by `duration_us`,
+/// down to a sleep shorter than a tick
+pub fn sys_nanosleep(duration_us: usize) -> isize {
+    sleep_current(duration_us);
     0
 }
 
 pub fn sys_getpid() -> isize {
-    current_task().unwrap().pid.0 as isize
+    current_process().unwrap().pid.0 as isize
 }
 
-/// Syscall Fork which returns 0 for child process and child_pid for parent process
+/// Move process `pid`, current process for 0, into the group `pgid`, a new group
+/// of its own for 0 or `pid`. Only current process and its children can be moved,
+/// and only into a new group or one with a live process in it.
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    let process = current_process().unwrap();
+    let pid = if pid == 0 { process.getpid() } else { pid };
+    let pgid = if pgid == 0 { pid } else { pgid };
+    let target = if pid == process.getpid() {
+        process
+    } else {
+        let inner = process.inner_exclusive_access();
+        let Some(child) = inner.children.iter().find(|child| child.getpid() == pid).cloned() else { return -1; };
+        child
+    };
+    if pgid != pid && group_members(pgid).is_empty() {
+        return -1;
+    }
+    target.inner_exclusive_access().pgid = pgid;
+    0
+}
+
+/// The group of process `pid`, current process for 0, -1 if there is no such process
+pub fn sys_getpgid(pid: usize) -> isize {
+    let process = if pid == 0 {
+        current_process().unwrap()
+    } else {
+        let Some(process) = PID2TCB.exclusive_access().get(&pid).cloned() else { return -1; };
+        process
+    };
+    let pgid = process.inner_exclusive_access().pgid;
+    pgid as isize
+}
+
+/// Kill each process in the group `pgid`, that of current process for 0, by `signal`.
+/// A process goes on its next return to user mode, a blocked one once it wakes up.
+/// Signal 0 only checks that the group has a process. Return -1 if it has none
+/// or the signal is not below 64. The initial process is never killed.
+pub fn sys_killpg(pgid: usize, signal: usize) -> isize {
+    if signal >= 64 {
+        return -1;
+    }
+    let pgid = if pgid == 0 {
+        let process = current_process().unwrap();
+        let pgid = process.inner_exclusive_access().pgid;
+        pgid
+    } else {
+        pgid
+    };
+    let members = group_members(pgid);
+    if members.is_empty() {
+        return -1;
+    }
+    for process in members {
+        if signal != 0 && !Arc::ptr_eq(&process, &INITPROC) {
+            let mut inner = process.inner_exclusive_access();
+            // the first signal sent is the one it dies of
+            if inner.pending_signal == 0 {
+                inner.pending_signal = signal as i32;
+            }
+        }
+    }
+    0
+}
+
+/// The processes in group `pgid` not exited yet
+fn group_members(pgid: usize) -> Vec<Arc<TaskControlBlock>> {
+    PID2TCB
+        .exclusive_access()
+        .values()
+        .filter(|process| {
+            let inner = process.inner_exclusive_access();
+            inner.pgid == pgid && !inner.is_zombie()
+        })
+        .cloned()
+        .collect()
+}
+
+/// Id of current thread, the pid for the first thread of a process
+pub fn sys_gettid() -> isize {
+    current_task().unwrap().gettid() as isize
+}
+
+/// Start a thread of current process at `entry` with `arg` as its argument and
+/// return its id, -1 if the process has `MAX_THREADS` threads besides its first one
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    let process = current_process().unwrap();
+    let Some(thread) = process.create_thread(entry, arg) else { return -1; };
+    let tid = thread.getpid();
+    add_task(thread);
+    tid as isize
+}
+
+/// Reap the exited thread `tid` of current process and return its exit code.
+/// Return -1 if there is no such thread or it is current thread, -2 if it is still running.
+pub fn sys_waittid(tid: usize) -> isize {
+    let task = current_task().unwrap();
+    if task.getpid() == tid {
+        return -1;
+    }
+    let process = task.process();
+    let mut inner = process.inner_exclusive_access();
+    let Some(slot) = inner.threads.iter_mut().find(|thread| {
+        thread
+            .as_ref()
+            .is_some_and(|thread| thread.getpid() == tid)
+    }) else { return -1; };
+    let thread_inner = slot.as_ref().unwrap().inner_exclusive_access();
+    if !thread_inner.is_zombie() {
+        return -2;
+    }
+    let exit_code = thread_inner.exit_code;
+    drop(thread_inner);
+    // its kernel stack goes with it, it is no longer running on it
+    slot.take();
+    exit_code as isize
+}
+
+/// Syscall Fork which returns 0 for child process and child_pid for parent process,
+/// -1 in a process running more than one thread
 pub fn sys_fork() -> isize {
-    let current_task = current_task().unwrap();
+    let current_task = current_process().unwrap();
+    if !current_task.inner_exclusive_access().is_single_threaded() {
+        return -1;
+    }
     let new_task = current_task.fork();
     let new_pid = new_task.pid.0;
     // modify trap context of new_task, because it returns immediately after switching
@@ -50,19 +223,59 @@ pub fn sys_fork() -> isize {
     // we do not have to move to next instruction since we have done it before
     // for child process, fork returns 0
     trap_cx.x[10] = 0;
+    insert_into_pid2task(new_pid, new_task.clone());
     // add new task to scheduler
     add_task(new_task);
     new_pid as isize
 }
 
-/// Syscall Exec which accepts the elf path
+/// Fork a child which runs in the address space of current process rather than
+/// a copy of it, and block current process till the child execs or exits.
+/// The user stack current process returns on is put back as it was then,
+/// whatever else the child wrote stays. -1 in a process running more than one thread.
+pub fn sys_vfork() -> isize {
+    let current_task = current_process().unwrap();
+    if !current_task.inner_exclusive_access().is_single_threaded() {
+        return -1;
+    }
+    // the child shares the trap context page and returns on the same stack
+    let trap_cx = *current_trap_cx();
+    let sp = trap_cx.x[2];
+    let stack_top = current_task.inner_exclusive_access().heap_bottom;
+    let len = stack_top.saturating_sub(sp);
+    let Some(stack) = checked_byte_buffer(current_user_token(), sp as *const u8, len) else {
+        return -1;
+    };
+    let stack: Vec<u8> = stack.concat();
+    let new_task = current_task.vfork();
+    let new_pid = new_task.pid.0;
+    new_task.inner_exclusive_access().get_trap_cx().x[10] = 0;
+    insert_into_pid2task(new_pid, new_task.clone());
+    add_task(new_task);
+    block_current_and_run_next(SwitchReason::BlockOnVfork);
+    // the address space is back
+    *current_trap_cx() = trap_cx;
+    let mut saved = stack.iter();
+    for slice in translated_kernel_buffer(current_user_token(), sp as *const u8, len) {
+        slice
+            .iter_mut()
+            .for_each(|byte| *byte = *saved.next().unwrap());
+    }
+    new_pid as isize
+}
+
+/// Syscall Exec which accepts the elf path,
+/// -1 in a process running more than one thread
 pub fn sys_exec(path: *const u8) -> isize {
     let token = current_user_token();
     let path = translated_str(token, path);
+    let task = current_process().unwrap();
+    if !task.inner_exclusive_access().is_single_threaded() {
+        return -1;
+    }
     if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
         let all_data = app_inode.read_all();
-        let task = current_task().unwrap();
-        task.exec(all_data.as_slice());
+        task.exec(path.as_str(), all_data.as_slice());
         0
     } else {
         -1
@@ -71,8 +284,17 @@ pub fn sys_exec(path: *const u8) -> isize {
 
 /// If there is not a child process whose pid is same as given, return -1.
 /// Else if there is a child process but it is still running, return -2.
+/// The status written has the signal that killed the child in the low byte,
+/// or the exit code it gave shifted above that.
 pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
-    let task = current_task().unwrap();
+    sys_wait_stats(pid, exit_code_ptr, core::ptr::null_mut())
+}
+
+/// Like `sys_waitpid`, and also fill `rusage` with the CPU time of the reaped child
+/// unless it is null. Only the time the child ran itself counts, not that of its own
+/// children, which go to initproc if they outlive it.
+pub fn sys_wait_stats(pid: isize, exit_code_ptr: *mut i32, rusage: *mut Rusage) -> isize {
+    let task = current_process().unwrap();
     // find a child process
 
     // ---- access current TCB exclusively
@@ -92,13 +314,26 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
     });
     if let Some((idx, _)) = pair {
         let child = inner.children.remove(idx);
+        remove_from_pid2task(child.getpid());
         // confirm that child will be deallocated after removing from children list
         assert_eq!(Arc::strong_count(&child), 1);
         let found_pid = child.getpid();
         // ++++ temporarily access child TCB exclusively
-        let exit_code = child.inner_exclusive_access().exit_code;
+        let child_inner = child.inner_exclusive_access();
+        let status = child_inner.wait_status();
+        let usage = Rusage {
+            utime_us: child_inner.user_time,
+            stime_us: child_inner.cpu_time - child_inner.user_time,
+        };
+        drop(child_inner);
         // ++++ release child PCB
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
+        let token = inner.memory_set.token();
+        // the write may copy a shared page, which takes current TCB again
+        drop(inner);
+        *translated_refmut(token, exit_code_ptr) = status;
+        if !rusage.is_null() {
+            *translated_refmut(token, rusage) = usage;
+        }
         found_pid as isize
     } else {
         -2
@@ -116,30 +351,197 @@ pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
     0
 }
 
+/// Map the clock page updated by timer interrupts into current task, return its address
+pub fn sys_map_vdso() -> isize {
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.memory_set.clock_page().is_none()
+        && !inner.memory_set.map_clock_page(CLOCK_FRAME.ppn)
+    {
+        return -1;
+    }
+    CLOCK_PAGE as isize
+}
+
 // YOUR JOB: 引入虚地址后重写 sys_task_info
 pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
     *translated_refmut(current_user_token(), ti) = get_current_task_info();
     0
 }
 
+/// Start a new interval of `sys_task_info`: zero the syscall counters, this syscall
+/// included, and count the time from now. The schedule count keeps going.
+pub fn sys_reset_syscall_stats() -> isize {
+    reset_syscall_stats();
+    0
+}
+
 // YOUR JOB: 实现sys_set_priority，为任务添加优先级
 pub fn sys_set_priority(prio: isize) -> isize {
     if prio <= 1 {
         return -1;
     }
-    current_task().unwrap().inner_exclusive_access().stride = BIG_STRIDE / (prio as usize);
+    // a stride of 0 would never let the others catch up
+    current_task().unwrap().inner_exclusive_access().stride = (BIG_STRIDE / (prio as usize)).max(1);
     prio
 }
 
+/// Put current task into the deadline scheduling class,
+/// it may run for `runtime_ms` in every `period_ms`.
+/// `period_ms` of 0 puts it back to a normal task.
+pub fn sys_set_deadline_sched(period_ms: usize, runtime_ms: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if period_ms == 0 {
+        inner.deadline_sched = None;
+        return 0;
+    }
+    if runtime_ms == 0 || runtime_ms > period_ms {
+        return -1;
+    }
+    inner.deadline_sched = Some(DeadlineSched::new(period_ms, runtime_ms));
+    0
+}
+
+/// Prefer frames of memory `node` for the pages in `[start, start + len)`,
+/// fail if the node does not exist or a page is not mapped
+pub fn sys_set_mempolicy(start: usize, len: usize, node: usize) -> isize {
+    if node >= MEMORY_NODES || len == 0 {
+        return -1;
+    }
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner
+        .memory_set
+        .set_mempolicy(VirtAddr(start), VirtAddr(start + len), node)
+    {
+        0
+    } else {
+        -1
+    }
+}
+
+/// The memory node preferred for the page at `addr`, -1 if no policy is set on it
+pub fn sys_get_mempolicy(addr: usize) -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    match inner.memory_set.mempolicy(VirtAddr(addr)) {
+        Some(node) => node as isize,
+        None => -1,
+    }
+}
+
+/// Reclaim file mapped pages whenever free frames drop below `free_frames`, 0 turns it off
+pub fn sys_set_reclaim_watermark(free_frames: usize) -> isize {
+    set_reclaim_watermark(free_frames);
+    0
+}
+
+pub fn sys_get_reclaim_stats(stats: *mut ReclaimStats) -> isize {
+    *translated_refmut(current_user_token(), stats) = reclaim_stats();
+    0
+}
+
+/// Report the frame allocator counters and how fragmented the free frames are
+pub fn sys_frame_stats(stats: *mut FrameStats) -> isize {
+    *translated_refmut(current_user_token(), stats) = frame_stats();
+    0
+}
+
+/// The most user pages current task has had resident at once
+pub fn sys_get_rss() -> isize {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    inner.max_rss as isize
+}
+
+/// Set the time slice of current task in timer ticks, clamped to 1..=MAX_TIMESLICE,
+/// and start a full one of it right away, return the slice it got
+pub fn sys_set_timeslice(ticks: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.timeslice = ticks.clamp(1, MAX_TIMESLICE);
+    inner.slice_left = inner.timeslice;
+    inner.timeslice as isize
+}
+
 // YOUR JOB: 扩展内核以实现 sys_mmap 和 sys_munmap
-pub fn sys_mmap(start: usize, len: usize, port: usize) -> isize {
-    mmap(start, len, port)
+/// Map `[start, start + len)` with the permission in `port`, zeroed pages without
+/// flags, or with [`MAP_SHARED`] the file behind `fd` from `offset` on.
+/// Writing through a file mapping needs the file open for writing.
+pub fn sys_mmap(
+    start: usize,
+    len: usize,
+    port: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    match flags {
+        0 => mmap(start, len, port),
+        MAP_SHARED => {
+            let process = current_process().unwrap();
+            let inner = process.inner_exclusive_access();
+            let Some(Some(file)) = inner.fd_table.get(fd).cloned() else { return -1; };
+            drop(inner);
+            if !file.readable() || port & PROT_WRITE != 0 && !file.writable() {
+                return -1;
+            }
+            let Some(inode) = file.inode() else { return -1; };
+            if inode.is_ring() {
+                return -1;
+            }
+            mmap_file(start, len, port, inode, offset)
+        }
+        _ => -1,
+    }
 }
 
 pub fn sys_munmap(start: usize, len: usize) -> isize {
     munmap(start, len)
 }
 
+/// The id of the shared memory segment named `key`, made with `size` zeroed bytes
+/// if there is none. Fail if `size` is 0 for a new one, more than an existing one
+/// holds, or there are not enough frames.
+pub fn sys_shmget(key: usize, size: usize) -> isize {
+    shm_get(key, size).map_or(-1, |id| id as isize)
+}
+
+/// Map the shared memory segment `id` read-write into current task, return the address.
+/// All tasks attached to it see the writes of each other.
+pub fn sys_shmat(id: usize) -> isize {
+    shmat(id)
+}
+
+/// Unmap the shared memory segment attached at `addr`,
+/// it is freed once the last task attached to it detaches or exits
+pub fn sys_shmdt(addr: usize) -> isize {
+    shmdt(addr)
+}
+
+/// Grow or shrink the heap of current task by `increment` bytes, 0 to read the break.
+/// Return the old break, or -1 past the heap limit or into memory in use.
+pub fn sys_sbrk(increment: isize) -> isize {
+    sbrk(increment)
+}
+
+/// Save the registers and writable pages of current task to the file at `path`.
+/// Return 0, and 1 once the checkpoint is restored, or -1 if the file can not be written
+/// or the process runs more than one thread.
+pub fn sys_checkpoint(path: *const u8) -> isize {
+    let path = translated_str(current_user_token(), path);
+    checkpoint(path.as_str())
+}
+
+/// Put current task back to the checkpoint at `path`, returning from its `sys_checkpoint`.
+/// Return -1 if it is not a checkpoint of a task with the same address layout,
+/// or the process runs more than one thread.
+pub fn sys_restore(path: *const u8) -> isize {
+    let path = translated_str(current_user_token(), path);
+    restore(path.as_str())
+}
+
 //
 // YOUR JOB: 实现 sys_spawn 系统调用
 // ALERT: 注意在实现 SPAWN 时不需要复制父进程地址空间，SPAWN != FORK + EXEC
@@ -148,8 +550,11 @@ pub fn sys_spawn(path: *const u8) -> isize {
     let path = translated_str(token, path);
     if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
         let all_data = app_inode.read_all();
-        let new_task = current_task().unwrap().spawn(all_data.as_slice());
+        let new_task = current_process()
+            .unwrap()
+            .spawn(path.as_str(), all_data.as_slice());
         let new_pid = new_task.pid.0;
+        insert_into_pid2task(new_pid, new_task.clone());
         // add new task to scheduler
         add_task(new_task);
         new_pid as isize
@@ -157,3 +562,147 @@ pub fn sys_spawn(path: *const u8) -> isize {
         -1
     }
 }
+
+/// Fill `buf` with at most `n` processes in the system and return the number of all of them
+pub fn sys_ps(buf: *mut ProcInfo, n: usize) -> isize {
+    let token = current_user_token();
+    let pid2task = PID2TCB.exclusive_access();
+    for (i, task) in pid2task.values().take(n).enumerate() {
+        let inner = task.inner_exclusive_access();
+        let mut info = ProcInfo {
+            pid: task.getpid(),
+            ppid: inner
+                .parent
+                .as_ref()
+                .and_then(|parent| parent.upgrade())
+                .map_or(0, |parent| parent.getpid()),
+            status: inner.task_status as usize,
+            cpu_time: inner.cpu_time / 1000,
+            name: [0; PROC_NAME_LEN],
+        };
+        let len = inner.name.len().min(PROC_NAME_LEN - 1);
+        info.name[..len].copy_from_slice(&inner.name.as_bytes()[..len]);
+        drop(inner);
+        if !copy_to_user(token, unsafe { buf.add(i) }, &info) {
+            return -1;
+        }
+    }
+    pid2task.len() as isize
+}
+
+/// Copy a `T` from `src` in user space byte by byte, since it may cross a page boundary,
+/// `None` if it is not all mapped readable
+pub fn copy_from_user<T: Copy>(token: usize, src: *const T) -> Option<T> {
+    let mut value = MaybeUninit::<T>::uninit();
+    let bytes =
+        unsafe { core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>()) };
+    let user_buf = UserBuffer::new(checked_byte_buffer(
+        token,
+        src as *const u8,
+        size_of::<T>(),
+    )?);
+    for (byte, value) in bytes.iter_mut().zip(user_buf) {
+        *byte = unsafe { value.read_volatile() };
+    }
+    Some(unsafe { value.assume_init() })
+}
+
+/// Copy `value` to `dst` in user space byte by byte, since it may cross a page boundary,
+/// false if it is not all mapped writable
+fn copy_to_user<T>(token: usize, dst: *mut T, value: &T) -> bool {
+    let bytes =
+        unsafe { core::slice::from_raw_parts(value as *const _ as *const u8, size_of::<T>()) };
+    let Some(buffers) = translated_byte_buffer(token, dst as *const u8, size_of::<T>()) else {
+        return false;
+    };
+    for (byte, value) in UserBuffer::new(buffers).into_iter().zip(bytes.iter()) {
+        unsafe {
+            byte.write_volatile(*value);
+        }
+    }
+    true
+}
+
+/// Fill `buf` with the last `n` context switches of the task `pid`, oldest first,
+/// and return how many were written.
+/// A task can only look at itself and its children.
+pub fn sys_switch_history(pid: usize, buf: *mut SwitchRecord, n: usize) -> isize {
+    let token = current_user_token();
+    let Some(task) = self_or_child(pid) else { return -1; };
+    let history = task.inner_exclusive_access().switch_history.clone();
+    let skip = history.len().saturating_sub(n);
+    for (i, record) in history.iter().skip(skip).enumerate() {
+        if !copy_to_user(token, unsafe { buf.add(i) }, record) {
+            return -1;
+        }
+    }
+    (history.len() - skip) as isize
+}
+
+/// The current task if `pid` is its own, or its child with `pid`
+fn self_or_child(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    let current = current_process().unwrap();
+    if pid == current.getpid() {
+        return Some(current);
+    }
+    let inner = current.inner_exclusive_access();
+    inner
+        .children
+        .iter()
+        .find(|child| child.getpid() == pid)
+        .cloned()
+}
+
+/// A buffer in user space, as `struct iovec`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct IoVec {
+    pub base: usize,
+    pub len: usize,
+}
+
+/// Copy the memory of the process `pid` described by `remote_iov` into the buffers of
+/// current task described by `local_iov`, filling them in order, and return the bytes copied.
+/// Only current task and its children can be read. The copy stops before the first
+/// remote buffer which is not mapped readable as a whole, -1 if nothing was copied.
+pub fn sys_process_vm_readv(
+    pid: usize,
+    local_iov: *const IoVec,
+    local_cnt: usize,
+    remote_iov: *const IoVec,
+    remote_cnt: usize,
+    flags: usize,
+) -> isize {
+    if flags != 0 {
+        return -1;
+    }
+    let token = current_user_token();
+    let Some(task) = self_or_child(pid) else { return -1; };
+    let remote_token = task.inner_exclusive_access().get_user_token();
+    let mut local_buffers = Vec::new();
+    for i in 0..local_cnt {
+        let Some(iov) = copy_from_user(token, unsafe { local_iov.add(i) }) else { return -1; };
+        let Some(buffers) = translated_byte_buffer(token, iov.base as *const u8, iov.len) else {
+            return -1;
+        };
+        local_buffers.extend(buffers);
+    }
+    let mut local = UserBuffer::new(local_buffers).into_iter();
+    let mut copied = 0;
+    for i in 0..remote_cnt {
+        let Some(iov) = copy_from_user(token, unsafe { remote_iov.add(i) }) else { return -1; };
+        let Some(remote) = checked_byte_buffer(remote_token, iov.base as *const u8, iov.len) else {
+            return if copied == 0 { -1 } else { copied as isize };
+        };
+        for byte in UserBuffer::new(remote).into_iter() {
+            let Some(dst) = local.next() else {
+                return copied as isize;
+            };
+            unsafe {
+                dst.write_volatile(byte.read_volatile());
+            }
+            copied += 1;
+        }
+    }
+    copied as isize
+}
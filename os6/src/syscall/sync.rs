@@ -0,0 +1,140 @@
+//! Mutex, condition variable and futex syscalls
+
+use crate::mm::translated_refmut;
+use crate::sync::{check_mutex_order, mutex_acquire, mutex_released, mutex_taken};
+use crate::sync::{futex_wait, futex_wake, Condvar, Mutex, MutexBlocking, MutexSpin};
+use crate::task::{current_process, current_task, current_user_token};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// Put `item` into the first free slot of `list`, returns its id
+fn alloc_id<T>(list: &mut Vec<Option<T>>, item: T) -> isize {
+    if let Some(id) = list.iter().position(|slot| slot.is_none()) {
+        list[id] = Some(item);
+        id as isize
+    } else {
+        list.push(Some(item));
+        list.len() as isize - 1
+    }
+}
+
+fn get_mutex(mutex_id: usize) -> Option<Arc<dyn Mutex>> {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    inner.mutex_list.get(mutex_id).cloned().flatten()
+}
+
+fn get_condvar(condvar_id: usize) -> Option<Arc<Condvar>> {
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    inner.condvar_list.get(condvar_id).cloned().flatten()
+}
+
+pub fn sys_mutex_create(blocking: bool) -> isize {
+    let mutex: Arc<dyn Mutex> = if blocking {
+        Arc::new(MutexBlocking::new())
+    } else {
+        Arc::new(MutexSpin::new())
+    };
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    alloc_id(&mut inner.mutex_list, mutex)
+}
+
+/// Returned instead of taking a mutex out of order, see [`sys_enable_deadlock_detect`].
+/// The value the lab tests expect, not an errno.
+const DEADLOCK: isize = 0xdead;
+
+/// Take the mutex `mutex_id`. With deadlock detection on, -0xdead if some thread
+/// of the process took it before a mutex current task holds, or current task
+/// holds it already, as both could deadlock. The mutex is not taken then.
+pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+    // the task is not borrowed any more when it blocks
+    let Some(mutex) = get_mutex(mutex_id) else {
+        return -1;
+    };
+    let pid = current_process().unwrap().getpid();
+    let tid = current_task().unwrap().gettid();
+    if let Err(cycle) = mutex_acquire(pid, tid, mutex_id) {
+        warn!("[kernel] tid {} mutex order cycle: {:?}", tid, cycle);
+        return -DEADLOCK;
+    }
+    mutex.lock();
+    mutex_taken(pid, tid, mutex_id);
+    0
+}
+
+pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+    let Some(mutex) = get_mutex(mutex_id) else {
+        return -1;
+    };
+    mutex.unlock();
+    let pid = current_process().unwrap().getpid();
+    mutex_released(pid, current_task().unwrap().gettid(), mutex_id);
+    0
+}
+
+/// Check the order the threads of current process take mutexes in from now on if
+/// `enabled` is 1, stop checking if it is 0, see [`sys_mutex_lock`]. -1 for anything else.
+/// Orders seen while not checking are not kept.
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    if enabled > 1 {
+        return -1;
+    }
+    check_mutex_order(current_process().unwrap().getpid(), enabled == 1);
+    0
+}
+
+pub fn sys_condvar_create(_arg: usize) -> isize {
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    alloc_id(&mut inner.condvar_list, Arc::new(Condvar::new()))
+}
+
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    let Some(condvar) = get_condvar(condvar_id) else {
+        return -1;
+    };
+    condvar.signal();
+    0
+}
+
+/// Physical address of the futex word at `addr` of current task, None if it is not aligned.
+/// A page shared copy-on-write is copied first, so it is the frame the task writes to.
+fn futex_pa(addr: usize) -> Option<usize> {
+    if addr == 0 || !addr.is_multiple_of(core::mem::size_of::<u32>()) {
+        return None;
+    }
+    let word = translated_refmut(current_user_token(), addr as *mut u32);
+    Some(word as *mut u32 as usize)
+}
+
+/// Block until [`sys_futex_wake`] on the same word, which may be mapped at another address,
+/// if the u32 at `addr` still holds `expected`. Return -1 at once if it does not.
+pub fn sys_futex_wait(addr: usize, expected: u32) -> isize {
+    let Some(pa) = futex_pa(addr) else {
+        return -1;
+    };
+    if futex_wait(pa, expected) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Wake up to `n` tasks waiting on the u32 at `addr`, return how many
+pub fn sys_futex_wake(addr: usize, n: usize) -> isize {
+    let Some(pa) = futex_pa(addr) else {
+        return -1;
+    };
+    futex_wake(pa, n) as isize
+}
+
+/// The caller must hold `mutex_id`, it holds it again when this returns
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    let (Some(condvar), Some(mutex)) = (get_condvar(condvar_id), get_mutex(mutex_id)) else {
+        return -1;
+    };
+    condvar.wait(mutex);
+    0
+}
@@ -0,0 +1,148 @@
+//! Checkpoint of a task to a file and restore from it
+//!
+//! A checkpoint holds the user registers and the pages of writable user areas
+//! holding a frame, which is enough to put the task back where it was as long
+//! as the task restoring it has the same areas, i.e. runs the same program.
+//! Read-only pages are taken as they are in the restoring task. Open files,
+//! mutexes and the rest of the task are left as they are. Only a process running
+//! no thread but its first one can be saved or restored.
+//!
+//! The file is a sequence of `usize` words:
+//! - [`CHECKPOINT_MAGIC`], the number of areas, the number of pages,
+//!   `heap_bottom` and `program_brk`
+//! - start vpn, end vpn and permission of each area, see [`MemorySet::user_layout`]
+//! - registers `x0`-`x31` and `sepc`
+//! - the vpn of each page followed by its bytes
+
+use super::current_process;
+use crate::config::{PAGE_SIZE, TRAP_CONTEXT};
+use crate::fs::{open_file, OSInode, OpenFlags};
+use crate::mm::{MemorySet, VirtAddr, VirtPageNum};
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+/// "ckpt", the first word of a checkpoint
+const CHECKPOINT_MAGIC: usize = 0x7470_6b63;
+const HEADER_WORDS: usize = 5;
+/// `x0`-`x31` and `sepc`
+const REGISTER_WORDS: usize = 33;
+
+fn to_bytes(words: &[usize]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+fn read_words(file: &OSInode, count: usize) -> Option<Vec<usize>> {
+    let mut bytes = vec![0u8; count * size_of::<usize>()];
+    if !file.read_exact(&mut bytes) {
+        return None;
+    }
+    let (words, _) = bytes.as_chunks::<{ size_of::<usize>() }>();
+    Some(
+        words
+            .iter()
+            .map(|word| usize::from_le_bytes(*word))
+            .collect(),
+    )
+}
+
+/// Save current task to the file at `path`, which is created or truncated.
+/// Return 0, or -1 if the file can not be written.
+///
+/// Reclaimed pages of file mappings have been read back before any syscall,
+/// so every page that is not a lazy one left untouched is saved.
+pub fn checkpoint(path: &str) -> isize {
+    let Some(file) = open_file(path, OpenFlags::CREATE | OpenFlags::WRONLY) else { return -1; };
+    let task = current_process().unwrap();
+    let inner = task.inner_exclusive_access();
+    if !inner.is_single_threaded() {
+        return -1;
+    }
+    let layout = inner.memory_set.user_layout();
+    let pages = inner.memory_set.writable_user_pages();
+    let mut words = vec![
+        CHECKPOINT_MAGIC,
+        layout.len(),
+        pages.len(),
+        inner.heap_bottom,
+        inner.program_brk,
+    ];
+    for (start, end, perm) in layout {
+        words.extend([start, end, perm as usize]);
+    }
+    let trap_cx = inner.get_trap_cx();
+    words.extend(trap_cx.x);
+    // past the ecall already, so a restore returns from `sys_checkpoint`
+    words.push(trap_cx.sepc);
+    if !file.write_all(&to_bytes(&words)) {
+        return -1;
+    }
+    for vpn in pages {
+        let ppn = inner.memory_set.translate(vpn).unwrap().ppn();
+        if !file.write_all(&vpn.0.to_le_bytes()) || !file.write_all(ppn.get_bytes_array()) {
+            return -1;
+        }
+    }
+    0
+}
+
+/// Put current task back to the checkpoint in the file at `path`, where `sys_checkpoint`
+/// then returns 1. Return -1 with current task left as it was if the file is not
+/// a checkpoint, the user areas or the heap of current task differ from those saved,
+/// or no frame is left.
+pub fn restore(path: &str) -> isize {
+    let Some(file) = open_file(path, OpenFlags::RDONLY) else { return -1; };
+    let Some(header) = read_words(&file, HEADER_WORDS) else { return -1; };
+    let &[magic, area_count, page_count, heap_bottom, brk] = &header[..] else { return -1; };
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let layout = inner.memory_set.user_layout();
+    if !inner.is_single_threaded()
+        || magic != CHECKPOINT_MAGIC
+        || area_count != layout.len()
+        || heap_bottom != inner.heap_bottom
+    {
+        return -1;
+    }
+    let Some(areas) = read_words(&file, area_count * 3) else { return -1; };
+    let (areas, _) = areas.as_chunks::<3>();
+    let same_layout = areas
+        .iter()
+        .zip(layout)
+        .all(|(area, (start, end, perm))| *area == [start, end, perm as usize]);
+    if !same_layout {
+        return -1;
+    }
+    let Some(registers) = read_words(&file, REGISTER_WORDS) else { return -1; };
+    // the pages go into a copy, so a checkpoint cut short leaves current task alone
+    let mut memory_set = MemorySet::from_existed_user(&mut inner.memory_set);
+    let mut saved = BTreeSet::new();
+    let mut page = vec![0u8; PAGE_SIZE];
+    for _ in 0..page_count {
+        let Some(vpn) = read_words(&file, 1) else { return -1; };
+        let vpn = VirtPageNum(vpn[0]);
+        if !file.read_exact(&mut page) || !memory_set.load_page(vpn, &page) {
+            return -1;
+        }
+        saved.insert(vpn);
+    }
+    // lazy pages first touched after the checkpoint
+    for vpn in memory_set.writable_user_pages() {
+        if !saved.contains(&vpn) && !memory_set.forget_page(vpn) {
+            return -1;
+        }
+    }
+    let trap_cx_ppn = memory_set
+        .translate(VirtAddr::from(TRAP_CONTEXT).into())
+        .unwrap()
+        .ppn();
+    inner.memory_set = memory_set;
+    inner.trap_cx_ppn = trap_cx_ppn;
+    inner.program_brk = brk;
+    inner.update_max_rss();
+    let trap_cx = inner.get_trap_cx();
+    trap_cx.x.copy_from_slice(&registers[..32]);
+    trap_cx.sepc = registers[32];
+    1
+}
@@ -6,7 +6,8 @@
 
 use super::TaskControlBlock;
 use crate::sync::UPSafeCell;
-use alloc::collections::VecDeque;
+use crate::timer::get_time_ms;
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::sync::Arc;
 use lazy_static::*;
 
@@ -14,8 +15,14 @@ pub struct TaskManager {
     ready_queue: VecDeque<Arc<TaskControlBlock>>,
 }
 
-// YOUR JOB: FIFO->Stride
-/// A simple FIFO scheduler.
+/// Whether pass `a` is behind pass `b`. Passes only grow and may wrap around,
+/// but the passes of live tasks stay far less than half the range apart,
+/// so the sign of the wrapped difference tells which one is behind.
+fn pass_before(a: usize, b: usize) -> bool {
+    (a.wrapping_sub(b) as isize) < 0
+}
+
+/// A stride scheduler, with deadline tasks going first.
 impl TaskManager {
     pub fn new() -> Self {
         Self {
@@ -27,8 +34,44 @@ impl TaskManager {
         self.ready_queue.push_back(task);
     }
     /// Take a process out of the ready queue
+    ///
+    /// The deadline task with budget left and the earliest deadline is picked first,
+    /// then the normal task with the smallest pass, which then moves on by its stride.
+    /// Ties go to the task that has waited longest.
+    /// Deadline tasks out of budget wait in the queue for their next period.
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        self.ready_queue.pop_front()
+        let now = get_time_ms();
+        let mut earliest: Option<(usize, usize)> = None;
+        for (idx, task) in self.ready_queue.iter().enumerate() {
+            let mut inner = task.inner_exclusive_access();
+            if let Some(sched) = inner.deadline_sched.as_mut() {
+                sched.replenish(now);
+                if sched.has_budget()
+                    && earliest.is_none_or(|(_, deadline)| sched.deadline_ms < deadline)
+                {
+                    earliest = Some((idx, sched.deadline_ms));
+                }
+            }
+        }
+        if let Some((idx, _)) = earliest {
+            return self.ready_queue.remove(idx);
+        }
+        let mut smallest: Option<(usize, usize)> = None;
+        for (idx, task) in self.ready_queue.iter().enumerate() {
+            let inner = task.inner_exclusive_access();
+            if inner.deadline_sched.is_none()
+                && smallest.is_none_or(|(_, pass)| pass_before(inner.pass, pass))
+            {
+                smallest = Some((idx, inner.pass));
+            }
+        }
+        let task = self.ready_queue.remove(smallest?.0)?;
+        // a task holding a mutex others wait on runs at their priority if it is higher
+        let stride = task.effective_stride();
+        let mut inner = task.inner_exclusive_access();
+        inner.pass = inner.pass.wrapping_add(stride);
+        drop(inner);
+        Some(task)
     }
 }
 
@@ -36,6 +79,9 @@ lazy_static! {
     /// TASK_MANAGER instance through lazy_static!
     pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
         unsafe { UPSafeCell::new(TaskManager::new()) };
+    /// All processes which have not been reaped yet, indexed by pid
+    pub static ref PID2TCB: UPSafeCell<BTreeMap<usize, Arc<TaskControlBlock>>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
 }
 
 pub fn add_task(task: Arc<TaskControlBlock>) {
@@ -45,3 +91,13 @@ pub fn add_task(task: Arc<TaskControlBlock>) {
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     TASK_MANAGER.exclusive_access().fetch()
 }
+
+pub fn insert_into_pid2task(pid: usize, task: Arc<TaskControlBlock>) {
+    PID2TCB.exclusive_access().insert(pid, task);
+}
+
+pub fn remove_from_pid2task(pid: usize) {
+    if PID2TCB.exclusive_access().remove(&pid).is_none() {
+        panic!("cannot find pid {} in pid2task!", pid);
+    }
+}
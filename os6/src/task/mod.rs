@@ -9,29 +9,64 @@
 //! Be careful when you see [`__switch`]. Control flow around this function
 //! might not be what you expect.
 
+mod checkpoint;
 mod context;
 mod manager;
 mod pid;
 mod processor;
+mod reclaim;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
 
 use crate::fs::{open_file, OpenFlags};
+use crate::sync::forget_mutex_order;
 pub use crate::syscall::process::TaskInfo;
 use alloc::sync::Arc;
 use lazy_static::*;
 use manager::fetch_task;
 use switch::__switch;
-pub use task::{TaskControlBlock, TaskStatus};
+pub use task::{
+    DeadlineSched, FileMapping, SwitchReason, SwitchRecord, TaskControlBlock, TaskStatus,
+};
 
+pub use checkpoint::{checkpoint, restore};
 pub use context::TaskContext;
-pub use manager::add_task;
+pub use manager::{add_task, insert_into_pid2task, remove_from_pid2task, PID2TCB};
 pub use pid::{pid_alloc, KernelStack, PidHandle};
 pub use processor::*;
+pub use reclaim::{reclaim_if_low, reclaim_stats, set_reclaim_watermark, ReclaimStats};
+
+/// Make current task blocked and switch to the next task
+///
+/// The task is not put back into the ready queue,
+/// someone has to call [`wakeup_task`] on it later.
+pub fn block_current_and_run_next(reason: SwitchReason) {
+    let task = take_current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    task_inner.task_status = TaskStatus::Blocking;
+    task_inner.record_switch(reason);
+    drop(task_inner);
+    schedule(task_cx_ptr);
+}
+
+/// Put a blocked task back into the ready queue
+///
+/// Waking up a task which is not blocked is a no-op, so a task waiting on
+/// several events (e.g. some condition or a timeout) is only woken up once.
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    let mut task_inner = task.inner_exclusive_access();
+    if task_inner.task_status != TaskStatus::Blocking {
+        return;
+    }
+    task_inner.task_status = TaskStatus::Ready;
+    drop(task_inner);
+    add_task(task);
+}
 
 /// Make current task suspended and switch to the next task
-pub fn suspend_current_and_run_next() {
+pub fn suspend_current_and_run_next(reason: SwitchReason) {
     // There must be an application running.
     let task = take_current_task().unwrap();
 
@@ -40,6 +75,7 @@ pub fn suspend_current_and_run_next() {
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
     // Change status to Ready
     task_inner.task_status = TaskStatus::Ready;
+    task_inner.record_switch(reason);
     drop(task_inner);
     // ---- release current PCB
 
@@ -49,34 +85,81 @@ pub fn suspend_current_and_run_next() {
     schedule(task_cx_ptr);
 }
 
-/// Exit current task, recycle process resources and switch to the next task
+/// Signal of a task killed for an illegal instruction
+pub const SIGILL: i32 = 4;
+/// Signal of a task killed for a bad memory access
+pub const SIGSEGV: i32 = 11;
+
+/// Exit current task killed by `signal` rather than by itself,
+/// its exit code is the negated signal
+pub fn kill_current_and_run_next(signal: i32) {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .term_signal = signal;
+    exit_current_and_run_next(-signal);
+}
+
+/// Exit current task and switch to the next task. The resources of its process
+/// are recycled once the last thread of the process exits, the first one or not.
 pub fn exit_current_and_run_next(exit_code: i32) {
     // take from Processor
     let task = take_current_task().unwrap();
+    let process = task.process();
     // **** access current TCB exclusively
     let mut inner = task.inner_exclusive_access();
-    // Change status to Zombie
-    inner.task_status = TaskStatus::Zombie;
     // Record exit code
     inner.exit_code = exit_code;
-    // do not move to its parent but under initproc
+    // a task gone cannot hand its mutexes on, its waiters have nothing to lend it
+    inner.donors.clear();
+    let slot = inner.slot;
+    // the first thread only becomes a zombie with its process, nobody wakes it up till then
+    inner.task_status = if slot == 0 {
+        TaskStatus::Blocking
+    } else {
+        TaskStatus::Zombie
+    };
+    drop(inner);
+    // **** release current TCB
+    // ---- access process PCB exclusively
+    let mut inner = process.inner_exclusive_access();
+    if slot != 0 {
+        // its kernel stack is freed when the thread is reaped, it is still running on it
+        inner.free_thread_areas(slot);
+    }
+    inner.live_threads -= 1;
+    forget_mutex_order(process.getpid(), task.gettid(), inner.live_threads == 0);
+    if inner.live_threads == 0 {
+        // the address space it may run in is not its own to recycle
+        inner.release_vfork_parent();
+        // Change status to Zombie
+        inner.task_status = TaskStatus::Zombie;
+        // do not move to its parent but under initproc
 
-    // ++++++ access initproc TCB exclusively
-    {
-        let mut initproc_inner = INITPROC.inner_exclusive_access();
-        for child in inner.children.iter() {
-            child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
-            initproc_inner.children.push(child.clone());
+        // ++++++ access initproc TCB exclusively
+        {
+            let mut initproc_inner = INITPROC.inner_exclusive_access();
+            for child in inner.children.iter() {
+                child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
+                initproc_inner.children.push(child.clone());
+            }
         }
-    }
-    // ++++++ release parent PCB
+        // ++++++ release parent PCB
 
-    inner.children.clear();
-    // deallocate user space
-    inner.memory_set.recycle_data_pages();
+        inner.children.clear();
+        inner.write_back_file_mappings();
+        // close its files now rather than when it is reaped,
+        // so the other end of a pipe sees this one gone
+        inner.fd_table.clear();
+        inner.cwd = None;
+        inner.root = None;
+        // deallocate user space
+        inner.memory_set.recycle_data_pages();
+    }
     drop(inner);
-    // **** release current PCB
+    // ---- release process PCB
     // drop task manually to maintain rc correctly
+    drop(process);
     drop(task);
     // we do not have to save task context
     let mut _unused = TaskContext::zero_init();
@@ -91,10 +174,11 @@ lazy_static! {
     pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new({
         let inode = open_file("ch6b_initproc", OpenFlags::RDONLY).unwrap();
         let v = inode.read_all();
-        TaskControlBlock::new(v.as_slice())
+        TaskControlBlock::new("ch6b_initproc", v.as_slice())
     });
 }
 
 pub fn add_initproc() {
+    insert_into_pid2task(INITPROC.getpid(), INITPROC.clone());
     add_task(INITPROC.clone());
 }
@@ -5,15 +5,16 @@
 //! and the replacement and transfer of control flow of different applications are executed.
 
 
-use super::{__switch, TaskInfo};
+use super::{__switch, reclaim_if_low, TaskInfo};
 use super::{fetch_task, TaskStatus};
-use super::{TaskContext, TaskControlBlock};
-use crate::config::PAGE_SIZE;
-use crate::mm::{VirtAddr, MapPermission};
+use super::{FileMapping, TaskContext, TaskControlBlock};
+use crate::config::{MAX_SYSCALL_NUM, PAGE_SIZE, USER_HEAP_SIZE};
+use crate::mm::{shm_attachment, VirtAddr, VirtPageNum, MapPermission};
 use crate::sync::UPSafeCell;
-use crate::timer::get_time_us;
+use crate::timer::{check_timer, get_time_us};
 use crate::trap::TrapContext;
 use alloc::sync::Arc;
+use easy_fs::Inode;
 use lazy_static::*;
 
 /// Processor management structure
@@ -53,6 +54,7 @@ lazy_static! {
 /// and switch the process through __switch
 pub fn run_tasks() {
     loop {
+        reclaim_if_low();
         let mut processor = PROCESSOR.exclusive_access();
         if let Some(task) = fetch_task() {
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
@@ -60,14 +62,30 @@ pub fn run_tasks() {
             let mut task_inner = task.inner_exclusive_access();
             let next_task_cx_ptr = &task_inner.task_cx as *const TaskContext;
             task_inner.task_status = TaskStatus::Running;
+            task_inner.schedule_count += 1;
+            // a task that gave up the CPU early starts over with a full slice
+            task_inner.slice_left = task_inner.timeslice;
             drop(task_inner);
             // release coming task TCB manually
-            processor.current = Some(task);
+            processor.current = Some(task.clone());
             // release processor manually
             drop(processor);
+            let switch_in_time = get_time_us();
             unsafe {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
+            // back from the task, charge it for the time it ran
+            let run_time = get_time_us() - switch_in_time;
+            let mut task_inner = task.inner_exclusive_access();
+            task_inner.cpu_time += run_time;
+            if let Some(sched) = task_inner.deadline_sched.as_mut() {
+                sched.used_us += run_time;
+            }
+        } else {
+            // timer interrupts are not taken in kernel mode,
+            // so blocked tasks have to be woken up from here when nothing is ready
+            drop(processor);
+            check_timer();
         }
     }
 }
@@ -82,9 +100,15 @@ pub fn current_task() -> Option<Arc<TaskControlBlock>> {
     PROCESSOR.exclusive_access().current()
 }
 
+/// Get the process of current task, whose address space and files it uses,
+/// see [`TaskControlBlock::process`]
+pub fn current_process() -> Option<Arc<TaskControlBlock>> {
+    current_task().map(|task| task.process())
+}
+
 /// Get token of the address space of current task
 pub fn current_user_token() -> usize {
-    let task = current_task().unwrap();
+    let task = current_process().unwrap();
     let token = task.inner_exclusive_access().get_user_token();
     token
 }
@@ -97,6 +121,14 @@ pub fn current_trap_cx() -> &'static mut TrapContext {
         .get_trap_cx()
 }
 
+/// Get where the trap context of current task is mapped in user space
+pub fn current_trap_cx_va() -> usize {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .get_trap_cx_va()
+}
+
 /// Return to idle control flow for new scheduling
 pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
     let mut processor = PROCESSOR.exclusive_access();
@@ -107,32 +139,211 @@ pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
     }
 }
 
+/// Charge the current task one timer tick, return whether its time slice is used up
+pub fn tick_current_task() -> bool {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.slice_left = inner.slice_left.saturating_sub(1);
+    inner.slice_left == 0
+}
+
+/// Current task is about to return to user mode
+pub fn enter_user() {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().user_since = get_time_us();
+}
 
-pub fn add_syscall_times(syscall_id: usize) {
+/// Current task trapped into the kernel, charge it for the time it ran in user mode
+pub fn leave_user() {
     let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.user_time += get_time_us() - inner.user_since;
+}
+
+pub fn add_syscall_times(syscall_id: usize) {
+    let task = current_process().unwrap();
     task.inner_exclusive_access().syscall_times[syscall_id] += 1;
 }
 
 pub fn get_current_task_info() -> TaskInfo {
-    let task = current_task().unwrap();
+    let task = current_process().unwrap();
     let inner = task.inner_exclusive_access();
     TaskInfo {
         syscall_times: inner.syscall_times,
         status: inner.task_status,
         time: (get_time_us() - inner.start_time) / 1000,
+        schedule_count: inner.schedule_count,
     }
 }
 
+/// Zero the syscall counters of current task and count `TaskInfo::time` from now on.
+/// Both happen in one access to the task, which [`add_syscall_times`] takes as well,
+/// so a syscall is counted either before the reset or after it.
+pub fn reset_syscall_stats() {
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.syscall_times = [0; MAX_SYSCALL_NUM];
+    inner.start_time = get_time_us();
+}
+
+/// Flag of [`mmap`] besides the permission: map with megapages if `start` and `len`
+/// are megapage aligned and there are enough contiguous frames, or with pages if not
+const MMAP_HUGE: usize = 1 << 3;
+/// Flag of [`mmap`]: like [`MMAP_HUGE`], but fail instead of mapping with pages
+const MMAP_HUGE_ONLY: usize = 1 << 4;
+
 pub fn mmap(start: usize, len: usize, port: usize) -> isize {
-    if start & (PAGE_SIZE - 1) != 0 || port & 0x7 == 0 || port & !0x7 != 0 {
+    if start & (PAGE_SIZE - 1) != 0
+        || port & 0x7 == 0
+        || port & !(0x7 | MMAP_HUGE | MMAP_HUGE_ONLY) != 0
+    {
         return -1;
     }
-    let task = current_task().unwrap();
-    if !task.inner_exclusive_access().memory_set.insert_framed_area(
-        VirtAddr(start),
-        VirtAddr(start + len),
-        MapPermission::from_bits_truncate((port << 1) as u8) | MapPermission::U,
-    ) {
+    let (start_va, end_va) = (VirtAddr(start), VirtAddr(start + len));
+    let permission =
+        MapPermission::from_bits_truncate(((port & 0x7) << 1) as u8) | MapPermission::U;
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if port & (MMAP_HUGE | MMAP_HUGE_ONLY) != 0
+        && inner
+            .memory_set
+            .insert_huge_area(start_va, end_va, permission)
+    {
+        inner.update_max_rss();
+        return 0;
+    }
+    // frames are only taken on the first touch of each page
+    if port & MMAP_HUGE_ONLY != 0
+        || !inner
+            .memory_set
+            .insert_lazy_area(start_va, end_va, permission)
+    {
+        return -1;
+    }
+    0
+}
+
+/// Map `len` bytes of `inode` from `offset` on at `start`, with the permission in `port`
+/// as for [`mmap`]. A page is read from the file on its first touch, reading as zeros
+/// past the end of the file, and what is written to it goes back to the file
+/// on msync, munmap, exec or exit, as far as the file reaches.
+pub fn mmap_file(start: usize, len: usize, port: usize, inode: Arc<Inode>, offset: usize) -> isize {
+    if start & (PAGE_SIZE - 1) != 0
+        || offset & (PAGE_SIZE - 1) != 0
+        || port & 0x7 == 0
+        || port & !0x7 != 0
+    {
+        return -1;
+    }
+    let permission =
+        MapPermission::from_bits_truncate(((port & 0x7) << 1) as u8) | MapPermission::U;
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if !inner
+        .memory_set
+        .insert_lazy_area(VirtAddr(start), VirtAddr(start + len), permission)
+    {
+        return -1;
+    }
+    inner.file_mappings.push(FileMapping {
+        start,
+        len,
+        inode,
+        offset,
+    });
+    0
+}
+
+/// Move the program break of current task by `increment` bytes, return the old one.
+/// The heap grows by lazy pages and gives the frames of the pages it shrinks off back.
+/// Fail if the break would leave `[heap_bottom, heap_bottom + USER_HEAP_SIZE]`
+/// or a page it grows into is in use.
+pub fn sbrk(increment: isize) -> isize {
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let old_brk = inner.program_brk;
+    let Some(new_brk) = old_brk.checked_add_signed(increment) else { return -1; };
+    if new_brk < inner.heap_bottom || new_brk > inner.heap_bottom + USER_HEAP_SIZE {
+        return -1;
+    }
+    let (old_end, new_end) = (VirtAddr(old_brk).ceil(), VirtAddr(new_brk).ceil());
+    if new_end > old_end
+        && !inner.memory_set.insert_lazy_area(
+            old_end.into(),
+            new_end.into(),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        )
+    {
+        return -1;
+    }
+    if new_end < old_end {
+        inner.memory_set.unmap(new_end.into(), old_end.into());
+    }
+    inner.program_brk = new_brk;
+    old_brk as isize
+}
+
+/// Read back the reclaimed page of a file mapping at `va` after a page fault on it,
+/// false if the fault is not on such a page
+pub fn fault_in_file_mapping(va: usize) -> bool {
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.fault_in(VirtAddr(va).floor())
+}
+
+/// Read back the reclaimed page at `vpn` of the address space `token` for the kernel,
+/// false if it is not such a page or `token` is not the address space of current task
+pub fn fault_in_reclaimed(token: usize, vpn: VirtPageNum) -> bool {
+    let Some(task) = current_process() else { return false; };
+    let mut inner = task.inner_exclusive_access();
+    inner.get_user_token() == token && inner.fault_in(vpn)
+}
+
+/// Give the page at `vpn` of the address space `token` a frame of its own if it is shared
+/// copy-on-write, false if it is not such a page, no frame is left,
+/// or `token` is not the address space of current task
+pub fn copy_on_write(token: usize, vpn: VirtPageNum) -> bool {
+    let Some(task) = current_process() else { return false; };
+    let mut inner = task.inner_exclusive_access();
+    inner.get_user_token() == token && inner.memory_set.copy_on_write(vpn)
+}
+
+/// Give the page at `vpn` of the address space `token` a frame if it is in a lazy area
+/// and not touched yet, false if it is not such a page, its area does not allow `access`,
+/// no frame is left, or `token` is not the address space of current task
+pub fn fault_in_lazy(token: usize, vpn: VirtPageNum, access: MapPermission) -> bool {
+    let Some(task) = current_process() else { return false; };
+    let mut inner = task.inner_exclusive_access();
+    if inner.get_user_token() != token || !inner.memory_set.fault_in_lazy(vpn, access) {
+        return false;
+    }
+    inner.load_file_page(vpn);
+    inner.update_max_rss();
+    true
+}
+
+/// The signal current process is to be killed by, 0 for none, see `sys_killpg`
+pub fn pending_signal() -> i32 {
+    let Some(process) = current_process() else { return 0; };
+    let signal = process.inner_exclusive_access().pending_signal;
+    signal
+}
+
+/// Attach the shared memory segment `id` to current task, return the address it is at
+pub fn shmat(id: usize) -> isize {
+    let Some(attachment) = shm_attachment(id) else { return -1; };
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let Some(start) = inner.memory_set.attach_shm(attachment) else { return -1; };
+    inner.update_max_rss();
+    start.0 as isize
+}
+
+/// Detach the shared memory segment attached at `start` from current task
+pub fn shmdt(start: usize) -> isize {
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if !inner.memory_set.detach_shm(VirtAddr(start)) {
         return -1;
     }
     0
@@ -142,11 +353,22 @@ pub fn munmap(start: usize, len: usize) -> isize {
     if start & (PAGE_SIZE - 1) != 0 {
         return -1;
     }
-    let task = current_task().unwrap();
-    if !task
-        .inner_exclusive_access()
+    let task = current_process().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    // file mappings touched are written back and no longer backed by the file
+    let token = inner.get_user_token();
+    inner.file_mappings.retain(|mapping| {
+        if mapping.overlaps(start, start + len) {
+            mapping.write_back(token);
+            false
+        } else {
+            true
+        }
+    });
+    if inner
         .memory_set
         .unmap(VirtAddr(start), VirtAddr(start + len))
+        .is_none()
     {
         return -1;
     }
@@ -0,0 +1,85 @@
+//! Proactive reclaim of file mapped pages when free frames run low
+//!
+//! Once the free frames drop below the low watermark set by
+//! `sys_set_reclaim_watermark`, the scheduler loop writes pages of file
+//! mappings back to their files and frees them, until the free frames are
+//! [`RECLAIM_GAP`] above the watermark. A reclaimed page is read back from
+//! the file when it is touched again.
+
+use super::PID2TCB;
+use crate::config::RECLAIM_GAP;
+use crate::mm::{frame_free_count, VirtPageNum};
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// Reclaim statistics for `sys_get_reclaim_stats`
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ReclaimStats {
+    /// frames freed by reclaim so far
+    pub reclaimed: usize,
+    pub free_frames: usize,
+    /// 0 if reclaim is off
+    pub low_watermark: usize,
+    pub high_watermark: usize,
+}
+
+struct Reclaimer {
+    low_watermark: usize,
+    reclaimed: usize,
+}
+
+lazy_static! {
+    static ref RECLAIMER: UPSafeCell<Reclaimer> = unsafe {
+        UPSafeCell::new(Reclaimer {
+            low_watermark: 0,
+            reclaimed: 0,
+        })
+    };
+}
+
+/// Start reclaiming when free frames drop below `free_frames`, 0 turns reclaim off
+pub fn set_reclaim_watermark(free_frames: usize) {
+    RECLAIMER.exclusive_access().low_watermark = free_frames;
+}
+
+pub fn reclaim_stats() -> ReclaimStats {
+    let reclaimer = RECLAIMER.exclusive_access();
+    ReclaimStats {
+        reclaimed: reclaimer.reclaimed,
+        free_frames: frame_free_count(),
+        low_watermark: reclaimer.low_watermark,
+        high_watermark: if reclaimer.low_watermark == 0 {
+            0
+        } else {
+            reclaimer.low_watermark + RECLAIM_GAP
+        },
+    }
+}
+
+/// Reclaim file mapped pages of all tasks if free frames are below the low watermark,
+/// called from the scheduler loop while no task is running
+pub fn reclaim_if_low() {
+    let mut reclaimer = RECLAIMER.exclusive_access();
+    if reclaimer.low_watermark == 0 || frame_free_count() >= reclaimer.low_watermark {
+        return;
+    }
+    let high_watermark = reclaimer.low_watermark + RECLAIM_GAP;
+    for task in PID2TCB.exclusive_access().values() {
+        let mut inner = task.inner_exclusive_access();
+        let pages: Vec<VirtPageNum> = inner
+            .file_mappings
+            .iter()
+            .flat_map(|mapping| mapping.pages())
+            .collect();
+        for vpn in pages {
+            if frame_free_count() >= high_watermark {
+                return;
+            }
+            if inner.reclaim_page(vpn) {
+                reclaimer.reclaimed += 1;
+            }
+        }
+    }
+}
@@ -1,15 +1,22 @@
 //! Types related to task management & Functions for completely changing TCB
 
 use super::TaskContext;
-use super::{pid_alloc, KernelStack, PidHandle};
-use crate::config::{BIG_STRIDE, MAX_SYSCALL_NUM, TRAP_CONTEXT};
-use crate::fs::{File, Stdin, Stdout};
-use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
-use crate::sync::UPSafeCell;
+use super::{pid_alloc, wakeup_task, KernelStack, PidHandle};
+use crate::config::{BIG_STRIDE, CLOCK_PAGE, DEFAULT_TIMESLICE, MAX_SYSCALL_NUM, MAX_THREADS};
+use crate::config::{PAGE_SIZE, TRAP_CONTEXT, USER_STACK_SIZE};
+use crate::fs::{File, OSInode, Stdin, Stdout};
+use crate::mm::translated_read_buffer;
+use crate::mm::{translated_kernel_buffer, MemorySet, PageTable, PhysPageNum, KERNEL_SPACE};
+use crate::mm::{MapPermission, VPNRange, VirtAddr, VirtPageNum};
+use crate::sync::{check_mutex_order, mutex_order_checked};
+use crate::sync::{Condvar, Mutex, UPRefMut, UPSafeCell};
+use crate::timer::{get_time_ms, get_time_us};
 use crate::trap::{trap_handler, TrapContext};
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
-use core::cell::RefMut;
+use easy_fs::Inode;
 
 /// Task control block structure
 ///
@@ -47,11 +54,76 @@ pub struct TaskControlBlockInner {
     pub children: Vec<Arc<TaskControlBlock>>,
     /// It is set when active exit or execution error occurs
     pub exit_code: i32,
+    /// Signal of the fault that killed it, 0 if it exited by itself
+    pub term_signal: i32,
+    /// Process group, see `sys_setpgid`. Unused in a thread other than the first.
+    pub pgid: usize,
+    /// Signal the process is to be killed by on its next return to user mode,
+    /// 0 for none, see `sys_killpg`
+    pub pending_signal: i32,
     pub syscall_times: [u32; MAX_SYSCALL_NUM],
+    /// Where `TaskInfo::time` counts from, moved by `sys_reset_syscall_stats`
     pub start_time: usize,
+    /// Times the task was switched in to run, not touched by `sys_reset_syscall_stats`
+    pub schedule_count: usize,
     pub stride: usize,
     pub pass: usize,
     pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    /// Fds to be closed on exec
+    pub cloexec_fds: BTreeSet<usize>,
+    /// Directory relative paths start at, None for the root. Kept open,
+    /// so that it is not freed while it is someone's working directory.
+    pub cwd: Option<Arc<OSInode>>,
+    /// Directory taken as '/' by the paths of the process, None for the root
+    /// of all inodes. Set once by `sys_chroot` and kept by its children.
+    pub root: Option<Arc<OSInode>>,
+    /// Name of the app the process is running
+    pub name: String,
+    /// Time spent running on the CPU, in microseconds
+    pub cpu_time: usize,
+    /// The part of `cpu_time` spent in user mode, in microseconds
+    pub user_time: usize,
+    /// When the task last returned to user mode
+    pub user_since: usize,
+    /// Set if the task is in the deadline scheduling class
+    pub deadline_sched: Option<DeadlineSched>,
+    /// Timer ticks the task runs each time it is switched in
+    pub timeslice: usize,
+    /// Timer ticks left before the task is preempted, refilled each time it is switched in
+    pub slice_left: usize,
+    /// The last [`SWITCH_HISTORY_LEN`] times the task was switched out, oldest first
+    pub switch_history: VecDeque<SwitchRecord>,
+    /// User memory to be written back to files
+    pub file_mappings: Vec<FileMapping>,
+    /// Mutexes by id, shared with the processes forked from this one
+    pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
+    /// Condition variables by id, shared like `mutex_list`
+    pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    /// The most user pages resident at once, kept across exec
+    pub max_rss: usize,
+    /// Tasks waiting on the blocking mutexes it holds, each with the id of its mutex.
+    /// They lend it their priority, see [`TaskControlBlock::effective_stride`].
+    pub donors: Vec<(usize, Arc<TaskControlBlock>)>,
+    /// Start of the heap, right above the user stack
+    pub heap_bottom: usize,
+    /// End of the heap, moved by `sys_sbrk`
+    pub program_brk: usize,
+    /// The process a thread created by `sys_thread_create` belongs to, None for
+    /// the first thread of a process. A thread runs in the address space of its process
+    /// and uses its files, mutexes and heap, its own are left empty.
+    pub process: Option<Weak<TaskControlBlock>>,
+    /// Where a thread keeps its trap context and user stack in its process,
+    /// see [`thread_trap_cx`], 0 for the first thread
+    pub slot: usize,
+    /// The other threads of a process at `slot - 1`, kept after they exit until
+    /// `sys_waittid` takes them, as a thread cannot free the kernel stack it exits on
+    pub threads: Vec<Option<Arc<TaskControlBlock>>>,
+    /// Threads of a process not exited yet, the first one included.
+    /// The address space and files of the process go with the last of them.
+    pub live_threads: usize,
+    /// The process blocked in `sys_vfork` till this child of it execs or exits,
+    /// whose address space the child runs in meanwhile
+    pub vfork_parent: Option<Arc<TaskControlBlock>>,
 }
 
 /// Simple access to its internal fields
@@ -67,12 +139,138 @@ impl TaskControlBlockInner {
     pub fn get_user_token(&self) -> usize {
         self.memory_set.token()
     }
+    /// Where the trap context is mapped in the address space the task runs in
+    pub fn get_trap_cx_va(&self) -> usize {
+        if self.slot == 0 {
+            TRAP_CONTEXT
+        } else {
+            thread_trap_cx(self.slot)
+        }
+    }
+    /// Whether the process runs no thread but its first one,
+    /// so its address space can be copied or replaced as a whole
+    pub fn is_single_threaded(&self) -> bool {
+        self.live_threads == 1
+    }
+    /// Unmap the trap context and user stack of the thread in `slot` of the process
+    pub fn free_thread_areas(&mut self, slot: usize) {
+        let (stack_bottom, _) = thread_user_stack(slot);
+        self.memory_set
+            .remove_area_with_start_vpn(VirtAddr::from(thread_trap_cx(slot)).into());
+        self.memory_set
+            .remove_area_with_start_vpn(VirtAddr::from(stack_bottom).into());
+    }
     fn get_status(&self) -> TaskStatus {
         self.task_status
     }
     pub fn is_zombie(&self) -> bool {
         self.get_status() == TaskStatus::Zombie
     }
+    /// The status word wait hands back, the signal that killed it in the low byte,
+    /// or the exit code it gave above that
+    pub fn wait_status(&self) -> i32 {
+        if self.term_signal != 0 {
+            self.term_signal & 0x7f
+        } else {
+            self.exit_code << 8
+        }
+    }
+    /// Write back and forget all file mappings, before the address space goes away
+    pub fn write_back_file_mappings(&mut self) {
+        let token = self.get_user_token();
+        for mapping in core::mem::take(&mut self.file_mappings) {
+            mapping.write_back(token);
+        }
+    }
+    /// Write back a page of a file mapping and free its frame, false if it has none
+    pub fn reclaim_page(&mut self, vpn: VirtPageNum) -> bool {
+        let token = self.get_user_token();
+        let Some(mapping) = self.file_mappings.iter().find(|mapping| mapping.contains(vpn)) else { return false; };
+        // the kernel writes user memory through its own mapping without setting
+        // the dirty bit, so every page is written back rather than just dirty ones
+        mapping.write_back_page(token, vpn);
+        // a page of a lazy area is read back on its next touch like the first time
+        self.memory_set.drop_page(vpn)
+    }
+    /// Read a reclaimed page of a file mapping back from the file,
+    /// false if `vpn` is not such a page
+    pub fn fault_in(&mut self, vpn: VirtPageNum) -> bool {
+        // see load_file_page for the pages of a mapping made by mmap
+        if self.memory_set.is_lazy(vpn) {
+            return false;
+        }
+        let Some(mapping) = self.file_mappings.iter().find(|mapping| mapping.contains(vpn)) else { return false; };
+        if !self.memory_set.map_page(vpn) {
+            return false;
+        }
+        mapping.load_page(self.memory_set.token(), vpn);
+        // the page matches the file again
+        self.memory_set.set_dirty(vpn, false);
+        self.update_max_rss();
+        true
+    }
+    /// Fill the page `vpn` of a lazy area, just given a frame, from the file
+    /// mapping covering it if there is one
+    pub fn load_file_page(&mut self, vpn: VirtPageNum) {
+        let token = self.get_user_token();
+        let Some(mapping) = self.file_mappings.iter().find(|mapping| mapping.contains(vpn)) else { return; };
+        mapping.load_page(token, vpn);
+        // the page matches the file
+        self.memory_set.set_dirty(vpn, false);
+    }
+    /// Write the dirty pages of file mappings in `[start, end)` back to their files,
+    /// with `invalidate` also drop them so they are read from the files again
+    pub fn sync_file_mappings(&mut self, start: usize, end: usize, invalidate: bool) {
+        let token = self.get_user_token();
+        let first = VirtAddr::from(start).floor();
+        let last = VirtAddr::from(end).ceil();
+        let pages: Vec<VirtPageNum> = self
+            .file_mappings
+            .iter()
+            .flat_map(|mapping| mapping.pages())
+            .filter(|&vpn| first <= vpn && vpn < last)
+            .collect();
+        for vpn in pages {
+            if self.memory_set.set_dirty(vpn, false) {
+                let mapping = self
+                    .file_mappings
+                    .iter()
+                    .find(|mapping| mapping.contains(vpn));
+                mapping.unwrap().write_back_page(token, vpn);
+            }
+            if invalidate {
+                self.memory_set.drop_page(vpn);
+            }
+        }
+    }
+    /// Remember that the task is being switched out for `reason`
+    pub fn record_switch(&mut self, reason: SwitchReason) {
+        if self.switch_history.len() == SWITCH_HISTORY_LEN {
+            self.switch_history.pop_front();
+        }
+        self.switch_history.push_back(SwitchRecord {
+            reason,
+            time_us: get_time_us(),
+        });
+    }
+    /// Raise `max_rss` to the pages resident now, after the address space grew
+    pub fn update_max_rss(&mut self) {
+        self.max_rss = self.max_rss.max(self.memory_set.resident_pages());
+    }
+    /// Trade the address space with `other`, along with the file mappings and the heap in it
+    fn swap_address_space(&mut self, other: &mut Self) {
+        core::mem::swap(&mut self.memory_set, &mut other.memory_set);
+        core::mem::swap(&mut self.file_mappings, &mut other.file_mappings);
+        core::mem::swap(&mut self.heap_bottom, &mut other.heap_bottom);
+        core::mem::swap(&mut self.program_brk, &mut other.program_brk);
+    }
+    /// Give the address space back to the process blocked in `sys_vfork` for
+    /// this child of it, and wake it up. Nothing to do for any other process.
+    pub fn release_vfork_parent(&mut self) {
+        let Some(parent) = self.vfork_parent.take() else { return; };
+        self.swap_address_space(&mut parent.inner_exclusive_access());
+        wakeup_task(parent);
+    }
     pub fn alloc_fd(&mut self) -> usize {
         if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
             fd
@@ -84,23 +282,46 @@ impl TaskControlBlockInner {
 }
 
 impl TaskControlBlock {
-    /// Get the mutex to get the RefMut TaskControlBlockInner
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
+    /// Get the mutex to get the UPRefMut TaskControlBlockInner
+    pub fn inner_exclusive_access(&self) -> UPRefMut<'_, TaskControlBlockInner> {
         self.inner.exclusive_access()
     }
 
+    /// The stride the scheduler moves it on by: its own,
+    /// or the smallest of the tasks waiting on the mutexes it holds, if that is smaller.
+    /// Waiters lend what they inherit themselves, so priority passes down a chain of holders.
+    pub fn effective_stride(&self) -> usize {
+        self.inherited_stride(MAX_INHERIT_DEPTH)
+    }
+
+    fn inherited_stride(&self, depth: usize) -> usize {
+        let inner = self.inner_exclusive_access();
+        let stride = inner.stride;
+        if depth == 0 || inner.donors.is_empty() {
+            return stride;
+        }
+        let donors: Vec<_> = inner.donors.iter().map(|(_, task)| task.clone()).collect();
+        drop(inner);
+        donors
+            .iter()
+            .map(|task| task.inherited_stride(depth - 1))
+            .fold(stride, usize::min)
+    }
+
     /// Create a new process
     ///
     /// At present, it is only used for the creation of initproc
-    pub fn new(elf_data: &[u8]) -> Self {
+    pub fn new(name: &str, elf_data: &[u8]) -> Self {
         // memory_set with elf program headers/trampoline/trap context/user stack
         let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
             .ppn();
+        let max_rss = memory_set.resident_pages();
         // alloc a pid and a kernel stack in kernel space
         let pid_handle = pid_alloc();
+        let pid = pid_handle.0;
         let kernel_stack = KernelStack::new(&pid_handle);
         let kernel_stack_top = kernel_stack.get_top();
         // push a task context which goes to trap_return to the top of kernel stack
@@ -117,6 +338,10 @@ impl TaskControlBlock {
                     parent: None,
                     children: Vec::new(),
                     exit_code: 0,
+                    term_signal: 0,
+                    // the first process leads a group of its own
+                    pgid: pid,
+                    pending_signal: 0,
                     fd_table: alloc::vec![
                         // 0 -> stdin
                         Some(Arc::new(Stdin)),
@@ -125,10 +350,34 @@ impl TaskControlBlock {
                         // 2 -> stderr
                         Some(Arc::new(Stdout)),
                     ],
+                    cloexec_fds: BTreeSet::new(),
+                    cwd: None,
+                    root: None,
                     syscall_times: [0; MAX_SYSCALL_NUM],
                     start_time: 0,
+                    schedule_count: 0,
                     stride: BIG_STRIDE / 16,
                     pass: 0,
+                    name: String::from(name),
+                    cpu_time: 0,
+                    user_time: 0,
+                    user_since: 0,
+                    deadline_sched: None,
+                    timeslice: DEFAULT_TIMESLICE,
+                    slice_left: 0,
+                    switch_history: VecDeque::new(),
+                    file_mappings: Vec::new(),
+                    mutex_list: Vec::new(),
+                    condvar_list: Vec::new(),
+                    donors: Vec::new(),
+                    max_rss,
+                    heap_bottom: user_sp,
+                    program_brk: user_sp,
+                    process: None,
+                    slot: 0,
+                    threads: Vec::new(),
+                    live_threads: 1,
+                    vfork_parent: None,
                 })
             },
         };
@@ -144,7 +393,7 @@ impl TaskControlBlock {
         task_control_block
     }
     /// Load a new elf to replace the original application address space and start execution
-    pub fn exec(&self, elf_data: &[u8]) {
+    pub fn exec(&self, name: &str, elf_data: &[u8]) {
         // memory_set with elf program headers/trampoline/trap context/user stack
         let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
         let trap_cx_ppn = memory_set
@@ -153,10 +402,21 @@ impl TaskControlBlock {
             .ppn();
         // **** access inner exclusively
         let mut inner = self.inner_exclusive_access();
+        // the address space of a parent in `sys_vfork` is not to be dropped
+        inner.release_vfork_parent();
+        inner.write_back_file_mappings();
         // substitute memory_set
         inner.memory_set = memory_set;
+        inner.update_max_rss();
+        inner.heap_bottom = user_sp;
+        inner.program_brk = user_sp;
         // update trap_cx ppn
         inner.trap_cx_ppn = trap_cx_ppn;
+        inner.name = String::from(name);
+        // close the fds marked close-on-exec
+        for fd in core::mem::take(&mut inner.cloexec_fds) {
+            inner.fd_table[fd].take();
+        }
         // initialize trap_cx
         let trap_cx = inner.get_trap_cx();
         *trap_cx = TrapContext::app_init_context(
@@ -170,14 +430,36 @@ impl TaskControlBlock {
     }
     /// Fork from parent to child
     pub fn fork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
+        self.fork_sharing(false)
+    }
+    /// Fork from parent to a child which runs in the address space of the parent,
+    /// trap context included, till it execs or exits, see `sys_vfork`
+    pub fn vfork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
+        self.fork_sharing(true)
+    }
+    fn fork_sharing(self: &Arc<TaskControlBlock>, share: bool) -> Arc<TaskControlBlock> {
         // ---- access parent PCB exclusively
         let mut parent_inner = self.inner_exclusive_access();
-        // copy user space(include trap context)
-        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
-        let trap_cx_ppn = memory_set
-            .translate(VirtAddr::from(TRAP_CONTEXT).into())
-            .unwrap()
-            .ppn();
+        // copy user space(include trap context), or take it over when shared
+        let memory_set = if share {
+            MemorySet::new_bare()
+        } else {
+            MemorySet::from_existed_user(&mut parent_inner.memory_set)
+        };
+        let trap_cx_ppn = if share {
+            parent_inner.trap_cx_ppn
+        } else {
+            memory_set
+                .translate(VirtAddr::from(TRAP_CONTEXT).into())
+                .unwrap()
+                .ppn()
+        };
+        // the frames shared with the parent are resident for the child as well
+        let max_rss = if share {
+            parent_inner.memory_set.resident_pages()
+        } else {
+            memory_set.resident_pages()
+        };
         // alloc a pid and a kernel stack in kernel space
         let pid_handle = pid_alloc();
         let kernel_stack = KernelStack::new(&pid_handle);
@@ -204,16 +486,53 @@ impl TaskControlBlock {
                     parent: Some(Arc::downgrade(self)),
                     children: Vec::new(),
                     exit_code: 0,
+                    term_signal: 0,
+                    pgid: parent_inner.pgid,
+                    pending_signal: 0,
                     fd_table: new_fd_table,
+                    cloexec_fds: parent_inner.cloexec_fds.clone(),
+                    cwd: parent_inner.cwd.clone(),
+                    root: parent_inner.root.clone(),
                     syscall_times: [0; MAX_SYSCALL_NUM],
                     start_time: 0,
+                    schedule_count: 0,
                     stride: BIG_STRIDE / 16,
-                    pass: 0,
+                    // starting behind the parent would let the child hog the CPU
+                    pass: parent_inner.pass,
+                    name: parent_inner.name.clone(),
+                    cpu_time: 0,
+                    user_time: 0,
+                    user_since: 0,
+                    deadline_sched: None,
+                    timeslice: parent_inner.timeslice,
+                    slice_left: 0,
+                    switch_history: VecDeque::new(),
+                    file_mappings: Vec::new(),
+                    mutex_list: parent_inner.mutex_list.clone(),
+                    condvar_list: parent_inner.condvar_list.clone(),
+                    donors: Vec::new(),
+                    max_rss,
+                    heap_bottom: parent_inner.heap_bottom,
+                    program_brk: parent_inner.program_brk,
+                    process: None,
+                    slot: 0,
+                    threads: Vec::new(),
+                    live_threads: 1,
+                    vfork_parent: None,
                 })
             },
         });
         // add child
         parent_inner.children.push(task_control_block.clone());
+        // the child checks its mutex order if the parent does, from nothing seen
+        if mutex_order_checked(self.getpid()) {
+            check_mutex_order(task_control_block.getpid(), true);
+        }
+        if share {
+            let mut child_inner = task_control_block.inner_exclusive_access();
+            child_inner.swap_address_space(&mut parent_inner);
+            child_inner.vfork_parent = Some(self.clone());
+        }
         // modify kernel_sp in trap_cx
         // **** access children PCB exclusively
         let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
@@ -226,8 +545,135 @@ impl TaskControlBlock {
     pub fn getpid(&self) -> usize {
         self.pid.0
     }
+    /// The id of the task as a thread, see `sys_gettid`. Unlike the pid of its process,
+    /// it tells the threads of a process apart.
+    pub fn gettid(&self) -> usize {
+        self.pid.0
+    }
+
+    /// The process of the task, which is the task itself unless it is a thread
+    /// created by `sys_thread_create`
+    pub fn process(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
+        let process = self.inner_exclusive_access().process.clone();
+        // a process is not reaped before the last of its threads exits
+        process.map_or_else(|| self.clone(), |process| process.upgrade().unwrap())
+    }
+
+    /// Create a thread in the process, which starts at `entry` with `arg` in `a0`.
+    /// None if the process has [`MAX_THREADS`] threads besides its first one,
+    /// reaped or not, or no frame is left.
+    pub fn create_thread(
+        self: &Arc<TaskControlBlock>,
+        entry: usize,
+        arg: usize,
+    ) -> Option<Arc<TaskControlBlock>> {
+        // ---- access process PCB exclusively
+        let mut process_inner = self.inner_exclusive_access();
+        let index = match process_inner.threads.iter().position(Option::is_none) {
+            Some(index) => index,
+            None if process_inner.threads.len() < MAX_THREADS => {
+                process_inner.threads.push(None);
+                process_inner.threads.len() - 1
+            }
+            None => return None,
+        };
+        let slot = index + 1;
+        let trap_cx_va = thread_trap_cx(slot);
+        let (stack_bottom, stack_top) = thread_user_stack(slot);
+        if !process_inner.memory_set.insert_framed_area(
+            trap_cx_va.into(),
+            (trap_cx_va + PAGE_SIZE).into(),
+            MapPermission::R | MapPermission::W,
+        ) {
+            return None;
+        }
+        if !process_inner.memory_set.insert_framed_area(
+            stack_bottom.into(),
+            stack_top.into(),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        ) {
+            process_inner.free_thread_areas(slot);
+            return None;
+        }
+        let trap_cx_ppn = process_inner
+            .memory_set
+            .translate(VirtAddr::from(trap_cx_va).into())
+            .unwrap()
+            .ppn();
+        process_inner.update_max_rss();
+        // a tid is taken from the pids, which places the kernel stack of the thread
+        let pid_handle = pid_alloc();
+        let kernel_stack = KernelStack::new(&pid_handle);
+        let kernel_stack_top = kernel_stack.get_top();
+        let thread = Arc::new(TaskControlBlock {
+            pid: pid_handle,
+            kernel_stack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    trap_cx_ppn,
+                    base_size: process_inner.base_size,
+                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                    task_status: TaskStatus::Ready,
+                    memory_set: MemorySet::new_bare(),
+                    parent: None,
+                    children: Vec::new(),
+                    exit_code: 0,
+                    term_signal: 0,
+                    pgid: 0,
+                    pending_signal: 0,
+                    syscall_times: [0; MAX_SYSCALL_NUM],
+                    start_time: 0,
+                    schedule_count: 0,
+                    stride: BIG_STRIDE / 16,
+                    pass: process_inner.pass,
+                    name: process_inner.name.clone(),
+                    cpu_time: 0,
+                    user_time: 0,
+                    user_since: 0,
+                    deadline_sched: None,
+                    timeslice: process_inner.timeslice,
+                    slice_left: 0,
+                    switch_history: VecDeque::new(),
+                    file_mappings: Vec::new(),
+                    mutex_list: Vec::new(),
+                    condvar_list: Vec::new(),
+                    donors: Vec::new(),
+                    max_rss: 0,
+                    heap_bottom: process_inner.heap_bottom,
+                    program_brk: process_inner.program_brk,
+                    fd_table: Vec::new(),
+                    cloexec_fds: BTreeSet::new(),
+                    cwd: None,
+                    root: None,
+                    process: Some(Arc::downgrade(self)),
+                    slot,
+                    threads: Vec::new(),
+                    live_threads: 0,
+                    vfork_parent: None,
+                })
+            },
+        });
+        process_inner.threads[index] = Some(thread.clone());
+        process_inner.live_threads += 1;
+        // **** access thread TCB exclusively
+        let trap_cx = thread.inner_exclusive_access().get_trap_cx();
+        *trap_cx = TrapContext::app_init_context(
+            entry,
+            stack_top,
+            KERNEL_SPACE.exclusive_access().token(),
+            kernel_stack_top,
+            trap_handler as usize,
+        );
+        trap_cx.x[10] = arg;
+        Some(thread)
+        // ---- release process PCB automatically
+    }
 
-    pub fn spawn(self: &Arc<TaskControlBlock>, elf_data: &[u8]) -> Arc<TaskControlBlock> {
+    pub fn spawn(
+        self: &Arc<TaskControlBlock>,
+        name: &str,
+        elf_data: &[u8],
+    ) -> Arc<TaskControlBlock> {
         // ---- access parent PCB exclusively
         let mut parent_inner = self.inner_exclusive_access();
         let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
@@ -235,6 +681,7 @@ impl TaskControlBlock {
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
             .ppn();
+        let max_rss = memory_set.resident_pages();
         // alloc a pid and a kernel stack in kernel space
         let pid_handle = pid_alloc();
         let kernel_stack = KernelStack::new(&pid_handle);
@@ -253,10 +700,29 @@ impl TaskControlBlock {
                     parent: Some(Arc::downgrade(self)),
                     children: Vec::new(),
                     exit_code: 0,
+                    term_signal: 0,
+                    pgid: parent_inner.pgid,
+                    pending_signal: 0,
                     syscall_times: [0; MAX_SYSCALL_NUM],
                     start_time: 0,
+                    schedule_count: 0,
                     stride: BIG_STRIDE / 16,
-                    pass: 0,
+                    pass: parent_inner.pass,
+                    name: String::from(name),
+                    cpu_time: 0,
+                    user_time: 0,
+                    user_since: 0,
+                    deadline_sched: None,
+                    timeslice: DEFAULT_TIMESLICE,
+                    slice_left: 0,
+                    switch_history: VecDeque::new(),
+                    file_mappings: Vec::new(),
+                    mutex_list: Vec::new(),
+                    condvar_list: Vec::new(),
+                    donors: Vec::new(),
+                    max_rss,
+                    heap_bottom: user_sp,
+                    program_brk: user_sp,
                     fd_table: alloc::vec![
                         // 0 -> stdin
                         Some(Arc::new(Stdin)),
@@ -265,11 +731,23 @@ impl TaskControlBlock {
                         // 2 -> stderr
                         Some(Arc::new(Stdout)),
                     ],
+                    cloexec_fds: BTreeSet::new(),
+                    cwd: parent_inner.cwd.clone(),
+                    root: parent_inner.root.clone(),
+                    process: None,
+                    slot: 0,
+                    threads: Vec::new(),
+                    live_threads: 1,
+                    vfork_parent: None,
                 })
             },
         });
         // add child
         parent_inner.children.push(task_control_block.clone());
+        // the child checks its mutex order if the parent does, from nothing seen
+        if mutex_order_checked(self.getpid()) {
+            check_mutex_order(task_control_block.getpid(), true);
+        }
         // modify kernel_sp in trap_cx
         // **** access children PCB exclusively
         let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
@@ -284,10 +762,169 @@ impl TaskControlBlock {
     }
 }
 
+/// Parameters of a task in the deadline scheduling class
+///
+/// A piece of user memory backed by a range of a file, see `sys_extend_and_map` and `sys_mmap`
+pub struct FileMapping {
+    pub start: usize,
+    pub len: usize,
+    pub inode: Arc<Inode>,
+    /// where the mapping starts in the file
+    pub offset: usize,
+}
+
+impl FileMapping {
+    /// Copy the mapped memory into the file, skipping pages already reclaimed
+    pub fn write_back(&self, token: usize) {
+        for vpn in self.pages() {
+            self.write_back_page(token, vpn);
+        }
+    }
+    /// Copy a page of the mapped memory into the file if it is mapped.
+    /// What lies past the end of the file is left out, the file does not grow.
+    pub fn write_back_page(&self, token: usize, vpn: VirtPageNum) {
+        if !PageTable::from_token(token)
+            .translate(vpn)
+            .is_some_and(|pte| pte.is_valid())
+        {
+            return;
+        }
+        let (start, len) = self.page_span(vpn);
+        let mut offset = self.offset + start - self.start;
+        let size = self
+            .inode
+            .read_disk_inode(|disk_inode| disk_inode.size as usize);
+        if offset >= size {
+            return;
+        }
+        let len = len.min(size - offset);
+        for slice in translated_read_buffer(token, start as *const u8, len) {
+            self.inode.write_at(offset, slice);
+            offset += slice.len();
+        }
+    }
+    /// Fill a mapped page from the file
+    pub fn load_page(&self, token: usize, vpn: VirtPageNum) {
+        let (start, len) = self.page_span(vpn);
+        let mut offset = self.offset + start - self.start;
+        for slice in translated_kernel_buffer(token, start as *const u8, len) {
+            self.inode.read_at(offset, slice);
+            offset += slice.len();
+        }
+    }
+    /// The pages the mapping covers
+    pub fn pages(&self) -> VPNRange {
+        VPNRange::new(
+            VirtAddr(self.start).floor(),
+            VirtAddr(self.start + self.len).ceil(),
+        )
+    }
+    /// Whether the mapping covers page `vpn`
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        let pages = self.pages();
+        pages.get_start() <= vpn && vpn < pages.get_end()
+    }
+    /// Start and length of the part of page `vpn` the mapping covers
+    fn page_span(&self, vpn: VirtPageNum) -> (usize, usize) {
+        let start = VirtAddr::from(vpn).0.max(self.start);
+        let end = (VirtAddr::from(vpn).0 + PAGE_SIZE).min(self.start + self.len);
+        (start, end - start)
+    }
+    /// Whether the mapping overlaps `[start, end)`
+    pub fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.start < end && start < self.start + self.len
+    }
+}
+
+/// Where the trap context of the thread in `slot` is mapped, a page further
+/// below the clock page for each slot
+fn thread_trap_cx(slot: usize) -> usize {
+    CLOCK_PAGE - slot * PAGE_SIZE
+}
+
+/// Bottom and top of the user stack of the thread in `slot`, below the trap contexts
+/// of all slots, each with a guard page above it
+fn thread_user_stack(slot: usize) -> (usize, usize) {
+    let top = thread_trap_cx(MAX_THREADS) - slot * (USER_STACK_SIZE + PAGE_SIZE);
+    (top - USER_STACK_SIZE, top)
+}
+
+/// Number of context switches remembered for each task
+pub const SWITCH_HISTORY_LEN: usize = 16;
+
+/// How far down a chain of mutex holders priority is passed,
+/// which also stops tasks deadlocked on each other from lending to each other forever
+const MAX_INHERIT_DEPTH: usize = 8;
+
+/// Why a task was switched out
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[repr(usize)]
+pub enum SwitchReason {
+    /// gave up the CPU with `sys_yield`
+    Yield,
+    /// its time slice ran out
+    Preempt,
+    /// waiting for a pipe to be read or written
+    BlockOnPipe,
+    /// waiting for a file, such as stdin or [`crate::fs::wait_size`]
+    BlockOnFile,
+    /// waiting for a mutex or a condition variable
+    BlockOnSync,
+    /// sleeping until a timer expires
+    Sleep,
+    /// waiting in `sys_vfork` for the child to exec or exit
+    BlockOnVfork,
+}
+
+/// A context switch of a task, as seen by `sys_switch_history`
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SwitchRecord {
+    pub reason: SwitchReason,
+    /// in microseconds
+    pub time_us: usize,
+}
+
+/// The task may run for `runtime_ms` in every period of `period_ms`,
+/// and is always picked before normal tasks while it has budget left.
+#[derive(Copy, Clone)]
+pub struct DeadlineSched {
+    pub period_ms: usize,
+    pub runtime_ms: usize,
+    /// Absolute deadline of the current period
+    pub deadline_ms: usize,
+    /// CPU time used in the current period, in microseconds
+    pub used_us: usize,
+}
+
+impl DeadlineSched {
+    pub fn new(period_ms: usize, runtime_ms: usize) -> Self {
+        Self {
+            period_ms,
+            runtime_ms,
+            deadline_ms: get_time_ms() + period_ms,
+            used_us: 0,
+        }
+    }
+    /// Start a new period with a full budget once the deadline has passed
+    pub fn replenish(&mut self, now_ms: usize) {
+        if now_ms >= self.deadline_ms {
+            // skip the periods missed as a whole
+            let missed = (now_ms - self.deadline_ms) / self.period_ms;
+            self.deadline_ms += (missed + 1) * self.period_ms;
+            self.used_us = 0;
+        }
+    }
+    pub fn has_budget(&self) -> bool {
+        self.used_us < self.runtime_ms * 1000
+    }
+}
+
 #[derive(Copy, Clone, PartialEq)]
-/// task status: UnInit, Ready, Running, Exited
+/// task status: Ready, Running, Blocking, Zombie
 pub enum TaskStatus {
     Ready,
     Running,
+    Blocking,
     Zombie,
 }
@@ -1,10 +1,20 @@
 //! RISC-V timer-related functionality
 
 use crate::config::CLOCK_FREQ;
+use crate::mm::{frame_alloc, FrameTracker};
 use crate::sbi::set_timer;
+use crate::sync::UPSafeCell;
+use crate::task::{
+    block_current_and_run_next, current_task, wakeup_task, SwitchReason, TaskControlBlock,
+};
+use alloc::collections::BinaryHeap;
+use alloc::sync::Arc;
+use core::cmp::Ordering;
+use lazy_static::*;
 use riscv::register::time;
 
 const TICKS_PER_SEC: usize = 100;
+const MILLI_PER_SEC: usize = 1_000;
 const MICRO_PER_SEC: usize = 1_000_000;
 
 /// read the `mtime` register
@@ -17,7 +27,146 @@ pub fn get_time_us() -> usize {
     time::read() / (CLOCK_FREQ / MICRO_PER_SEC)
 }
 
-/// set the next timer interrupt
+/// get current time in milliseconds
+pub fn get_time_ms() -> usize {
+    time::read() / (CLOCK_FREQ / MILLI_PER_SEC)
+}
+
+/// Start the next tick, the timer interrupt comes at it or earlier for a timer due before it
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    let mut timers = TIMERS.exclusive_access();
+    timers.next_tick = get_time() + CLOCK_FREQ / TICKS_PER_SEC;
+    timers.arm();
+}
+
+/// Whether the tick is due, rather than the timer interrupt coming early for a timer
+pub fn tick_due() -> bool {
+    get_time() >= TIMERS.exclusive_access().next_tick
+}
+
+/// Layout of the clock page shared read-only with user space
+#[repr(C)]
+pub struct ClockPage {
+    /// number of timer interrupts so far
+    pub ticks: usize,
+    /// time of the last timer interrupt, in microseconds
+    pub time_us: usize,
+}
+
+lazy_static! {
+    /// The frame backing the clock page, one for all tasks which map it
+    pub static ref CLOCK_FRAME: FrameTracker = frame_alloc().unwrap();
+}
+
+/// Refresh the clock page, called on every timer interrupt
+pub fn update_clock_page() {
+    let page: &mut ClockPage = CLOCK_FRAME.ppn.get_mut();
+    page.ticks += 1;
+    page.time_us = get_time_us();
+}
+
+/// A blocked task waiting to be woken up at `expire_us`
+pub struct TimerCondVar {
+    pub expire_us: usize,
+    /// order the timers were added in, so those with the same deadline fire first come first
+    seq: usize,
+    pub task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for TimerCondVar {
+    fn eq(&self, other: &Self) -> bool {
+        (self.expire_us, self.seq) == (other.expire_us, other.seq)
+    }
+}
+impl Eq for TimerCondVar {}
+impl PartialOrd for TimerCondVar {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerCondVar {
+    // reversed, so the max-heap pops the earliest timer first
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.expire_us, other.seq).cmp(&(self.expire_us, self.seq))
+    }
+}
+
+/// Pending timers sorted by deadline, and the tick they share the timer interrupt with
+struct TimerQueue {
+    timers: BinaryHeap<TimerCondVar>,
+    next_seq: usize,
+    /// `mtime` of the next tick
+    next_tick: usize,
+}
+
+impl TimerQueue {
+    /// Set the timer interrupt to the next tick or the earliest timer, whichever comes first,
+    /// so a sleep shorter than a tick is not rounded up to it
+    fn arm(&self) {
+        let mut next = self.next_tick;
+        if let Some(timer) = self.timers.peek() {
+            next = next.min(timer.expire_us * (CLOCK_FREQ / MICRO_PER_SEC));
+        }
+        set_timer(next);
+    }
+}
+
+lazy_static! {
+    static ref TIMERS: UPSafeCell<TimerQueue> = unsafe {
+        UPSafeCell::new(TimerQueue {
+            timers: BinaryHeap::new(),
+            next_seq: 0,
+            next_tick: 0,
+        })
+    };
+}
+
+/// Wake `task` up once the clock reaches `expire_us`
+pub fn add_timer(expire_us: usize, task: Arc<TaskControlBlock>) {
+    let mut queue = TIMERS.exclusive_access();
+    let seq = queue.next_seq;
+    queue.next_seq += 1;
+    queue.timers.push(TimerCondVar {
+        expire_us,
+        seq,
+        task,
+    });
+    queue.arm();
+}
+
+/// Drop every pending timer of `task`, e.g. when it was woken up by something else
+pub fn remove_timer(task: &Arc<TaskControlBlock>) {
+    let mut queue = TIMERS.exclusive_access();
+    let remaining: BinaryHeap<TimerCondVar> = queue
+        .timers
+        .drain()
+        .filter(|timer| !Arc::ptr_eq(&timer.task, task))
+        .collect();
+    queue.timers = remaining;
+}
+
+/// Wake up all the tasks whose timer has expired, earliest deadline first
+pub fn check_timer() {
+    let current_us = get_time_us();
+    let mut queue = TIMERS.exclusive_access();
+    while let Some(timer) = queue.timers.peek() {
+        if timer.expire_us <= current_us {
+            wakeup_task(Arc::clone(&timer.task));
+            queue.timers.pop();
+        } else {
+            break;
+        }
+    }
+    queue.arm();
+}
+
+/// Block current task until the clock has advanced by `duration_us` microseconds
+pub fn sleep_current(duration_us: usize) {
+    let expire_us = get_time_us() + duration_us;
+    // nothing but the timer wakes a sleeping task, the loop is only for safety
+    while get_time_us() < expire_us {
+        add_timer(expire_us, current_task().unwrap());
+        block_current_and_run_next(SwitchReason::Sleep);
+    }
 }
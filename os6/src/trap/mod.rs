@@ -14,12 +14,16 @@
 
 mod context;
 
-use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
+use crate::config::TRAMPOLINE;
+use crate::mm::{MapPermission, VirtAddr};
 use crate::syscall::syscall;
 use crate::task::{
-    current_trap_cx, current_user_token, exit_current_and_run_next, suspend_current_and_run_next,
+    copy_on_write, current_trap_cx, current_trap_cx_va, current_user_token, enter_user,
+    fault_in_file_mapping, fault_in_lazy, kill_current_and_run_next, leave_user,
+    pending_signal, suspend_current_and_run_next, tick_current_task, SwitchReason,
+    SIGILL, SIGSEGV,
 };
-use crate::timer::set_next_trigger;
+use crate::timer::{check_timer, set_next_trigger, tick_due, update_clock_page};
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
@@ -50,9 +54,19 @@ pub fn enable_timer_interrupt() {
     }
 }
 
+/// The permission a page fault of `e` asks of the page
+fn access_of(e: Exception) -> MapPermission {
+    match e {
+        Exception::StorePageFault => MapPermission::W,
+        Exception::InstructionPageFault => MapPermission::X,
+        _ => MapPermission::R,
+    }
+}
+
 #[no_mangle]
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
+    leave_user();
     let scause = scause::read();
     let stval = stval::read();
     match scause.cause() {
@@ -61,11 +75,31 @@ pub fn trap_handler() -> ! {
             let mut cx = current_trap_cx();
             cx.sepc += 4;
             // get system call return value
-            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12], cx.x[13]]);
+            let result = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
             // cx is changed during sys_exec, so we have to call it again
             cx = current_trap_cx();
             cx.x[10] = result as usize;
         }
+        Trap::Exception(Exception::StorePageFault)
+            if copy_on_write(current_user_token(), VirtAddr(stval).floor()) =>
+        {
+            // the page got a frame of its own, run the store again
+        }
+        Trap::Exception(Exception::StorePageFault) | Trap::Exception(Exception::LoadPageFault)
+            if fault_in_file_mapping(stval) =>
+        {
+            // a reclaimed page of a file mapping was read back, run the instruction again
+        }
+        Trap::Exception(
+            e @ (Exception::StorePageFault
+            | Exception::LoadPageFault
+            | Exception::InstructionPageFault),
+        ) if fault_in_lazy(current_user_token(), VirtAddr(stval).floor(), access_of(e)) => {
+            // first touch of a page of a lazy area, it has a frame now
+        }
         Trap::Exception(Exception::StoreFault)
         | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::InstructionFault)
@@ -78,17 +112,24 @@ pub fn trap_handler() -> ! {
                 stval,
                 current_trap_cx().sepc,
             );
-            // page fault exit code
-            exit_current_and_run_next(-2);
+            kill_current_and_run_next(SIGSEGV);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
             println!("[kernel] IllegalInstruction in application, core dumped.");
-            // illegal instruction exit code
-            exit_current_and_run_next(-3);
+            kill_current_and_run_next(SIGILL);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            set_next_trigger();
-            suspend_current_and_run_next();
+            if tick_due() {
+                set_next_trigger();
+                update_clock_page();
+                check_timer();
+                if tick_current_task() {
+                    suspend_current_and_run_next(SwitchReason::Preempt);
+                }
+            } else {
+                // early for a timer, which neither ticks nor takes the time slice of current task
+                check_timer();
+            }
         }
         _ => {
             panic!(
@@ -103,8 +144,14 @@ pub fn trap_handler() -> ! {
 
 #[no_mangle]
 pub fn trap_return() -> ! {
+    // each thread of a process sent a signal while it was away goes before running again
+    let signal = pending_signal();
+    if signal != 0 {
+        kill_current_and_run_next(signal);
+    }
     set_user_trap_entry();
-    let trap_cx_ptr = TRAP_CONTEXT;
+    enter_user();
+    let trap_cx_ptr = current_trap_cx_va();
     let user_satp = current_user_token();
     extern "C" {
         fn __alltraps();
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{access, chmod, close, open, unlink, write, OpenFlags, F_OK, R_OK, W_OK, X_OK};
+
+/// 测试 access 与 chmod，文件改为只读后以写方式打开失败，只读加 NONBLOCK 等标志打开仍成功且不可写，改回可写后打开成功，根目录去掉所有权限后仍能查找其下的文件，输出 Test access OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_access\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(access(fname, F_OK), 0);
+    assert_eq!(access(fname, R_OK | W_OK), 0);
+    assert_eq!(access(fname, X_OK), -1);
+    assert_eq!(access("fname_access_missing\0", F_OK), -1);
+    // read-only, so no writing and no truncating
+    assert_eq!(chmod(fname, 0o444), 0);
+    assert_eq!(access(fname, R_OK), 0);
+    assert_eq!(access(fname, W_OK), -1);
+    assert_eq!(open(fname, OpenFlags::WRONLY), -1);
+    assert_eq!(open(fname, OpenFlags::RDWR), -1);
+    assert_eq!(open(fname, OpenFlags::RDONLY | OpenFlags::TRUNC), -1);
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    // flags other than the access mode do not ask for writing
+    assert_eq!(chmod(fname, 0o400), 0);
+    for flags in [OpenFlags::NONBLOCK, OpenFlags::APPEND, OpenFlags::EXCL] {
+        let fd = open(fname, OpenFlags::RDONLY | flags);
+        assert!(fd > 0);
+        assert_eq!(write(fd as usize, b"x"), -1);
+        close(fd as usize);
+    }
+    // writable again
+    assert_eq!(chmod(fname, 0o644), 0);
+    assert_eq!(access(fname, W_OK), 0);
+    let fd = open(fname, OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"access"), 6);
+    close(fd as usize);
+    // the root directory stays searchable whatever its mode
+    assert_eq!(chmod("/\0", 0), 0);
+    assert_eq!(access("/\0", X_OK), 0);
+    assert_eq!(access(fname, F_OK), 0);
+    assert_eq!(chmod("/\0", 0o755), 0);
+    assert_eq!(chmod("fname_access_missing\0", 0o644), -1);
+    assert_eq!(unlink(fname), 0);
+    println!("Test access OK!");
+    0
+}
@@ -0,0 +1,108 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use user_lib::{
+    append, close, exit, fork, lseek, open, read, wait, write, yield_, OpenFlags, SEEK_CUR,
+    SEEK_SET,
+};
+
+/// 测试 append，多个进程同时追加记录，每条记录得到的偏移互不相同且该处正是它写的内容，输出 Test append OK! 就算正确。
+
+const CHILDREN: usize = 4;
+const RECORDS: usize = 16;
+const RECORD: usize = 16;
+
+fn record(tag: usize, i: usize) -> [u8; RECORD] {
+    let mut data = [0u8; RECORD];
+    for (j, byte) in data.iter_mut().enumerate() {
+        *byte = if j % 2 == 0 { tag as u8 } else { i as u8 };
+    }
+    data
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_append\0";
+    let fd = open(
+        fname,
+        OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // the offset of the fd is not moved
+    assert_eq!(write(fd, b"head"), 4);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(append(fd, b"tail"), 4);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 0);
+    let mut buf = [0u8; 8];
+    assert_eq!(read(fd, &mut buf), 8);
+    assert_eq!(&buf, b"headtail");
+    close(fd);
+    let ro = open(fname, OpenFlags::RDONLY);
+    assert!(ro > 0);
+    assert_eq!(append(ro as usize, b"no"), -1);
+    close(ro as usize);
+    // every child appends its records and keeps the offsets in a file of its own
+    for tag in 1..=CHILDREN {
+        if fork() == 0 {
+            let fd = open(fname, OpenFlags::WRONLY);
+            assert!(fd > 0);
+            let mut offsets = [0u8; RECORDS * 8];
+            for i in 0..RECORDS {
+                let offset = append(fd as usize, &record(tag, i));
+                assert!(offset >= 8);
+                offsets[i * 8..(i + 1) * 8].copy_from_slice(&(offset as u64).to_ne_bytes());
+                yield_();
+            }
+            close(fd as usize);
+            let name = format!("fname_append_{}\0", tag);
+            let off_fd = open(
+                &name,
+                OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY,
+            );
+            assert!(off_fd > 0);
+            assert_eq!(write(off_fd as usize, &offsets), offsets.len() as isize);
+            close(off_fd as usize);
+            exit(0);
+        }
+    }
+    for _ in 0..CHILDREN {
+        let mut exit_code: i32 = 0;
+        assert!(wait(&mut exit_code) > 0);
+        assert_eq!(exit_code, 0);
+    }
+    // the records tile the file after the first 8 bytes, each where its writer was told
+    let mut seen = [false; CHILDREN * RECORDS];
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    for tag in 1..=CHILDREN {
+        let name = format!("fname_append_{}\0", tag);
+        let off_fd = open(&name, OpenFlags::RDONLY);
+        assert!(off_fd > 0);
+        let mut offsets = [0u8; RECORDS * 8];
+        assert_eq!(read(off_fd as usize, &mut offsets), offsets.len() as isize);
+        close(off_fd as usize);
+        for i in 0..RECORDS {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&offsets[i * 8..(i + 1) * 8]);
+            let offset = u64::from_ne_bytes(bytes) as usize;
+            assert_eq!((offset - 8) % RECORD, 0);
+            let slot = (offset - 8) / RECORD;
+            assert!(slot < seen.len() && !seen[slot]);
+            seen[slot] = true;
+            let mut data = [0u8; RECORD];
+            assert_eq!(lseek(fd, offset as isize, SEEK_SET), offset as isize);
+            assert_eq!(read(fd, &mut data), RECORD as isize);
+            assert_eq!(data, record(tag, i));
+        }
+    }
+    close(fd);
+    println!("Test append OK!");
+    0
+}
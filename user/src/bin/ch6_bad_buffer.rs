@@ -0,0 +1,78 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, lseek, mmap, munmap, open, read, readv, unlink, write, writev, IoVec, OpenFlags,
+    SEEK_END, SEEK_SET,
+};
+
+/// 测试缓冲区检查，跨过映射区末尾或落在只读页上的缓冲区让 read、write、readv、writev 返回 -1 而内核不崩溃，文件也不被改动，只读页仍可作为 write 的来源，输出 Test bad_buffer OK! 就算正确。
+
+const PAGE_SIZE: usize = 4096;
+const ADDR: usize = 0x10000000;
+
+fn user_slice(start: usize, len: usize) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(start as *mut u8, len) }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_bad_buffer\0";
+    let fd = open(
+        fname,
+        OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, &[b'x'; 64]), 64);
+
+    assert_eq!(mmap(ADDR, PAGE_SIZE, 0b11), 0);
+    // the last 10 bytes of the page and 10 past it
+    let straddling = user_slice(ADDR + PAGE_SIZE - 10, 20);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, straddling), -1);
+    // nothing was read before the check failed
+    assert!(user_slice(ADDR + PAGE_SIZE - 10, 10)
+        .iter()
+        .all(|&byte| byte == 0));
+    assert_eq!(lseek(fd, 0, SEEK_END), 64);
+    assert_eq!(write(fd, straddling), -1);
+    assert_eq!(lseek(fd, 0, SEEK_END), 64);
+    // not mapped at all
+    assert_eq!(read(fd, user_slice(ADDR + PAGE_SIZE, 8)), -1);
+    // one good buffer and one bad in a vector fail as a whole
+    let iov = [
+        IoVec { base: ADDR, len: 8 },
+        IoVec {
+            base: ADDR + PAGE_SIZE - 4,
+            len: 8,
+        },
+    ];
+    assert_eq!(writev(fd, &iov), -1);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(readv(fd, &iov), -1);
+    assert_eq!(lseek(fd, 0, SEEK_END), 64);
+    // inside the page all is fine
+    let inside = user_slice(ADDR + PAGE_SIZE - 10, 10);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, inside), 10);
+    assert_eq!(*inside, [b'x'; 10]);
+    assert_eq!(munmap(ADDR, PAGE_SIZE), 0);
+
+    // a read-only page can be written from but not read into
+    assert_eq!(mmap(ADDR, PAGE_SIZE, 0b1), 0);
+    let readonly = user_slice(ADDR, 16);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, readonly), -1);
+    assert_eq!(lseek(fd, 0, SEEK_END), 64);
+    assert_eq!(write(fd, readonly), 16);
+    assert_eq!(lseek(fd, 0, SEEK_END), 80);
+    assert_eq!(munmap(ADDR, PAGE_SIZE), 0);
+
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    println!("Test bad_buffer OK!");
+    0
+}
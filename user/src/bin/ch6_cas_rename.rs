@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use user_lib::{cas_rename, close, exit, fork, open, read, unlink, wait, write, yield_, OpenFlags};
+
+/// 测试 cas_rename，内容不符或文件不存在时不改名，多个进程同时抢同一个锁文件时只有一个成功，输出 Test cas_rename OK! 就算正确。
+
+const CHILDREN: usize = 5;
+
+fn create_with(name: &str, data: &[u8]) {
+    let fd = open(
+        name,
+        OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY,
+    );
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, data), data.len() as isize);
+    close(fd as usize);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let lock = "cas_lock\0";
+    let taken = "cas_taken\0";
+    create_with(lock, b"free");
+    create_with(taken, b"");
+    // a different content, a prefix of it, a missing name or a taken new name change nothing
+    assert_eq!(cas_rename(lock, b"held", "cas_won\0"), 0);
+    assert_eq!(cas_rename(lock, b"fre", "cas_won\0"), 0);
+    assert_eq!(cas_rename("cas_missing\0", b"free", "cas_won\0"), 0);
+    assert_eq!(cas_rename(lock, b"free", taken), -1);
+    assert!(open("cas_won\0", OpenFlags::RDONLY) < 0);
+    assert_eq!(unlink(taken), 0);
+    // every child races to take the free lock under a name of its own
+    for tag in 0..CHILDREN {
+        if fork() == 0 {
+            yield_();
+            let won = cas_rename(lock, b"free", &format!("cas_leader_{}\0", tag));
+            assert!(won == 0 || won == 1);
+            exit(won as i32);
+        }
+    }
+    let mut winners = 0;
+    for _ in 0..CHILDREN {
+        let mut exit_code: i32 = 0;
+        assert!(wait(&mut exit_code) > 0);
+        winners += exit_code;
+    }
+    assert_eq!(winners, 1);
+    assert!(open(lock, OpenFlags::RDONLY) < 0);
+    let mut leaders = 0;
+    for tag in 0..CHILDREN {
+        let name = format!("cas_leader_{}\0", tag);
+        let fd = open(&name, OpenFlags::RDONLY);
+        if fd < 0 {
+            continue;
+        }
+        leaders += 1;
+        let mut buf = [0u8; 8];
+        assert_eq!(read(fd as usize, &mut buf), 4);
+        assert_eq!(&buf[..4], b"free");
+        close(fd as usize);
+        assert_eq!(unlink(&name), 0);
+    }
+    assert_eq!(leaders, 1);
+    println!("Test cas_rename OK!");
+    0
+}
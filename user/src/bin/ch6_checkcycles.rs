@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, fs_checkcycles, link, open, unlink, write, OpenFlags};
+
+/// 测试 fs_checkcycles，正常的文件系统中没有目录环，硬链接不算环，输出 Test checkcycles OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_checkcycles\0";
+    let lname = "linkname_checkcycles\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    write(fd, "cycles".as_bytes());
+    close(fd);
+    // two names of one file are not a cycle
+    assert_eq!(link(fname, lname), 0);
+    assert_eq!(fs_checkcycles(false), 0);
+    assert_eq!(fs_checkcycles(true), 0);
+    // nothing was removed
+    let fd = open(lname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(unlink(lname), 0);
+    assert_eq!(unlink(fname), 0);
+    println!("Test checkcycles OK!");
+    0
+}
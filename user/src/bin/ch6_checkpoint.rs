@@ -0,0 +1,68 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{checkpoint, mmap, munmap, restore, sbrk, unlink};
+
+/// 测试 checkpoint 与 restore，计数循环数到一半时保存检查点，数完后恢复，从保存时的计数接着数，检查点之后才写过的堆页重新读出 0，地址空间布局不同时拒绝恢复，输出 Test checkpoint OK! 就算正确。
+
+const SAVED: usize = 1000;
+const END: usize = 3000;
+const PAGE_SIZE: usize = 4096;
+const BASE: usize = 0x10000000;
+
+/// Counts along with the loop, in the data of the program rather than a register
+static mut TOTAL: usize = 0;
+
+fn total() -> usize {
+    unsafe { core::ptr::addr_of!(TOTAL).read_volatile() }
+}
+
+fn count_on() {
+    unsafe { core::ptr::addr_of_mut!(TOTAL).write_volatile(total() + 1) };
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "checkpoint\0";
+    // a heap page left untouched until after the checkpoint
+    let heap = sbrk(PAGE_SIZE as isize);
+    assert!(heap > 0);
+    let heap = heap as *mut u8;
+    let mut count = 0;
+    let mut resumed = false;
+    while count < END {
+        count += 1;
+        count_on();
+        if count == SAVED {
+            match checkpoint(path) {
+                0 => {}
+                1 => {
+                    // back where the checkpoint was taken
+                    assert_eq!(count, SAVED);
+                    assert_eq!(total(), SAVED);
+                    assert_eq!(unsafe { heap.read_volatile() }, 0);
+                    resumed = true;
+                }
+                ret => panic!("checkpoint returned {}", ret),
+            }
+        } else if count == SAVED + 1 && !resumed {
+            unsafe { heap.write_volatile(0xaa) };
+        }
+    }
+    assert_eq!(total(), END);
+    if !resumed {
+        assert_eq!(unsafe { heap.read_volatile() }, 0xaa);
+        // not into an address space laid out otherwise
+        assert_eq!(mmap(BASE, PAGE_SIZE, 3), 0);
+        assert_eq!(restore(path), -1);
+        assert_eq!(munmap(BASE, PAGE_SIZE), 0);
+        restore(path);
+        panic!("restore returned");
+    }
+    assert_eq!(unlink(path), 0);
+    assert_eq!(restore(path), -1);
+    println!("Test checkpoint OK!");
+    0
+}
@@ -0,0 +1,94 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    chdir, chroot, close, exec, exit, fork, getcwd, open, read, symlink, unlink, waitpid, write,
+    OpenFlags,
+};
+
+/// 测试 chroot，子进程把 /jail 作为根目录后 /file 就是 /jail/file，getcwd 读回 /，经由 ..、绝对路径、符号链接都到不了 jail 外的文件，也不能 exec 外面的程序或再次 chroot，孙进程继承同样的根，父进程不受影响，输出 Test chroot OK! 就算正确。
+
+fn cwd_is(path: &str) -> bool {
+    let mut buf = [0u8; 32];
+    let len = getcwd(&mut buf);
+    len == path.len() as isize && &buf[..path.len()] == path.as_bytes() && buf[path.len()] == 0
+}
+
+fn write_file(path: &str, data: &[u8]) {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, data), data.len() as isize);
+    close(fd as usize);
+}
+
+/// Whether the file at `path` opens and holds exactly `data`
+fn holds(path: &str, data: &[u8]) -> bool {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return false;
+    }
+    let mut buf = [0u8; 32];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    len == data.len() as isize && &buf[..data.len()] == data
+}
+
+/// Check from inside the jail that only what is under it can be reached
+fn confined() {
+    assert!(cwd_is("/"));
+    assert!(holds("/file\0", b"inside"));
+    assert!(holds("file\0", b"inside"));
+    // `..` at the root stays there, however it is reached
+    assert!(!holds("../outside\0", b"outside"));
+    assert!(!holds("/../../outside\0", b"outside"));
+    assert!(holds("/../file\0", b"inside"));
+    assert_eq!(chdir("..\0"), 0);
+    assert!(cwd_is("/"));
+    assert!(!holds("outside\0", b"outside"));
+    assert!(!holds("/jail/file\0", b"inside"));
+    // links made inside resolve inside too
+    assert_eq!(symlink("../outside\0", "/esc\0"), 0);
+    assert!(!holds("esc\0", b"outside"));
+    assert_eq!(unlink("esc\0"), 0);
+    // the apps are outside
+    let args = ["ch5_exit0\0".as_ptr(), core::ptr::null::<u8>()];
+    assert_eq!(exec("ch5_exit0\0", &args), -1);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    write_file("/jail/file\0", b"inside");
+    write_file("/outside\0", b"outside");
+    let pid = fork();
+    if pid == 0 {
+        // not a directory, not there
+        assert_eq!(chroot("/outside\0"), -1);
+        assert_eq!(chroot("/nowhere\0"), -1);
+        assert_eq!(chroot("/jail\0"), 0);
+        confined();
+        // once is all a process gets
+        assert_eq!(chroot("/\0"), -1);
+        let pid = fork();
+        if pid == 0 {
+            confined();
+            exit(0);
+        }
+        let mut exit_code = -1;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        assert_eq!(exit_code, 0);
+        assert_eq!(unlink("file\0"), 0);
+        exit(0);
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // the parent still sees everything from the real root
+    assert!(cwd_is("/"));
+    assert!(holds("/outside\0", b"outside"));
+    assert!(!holds("/jail/file\0", b"inside"));
+    unlink("outside\0");
+    println!("Test chroot OK!");
+    0
+}
@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, close_range, fstat, open, CloseRangeFlags, OpenFlags, Stat};
+
+/// 测试 close_range，只关闭区间内已打开的 fd，输出 Test close_range OK! 就算正确。
+
+fn is_open(fd: usize) -> bool {
+    fstat(fd, &Stat::new()) == 0
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_close_range\0";
+    let mut fds = [0usize; 6];
+    for fd in fds.iter_mut() {
+        let ret = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(ret > 0);
+        *fd = ret as usize;
+    }
+    // leave a hole in the range
+    close(fds[2]);
+    assert!(!is_open(fds[2]));
+    assert_eq!(close_range(fds[1], fds[4], CloseRangeFlags::empty()), 0);
+    for (i, fd) in fds.iter().enumerate() {
+        assert_eq!(is_open(*fd), i == 0 || i == 5);
+    }
+    // marking close-on-exec keeps them open for now
+    assert_eq!(close_range(fds[0], usize::MAX, CloseRangeFlags::CLOEXEC), 0);
+    assert!(is_open(fds[0]) && is_open(fds[5]));
+    // a bad range does nothing
+    assert_eq!(close_range(fds[5], fds[0], CloseRangeFlags::empty()), -1);
+    assert!(is_open(fds[0]) && is_open(fds[5]));
+    assert_eq!(close_range(fds[0], usize::MAX, CloseRangeFlags::empty()), 0);
+    assert!(!is_open(fds[0]) && !is_open(fds[5]));
+    println!("Test close_range OK!");
+    0
+}
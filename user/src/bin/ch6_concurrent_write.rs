@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, exit, fork, open, read, unlink, waitpid, write, yield_, OpenFlags};
+
+/// 测试并发写不同文件，两个子进程各自交替写入一个文件并在每块之后让出 CPU，写完后两个文件的内容都与各自写入的一致，输出 Test concurrent write OK! 就算正确。
+
+const BLOCK: usize = 512;
+const BLOCKS: usize = 40;
+const FILES: [&str; 2] = ["fname_concurrent0\0", "fname_concurrent1\0"];
+
+fn byte(file: usize, block: usize) -> u8 {
+    (file * BLOCKS + block) as u8
+}
+
+fn writer(file: usize) -> i32 {
+    let fd = open(FILES[file], OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    for block in 0..BLOCKS {
+        let data = [byte(file, block); BLOCK];
+        assert_eq!(write(fd as usize, &data), BLOCK as isize);
+        // let the other writer in between
+        yield_();
+    }
+    close(fd as usize);
+    0
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pids = [0; 2];
+    for (file, pid) in pids.iter_mut().enumerate() {
+        *pid = fork();
+        if *pid == 0 {
+            exit(writer(file));
+        }
+        assert!(*pid > 0);
+    }
+    for pid in pids {
+        let mut exit_code = 0;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        assert_eq!(exit_code, 0);
+    }
+    for (file, name) in FILES.iter().enumerate() {
+        let fd = open(name, OpenFlags::RDONLY);
+        assert!(fd > 0);
+        let mut buf = [0u8; BLOCK];
+        for block in 0..BLOCKS {
+            assert_eq!(read(fd as usize, &mut buf), BLOCK as isize);
+            assert!(buf.iter().all(|&b| b == byte(file, block)));
+        }
+        assert_eq!(read(fd as usize, &mut buf), 0);
+        close(fd as usize);
+        assert_eq!(unlink(name), 0);
+    }
+    println!("Test concurrent write OK!");
+    0
+}
@@ -0,0 +1,103 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, condvar_create, condvar_signal, condvar_wait, exit, fork, lseek, mutex_blocking_create,
+    mutex_lock, mutex_unlock, open, read, unlink, waitpid, write, OpenFlags, SEEK_SET,
+};
+
+/// 测试条件变量，父进程作为生产者、子进程作为消费者，通过互斥锁和两个条件变量共用一个放在文件里的有界缓冲区，消费者按顺序取到全部数据，输出 Test condvar OK! 就算正确。
+
+const CAP: usize = 4;
+const ITEMS: usize = 64;
+
+/// `[head, count, slots...]`, only touched with `mutex` held
+struct Buffer {
+    fd: usize,
+    mutex: usize,
+    not_full: usize,
+    not_empty: usize,
+}
+
+impl Buffer {
+    fn load(&self) -> [u8; CAP + 2] {
+        let mut state = [0u8; CAP + 2];
+        assert_eq!(lseek(self.fd, 0, SEEK_SET), 0);
+        assert_eq!(read(self.fd, &mut state), state.len() as isize);
+        state
+    }
+    fn store(&self, state: &[u8; CAP + 2]) {
+        assert_eq!(lseek(self.fd, 0, SEEK_SET), 0);
+        assert_eq!(write(self.fd, state), state.len() as isize);
+    }
+    fn put(&self, item: u8) {
+        mutex_lock(self.mutex);
+        let mut state = self.load();
+        while state[1] as usize == CAP {
+            condvar_wait(self.not_full, self.mutex);
+            state = self.load();
+        }
+        let tail = (state[0] as usize + state[1] as usize) % CAP;
+        state[2 + tail] = item;
+        state[1] += 1;
+        self.store(&state);
+        condvar_signal(self.not_empty);
+        mutex_unlock(self.mutex);
+    }
+    fn take(&self) -> u8 {
+        mutex_lock(self.mutex);
+        let mut state = self.load();
+        while state[1] == 0 {
+            condvar_wait(self.not_empty, self.mutex);
+            state = self.load();
+        }
+        let item = state[2 + state[0] as usize];
+        state[0] = ((state[0] as usize + 1) % CAP) as u8;
+        state[1] -= 1;
+        self.store(&state);
+        condvar_signal(self.not_full);
+        mutex_unlock(self.mutex);
+        item
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_condvar\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let mutex = mutex_blocking_create();
+    let not_full = condvar_create();
+    let not_empty = condvar_create();
+    assert!(mutex >= 0 && not_full >= 0 && not_empty >= 0);
+    let buffer = Buffer {
+        fd: fd as usize,
+        mutex: mutex as usize,
+        not_full: not_full as usize,
+        not_empty: not_empty as usize,
+    };
+    buffer.store(&[0; CAP + 2]);
+    // locks and condition variables are shared with the child
+    let pid = fork();
+    if pid == 0 {
+        for i in 0..ITEMS {
+            assert_eq!(buffer.take(), i as u8);
+        }
+        exit(0);
+    }
+    assert!(pid > 0);
+    for i in 0..ITEMS {
+        buffer.put(i as u8);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // everything put in was taken out
+    assert_eq!(buffer.load()[1], 0);
+    close(fd as usize);
+    assert_eq!(unlink(fname), 0);
+    println!("Test condvar OK!");
+    0
+}
@@ -0,0 +1,85 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, exit, fork, frame_stats, mmap, pipe, read, waitpid, write, FrameStats};
+
+/// 测试 fork 的写时复制，fork 后父子进程共享页帧，子进程写入（包括内核代为写入）时才复制出自己的页帧且不影响父进程，子进程退出后父进程写入不再复制，输出 Test cow OK! 就算正确。
+
+const PAGES: usize = 32;
+const PAGE_SIZE: usize = 4096;
+const BASE: usize = 0x10000000;
+
+fn free_frames() -> usize {
+    let mut stats = FrameStats::default();
+    assert_eq!(frame_stats(&mut stats), 0);
+    stats.free
+}
+
+fn page(i: usize) -> *mut u8 {
+    (BASE + i * PAGE_SIZE) as *mut u8
+}
+
+fn fill(i: usize, value: u8) {
+    for j in (0..PAGE_SIZE).step_by(512) {
+        unsafe { page(i).add(j).write_volatile(value) };
+    }
+}
+
+fn check(i: usize, value: u8) {
+    for j in (0..PAGE_SIZE).step_by(512) {
+        assert_eq!(unsafe { page(i).add(j).read_volatile() }, value);
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mmap(BASE, PAGES * PAGE_SIZE, 3), 0);
+    for i in 0..PAGES {
+        fill(i, i as u8);
+    }
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let before = free_frames();
+    let pid = fork();
+    if pid == 0 {
+        // the pages of the parent are shared, not copied
+        let forked = free_frames();
+        assert!(forked + PAGES / 2 > before);
+        for i in 0..PAGES {
+            check(i, i as u8);
+        }
+        // every page written gets a frame of its own
+        for i in 0..PAGES / 2 {
+            fill(i, 0xff);
+        }
+        assert!(free_frames() + PAGES / 2 <= forked);
+        // the kernel writing into a shared page copies it as well
+        let last = unsafe { core::slice::from_raw_parts_mut(page(PAGES - 1), 4) };
+        assert_eq!(read(fds[0], last), 4);
+        assert_eq!(last, b"cow!");
+        exit(0);
+    }
+    assert!(pid > 0);
+    assert_eq!(write(fds[1], b"cow!"), 4);
+    close(fds[1]);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // nothing the child wrote shows up here
+    for i in 0..PAGES {
+        check(i, i as u8);
+    }
+    // the child is gone, its last owner writes to a page without copying it
+    let alone = free_frames();
+    for i in 0..PAGES {
+        fill(i, !(i as u8));
+    }
+    assert_eq!(free_frames(), alone);
+    for i in 0..PAGES {
+        check(i, !(i as u8));
+    }
+    println!("Test cow OK!");
+    0
+}
@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, create_with_data, exit, fork, open, read, unlink, waitpid, yield_, OpenFlags,
+};
+
+const LEN: usize = 2000;
+
+/// 测试 create_with_data，另一个进程只要能看到文件名，文件内容就已经是完整的，输出 Test create_with_data OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_create_with_data\0";
+    let mut data = [0u8; LEN];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i % 251) as u8 + 1;
+    }
+    let pid = fork();
+    if pid == 0 {
+        // poll for the name, whenever it is there the content must be complete
+        let fd = loop {
+            let fd = open(fname, OpenFlags::RDONLY);
+            if fd > 0 {
+                break fd as usize;
+            }
+            yield_();
+        };
+        let mut buf = [0u8; LEN + 1];
+        let mut len = 0;
+        loop {
+            let n = read(fd, &mut buf[len..]);
+            if n <= 0 {
+                break;
+            }
+            len += n as usize;
+        }
+        assert_eq!(len, LEN);
+        assert_eq!(buf[..LEN], data);
+        close(fd);
+        exit(0);
+    }
+    for _ in 0..5 {
+        yield_();
+    }
+    assert_eq!(create_with_data(fname, &data), 0);
+    // the name is taken now
+    assert_eq!(create_with_data(fname, &data), -1);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    unlink(fname);
+    println!("Test create_with_data OK!");
+    0
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{chdir, close, exit, fork, getcwd, open, read, unlink, waitpid, write, OpenFlags};
+
+/// 测试 chdir 与 getcwd，进入 home 目录后相对路径在其中打开与创建文件，getcwd 读回 /home，不能进入文件或不存在的路径，子进程继承工作目录，回到上级后 getcwd 为 /，输出 Test cwd OK! 就算正确。
+
+fn cwd_is(path: &str) -> bool {
+    let mut buf = [0u8; 32];
+    let len = getcwd(&mut buf);
+    len == path.len() as isize && &buf[..path.len()] == path.as_bytes() && buf[path.len()] == 0
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert!(cwd_is("/"));
+    assert_eq!(chdir("home\0"), 0);
+    assert!(cwd_is("/home"));
+    // relative to the working directory
+    let fd = open("note\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"in home"), 7);
+    close(fd as usize);
+    // not a directory, not there, too short a buffer
+    assert_eq!(chdir("/mount.img\0"), -1);
+    assert_eq!(chdir("nowhere\0"), -1);
+    assert_eq!(getcwd(&mut [0u8; 5]), -1);
+    assert!(cwd_is("/home"));
+
+    let pid = fork();
+    if pid == 0 {
+        // inherited
+        assert!(cwd_is("/home"));
+        let fd = open("note\0", OpenFlags::RDONLY);
+        assert!(fd > 0);
+        assert_eq!(chdir("/\0"), 0);
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // the child moving did not move the parent
+    assert!(cwd_is("/home"));
+
+    assert_eq!(chdir("..\0"), 0);
+    assert!(cwd_is("/"));
+    let fd = open("home/note\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 16];
+    assert_eq!(read(fd as usize, &mut buf), 7);
+    assert_eq!(&buf[..7], b"in home");
+    close(fd as usize);
+    unlink("/home/note\0");
+    println!("Test cwd OK!");
+    0
+}
@@ -0,0 +1,95 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, exit, fork, get_time, open, read, set_deadline_sched, unlink, waitpid, write, yield_,
+    OpenFlags,
+};
+
+/// 测量时长 (ms)
+const DURATION: usize = 200;
+/// 短周期任务 A 的周期与预算 (ms)
+const PERIOD_A: usize = 20;
+const RUNTIME_A: usize = 5;
+/// 长周期任务 B 的周期与预算 (ms)
+const PERIOD_B: usize = 50;
+const RUNTIME_B: usize = 25;
+
+/// Busy loop from `start` for DURATION ms, marking every ms in which we got the CPU
+fn run(start: usize, ran: &mut [u8; DURATION]) {
+    while (get_time() as usize) < start {
+        yield_();
+    }
+    loop {
+        let t = get_time() as usize - start;
+        if t >= DURATION {
+            break;
+        }
+        ran[t] = 1;
+    }
+}
+
+fn child(fname: &str, start: usize, period_ms: usize, runtime_ms: usize) -> ! {
+    let mut ran = [0u8; DURATION];
+    if period_ms != 0 {
+        assert_eq!(set_deadline_sched(period_ms, runtime_ms), 0);
+    }
+    run(start, &mut ran);
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, &ran), DURATION as isize);
+    close(fd as usize);
+    exit(0)
+}
+
+fn load(fname: &str) -> [u8; DURATION] {
+    let mut ran = [0u8; DURATION];
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(read(fd as usize, &mut ran), DURATION as isize);
+    close(fd as usize);
+    unlink(fname);
+    ran
+}
+
+/// 测试 set_deadline_sched，两个周期不同的 deadline 任务与一个普通任务一起忙等，
+/// 短周期任务在它的每个周期内都能运行，输出 Test deadline_sched OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // bad parameters
+    assert_eq!(set_deadline_sched(10, 0), -1);
+    assert_eq!(set_deadline_sched(10, 20), -1);
+    let start = get_time() as usize + 50;
+    let children = [
+        ("edf_a\0", PERIOD_A, RUNTIME_A),
+        ("edf_b\0", PERIOD_B, RUNTIME_B),
+        // a normal task competing for the CPU
+        ("edf_normal\0", 0, 0),
+    ];
+    let mut pids = [0usize; 3];
+    for (i, (fname, period_ms, runtime_ms)) in children.iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            child(fname, start, *period_ms, *runtime_ms);
+        }
+        pids[i] = pid as usize;
+    }
+    let mut exit_code: i32 = 0;
+    for pid in pids {
+        assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+        assert_eq!(exit_code, 0);
+    }
+    let ran_a = load("edf_a\0");
+    let ran_b = load("edf_b\0");
+    load("edf_normal\0");
+    // the short period task never misses a period, though it has the smallest budget
+    for period in ran_a.chunks(PERIOD_A).skip(1) {
+        assert!(period.contains(&1));
+    }
+    assert!(ran_b.contains(&1));
+    println!("Test deadline_sched OK!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, defrag, open, pipe, read, unlink, write, OpenFlags};
+
+/// 测试 defrag，两个文件交替逐块增长使第一个文件的块分散，删掉另一个后整理第一个文件，有块被移动且内容不变，再次整理不移动任何块，管道不能整理，输出 Test defrag OK! 就算正确。
+
+const BLOCK: usize = 512;
+const BLOCKS: usize = 24;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_defrag\0";
+    let filler = "fname_defrag_filler\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    let filler_fd = open(filler, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0 && filler_fd > 0);
+    let (fd, filler_fd) = (fd as usize, filler_fd as usize);
+    // growing in turns, the blocks of the two files alternate
+    for i in 0..BLOCKS {
+        let block = [i as u8; BLOCK];
+        assert_eq!(write(fd, &block), BLOCK as isize);
+        assert_eq!(write(filler_fd, &block), BLOCK as isize);
+    }
+    close(filler_fd);
+    unlink(filler);
+    assert!(defrag(fd) > 0);
+    // in one run now
+    assert_eq!(defrag(fd), 0);
+    close(fd);
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buf = [0u8; BLOCK];
+    for i in 0..BLOCKS {
+        assert_eq!(read(fd, &mut buf), BLOCK as isize);
+        assert!(buf.iter().all(|&byte| byte == i as u8));
+    }
+    close(fd);
+    unlink(fname);
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(defrag(fds[0]), -1);
+    close(fds[0]);
+    close(fds[1]);
+    println!("Test defrag OK!");
+    0
+}
@@ -0,0 +1,92 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    append, close, fallocate, get_dir_quota, open, rename, set_dir_quota, unlink, write, DirQuota,
+    OpenFlags,
+};
+
+/// 测试 set_dir_quota，根目录设置配额后写满配额内的块，再写入需要新块时返回 EDQUOT，删除文件后用量回落又可写入，子目录的配额只限制其下的文件，输出 Test dir_quota OK! 就算正确。
+
+const BLOCK_SIZE: usize = 512;
+const EDQUOT: isize = 122;
+
+fn quota(path: &str) -> DirQuota {
+    let mut quota = DirQuota::default();
+    assert_eq!(get_dir_quota(path, &mut quota), 0);
+    quota
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_quota\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // only directories take a quota
+    assert_eq!(set_dir_quota(fname, 4), -1);
+    assert_eq!(set_dir_quota("missing\0", 4), -1);
+    assert_eq!(quota("/\0").limit, 0);
+    // the files already there count toward a new quota
+    assert_eq!(set_dir_quota("/\0", u32::MAX as usize), 0);
+    let used = quota("/\0").used as usize;
+    assert_eq!(set_dir_quota("/\0", used + 4), 0);
+    let block = [b'q'; BLOCK_SIZE];
+    for _ in 0..4 {
+        assert_eq!(write(fd, &block), BLOCK_SIZE as isize);
+    }
+    let full = quota("/\0");
+    assert_eq!(full.limit as usize, used + 4);
+    assert_eq!(full.used as usize, used + 4);
+    // anything needing one more block fails and changes nothing
+    assert_eq!(write(fd, b"over"), -EDQUOT);
+    assert_eq!(append(fd, b"over"), -EDQUOT);
+    assert_eq!(fallocate(fd, 0, 5 * BLOCK_SIZE), -EDQUOT);
+    let other = open("fname_quota_other\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(other > 0);
+    assert_eq!(write(other as usize, b"x"), -EDQUOT);
+    close(other as usize);
+    assert_eq!(unlink("fname_quota_other\0"), 0);
+    assert_eq!(quota("/\0").used as usize, used + 4);
+    // removing the file gives its blocks back
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    assert_eq!(quota("/\0").used as usize, used);
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"again"), 5);
+    close(fd as usize);
+    assert_eq!(unlink(fname), 0);
+    assert_eq!(set_dir_quota("/\0", 0), 0);
+    assert_eq!(quota("/\0").limit, 0);
+    // a quota on a subdirectory caps the files under it only
+    assert_eq!(set_dir_quota("/quota\0", 2), 0);
+    let inside = open(
+        "/quota/fname_quota\0",
+        OpenFlags::CREATE | OpenFlags::WRONLY,
+    );
+    assert!(inside > 0);
+    for _ in 0..2 {
+        assert_eq!(write(inside as usize, &block), BLOCK_SIZE as isize);
+    }
+    assert_eq!(write(inside as usize, b"over"), -EDQUOT);
+    assert_eq!(quota("/quota\0").used, 2);
+    let outside = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(outside > 0);
+    for _ in 0..4 {
+        assert_eq!(write(outside as usize, &block), BLOCK_SIZE as isize);
+    }
+    assert_eq!(quota("/quota\0").used, 2);
+    close(inside as usize);
+    close(outside as usize);
+    assert_eq!(unlink(fname), 0);
+    // unlink takes a name in the root only
+    assert_eq!(rename("/quota/fname_quota\0", fname), 0);
+    assert_eq!(unlink(fname), 0);
+    assert_eq!(quota("/quota\0").used, 0);
+    assert_eq!(set_dir_quota("/quota\0", 0), 0);
+    println!("Test dir_quota OK!");
+    0
+}
@@ -0,0 +1,80 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{close, drain, exit, fork, open, sys_waitpid, unlink, write, yield_, OpenFlags};
+
+const PRODUCERS: usize = 3;
+const RECORDS: usize = 50;
+
+/// 测试 drain，多个生产者写入的同时消费者不断 drain，数据不丢不重，输出 Test drain OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_drain\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+
+    // a short buffer only takes the prefix
+    assert_eq!(write(fd, b"0123456789"), 10);
+    let mut buf = [0u8; 32];
+    assert_eq!(drain(fd, &mut buf[..4]), 4);
+    assert_eq!(&buf[..4], b"0123");
+    assert_eq!(drain(fd, &mut buf), 6);
+    assert_eq!(&buf[..6], b"456789");
+    assert_eq!(drain(fd, &mut buf), 0);
+
+    // producers share the fd offset with the consumer, so each write lands at the end
+    for id in 0..PRODUCERS {
+        if fork() == 0 {
+            for seq in 0..RECORDS {
+                assert_eq!(write(fd, &[id as u8 + 1, seq as u8]), 2);
+                yield_();
+            }
+            exit(0);
+        }
+    }
+    let mut stream: Vec<u8> = Vec::new();
+    let mut finished = 0;
+    loop {
+        // odd length, records get split across drains
+        let n = drain(fd, &mut buf[..7]);
+        assert!(n >= 0);
+        stream.extend_from_slice(&buf[..n as usize]);
+        if n > 0 {
+            continue;
+        }
+        if finished == PRODUCERS {
+            break;
+        }
+        let mut exit_code: i32 = 0;
+        match sys_waitpid(-1, &mut exit_code as *mut _) {
+            -2 => {
+                yield_();
+            }
+            pid => {
+                assert!(pid > 0);
+                assert_eq!(exit_code, 0);
+                finished += 1;
+            }
+        }
+    }
+    // every record shows up exactly once and in order
+    assert_eq!(stream.len(), PRODUCERS * RECORDS * 2);
+    let mut next = [0usize; PRODUCERS];
+    for record in stream.chunks(2) {
+        let id = record[0] as usize - 1;
+        assert_eq!(record[1] as usize, next[id]);
+        next[id] += 1;
+    }
+    assert!(next.iter().all(|n| *n == RECORDS));
+    close(fd);
+    unlink(fname);
+    println!("Test drain OK!");
+    0
+}
@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, dup, dup2, lseek, open, read, unlink, write, OpenFlags, SEEK_CUR};
+
+/// 测试 dup 和 dup2，复制出的 fd 与原 fd 共享同一个偏移，经两者的写入依次接在文件后面，关闭一个不影响另一个，对已关闭的 fd 返回 -1，dup2 到自身什么也不做，输出 Test dup OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_dup\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // the lowest free fd is taken
+    let copy = dup(fd);
+    assert!(copy > fd as isize);
+    let copy = copy as usize;
+    assert_eq!(write(fd, b"first "), 6);
+    assert_eq!(write(copy, b"second "), 7);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 13);
+    // dup2 replaces an open fd, the file it had stays open elsewhere
+    let other = open(fname, OpenFlags::WRONLY);
+    assert!(other > 0);
+    let other = other as usize;
+    assert_eq!(dup2(copy, other), other as isize);
+    assert_eq!(write(other, b"third"), 5);
+    // and grows the fd table to reach one far out
+    assert_eq!(dup2(fd, 40), 40);
+    assert_eq!(lseek(40, 0, SEEK_CUR), 18);
+    assert_eq!(dup2(other, other), other as isize);
+    assert_eq!(close(fd), 0);
+    assert_eq!(write(copy, b"!"), 1);
+    // a closed fd has nothing to duplicate
+    assert_eq!(dup(fd), -1);
+    assert_eq!(dup2(fd, copy), -1);
+    assert_eq!(dup2(fd, fd), -1);
+    for fd in [copy, other, 40] {
+        assert_eq!(close(fd), 0);
+    }
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 32];
+    assert_eq!(read(fd as usize, &mut buf), 19);
+    assert_eq!(&buf[..19], b"first second third!");
+    close(fd as usize);
+    assert_eq!(unlink(fname), 0);
+    println!("Test dup OK!");
+    0
+}
@@ -0,0 +1,78 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, eventfd, eventfd_read, eventfd_write, exit, fork, sys_eventfd, waitpid, yield_,
+    EventFdFlags,
+};
+
+const EAGAIN: isize = 11;
+
+/// 测试 eventfd，子进程写入的事件数由父进程一次读出，信号量模式每次读出 1，输出 Test eventfd OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut value = 0u64;
+    // the initial value can be read at once
+    let fd = eventfd(7, EventFdFlags::empty());
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(eventfd_read(fd, &mut value), 8);
+    assert_eq!(value, 7);
+    // the parent waits on an empty counter until the child writes to it
+    let pid = fork();
+    if pid == 0 {
+        for _ in 0..5 {
+            yield_();
+        }
+        for n in 1..=3 {
+            assert_eq!(eventfd_write(fd, n), 8);
+        }
+        exit(0);
+    }
+    let mut total = 0;
+    while total < 6 {
+        assert_eq!(eventfd_read(fd, &mut value), 8);
+        assert!(value > 0);
+        total += value;
+    }
+    assert_eq!(total, 6);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // events written by a child that is gone add up
+    let pid = fork();
+    if pid == 0 {
+        for _ in 0..4 {
+            assert_eq!(eventfd_write(fd, 10), 8);
+        }
+        exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(eventfd_read(fd, &mut value), 8);
+    assert_eq!(value, 40);
+    close(fd);
+    // semaphore mode takes one at a time
+    let fd = eventfd(3, EventFdFlags::SEMAPHORE | EventFdFlags::NONBLOCK);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    for _ in 0..3 {
+        assert_eq!(eventfd_read(fd, &mut value), 8);
+        assert_eq!(value, 1);
+    }
+    assert_eq!(eventfd_read(fd, &mut value), -EAGAIN);
+    assert_eq!(eventfd_write(fd, 2), 8);
+    assert_eq!(eventfd_read(fd, &mut value), 8);
+    assert_eq!(eventfd_read(fd, &mut value), 8);
+    assert_eq!(value, 1);
+    assert_eq!(eventfd_read(fd, &mut value), -EAGAIN);
+    // the counter can not overflow
+    assert_eq!(eventfd_write(fd, u64::MAX), 0);
+    close(fd);
+    // unknown flags
+    assert_eq!(sys_eventfd(0, 1 << 3), -1);
+    println!("Test eventfd OK!");
+    0
+}
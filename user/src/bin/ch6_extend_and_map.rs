@@ -0,0 +1,73 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, extend_and_map, fsync, get_dir_quota, munmap, open, read, set_dir_quota, write,
+    DirQuota, OpenFlags,
+};
+
+/// 测试 extend_and_map，经映射写入的数据在 fsync 或 munmap 后落到文件里，超出配额时返回 EDQUOT 且文件不变，输出 Test extend_and_map OK! 就算正确。
+
+fn read_file(name: &str, buf: &mut [u8]) -> usize {
+    let fd = open(name, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let len = read(fd as usize, buf);
+    close(fd as usize);
+    len as usize
+}
+
+const EDQUOT: isize = 122;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_extend_and_map\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"head"), 4);
+    let len = 3000;
+    let addr = extend_and_map(fd, len);
+    assert!(addr > 0);
+    let region = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) };
+    // the file grew by zeros right away
+    let mut buf = [0xffu8; 4096];
+    assert_eq!(read_file(fname, &mut buf), 4 + len);
+    assert!(buf[4..4 + len].iter().all(|b| *b == 0));
+    for (i, byte) in region.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    assert_eq!(fsync(fd), 0);
+    assert_eq!(read_file(fname, &mut buf), 4 + len);
+    assert_eq!(&buf[..4], b"head");
+    assert!(buf[4..4 + len]
+        .iter()
+        .enumerate()
+        .all(|(i, b)| *b == (i % 251) as u8));
+    // munmap writes back as well
+    let addr2 = extend_and_map(fd, 100);
+    assert!(addr2 > 0 && addr2 != addr);
+    let region2 = unsafe { core::slice::from_raw_parts_mut(addr2 as *mut u8, 100) };
+    region2.fill(b'z');
+    assert_eq!(munmap(addr2 as usize, 100), 0);
+    assert_eq!(read_file(fname, &mut buf), 4 + len + 100);
+    assert!(buf[4 + len..4 + len + 100].iter().all(|b| *b == b'z'));
+    // a part that does not fit in the quota leaves the file as it was
+    let mut quota = DirQuota::default();
+    assert_eq!(set_dir_quota("/\0", u32::MAX as usize), 0);
+    assert_eq!(get_dir_quota("/\0", &mut quota), 0);
+    assert_eq!(set_dir_quota("/\0", quota.used as usize + 2), 0);
+    assert_eq!(extend_and_map(fd, 4 * 512), -EDQUOT);
+    assert_eq!(set_dir_quota("/\0", 0), 0);
+    assert_eq!(read_file(fname, &mut buf), 4 + len + 100);
+    // only writable regular files can be extended
+    let ro = open(fname, OpenFlags::RDONLY);
+    assert!(ro > 0);
+    assert_eq!(extend_and_map(ro as usize, 100), -1);
+    close(ro as usize);
+    assert_eq!(extend_and_map(0, 100), -1);
+    close(fd);
+    println!("Test extend_and_map OK!");
+    0
+}
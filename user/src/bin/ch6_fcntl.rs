@@ -0,0 +1,82 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, dup2, exec, exit, fcntl, fork, fstat, lseek, open, unlink, waitpid, write, OpenFlags,
+    Stat, FD_CLOEXEC, F_GETFD, F_GETFL, F_SETFD, F_SETFL, SEEK_END, SEEK_SET,
+};
+
+/// 测试 fcntl，F_SETFD 设置 close-on-exec 的 fd 在 exec 之后被关闭，其他 fd 仍然打开，F_SETFL 打开 APPEND 之后写入都追加到文件末尾，对已关闭的 fd 操作返回 -1，输出 Test fcntl OK! 就算正确。
+
+/// Marked close-on-exec
+const CLOEXEC_FD: usize = 10;
+/// Kept across exec
+const KEPT_FD: usize = 11;
+
+fn is_open(fd: usize) -> bool {
+    fstat(fd, &Stat::new()) == 0
+}
+
+#[no_mangle]
+pub fn main(argc: usize, _argv: &[&str]) -> i32 {
+    if argc > 1 {
+        // run again by exec
+        assert!(!is_open(CLOEXEC_FD));
+        assert!(is_open(KEPT_FD));
+        assert_eq!(fcntl(KEPT_FD, F_GETFD, 0), 0);
+        return 0;
+    }
+    let fname = "fname_fcntl\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"abc"), 3);
+    // status flags of the open file
+    assert_eq!(fcntl(fd, F_GETFL, 0), 0);
+    assert_eq!(fcntl(fd, F_SETFL, OpenFlags::APPEND.bits() as usize), 0);
+    assert_eq!(fcntl(fd, F_GETFL, 0), OpenFlags::APPEND.bits() as isize);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(write(fd, b"d"), 1);
+    assert_eq!(lseek(fd, 0, SEEK_END), 4);
+    assert_eq!(fcntl(fd, F_SETFL, 0), 0);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(write(fd, b"e"), 1);
+    assert_eq!(lseek(fd, 0, SEEK_END), 4);
+
+    // fd flags, only of the fd itself
+    assert_eq!(dup2(fd, CLOEXEC_FD), CLOEXEC_FD as isize);
+    assert_eq!(dup2(fd, KEPT_FD), KEPT_FD as isize);
+    close(fd);
+    assert_eq!(fcntl(CLOEXEC_FD, F_GETFD, 0), 0);
+    assert_eq!(fcntl(CLOEXEC_FD, F_SETFD, FD_CLOEXEC), 0);
+    assert_eq!(fcntl(CLOEXEC_FD, F_GETFD, 0), FD_CLOEXEC as isize);
+    assert_eq!(fcntl(KEPT_FD, F_GETFD, 0), 0);
+    // not on a closed fd
+    assert_eq!(fcntl(fd, F_SETFD, FD_CLOEXEC), -1);
+    assert_eq!(fcntl(fd, F_GETFD, 0), -1);
+
+    let pid = fork();
+    if pid == 0 {
+        // inherited by fork, dropped by exec
+        assert_eq!(fcntl(CLOEXEC_FD, F_GETFD, 0), FD_CLOEXEC as isize);
+        let args = [
+            "ch6_fcntl\0".as_ptr(),
+            "child\0".as_ptr(),
+            core::ptr::null::<u8>(),
+        ];
+        exec("ch6_fcntl\0", &args);
+        exit(-1);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // the parent keeps both
+    assert!(is_open(CLOEXEC_FD) && is_open(KEPT_FD));
+    close(CLOEXEC_FD);
+    close(KEPT_FD);
+    unlink(fname);
+    println!("Test fcntl OK!");
+    0
+}
@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, fd_path, fstat, link, open, rename, unlink, OpenFlags, Stat};
+
+/// 测试 fd_path，由 fd 得到的绝对路径能重新打开同一个 inode，子目录下的文件也带上目录，输出 Test fd_path OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_fd_path\0";
+    let abs_fname = "/fname_fd_path\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buf = [0u8; 32];
+    let len = fd_path(fd, &mut buf);
+    assert!(len > 0);
+    let len = len as usize;
+    assert_eq!(&buf[..len + 1], abs_fname.as_bytes());
+    // the path leads back to the same inode
+    let fd2 = open(core::str::from_utf8(&buf[..len + 1]).unwrap(), OpenFlags::RDONLY);
+    assert!(fd2 > 0);
+    let fd2 = fd2 as usize;
+    let stat = Stat::new();
+    let stat2 = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(fstat(fd2, &stat2), 0);
+    assert_eq!(stat.ino, stat2.ino);
+    close(fd2);
+    // the buffer must hold the trailing '\0'
+    assert_eq!(fd_path(fd, &mut buf[..len]), -1);
+    // with a hard link, either name is fine
+    let lname = "linkname_fd_path\0";
+    let abs_lname = "/linkname_fd_path\0";
+    assert_eq!(link(fname, lname), 0);
+    unlink(fname);
+    assert_eq!(fd_path(fd, &mut buf), abs_lname.len() as isize - 1);
+    assert_eq!(&buf[..abs_lname.len()], abs_lname.as_bytes());
+    // no path once unlinked
+    unlink(lname);
+    assert_eq!(fd_path(fd, &mut buf), -1);
+    close(fd);
+    // a file under a directory has it in its path, a directory too
+    let nested = "/home/fname_fd_path\0";
+    let fd = open(nested, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(fd_path(fd as usize, &mut buf), nested.len() as isize - 1);
+    assert_eq!(&buf[..nested.len()], nested.as_bytes());
+    close(fd as usize);
+    let dir = open("/home\0", OpenFlags::RDONLY);
+    assert!(dir > 0);
+    assert_eq!(fd_path(dir as usize, &mut buf), 5);
+    assert_eq!(&buf[..6], b"/home\0");
+    close(dir as usize);
+    // unlink takes a name in the root only
+    assert_eq!(rename(nested, fname), 0);
+    assert_eq!(unlink(fname), 0);
+    println!("Test fd_path OK!");
+    0
+}
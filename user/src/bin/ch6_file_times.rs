@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, fstat, open, read, sleep, unlink, write, OpenFlags, Stat};
+
+/// 测试文件时间戳，新建文件的三个时间相同，写入后 mtime 与 ctime 前进而 atime 不变，读出后只有 atime 前进，输出 Test file times OK! 就算正确。
+
+fn stat_of(fd: usize) -> Stat {
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    stat
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_times\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let created = stat_of(fd);
+    assert!(created.mtime > 0);
+    assert_eq!(created.atime, created.mtime);
+    assert_eq!(created.ctime, created.mtime);
+    // the clock counts microseconds, wait a bit so that every step shows
+    sleep(2);
+    assert_eq!(write(fd, b"hello"), 5);
+    let written = stat_of(fd);
+    assert!(written.mtime > created.mtime);
+    assert_eq!(written.ctime, written.mtime);
+    assert_eq!(written.atime, created.atime);
+    sleep(2);
+    close(fd);
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buf = [0u8; 8];
+    assert_eq!(read(fd, &mut buf), 5);
+    let read_back = stat_of(fd);
+    assert!(read_back.atime > written.mtime);
+    assert_eq!(read_back.mtime, written.mtime);
+    assert_eq!(read_back.ctime, written.ctime);
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    println!("Test file times OK!");
+    0
+}
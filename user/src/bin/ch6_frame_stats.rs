@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, frame_stats, mmap, pipe, read, waitpid, write, FrameStats};
+
+/// 测试 frame_stats，两个进程交替分配页帧后先后退出，报告的空闲页帧数、分配与释放次数随之变化且最长连续空闲段不超过空闲页帧数，输出 Test frame_stats OK! 就算正确。
+
+const ROUNDS: usize = 16;
+const PAGE_SIZE: usize = 4096;
+const BASE: usize = 0x10000000;
+
+fn stats() -> FrameStats {
+    let mut stats = FrameStats::default();
+    assert_eq!(frame_stats(&mut stats), 0);
+    // every frame is either free or allocated and not freed yet
+    assert_eq!(stats.free + stats.allocs - stats.frees, stats.total);
+    assert!(stats.largest_free_run > 0 && stats.largest_free_run <= stats.free);
+    stats
+}
+
+/// Take turns with the other child: wait for the token on `mine`, map one page,
+/// then pass the token on through `theirs`, so the frames of the two interleave
+fn take_turns(mine: usize, theirs: usize, done: usize, quit: usize) -> ! {
+    let mut token = [0u8; 1];
+    for i in 0..ROUNDS {
+        assert_eq!(read(mine, &mut token), 1);
+        let addr = BASE + i * PAGE_SIZE;
+        assert_eq!(mmap(addr, PAGE_SIZE, 3), 0);
+        unsafe { (addr as *mut u8).write_volatile(i as u8) };
+        assert_eq!(write(theirs, &token), 1);
+    }
+    assert_eq!(write(done, b"d"), 1);
+    assert_eq!(read(quit, &mut token), 1);
+    exit(0);
+}
+
+fn reap(pid: isize) {
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut to_a = [0usize; 2];
+    let mut to_b = [0usize; 2];
+    let mut done = [0usize; 2];
+    let mut quit_a = [0usize; 2];
+    let mut quit_b = [0usize; 2];
+    for p in [&mut to_a, &mut to_b, &mut done, &mut quit_a, &mut quit_b] {
+        assert_eq!(pipe(p), 0);
+    }
+    let before = stats();
+    let a = fork();
+    if a == 0 {
+        take_turns(to_a[0], to_b[1], done[1], quit_a[0]);
+    }
+    let b = fork();
+    if b == 0 {
+        // the last token of b has no one to go to, it lands back in the pipe of a
+        take_turns(to_b[0], to_a[1], done[1], quit_b[0]);
+    }
+    assert_eq!(write(to_a[1], b"t"), 1);
+    let mut buf = [0u8; 2];
+    assert_eq!(read(done[0], &mut buf[..1]), 1);
+    assert_eq!(read(done[0], &mut buf[1..]), 1);
+    // both children hold their pages now
+    let held = stats();
+    assert_eq!(held.total, before.total);
+    assert!(held.allocs - before.allocs >= 2 * ROUNDS);
+    assert!(held.free + 2 * ROUNDS <= before.free);
+    // a leaves a free frame between each two of b
+    assert_eq!(write(quit_a[1], b"q"), 1);
+    reap(a);
+    let half = stats();
+    assert!(half.frees - held.frees >= ROUNDS);
+    assert!(half.free >= held.free + ROUNDS);
+    assert_eq!(write(quit_b[1], b"q"), 1);
+    reap(b);
+    let after = stats();
+    assert!(after.frees - half.frees >= ROUNDS);
+    assert!(after.free >= half.free + ROUNDS);
+    // the frames never handed out make one long run, which the frees can only extend
+    assert!(after.largest_free_run >= held.largest_free_run);
+    println!(
+        "free frames: {} of {}, largest free run: {}",
+        after.free, after.total, after.largest_free_run
+    );
+    println!("Test frame_stats OK!");
+    0
+}
@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{fs_info, FsInfo};
+
+// easy-fs-fuse 打包镜像时使用的参数
+const BLOCK_SZ: u32 = 512;
+const BLOCK_NUM: u32 = 131072;
+const INODE_BITMAP_BLOCKS: u32 = 1;
+
+/// 测试 fs_info，读取超级块中的文件系统布局，输出 Test fs_info OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let info = FsInfo::default();
+    assert_eq!(fs_info(&info), 0);
+    assert_eq!(info.block_size, BLOCK_SZ);
+    assert_eq!(info.total_blocks, BLOCK_NUM);
+    assert_eq!(info.inode_bitmap_blocks, INODE_BITMAP_BLOCKS);
+    assert_eq!(info.inode_num, INODE_BITMAP_BLOCKS * BLOCK_SZ * 8);
+    // super block + all areas cover the whole device
+    assert_eq!(
+        1 + info.inode_bitmap_blocks
+            + info.inode_area_blocks
+            + info.data_bitmap_blocks
+            + info.data_area_blocks
+            + info.journal_blocks,
+        info.total_blocks
+    );
+    // areas are laid out back to back
+    assert_eq!(info.inode_bitmap_start, 1);
+    assert_eq!(
+        info.inode_area_start,
+        info.inode_bitmap_start + info.inode_bitmap_blocks
+    );
+    assert_eq!(
+        info.data_bitmap_start,
+        info.inode_area_start + info.inode_area_blocks
+    );
+    assert_eq!(
+        info.data_area_start,
+        info.data_bitmap_start + info.data_bitmap_blocks
+    );
+    assert_eq!(
+        info.journal_start,
+        info.data_area_start + info.data_area_blocks
+    );
+    println!("Test fs_info OK!");
+    0
+}
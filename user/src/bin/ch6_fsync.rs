@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, fsync, open, read, unlink, write, OpenFlags};
+
+/// 测试 fsync，对刚创建还没有数据块的文件和写入数据后的文件 fsync 都返回 0，数据读回不变，对不存在的 fd 和标准输入返回 -1，输出 Test fsync OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_fsync\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // nothing but the disk inode to write back
+    assert_eq!(fsync(fd), 0);
+    let data = [0x5au8; 1500];
+    assert_eq!(write(fd, &data), data.len() as isize);
+    assert_eq!(fsync(fd), 0);
+    // once more with nothing dirty
+    assert_eq!(fsync(fd), 0);
+    close(fd);
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 2048];
+    assert_eq!(read(fd as usize, &mut buf), data.len() as isize);
+    assert_eq!(buf[..data.len()], data);
+    close(fd as usize);
+    assert_eq!(fsync(fd as usize), -1);
+    assert_eq!(fsync(0), -1);
+    assert_eq!(unlink(fname), 0);
+    println!("Test fsync OK!");
+    0
+}
@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, ftruncate, lseek, open, read, unlink, write, OpenFlags, SEEK_END, SEEK_SET};
+
+const BLOCK: usize = 512;
+
+/// 测试 ftruncate，写满 160 块后截到第 11 块中间，只剩截断处之前的数据，再扩到 30 块，新增部分全部读出 0，输出 Test ftruncate OK! 就算正确。
+
+fn byte(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_ftruncate\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buf = [0u8; BLOCK];
+    for i in 0..160 {
+        for (j, b) in buf.iter_mut().enumerate() {
+            *b = byte(i * BLOCK + j);
+        }
+        assert_eq!(write(fd, &buf), BLOCK as isize);
+    }
+    // down past the indirect blocks into the middle of a direct one
+    let cut = 10 * BLOCK + 100;
+    assert_eq!(ftruncate(fd, cut), 0);
+    assert_eq!(lseek(fd, 0, SEEK_END), cut as isize);
+    // up again, everything past the cut reads as zeros
+    let grown = 30 * BLOCK;
+    assert_eq!(ftruncate(fd, grown), 0);
+    assert_eq!(lseek(fd, 0, SEEK_END), grown as isize);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let mut pos = 0;
+    loop {
+        let n = read(fd, &mut buf);
+        if n <= 0 {
+            break;
+        }
+        for (j, &b) in buf[..n as usize].iter().enumerate() {
+            let i = pos + j;
+            assert_eq!(b, if i < cut { byte(i) } else { 0 });
+        }
+        pos += n as usize;
+    }
+    assert_eq!(pos, grown);
+    close(fd);
+    // a read-only file cannot be truncated
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(ftruncate(fd as usize, 0), -1);
+    close(fd as usize);
+    assert_eq!(unlink(fname), 0);
+    println!("Test ftruncate OK!");
+    0
+}
@@ -0,0 +1,94 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::sync::atomic::{AtomicU32, Ordering};
+use user_lib::{exit, fork, futex_wait, futex_wake, shmat, shmget, waitpid, yield_};
+
+/// 测试 futex，父子进程在同一共享段的不同虚拟地址上，用基于 futex 的互斥锁各给共享计数加 200 次，最后计数为 400，值不符时 futex_wait 立即返回 -1，没有等待者时 futex_wake 唤醒 0 个，输出 Test futex OK! 就算正确。
+
+const KEY: usize = 0x4655;
+const SIZE: usize = 4096;
+const ADDS: u32 = 200;
+
+/// Lock word states
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+/// locked, and someone may be sleeping on it
+const CONTENDED: u32 = 2;
+
+/// A mutex living in the segment at `addr`: the lock word, then the counter it guards
+struct FutexMutex(usize);
+
+impl FutexMutex {
+    fn word(&self) -> &AtomicU32 {
+        unsafe { &*(self.0 as *const AtomicU32) }
+    }
+    fn counter(&self) -> *mut u32 {
+        (self.0 + 4) as *mut u32
+    }
+    fn lock(&self) {
+        let word = self.word();
+        let mut state =
+            match word.compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(state) => state,
+            };
+        if state != CONTENDED {
+            state = word.swap(CONTENDED, Ordering::Acquire);
+        }
+        while state != UNLOCKED {
+            futex_wait(word.as_ptr(), CONTENDED);
+            state = word.swap(CONTENDED, Ordering::Acquire);
+        }
+    }
+    fn unlock(&self) {
+        let word = self.word();
+        if word.fetch_sub(1, Ordering::Release) != LOCKED {
+            word.store(UNLOCKED, Ordering::Release);
+            futex_wake(word.as_ptr(), 1);
+        }
+    }
+    fn add(&self) {
+        for _ in 0..ADDS {
+            self.lock();
+            let value = unsafe { self.counter().read_volatile() };
+            // let the other one run into the held lock
+            yield_();
+            unsafe { self.counter().write_volatile(value + 1) };
+            self.unlock();
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let id = shmget(KEY, SIZE);
+    assert!(id > 0);
+    let addr = shmat(id as usize);
+    assert!(addr > 0);
+    let mutex = FutexMutex(addr as usize);
+    let word = mutex.word().as_ptr();
+    assert_eq!(futex_wait(word, 1), -1);
+    assert_eq!(futex_wake(word, 1), 0);
+    // not a word
+    assert_eq!(futex_wake((addr + 1) as *const u32, 1), -1);
+
+    let pid = fork();
+    if pid == 0 {
+        // the same lock through another address
+        let again = shmat(id as usize);
+        assert!(again > 0 && again != addr);
+        FutexMutex(again as usize).add();
+        exit(0);
+    }
+    mutex.add();
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(unsafe { mutex.counter().read_volatile() }, 2 * ADDS);
+    assert_eq!(mutex.word().load(Ordering::Relaxed), UNLOCKED);
+    println!("Test futex OK!");
+    0
+}
@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, fstat, getdents, open, unlink, Dirent, OpenFlags, Stat, StatMode};
+
+/// 测试 getdents，在根目录创建 100 个文件后每次读 3 项读完整个目录，每个文件恰好出现一次且类型和 inode 号正确，缓冲区不足一项或对普通文件调用时返回 -1，输出 Test getdents OK! 就算正确。
+
+const FILES: usize = 100;
+
+fn name(i: usize, buf: &mut [u8; 16]) -> &str {
+    let s = b"getdents_";
+    buf[..s.len()].copy_from_slice(s);
+    buf[s.len()] = b'0' + (i / 100) as u8;
+    buf[s.len() + 1] = b'0' + (i / 10 % 10) as u8;
+    buf[s.len() + 2] = b'0' + (i % 10) as u8;
+    buf[s.len() + 3] = 0;
+    core::str::from_utf8(&buf[..s.len() + 4]).unwrap()
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut buf = [0u8; 16];
+    let mut inos = [0u64; FILES];
+    for (i, ino) in inos.iter_mut().enumerate() {
+        let fd = open(name(i, &mut buf), OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(fd > 0);
+        let stat = Stat::new();
+        assert_eq!(fstat(fd as usize, &stat), 0);
+        *ino = stat.ino;
+        close(fd as usize);
+    }
+    let dir = open("/\0", OpenFlags::RDONLY);
+    assert!(dir > 0);
+    let dir = dir as usize;
+    // not even room for one entry
+    assert_eq!(getdents(dir, &mut []), -1);
+    let mut seen = [0usize; FILES];
+    let mut dots = 0;
+    let mut dirents: [Dirent; 3] = Default::default();
+    loop {
+        let n = getdents(dir, &mut dirents);
+        assert!(n >= 0);
+        if n == 0 {
+            break;
+        }
+        let n = n as usize / core::mem::size_of::<Dirent>();
+        assert!(n <= dirents.len());
+        for d in &dirents[..n] {
+            let entry = d.name();
+            if entry == "." || entry == ".." {
+                assert_eq!(d.mode, StatMode::DIR);
+                dots += 1;
+                continue;
+            }
+            let Some(i) = entry
+                .strip_prefix("getdents_")
+                .and_then(|s| s.parse::<usize>().ok())
+            else {
+                continue;
+            };
+            assert_eq!(d.mode, StatMode::FILE);
+            assert_eq!(d.ino, inos[i]);
+            seen[i] += 1;
+        }
+    }
+    assert_eq!(dots, 2);
+    assert!(seen.iter().all(|&c| c == 1));
+    // reading on past the end keeps returning 0
+    assert_eq!(getdents(dir, &mut dirents), 0);
+    close(dir);
+    // only directories have entries
+    let fd = open(name(0, &mut buf), OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(getdents(fd as usize, &mut dirents), -1);
+    close(fd as usize);
+    for i in 0..FILES {
+        assert_eq!(unlink(name(i, &mut buf)), 0);
+    }
+    println!("Test getdents OK!");
+    0
+}
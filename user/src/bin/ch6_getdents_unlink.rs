@@ -0,0 +1,88 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, getdents, open, unlink, Dirent, OpenFlags};
+
+/// 测试 getdents 中途删除文件，读过几项之后删除已经读过的文件和一个还没读到的文件，接着读下去的结果既不重复也不遗漏，被删的那个不再出现，之后新建同名文件会重新出现，输出 Test getdents_unlink OK! 就算正确。
+
+const FILES: usize = 30;
+
+fn name(i: usize, buf: &mut [u8; 16]) -> &str {
+    let s = b"tomb_";
+    buf[..s.len()].copy_from_slice(s);
+    buf[s.len()] = b'0' + (i / 10) as u8;
+    buf[s.len() + 1] = b'0' + (i % 10) as u8;
+    buf[s.len() + 2] = 0;
+    core::str::from_utf8(&buf[..s.len() + 3]).unwrap()
+}
+
+/// Read on from the cursor of `dir` till the end, counting each of our files seen
+fn read_rest(dir: usize, seen: &mut [usize; FILES]) {
+    let mut dirents: [Dirent; 3] = Default::default();
+    loop {
+        let n = getdents(dir, &mut dirents);
+        assert!(n >= 0);
+        if n == 0 {
+            break;
+        }
+        for d in &dirents[..n as usize / core::mem::size_of::<Dirent>()] {
+            if let Some(i) = d
+                .name()
+                .strip_prefix("tomb_")
+                .and_then(|s| s.parse::<usize>().ok())
+            {
+                seen[i] += 1;
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut buf = [0u8; 16];
+    for i in 0..FILES {
+        let fd = open(name(i, &mut buf), OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(fd > 0);
+        close(fd as usize);
+    }
+    let dir = open("/\0", OpenFlags::RDONLY);
+    assert!(dir > 0);
+    let dir = dir as usize;
+    // up to the first of our files
+    let mut seen = [0usize; FILES];
+    let mut dirents: [Dirent; 1] = Default::default();
+    while seen[0] == 0 {
+        assert!(getdents(dir, &mut dirents) > 0);
+        if dirents[0].name() == "tomb_00" {
+            seen[0] = 1;
+        }
+    }
+    // behind the cursor, and one ahead of it
+    assert_eq!(unlink(name(0, &mut buf)), 0);
+    assert_eq!(unlink(name(20, &mut buf)), 0);
+    read_rest(dir, &mut seen);
+    for (i, &count) in seen.iter().enumerate() {
+        assert_eq!(count, if i == 20 { 0 } else { 1 });
+    }
+    close(dir);
+
+    // a new file takes the place of one removed
+    let fd = open(name(20, &mut buf), OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    let dir = open("/\0", OpenFlags::RDONLY);
+    assert!(dir > 0);
+    let mut seen = [0usize; FILES];
+    read_rest(dir as usize, &mut seen);
+    for (i, &count) in seen.iter().enumerate() {
+        assert_eq!(count, if i == 0 { 0 } else { 1 });
+    }
+    close(dir as usize);
+    for i in 1..FILES {
+        assert_eq!(unlink(name(i, &mut buf)), 0);
+    }
+    println!("Test getdents_unlink OK!");
+    0
+}
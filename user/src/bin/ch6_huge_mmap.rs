@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    exit, fork, frame_stats, mmap, munmap, waitpid, FrameStats, MMAP_HUGE, MMAP_HUGE_ONLY,
+};
+
+/// 测试 mmap 的 MMAP_HUGE 标志，对齐的 2MiB 区域只用一个大页表项映射、不对齐时退回普通页，部分 munmap 大页失败，输出 Test huge_mmap OK! 就算正确。
+
+const PAGE_SIZE: usize = 4096;
+const HUGE_PAGE_SIZE: usize = 0x20_0000;
+const PAGES: usize = HUGE_PAGE_SIZE / PAGE_SIZE;
+/// Each in a 1GiB region of its own, so the level-1 tables are new
+const HUGE_BASE: usize = 0x8000_0000;
+const SMALL_BASE: usize = 0xc000_0000;
+
+fn allocs() -> usize {
+    let mut stats = FrameStats::default();
+    assert_eq!(frame_stats(&mut stats), 0);
+    stats.allocs
+}
+
+fn fill(base: usize, tag: u8) {
+    for i in 0..PAGES {
+        unsafe { ((base + i * PAGE_SIZE) as *mut u8).write_volatile(tag ^ i as u8) };
+    }
+}
+
+fn check(base: usize, tag: u8) {
+    for i in 0..PAGES {
+        assert_eq!(
+            unsafe { ((base + i * PAGE_SIZE) as *const u8).read_volatile() },
+            tag ^ i as u8
+        );
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // a megapage is a single level-1 PTE: its frames and the new level-1 table, no level-0 table
+    let before = allocs();
+    assert_eq!(mmap(HUGE_BASE, HUGE_PAGE_SIZE, 3 | MMAP_HUGE), 0);
+    assert_eq!(allocs() - before, PAGES + 1);
+    fill(HUGE_BASE, 0x5a);
+    check(HUGE_BASE, 0x5a);
+    // not aligned: pages instead, whose range needs two level-0 tables
+    let start = SMALL_BASE + PAGE_SIZE;
+    let before = allocs();
+    assert_eq!(mmap(start, HUGE_PAGE_SIZE, 3 | MMAP_HUGE_ONLY), -1);
+    assert_eq!(allocs(), before);
+    assert_eq!(mmap(start, HUGE_PAGE_SIZE, 3 | MMAP_HUGE), 0);
+    assert_eq!(allocs() - before, PAGES + 3);
+    fill(start, 0xa5);
+    // a child gets a copy of the megapage
+    let pid = fork();
+    if pid == 0 {
+        check(HUGE_BASE, 0x5a);
+        fill(HUGE_BASE, 0x33);
+        check(start, 0xa5);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    check(HUGE_BASE, 0x5a);
+    // a megapage is unmapped as a whole or not at all
+    assert_eq!(munmap(HUGE_BASE, PAGE_SIZE), -1);
+    assert_eq!(
+        munmap(HUGE_BASE + PAGE_SIZE, HUGE_PAGE_SIZE - PAGE_SIZE),
+        -1
+    );
+    check(HUGE_BASE, 0x5a);
+    assert_eq!(munmap(HUGE_BASE, HUGE_PAGE_SIZE), 0);
+    // the level-1 slot is free again, so a megapage fits without a new table
+    let before = allocs();
+    assert_eq!(mmap(HUGE_BASE, HUGE_PAGE_SIZE, 3 | MMAP_HUGE_ONLY), 0);
+    assert_eq!(allocs() - before, PAGES);
+    check(HUGE_BASE, 0);
+    println!("Test huge_mmap OK!");
+    0
+}
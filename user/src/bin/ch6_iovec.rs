@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, open, readv, unlink, writev, IoVec, OpenFlags};
+
+/// 测试 writev 与 readv，用三段缓冲区（中间一段长度为 0）一次写入，再用两段长度不同的缓冲区一次读回，内容与写入时一致，输出 Test iovec OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_iovec\0";
+    let head = b"scatter-";
+    let empty = b"";
+    let tail = b"gather records";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let iov = [
+        IoVec::from_bytes(head),
+        IoVec::from_bytes(empty),
+        IoVec::from_bytes(tail),
+    ];
+    let total = head.len() + tail.len();
+    assert_eq!(writev(fd as usize, &iov), total as isize);
+    close(fd as usize);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    // split elsewhere than it was written, the second one longer than what is left
+    let mut first = [0u8; 5];
+    let mut second = [0u8; 64];
+    let iov = [IoVec::from_slice(&mut first), IoVec::from_slice(&mut second)];
+    assert_eq!(readv(fd as usize, &iov), total as isize);
+    assert_eq!(&first, b"scatt");
+    assert_eq!(&second[..total - first.len()], b"er-gather records");
+    // at the end, nothing left to read
+    assert_eq!(readv(fd as usize, &iov), 0);
+    close(fd as usize);
+    assert_eq!(writev(fd as usize, &[IoVec::from_bytes(head)]), -1);
+    assert_eq!(unlink(fname), 0);
+    println!("Test iovec OK!");
+    0
+}
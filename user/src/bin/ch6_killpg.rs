@@ -0,0 +1,73 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    exit, fork, getpgid, getpid, killpg, setpgid, sleep, waitpid, waitpid_status, wifsignaled,
+    wtermsig, SIGKILL,
+};
+
+/// 测试进程组，fork 出的子进程默认与父进程同组，把三个子进程移入以第一个为首的新组后，一次 killpg 就把三个都杀死，组外的进程不受影响，不存在的组与非子进程不能设置，输出 Test killpg OK! 就算正确。
+
+fn forever() -> ! {
+    loop {
+        sleep(10);
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let own_group = getpgid(0);
+    assert!(own_group > 0);
+    let mut members = [0isize; 3];
+    for member in members.iter_mut() {
+        let pid = fork();
+        if pid == 0 {
+            forever();
+        }
+        // the group goes with a fork
+        assert_eq!(getpgid(pid as usize), own_group);
+        *member = pid;
+    }
+    let outsider = fork();
+    if outsider == 0 {
+        sleep(50);
+        exit(7);
+    }
+
+    // the first one leads a new group, the other two join it
+    let leader = members[0] as usize;
+    assert_eq!(setpgid(leader, 0), 0);
+    assert_eq!(getpgid(leader), leader as isize);
+    for &pid in members[1..].iter() {
+        assert_eq!(setpgid(pid as usize, leader), 0);
+        assert_eq!(getpgid(pid as usize), leader as isize);
+    }
+    assert_eq!(getpgid(0), own_group);
+    // no group of that id, not a child, no such process
+    let none = leader + 1000;
+    assert_eq!(setpgid(outsider as usize, none), -1);
+    assert_eq!(setpgid(none, 0), -1);
+    assert_eq!(getpgid(none), -1);
+    assert_eq!(killpg(none, SIGKILL), -1);
+    // a bad signal, and signal 0 that only checks
+    assert_eq!(killpg(leader, 64), -1);
+    assert_eq!(killpg(leader, 0), 0);
+
+    assert_eq!(killpg(leader, SIGKILL), 0);
+    for &pid in members.iter() {
+        let mut status = 0;
+        assert_eq!(waitpid_status(pid, &mut status), pid);
+        assert!(wifsignaled(status));
+        assert_eq!(wtermsig(status), SIGKILL);
+    }
+    // nobody left in it
+    assert_eq!(killpg(leader, 0), -1);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(outsider as usize, &mut exit_code), outsider);
+    assert_eq!(exit_code, 7);
+    assert!(getpid() > 0);
+    println!("Test killpg OK!");
+    0
+}
@@ -0,0 +1,80 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, exit, fork, frame_stats, mmap, munmap, pipe, read, waitpid, write, FrameStats, SIGSEGV,
+};
+
+/// 测试 mmap 按需分配页帧，映射 16 页后只访问其中两页，只分配了两个页帧，内核代为访问的页也能分到页帧，访问已 munmap 的页或写只读页的进程被杀死，输出 Test lazy_mmap OK! 就算正确。
+
+const PAGES: usize = 16;
+const PAGE_SIZE: usize = 4096;
+/// one page touched up front, the page tables of the whole range are there before counting
+const GUARD: usize = 0x10000000;
+const BASE: usize = GUARD + PAGE_SIZE;
+
+fn allocs() -> usize {
+    let mut stats = FrameStats::default();
+    assert_eq!(frame_stats(&mut stats), 0);
+    stats.allocs
+}
+
+fn page(i: usize) -> *mut u8 {
+    (BASE + i * PAGE_SIZE) as *mut u8
+}
+
+/// Run `f` in a child, it has to be killed by a page fault
+fn killed(f: fn()) {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        exit(0);
+    }
+    assert!(pid > 0);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -SIGSEGV);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mmap(GUARD, PAGE_SIZE, 3), 0);
+    unsafe { (GUARD as *mut u8).write_volatile(1) };
+    let before = allocs();
+    assert_eq!(mmap(BASE, PAGES * PAGE_SIZE, 3), 0);
+    assert_eq!(allocs(), before);
+    // only the pages touched get a frame
+    unsafe {
+        page(3).write_volatile(3);
+        page(12).add(100).write_volatile(12);
+        assert_eq!(page(3).read_volatile(), 3);
+        assert_eq!(page(12).add(100).read_volatile(), 12);
+        assert_eq!(page(12).read_volatile(), 0);
+    }
+    assert_eq!(allocs(), before + 2);
+    // the range is taken, even where nothing is mapped yet
+    assert_eq!(mmap(page(5) as usize, PAGE_SIZE, 3), -1);
+    // the kernel touching a page gives it a frame as well
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(write(fds[1], b"lazy"), 4);
+    let untouched = unsafe { core::slice::from_raw_parts_mut(page(7), 4) };
+    assert_eq!(read(fds[0], untouched), 4);
+    assert_eq!(untouched, b"lazy");
+    assert_eq!(allocs(), before + 3);
+    close(fds[0]);
+    close(fds[1]);
+    // pages unmapped before they were touched are gone all the same
+    assert_eq!(munmap(page(8) as usize, 8 * PAGE_SIZE), 0);
+    killed(|| unsafe { page(9).write_volatile(9) });
+    assert_eq!(mmap(page(8) as usize, 8 * PAGE_SIZE, 1), 0);
+    // read-only, reading is fine but writing is not
+    killed(|| unsafe {
+        assert_eq!(page(10).read_volatile(), 0);
+        page(10).write_volatile(10);
+    });
+    println!("Test lazy_mmap OK!");
+    0
+}
@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, fstat, link, linkat, open, read, unlink, write, LinkFlags, OpenFlags, Stat};
+
+/// 测试 linkat 的 REPLACE 标志，覆盖已存在的名字后它指向新的 inode，被替换的最后一个链接的数据被释放，输出 Test link_replace OK! 就算正确。
+
+fn create_with(name: &str, data: &[u8]) {
+    let fd = open(name, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, data), data.len() as isize);
+    close(fd as usize);
+}
+
+fn stat_of(name: &str) -> Stat {
+    let fd = open(name, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let stat = Stat::new();
+    assert_eq!(fstat(fd as usize, &stat), 0);
+    close(fd as usize);
+    stat
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let src = "lr_src\0";
+    let dst = "lr_dst\0";
+    create_with(src, b"source");
+    create_with(dst, &[b'd'; 2048]);
+    // without the flag an existing name is not touched
+    assert_eq!(link(src, dst), -1);
+    assert_eq!(linkat(src, dst, LinkFlags::empty()), -1);
+    let old_dst = open(dst, OpenFlags::RDONLY);
+    assert!(old_dst > 0);
+    let old_dst = old_dst as usize;
+    assert_eq!(linkat(src, dst, LinkFlags::REPLACE), 0);
+    let src_stat = stat_of(src);
+    let dst_stat = stat_of(dst);
+    assert_eq!(dst_stat.ino, src_stat.ino);
+    assert_eq!(src_stat.nlink, 2);
+    let fd = open(dst, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 2048];
+    assert_eq!(read(fd as usize, &mut buf), 6);
+    assert_eq!(&buf[..6], b"source");
+    close(fd as usize);
+    // the replaced file had no other link, so its data is gone
+    assert_eq!(read(old_dst, &mut buf), 0);
+    close(old_dst);
+    // replacing a name which has another link keeps the data
+    let keep = "lr_keep\0";
+    let keep2 = "lr_keep2\0";
+    create_with(keep, b"kept");
+    assert_eq!(link(keep, keep2), 0);
+    assert_eq!(linkat(src, keep, LinkFlags::REPLACE), 0);
+    assert_eq!(stat_of(src).nlink, 3);
+    assert_eq!(stat_of(keep2).nlink, 1);
+    let fd = open(keep2, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(read(fd as usize, &mut buf), 4);
+    assert_eq!(&buf[..4], b"kept");
+    close(fd as usize);
+    // a missing source fails
+    assert_eq!(linkat("lr_missing\0", dst, LinkFlags::REPLACE), -1);
+    for name in [src, dst, keep, keep2] {
+        assert_eq!(unlink(name), 0);
+    }
+    println!("Test link_replace OK!");
+    0
+}
@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    enable_deadlock_detect, exit, mutex_blocking_create, mutex_lock, mutex_unlock, thread_create,
+    waittid,
+};
+
+/// 测试互斥锁加锁顺序检测，打开检测后一个线程先锁 a 再锁 b，另一个线程先锁 b 再锁 a 时第二把锁返回 -0xdead 且没有拿到锁，重复加同一把锁也返回 -0xdead，打开检测前的顺序不算，同样顺序再加锁仍然成功，输出 Test lock_order OK! 就算正确。
+
+static A: AtomicUsize = AtomicUsize::new(0);
+static B: AtomicUsize = AtomicUsize::new(0);
+
+/// Take both in the order given and let go, exit with the result of the second
+fn take_both(first_is_a: usize) -> ! {
+    let (a, b) = (A.load(Ordering::Relaxed), B.load(Ordering::Relaxed));
+    let (first, second) = if first_is_a == 1 { (a, b) } else { (b, a) };
+    assert_eq!(mutex_lock(first), 0);
+    let result = mutex_lock(second);
+    if result == 0 {
+        mutex_unlock(second);
+    }
+    mutex_unlock(first);
+    exit(if result == -0xdead { 1 } else { result as i32 })
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let a = mutex_blocking_create() as usize;
+    let b = mutex_blocking_create() as usize;
+    A.store(a, Ordering::Relaxed);
+    B.store(b, Ordering::Relaxed);
+    // not checked yet, so this order is not held against the one below
+    assert_eq!(mutex_lock(b), 0);
+    assert_eq!(mutex_lock(a), 0);
+    mutex_unlock(a);
+    mutex_unlock(b);
+    assert_eq!(enable_deadlock_detect(true), 0);
+
+    // a then b in one thread, b then a in another: they never meet, but could deadlock
+    let tid = thread_create(take_both as usize, 1);
+    assert_eq!(waittid(tid as usize), 0);
+    let tid = thread_create(take_both as usize, 0);
+    assert_eq!(waittid(tid as usize), 1);
+    // the order seen first still holds
+    let tid = thread_create(take_both as usize, 1);
+    assert_eq!(waittid(tid as usize), 0);
+
+    // a mutex held already
+    assert_eq!(mutex_lock(a), 0);
+    assert_eq!(mutex_lock(a), -0xdead);
+    mutex_unlock(a);
+    // the thread refused a let go of b, and nobody holds either now
+    assert_eq!(mutex_lock(b), 0);
+    assert_eq!(mutex_lock(a), -0xdead);
+    mutex_unlock(b);
+    println!("Test lock_order OK!");
+    0
+}
@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use user_lib::{close, exit, fork, lock_stats, open, unlink, wait, write, LockStats, OpenFlags};
+
+/// 测试 lock_stats，单个进程读写文件时文件系统锁没有等待，多个进程同时分配块后计数仍然自洽，输出 Test lock_stats OK! 就算正确。
+
+const CHILDREN: usize = 4;
+const FILES: usize = 8;
+
+/// Create some files and write a few blocks to each, allocating inodes and data blocks
+fn churn(tag: usize) {
+    let data = [b'l'; 1024];
+    for i in 0..FILES {
+        let name = format!("lock_stats_{}_{}\0", tag, i);
+        let fd = open(&name, OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(fd > 0);
+        assert_eq!(write(fd as usize, &data), data.len() as isize);
+        close(fd as usize);
+    }
+    for i in 0..FILES {
+        assert_eq!(unlink(&format!("lock_stats_{}_{}\0", tag, i)), 0);
+    }
+}
+
+fn stats() -> LockStats {
+    let mut stats = LockStats::default();
+    assert_eq!(lock_stats(&mut stats), 0);
+    stats
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // a single task never finds the locks held
+    let before = stats();
+    churn(0);
+    let after = stats();
+    assert_eq!(after.fs_waits, before.fs_waits);
+    assert_eq!(after.block_cache_waits, before.block_cache_waits);
+    // many tasks allocating at once, switched on timer interrupts
+    let before = stats();
+    for tag in 1..=CHILDREN {
+        if fork() == 0 {
+            churn(tag);
+            exit(0);
+        }
+    }
+    for _ in 0..CHILDREN {
+        let mut exit_code: i32 = 0;
+        assert!(wait(&mut exit_code) > 0);
+        assert_eq!(exit_code, 0);
+    }
+    let after = stats();
+    // every wait spins at least once. The kernel is never preempted while holding
+    // a lock on a single hart, so the waits stay at zero until there are more harts.
+    let fs_waits = after.fs_waits - before.fs_waits;
+    let block_cache_waits = after.block_cache_waits - before.block_cache_waits;
+    assert!(after.fs_spins - before.fs_spins >= fs_waits);
+    assert!(after.block_cache_spins - before.block_cache_spins >= block_cache_waits);
+    println!(
+        "fs lock waits: {}, block cache lock waits: {}",
+        fs_waits, block_cache_waits
+    );
+    println!("Test lock_stats OK!");
+    0
+}
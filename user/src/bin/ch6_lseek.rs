@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, lseek, open, read, unlink, write, OpenFlags, SEEK_CUR, SEEK_END, SEEK_SET};
+
+/// 测试 lseek，分别按 SEEK_SET、SEEK_CUR、SEEK_END 移动偏移量，移到文件开头之前或用未知的 whence 时报错且偏移量不变，移过文件末尾再写入时中间读出 0，输出 Test lseek OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_lseek\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"0123456789"), 10);
+    assert_eq!(lseek(fd, 3, SEEK_SET), 3);
+    let mut buf = [0u8; 4];
+    assert_eq!(read(fd, &mut buf[..2]), 2);
+    assert_eq!(&buf[..2], b"34");
+    assert_eq!(lseek(fd, 2, SEEK_CUR), 7);
+    assert_eq!(lseek(fd, -4, SEEK_CUR), 3);
+    assert_eq!(lseek(fd, -3, SEEK_END), 7);
+    assert_eq!(read(fd, &mut buf), 3);
+    assert_eq!(&buf[..3], b"789");
+    // nothing before the start of the file, the offset stays where it was
+    assert_eq!(lseek(fd, -11, SEEK_CUR), -1);
+    assert_eq!(lseek(fd, -1, SEEK_SET), -1);
+    assert_eq!(lseek(fd, 0, 3), -1);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 10);
+    // bad fds and files without an offset
+    assert_eq!(lseek(fd + 10, 0, SEEK_SET), -1);
+    assert_eq!(lseek(0, 0, SEEK_SET), -1);
+    // past the end, the gap reads as zeros once something is written behind it
+    let gap = 1000;
+    assert_eq!(lseek(fd, gap, SEEK_END), 10 + gap);
+    assert_eq!(lseek(fd, 0, SEEK_END), 10);
+    assert_eq!(lseek(fd, gap, SEEK_END), 10 + gap);
+    assert_eq!(write(fd, b"end"), 3);
+    assert_eq!(lseek(fd, 0, SEEK_END), 13 + gap);
+    assert_eq!(lseek(fd, 10, SEEK_SET), 10);
+    let mut hole = [0xffu8; 1000];
+    assert_eq!(read(fd, &mut hole), gap);
+    assert!(hole.iter().all(|&b| b == 0));
+    assert_eq!(read(fd, &mut buf), 3);
+    assert_eq!(&buf[..3], b"end");
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    println!("Test lseek OK!");
+    0
+}
@@ -0,0 +1,81 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, exit, fork, lseek, lseek_swap, open, pipe, read, unlink, waitpid, write, yield_,
+    OpenFlags, SEEK_CUR,
+};
+
+/// claims made by each task
+const CLAIMS: usize = 16;
+/// size of a claimed region
+const REGION: usize = 64;
+
+/// 测试 lseek_swap，fork 后共享同一个 fd 的两个进程交替换出偏移量，拿到的偏移量互不重复，输出 Test lseek_swap OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_lseek_swap\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // stdin and closed fds have no offset
+    assert_eq!(lseek_swap(0, 0), -1);
+    assert_eq!(lseek_swap(fd + 10, 0), -1);
+    assert_eq!(lseek_swap(fd, 100), 0);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 100);
+    assert_eq!(lseek_swap(fd, 0), 100);
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let (read_end, write_end) = (pipe_fd[0], pipe_fd[1]);
+    // each swap puts in a region nobody has held yet and takes out the one before it,
+    // the child puts in odd regions and the parent even ones
+    let claim = |k: usize, first: usize| {
+        let got = lseek_swap(fd, (2 * k + first) * REGION);
+        assert!(got >= 0);
+        yield_();
+        got as usize
+    };
+    let pid = fork();
+    if pid == 0 {
+        close(read_end);
+        for k in 0..CLAIMS {
+            let got = claim(k, 1);
+            assert_eq!(write(write_end, &got.to_le_bytes()), 8);
+        }
+        close(write_end);
+        exit(0);
+    }
+    close(write_end);
+    let mut claimed = [0usize; 2 * CLAIMS + 1];
+    for (k, slot) in claimed[..CLAIMS].iter_mut().enumerate() {
+        *slot = claim(k, 2);
+    }
+    for slot in claimed[CLAIMS..2 * CLAIMS].iter_mut() {
+        let mut bytes = [0u8; 8];
+        let mut len = 0;
+        while len < bytes.len() {
+            let n = read(read_end, &mut bytes[len..]);
+            assert!(n > 0);
+            len += n as usize;
+        }
+        *slot = usize::from_le_bytes(bytes);
+    }
+    close(read_end);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // the region left in the fd was claimed by nobody
+    claimed[2 * CLAIMS] = lseek_swap(fd, 0) as usize;
+    // every region handed out went to exactly one task
+    claimed.sort_unstable();
+    for (i, &offset) in claimed.iter().enumerate() {
+        assert_eq!(offset, i * REGION);
+    }
+    close(fd);
+    unlink(fname);
+    println!("Test lseek_swap OK!");
+    0
+}
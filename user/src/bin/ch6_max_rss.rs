@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, get_rss, mmap, munmap, waitpid};
+
+/// 测试 get_rss，mmap 的页面在第一次访问时才计入，munmap 后峰值不下降，再次映射未超过峰值时峰值不变、超过时随之增长，fork 出的子进程与父进程共享的页面同样计入，输出 Test max_rss OK! 就算正确。
+
+const PAGE_SIZE: usize = 4096;
+const BASE: usize = 0x10000000;
+
+fn touch(start: usize, pages: usize) {
+    for i in 0..pages {
+        unsafe { ((start + i * PAGE_SIZE) as *mut u8).write_volatile(i as u8) };
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let base = get_rss();
+    assert!(base > 0);
+    // untouched pages hold no frame yet
+    assert_eq!(mmap(BASE, 16 * PAGE_SIZE, 3), 0);
+    assert_eq!(get_rss(), base);
+    touch(BASE, 16);
+    assert_eq!(get_rss(), base + 16);
+    // the peak stays where it was after half of them are gone
+    assert_eq!(munmap(BASE + 8 * PAGE_SIZE, 8 * PAGE_SIZE), 0);
+    assert_eq!(get_rss(), base + 16);
+    // back up to 12 more than at the start, still under the peak
+    let second = BASE + 32 * PAGE_SIZE;
+    assert_eq!(mmap(second, 4 * PAGE_SIZE, 3), 0);
+    touch(second, 4);
+    assert_eq!(get_rss(), base + 16);
+    // and past it, to 24 more
+    let third = BASE + 64 * PAGE_SIZE;
+    assert_eq!(mmap(third, 12 * PAGE_SIZE, 3), 0);
+    touch(third, 12);
+    let peak = get_rss();
+    assert_eq!(peak, base + 24);
+    assert_eq!(munmap(BASE, 8 * PAGE_SIZE), 0);
+    assert_eq!(munmap(third, 12 * PAGE_SIZE), 0);
+    let pid = fork();
+    if pid == 0 {
+        // the child starts with the pages it shares with the parent, not the peak of it
+        let shared = get_rss();
+        assert_eq!(shared, base + 4);
+        // copying a shared page on write keeps the page count as it is
+        touch(second, 4);
+        assert_eq!(get_rss(), shared);
+        exit(0);
+    }
+    assert!(pid > 0);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(get_rss(), peak);
+    println!("peak resident pages: {}", peak);
+    println!("Test max_rss OK!");
+    0
+}
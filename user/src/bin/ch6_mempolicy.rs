@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, get_mempolicy, mmap, munmap, set_mempolicy, waitpid};
+
+/// 测试 set_mempolicy 与 get_mempolicy，设置的内存节点能被查回，区域照常读写，输出 Test mempolicy OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 2;
+    // nothing is mapped there yet
+    assert_eq!(set_mempolicy(start, len, 0), -1);
+    assert_eq!(get_mempolicy(start), -1);
+    assert_eq!(mmap(start, len, 3), 0);
+    assert_eq!(get_mempolicy(start), -1);
+    // there is only node 0
+    assert_eq!(set_mempolicy(start, len, 1), -1);
+    assert_eq!(set_mempolicy(start, len, 0), 0);
+    assert_eq!(get_mempolicy(start), 0);
+    assert_eq!(get_mempolicy(start + len - 1), 0);
+    assert_eq!(get_mempolicy(start + len), -1);
+    // the region is used as usual
+    for i in start..(start + len) {
+        unsafe {
+            (i as *mut u8).write_volatile(i as u8);
+        }
+    }
+    for i in start..(start + len) {
+        assert_eq!(unsafe { (i as *const u8).read_volatile() }, i as u8);
+    }
+    // a forked child keeps the policy
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(get_mempolicy(start), 0);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(munmap(start, len), 0);
+    assert_eq!(set_mempolicy(start, len, 0), -1);
+    println!("Test mempolicy OK!");
+    0
+}
@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, lseek, mmap_file, msync, munmap, open, read, unlink, write, OpenFlags, MS_SYNC,
+    SEEK_END, SEEK_SET,
+};
+
+/// 测试 mmap 映射文件，页在第一次访问时才从文件读入，文件末尾之后读到零，经映射写入的数据在 msync 或 munmap 时写回文件且文件不会变长，只读打开的文件不能映射为可写，输出 Test mmap_file OK! 就算正确。
+
+const PAGE_SIZE: usize = 4096;
+const ADDR: usize = 0x10000000;
+/// Bytes of the file, the second page only partly in it
+const FILE_SIZE: usize = PAGE_SIZE + 100;
+
+/// The 8 bytes at `offset` of the file
+fn on_disk(fd: usize, offset: usize) -> [u8; 8] {
+    let mut buf = [0xffu8; 8];
+    assert_eq!(lseek(fd, offset as isize, SEEK_SET), offset as isize);
+    assert_eq!(read(fd, &mut buf), 8);
+    buf
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_mmap_file\0";
+    let fd = open(
+        fname,
+        OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, &[b'a'; PAGE_SIZE]), PAGE_SIZE as isize);
+    assert_eq!(write(fd, &[b'b'; 100]), 100);
+
+    // a page past the end of the file too
+    assert_eq!(mmap_file(ADDR, 3 * PAGE_SIZE, 0b11, fd, 0), 0);
+    let region = unsafe { core::slice::from_raw_parts_mut(ADDR as *mut u8, 3 * PAGE_SIZE) };
+    assert!(region[..PAGE_SIZE].iter().all(|&byte| byte == b'a'));
+    // the second page is read on its first touch, after the file changed
+    assert_eq!(lseek(fd, PAGE_SIZE as isize, SEEK_SET), PAGE_SIZE as isize);
+    assert_eq!(write(fd, b"cccc"), 4);
+    assert_eq!(region[PAGE_SIZE..PAGE_SIZE + 4], *b"cccc");
+    assert!(region[PAGE_SIZE + 4..FILE_SIZE].iter().all(|&byte| byte == b'b'));
+    assert!(region[FILE_SIZE..].iter().all(|&byte| byte == 0));
+
+    // writes sit in memory until synced or unmapped
+    region[..8].copy_from_slice(b"mmapped!");
+    region[PAGE_SIZE..PAGE_SIZE + 8].copy_from_slice(b"synced!!");
+    region[FILE_SIZE..FILE_SIZE + 8].copy_from_slice(b"past end");
+    assert_eq!(on_disk(fd, 0), [b'a'; 8]);
+    assert_eq!(msync(ADDR + PAGE_SIZE, PAGE_SIZE, MS_SYNC), 0);
+    assert_eq!(on_disk(fd, PAGE_SIZE), *b"synced!!");
+    assert_eq!(on_disk(fd, 0), [b'a'; 8]);
+    assert_eq!(munmap(ADDR, 3 * PAGE_SIZE), 0);
+    assert_eq!(on_disk(fd, 0), *b"mmapped!");
+    // the file does not grow to take what was written past its end
+    assert_eq!(lseek(fd, 0, SEEK_END), FILE_SIZE as isize);
+
+    // from an offset into the file
+    assert_eq!(mmap_file(ADDR, PAGE_SIZE, 0b1, fd, PAGE_SIZE), 0);
+    let region = unsafe { core::slice::from_raw_parts(ADDR as *const u8, PAGE_SIZE) };
+    assert_eq!(region[..8], *b"synced!!");
+    assert_eq!(munmap(ADDR, PAGE_SIZE), 0);
+
+    // unaligned offset, no such fd, a page in use
+    assert_eq!(mmap_file(ADDR, PAGE_SIZE, 0b1, fd, 1), -1);
+    assert_eq!(mmap_file(ADDR, PAGE_SIZE, 0b1, 99, 0), -1);
+    assert_eq!(mmap_file(ADDR, PAGE_SIZE, 0b1, fd, 0), 0);
+    assert_eq!(mmap_file(ADDR, PAGE_SIZE, 0b1, fd, 0), -1);
+    assert_eq!(munmap(ADDR, PAGE_SIZE), 0);
+    close(fd);
+    // not writable through a file open read only
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(mmap_file(ADDR, PAGE_SIZE, 0b11, fd, 0), -1);
+    assert_eq!(mmap_file(ADDR, PAGE_SIZE, 0b1, fd, 0), 0);
+    assert_eq!(unsafe { *(ADDR as *const u8) }, b'm');
+    assert_eq!(munmap(ADDR, PAGE_SIZE), 0);
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    println!("Test mmap_file OK!");
+    0
+}
@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, mount, open, read, umount, write, OpenFlags};
+
+/// 测试 mount，把 mount.img 挂到 /mnt 上后能读到镜像中的 hello，mnt/.. 回到根目录，有文件打开时不能卸载，写入镜像的文件在重新挂载后仍在，卸载后 /mnt 下不再有镜像中的文件，输出 Test mount OK! 就算正确。
+
+const HELLO: &[u8] = b"hello from mount.img\n";
+
+fn read_file(path: &str, buf: &mut [u8]) -> isize {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return fd;
+    }
+    let len = read(fd as usize, buf);
+    close(fd as usize);
+    len
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut buf = [0u8; 64];
+    // not an easy-fs image, not a directory, the root
+    assert_eq!(mount("ch6_mount\0", "mnt\0"), -1);
+    assert_eq!(mount("mount.img\0", "mount.img\0"), -1);
+    assert_eq!(mount("mount.img\0", "/\0"), -1);
+    assert_eq!(umount("mnt\0"), -1);
+
+    assert_eq!(mount("mount.img\0", "/mnt\0"), 0);
+    assert_eq!(mount("mount.img\0", "/mnt\0"), -1);
+    let fd = open("/mnt/hello\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(read(fd as usize, &mut buf), HELLO.len() as isize);
+    assert_eq!(&buf[..HELLO.len()], HELLO);
+    // busy while a file on it is open
+    assert_eq!(umount("/mnt\0"), -1);
+    close(fd as usize);
+    // `..` at its root is the root directory again
+    assert!(read_file("/mnt/../mnt/hello\0", &mut buf) > 0);
+    assert!(read_file("mnt/../ch6_mount\0", &mut buf) > 0);
+
+    let fd = open("/mnt/note\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"note"), 4);
+    close(fd as usize);
+    assert_eq!(umount("/mnt\0"), 0);
+    assert!(read_file("/mnt/hello\0", &mut buf) < 0);
+    assert!(read_file("/mnt/note\0", &mut buf) < 0);
+    assert_eq!(umount("/mnt\0"), -1);
+
+    // what was written went into the image
+    assert_eq!(mount("mount.img\0", "mnt\0"), 0);
+    assert_eq!(read_file("mnt/note\0", &mut buf), 4);
+    assert_eq!(&buf[..4], b"note");
+    assert_eq!(umount("mnt\0"), 0);
+    println!("Test mount OK!");
+    0
+}
@@ -0,0 +1,96 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, exit, fork, fstat, mount, open, read, rename_replace, sys_waitpid, umount, unlink,
+    wait_size, waitpid, write, yield_, OpenFlags, Stat,
+};
+
+/// 测试两个文件系统上编号相同的 inode 互不相干，把 mount.img 复制两份分别挂到 /mnt 与 /home，两边同名文件的 inode 号相同，两边都在打开时被覆盖后关闭各自释放，写一边不唤醒等另一边变长的进程，输出 Test mount twins OK! 就算正确。
+
+const TWIN_A: &str = "fname_twin_a.img\0";
+const TWIN_B: &str = "fname_twin_b.img\0";
+
+fn copy(source: &str, target: &str) {
+    let src = open(source, OpenFlags::RDONLY);
+    assert!(src > 0);
+    let dst = open(target, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(dst > 0);
+    let mut buf = [0u8; 512];
+    loop {
+        let len = read(src as usize, &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        assert_eq!(write(dst as usize, &buf[..len as usize]), len);
+    }
+    close(src as usize);
+    close(dst as usize);
+}
+
+fn create(path: &str) -> usize {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    fd as usize
+}
+
+fn ino(fd: usize) -> u64 {
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    stat.ino
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // copies of one image, so that files created alike on them get the same inode ids
+    copy("mount.img\0", TWIN_A);
+    copy("mount.img\0", TWIN_B);
+    assert_eq!(mount(TWIN_A, "/mnt\0"), 0);
+    assert_eq!(mount(TWIN_B, "/home\0"), 0);
+
+    // the same inode id on both
+    let mnt = create("/mnt/same\0");
+    let home = create("/home/same\0");
+    let same = ino(mnt);
+    assert_eq!(ino(home), same);
+
+    // waiting on one does not end with a write to the other
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(wait_size(home, 10, 200), -11);
+        exit(0);
+    }
+    assert_eq!(write(mnt, &[b'x'; 20]), 20);
+    for _ in 0..10 {
+        yield_();
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(sys_waitpid(pid, &mut exit_code as *mut _), -2);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // replaced on both while open, each is freed once its own file is closed
+    close(create("/mnt/other\0"));
+    close(create("/home/other\0"));
+    assert_eq!(rename_replace("/mnt/other\0", "/mnt/same\0"), 0);
+    assert_eq!(rename_replace("/home/other\0", "/home/same\0"), 0);
+    close(mnt);
+    close(home);
+    // the inode ids freed are handed out again
+    let mnt = create("/mnt/new\0");
+    let home = create("/home/new\0");
+    assert_eq!(ino(mnt), same);
+    assert_eq!(ino(home), same);
+    close(mnt);
+    close(home);
+
+    assert_eq!(umount("/mnt\0"), 0);
+    assert_eq!(umount("/home\0"), 0);
+    assert_eq!(unlink(TWIN_A), 0);
+    assert_eq!(unlink(TWIN_B), 0);
+    println!("Test mount twins OK!");
+    0
+}
@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, extend_and_map, lseek, msync, munmap, open, read, unlink, write, OpenFlags, MS_ASYNC,
+    MS_INVALIDATE, MS_SYNC, SEEK_SET,
+};
+
+/// 测试 msync，经映射写入的数据在 msync 返回前落到文件里且只写回给定范围，MS_INVALIDATE 后映射重新读到文件内容，输出 Test msync OK! 就算正确。
+
+const PAGE_SIZE: usize = 4096;
+
+/// The 16 bytes at `offset` of the file
+fn on_disk(fd: usize, offset: usize) -> [u8; 16] {
+    let mut buf = [0xffu8; 16];
+    assert_eq!(lseek(fd, offset as isize, SEEK_SET), offset as isize);
+    assert_eq!(read(fd, &mut buf), 16);
+    buf
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_msync\0";
+    let fd = open(
+        fname,
+        OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let addr = extend_and_map(fd, 2 * PAGE_SIZE);
+    assert!(addr > 0);
+    let addr = addr as usize;
+    let region = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, 2 * PAGE_SIZE) };
+    region[..16].fill(b'a');
+    region[PAGE_SIZE..PAGE_SIZE + 16].fill(b'b');
+    // the writes sit in memory until synced
+    assert_eq!(on_disk(fd, 0), [0u8; 16]);
+    assert_eq!(on_disk(fd, PAGE_SIZE), [0u8; 16]);
+    // only the pages in the range are written back
+    assert_eq!(msync(addr + PAGE_SIZE, PAGE_SIZE, MS_SYNC), 0);
+    assert_eq!(on_disk(fd, PAGE_SIZE), [b'b'; 16]);
+    assert_eq!(on_disk(fd, 0), [0u8; 16]);
+    assert_eq!(msync(addr, 2 * PAGE_SIZE, MS_ASYNC), 0);
+    assert_eq!(on_disk(fd, 0), [b'a'; 16]);
+    // a clean page is not written back over a change to the file,
+    // and invalidating it brings the change into the mapping
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(write(fd, b"disk"), 4);
+    assert_eq!(msync(addr, PAGE_SIZE, MS_SYNC | MS_INVALIDATE), 0);
+    assert_eq!(on_disk(fd, 0)[..4], *b"disk");
+    assert_eq!(region[..4], *b"disk");
+    assert_eq!(region[4..16], [b'a'; 12]);
+    // both of MS_ASYNC and MS_SYNC, or unknown flags
+    assert_eq!(msync(addr, PAGE_SIZE, MS_ASYNC | MS_SYNC), -1);
+    assert_eq!(msync(addr, PAGE_SIZE, 1 << 3), -1);
+    assert_eq!(munmap(addr, 2 * PAGE_SIZE), 0);
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    println!("Test msync OK!");
+    0
+}
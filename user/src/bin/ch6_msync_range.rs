@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, lseek, mmap_file, msync, munmap, open, read, unlink, write, OpenFlags, MS_ASYNC,
+    MS_SYNC, SEEK_SET,
+};
+
+/// 测试 msync 的范围，起止不对齐页时扩展到整页，只写回范围碰到的页，映射中的修改在 munmap 之前就已写回文件，输出 Test msync range OK! 就算正确。
+
+const PAGE_SIZE: usize = 4096;
+const ADDR: usize = 0x10000000;
+const PAGES: usize = 3;
+
+/// The 8 bytes at `offset` of the file
+fn on_disk(fd: usize, offset: usize) -> [u8; 8] {
+    let mut buf = [0xffu8; 8];
+    assert_eq!(lseek(fd, offset as isize, SEEK_SET), offset as isize);
+    assert_eq!(read(fd, &mut buf), 8);
+    buf
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_msync_range\0";
+    let fd = open(
+        fname,
+        OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    for _ in 0..PAGES {
+        assert_eq!(write(fd, &[0u8; PAGE_SIZE]), PAGE_SIZE as isize);
+    }
+    assert_eq!(mmap_file(ADDR, PAGES * PAGE_SIZE, 0b11, fd, 0), 0);
+    let region = unsafe { core::slice::from_raw_parts_mut(ADDR as *mut u8, PAGES * PAGE_SIZE) };
+    for i in 0..PAGES {
+        region[i * PAGE_SIZE + 100..i * PAGE_SIZE + 108].fill(b'0' + i as u8);
+    }
+    // a few bytes in the middle of the second page bring the whole page
+    assert_eq!(msync(ADDR + PAGE_SIZE + 10, 5, MS_SYNC), 0);
+    assert_eq!(on_disk(fd, PAGE_SIZE + 100), [b'1'; 8]);
+    assert_eq!(on_disk(fd, 100), [0u8; 8]);
+    assert_eq!(on_disk(fd, 2 * PAGE_SIZE + 100), [0u8; 8]);
+    // from inside the first page to just into the third
+    assert_eq!(msync(ADDR + 1, 2 * PAGE_SIZE, MS_ASYNC), 0);
+    assert_eq!(on_disk(fd, 100), [b'0'; 8]);
+    assert_eq!(on_disk(fd, 2 * PAGE_SIZE + 100), [b'2'; 8]);
+    // nothing in an empty range
+    region[200..208].fill(b'e');
+    assert_eq!(msync(ADDR + 200, 0, MS_SYNC), 0);
+    assert_eq!(on_disk(fd, 200), [0u8; 8]);
+    assert_eq!(munmap(ADDR, PAGES * PAGE_SIZE), 0);
+    assert_eq!(on_disk(fd, 200), [b'e'; 8]);
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    println!("Test msync range OK!");
+    0
+}
@@ -0,0 +1,90 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, frame_stats, mmap, munmap, waitpid, FrameStats, SIGSEGV};
+
+/// 测试部分 munmap，映射 8 页后取消中间两页、第一页和最后一页的映射，对应页帧被释放，其余页照常读写，访问被取消映射的页的进程被杀死，输出 Test munmap_split OK! 就算正确。
+
+const PAGES: usize = 8;
+const PAGE_SIZE: usize = 4096;
+const BASE: usize = 0x10000000;
+
+fn frees() -> usize {
+    let mut stats = FrameStats::default();
+    assert_eq!(frame_stats(&mut stats), 0);
+    stats.frees
+}
+
+fn page(i: usize) -> *mut u8 {
+    (BASE + i * PAGE_SIZE) as *mut u8
+}
+
+fn fill(i: usize, value: u8) {
+    for j in (0..PAGE_SIZE).step_by(512) {
+        unsafe { page(i).add(j).write_volatile(value) };
+    }
+}
+
+fn check(i: usize, value: u8) {
+    for j in (0..PAGE_SIZE).step_by(512) {
+        assert_eq!(unsafe { page(i).add(j).read_volatile() }, value);
+    }
+}
+
+/// Unmap pages `[from, to)`, their frames are freed right away
+fn unmap(from: usize, to: usize) {
+    let before = frees();
+    assert_eq!(munmap(page(from) as usize, (to - from) * PAGE_SIZE), 0);
+    assert_eq!(frees(), before + to - from);
+}
+
+/// Touch page `i` in a child, it has to be killed by a page fault
+fn gone(i: usize) {
+    let pid = fork();
+    if pid == 0 {
+        fill(i, 0xff);
+        exit(0);
+    }
+    assert!(pid > 0);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -SIGSEGV);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mmap(BASE, PAGES * PAGE_SIZE, 3), 0);
+    for i in 0..PAGES {
+        fill(i, i as u8);
+    }
+    // a hole in the middle splits the mapping in two
+    unmap(3, 5);
+    for i in [0, 1, 2, 5, 6, 7] {
+        check(i, i as u8);
+        fill(i, !(i as u8));
+    }
+    gone(3);
+    gone(4);
+    // then a prefix and a suffix of what is left
+    unmap(0, 1);
+    unmap(7, 8);
+    for i in [1, 2, 5, 6] {
+        check(i, !(i as u8));
+    }
+    gone(0);
+    gone(7);
+    // the hole can be mapped again, the pages around it are untouched
+    assert_eq!(mmap(page(3) as usize, 2 * PAGE_SIZE, 3), 0);
+    check(3, 0);
+    check(4, 0);
+    check(2, !2);
+    check(5, !5);
+    // one range across the pieces
+    unmap(1, 7);
+    assert_eq!(munmap(page(1) as usize, PAGE_SIZE), -1);
+    gone(2);
+    println!("Test munmap_split OK!");
+    0
+}
@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, exit, fork, nanosleep, pipe, read, sys_get_time, waitpid, write, TimeVal};
+
+/// 测试 nanosleep，短于一个时钟周期的睡眠至少睡够所要的时间，三个子进程按 24ms、8ms、16ms 睡眠，醒来顺序按截止时间为 8ms、16ms、24ms，输出 Test nanosleep OK! 就算正确。
+
+/// sleep of each child, apart by less than a tick, but enough for the children to start
+const SLEEPS_US: [usize; 3] = [24000, 8000, 16000];
+
+fn now_us() -> usize {
+    let time = TimeVal::new();
+    assert_eq!(sys_get_time(&time, 0), 0);
+    time.sec * 1_000_000 + time.usec
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // far shorter than a tick, still not cut short
+    let start = now_us();
+    assert_eq!(nanosleep(500), 0);
+    let slept = now_us() - start;
+    println!("slept {}us for 500us", slept);
+    assert!(slept >= 500);
+    assert_eq!(nanosleep(0), 0);
+
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let mut pids = [0usize; 3];
+    for (i, pid) in pids.iter_mut().enumerate() {
+        let ret = fork();
+        if ret == 0 {
+            close(pipe_fd[0]);
+            let start = now_us();
+            nanosleep(SLEEPS_US[i]);
+            assert!(now_us() - start >= SLEEPS_US[i]);
+            // the wake up order is the order the pipe is written in
+            assert_eq!(write(pipe_fd[1], &[i as u8]), 1);
+            exit(0);
+        }
+        assert!(ret > 0);
+        *pid = ret as usize;
+    }
+    close(pipe_fd[1]);
+    let mut order = [0u8; 3];
+    for slot in order.iter_mut() {
+        assert_eq!(read(pipe_fd[0], core::slice::from_mut(slot)), 1);
+    }
+    close(pipe_fd[0]);
+    for pid in pids {
+        let mut exit_code = 0;
+        assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+        assert_eq!(exit_code, 0);
+    }
+    assert_eq!(order, [1, 2, 0]);
+    println!("Test nanosleep OK!");
+    0
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, fcntl, pipe, read, write, OpenFlags, F_GETFL, F_SETFL};
+
+/// 测试 O_NONBLOCK，非阻塞管道读空、写满时立即返回 -EAGAIN，输出 Test nonblock OK! 就算正确。
+
+const EAGAIN: isize = 11;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let (read_end, write_end) = (pipe_fd[0], pipe_fd[1]);
+    let nonblock = OpenFlags::NONBLOCK.bits() as usize;
+    assert_eq!(fcntl(read_end, F_GETFL, 0), 0);
+    assert_eq!(fcntl(read_end, F_SETFL, nonblock), 0);
+    assert_eq!(fcntl(read_end, F_GETFL, 0), nonblock as isize);
+    // an empty pipe, with the write end still open, would block forever
+    let mut buf = [0u8; 64];
+    assert_eq!(read(read_end, &mut buf), -EAGAIN);
+    // whatever is there is returned without waiting for the rest
+    assert_eq!(write(write_end, b"hello"), 5);
+    assert_eq!(read(read_end, &mut buf), 5);
+    assert_eq!(&buf[..5], b"hello");
+    assert_eq!(read(read_end, &mut buf), -EAGAIN);
+    // a full pipe takes what fits and then refuses
+    assert_eq!(fcntl(write_end, F_SETFL, nonblock), 0);
+    let written = write(write_end, &buf);
+    assert!(written > 0 && written < buf.len() as isize);
+    assert_eq!(write(write_end, &buf), -EAGAIN);
+    assert_eq!(read(read_end, &mut buf), written);
+    close(write_end);
+    // with no writer left, an empty read is the end of file
+    assert_eq!(read(read_end, &mut buf), 0);
+    close(read_end);
+    println!("Test nonblock OK!");
+    0
+}
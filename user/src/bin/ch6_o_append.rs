@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, lseek, open, read, unlink, write, OpenFlags, SEEK_END, SEEK_SET};
+
+/// 测试 O_APPEND，两个以 APPEND 打开的 fd 交替写入时互不覆盖，每次写入都落在文件末尾，文件大小等于各次写入之和，输出 Test O_APPEND OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_o_append\0";
+    let fd = open(
+        fname,
+        OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY,
+    );
+    assert!(fd > 0);
+    close(fd as usize);
+    let first = open(fname, OpenFlags::RDWR | OpenFlags::APPEND);
+    let second = open(fname, OpenFlags::WRONLY | OpenFlags::APPEND);
+    assert!(first > 0 && second > 0);
+    let (first, second) = (first as usize, second as usize);
+    // each fd still has offset 0 of its own, the writes go to the end anyway
+    let chunks: [(usize, &[u8]); 3] = [(first, b"one,"), (second, b"two,"), (first, b"three")];
+    let mut total = 0;
+    for (fd, chunk) in chunks {
+        assert_eq!(write(fd, chunk), chunk.len() as isize);
+        total += chunk.len();
+        // the offset follows the write
+        assert_eq!(lseek(fd, 0, SEEK_END) as usize, total);
+    }
+    assert_eq!(lseek(second, 0, SEEK_END) as usize, total);
+    // seeking back does not make the next write overwrite
+    assert_eq!(lseek(second, 0, SEEK_SET), 0);
+    assert_eq!(write(second, b"!"), 1);
+    total += 1;
+    assert_eq!(lseek(first, 0, SEEK_SET), 0);
+    let mut buf = [0u8; 32];
+    assert_eq!(read(first, &mut buf) as usize, total);
+    assert_eq!(&buf[..total], b"one,two,three!");
+    close(first);
+    close(second);
+    // without the flag a write lands at the offset of the fd
+    let plain = open(fname, OpenFlags::WRONLY);
+    assert!(plain > 0);
+    assert_eq!(write(plain as usize, b"ONE"), 3);
+    close(plain as usize);
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(read(fd as usize, &mut buf) as usize, total);
+    assert_eq!(&buf[..total], b"ONE,two,three!");
+    close(fd as usize);
+    assert_eq!(unlink(fname), 0);
+    println!("Test O_APPEND OK!");
+    0
+}
@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, lseek, open, read, symlink, unlink, write, OpenFlags, SEEK_END};
+
+/// 测试 O_TRUNC 与 O_EXCL，以 TRUNC 打开非空文件后大小为 0，CREATE|TRUNC 打开不存在的文件时创建空文件，CREATE|EXCL 打开已存在的文件或符号链接时失败，输出 Test open flags OK! 就算正确。
+
+fn size_of(fd: usize) -> isize {
+    lseek(fd, 0, SEEK_END)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_open_flags\0";
+    // created empty, nothing to truncate
+    let fd = open(
+        fname,
+        OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR,
+    );
+    assert!(fd > 0);
+    assert_eq!(size_of(fd as usize), 0);
+    assert_eq!(write(fd as usize, b"not empty"), 9);
+    close(fd as usize);
+    // without TRUNC the data stays
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(size_of(fd as usize), 9);
+    close(fd as usize);
+    // truncated on open
+    let fd = open(fname, OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    assert_eq!(size_of(fd as usize), 0);
+    let mut buf = [0u8; 16];
+    assert_eq!(read(fd as usize, &mut buf), 0);
+    assert_eq!(write(fd as usize, b"again"), 5);
+    close(fd as usize);
+    // an exclusive create fails on an existing file and leaves it alone
+    let excl = OpenFlags::CREATE | OpenFlags::EXCL | OpenFlags::WRONLY;
+    assert_eq!(open(fname, excl), -1);
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert_eq!(size_of(fd as usize), 5);
+    close(fd as usize);
+    // and on a link, even one leading nowhere
+    let link = "fname_open_flags_link\0";
+    assert_eq!(symlink("fname_open_flags_missing\0", link), 0);
+    assert_eq!(open(link, excl), -1);
+    assert_eq!(unlink(link), 0);
+    // but creates a new file
+    let fresh = "fname_open_flags_excl\0";
+    let fd = open(fresh, excl);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(open(fresh, excl), -1);
+    assert_eq!(unlink(fresh), 0);
+    assert_eq!(unlink(fname), 0);
+    println!("Test open flags OK!");
+    0
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, open, pin_inode, read, unpin_inode, write, OpenFlags};
+
+/// 测试 pin_inode，被钉住的 inode 读写正常，关闭文件后自动解除，输出 Test pin_inode OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_pin_inode\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // stdio has no inode to pin
+    assert_eq!(pin_inode(0), -1);
+    assert_eq!(unpin_inode(fd), -1);
+    assert_eq!(pin_inode(fd), 0);
+    assert_eq!(pin_inode(fd), 0);
+    let data = [0x5au8; 2048];
+    assert_eq!(write(fd, &data), data.len() as isize);
+    assert_eq!(unpin_inode(fd), 0);
+    assert_eq!(unpin_inode(fd), -1);
+    // a pin is dropped when the file is closed
+    assert_eq!(pin_inode(fd), 0);
+    close(fd);
+    assert_eq!(pin_inode(fd), -1);
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(pin_inode(fd), 0);
+    let mut buf = [0u8; 4096];
+    assert_eq!(read(fd, &mut buf), data.len() as isize);
+    assert_eq!(buf[..data.len()], data);
+    close(fd);
+    println!("Test pin_inode OK!");
+    0
+}
@@ -0,0 +1,80 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, exit, fork, pipe, read, waitpid, write};
+
+/// 测试 pipe，父进程写入远超缓冲区大小的数据，子进程按顺序全部读出，写端关闭后读到 0；读端全部关闭后写入返回 EPIPE，输出 Test pipe OK! 就算正确。
+
+const EPIPE: isize = 32;
+const LEN: usize = 1000;
+
+fn byte(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+fn reap(pid: isize) {
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[1]);
+        let mut buf = [0u8; 64];
+        let mut pos = 0;
+        loop {
+            let n = read(fds[0], &mut buf);
+            assert!(n >= 0);
+            if n == 0 {
+                break;
+            }
+            for (j, &b) in buf[..n as usize].iter().enumerate() {
+                assert_eq!(b, byte(pos + j));
+            }
+            pos += n as usize;
+        }
+        // everything written arrives before the end
+        assert_eq!(pos, LEN);
+        close(fds[0]);
+        exit(0);
+    }
+    assert!(pid > 0);
+    close(fds[0]);
+    let mut data = [0u8; LEN];
+    for (i, b) in data.iter_mut().enumerate() {
+        *b = byte(i);
+    }
+    // far more than the pipe holds, the write waits for the child to make room
+    assert_eq!(write(fds[1], &data), LEN as isize);
+    close(fds[1]);
+    reap(pid);
+    // a write with no read end left can never be read
+    assert_eq!(pipe(&mut fds), 0);
+    close(fds[0]);
+    assert_eq!(write(fds[1], b"lost"), -EPIPE);
+    close(fds[1]);
+    // nor can a write waiting on a full pipe once its last reader is gone
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[1]);
+        let mut buf = [0u8; 1];
+        assert_eq!(read(fds[0], &mut buf), 1);
+        exit(0);
+    }
+    close(fds[0]);
+    let written = write(fds[1], &data[..100]);
+    assert!(written > 0 && written < 100);
+    assert_eq!(write(fds[1], b"lost"), -EPIPE);
+    close(fds[1]);
+    reap(pid);
+    println!("Test pipe OK!");
+    0
+}
@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, exit, fork, get_time, open, pipe, poll, read, sleep, unlink, waitpid, write, OpenFlags,
+    PollFd, POLLIN, POLLNVAL, POLLOUT,
+};
+
+/// 测试 poll，空管道的读端不可读，写端可写，超时为 0 时立即返回 0，超时到期返回 0，子进程写入后读端变为可读，写端全部关闭后读端可读，普通文件总是就绪，未打开的 fd 报告 POLLNVAL，输出 Test poll OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let mut polls = [PollFd::new(fds[0], POLLIN), PollFd::new(fds[1], POLLOUT)];
+    // a pure poll: only the write end is ready
+    assert_eq!(poll(&mut polls, 0), 1);
+    assert_eq!(polls[0].revents, 0);
+    assert_eq!(polls[1].revents, POLLOUT);
+    // the time runs out on the empty read end
+    let start = get_time();
+    assert_eq!(poll(&mut polls[..1], 10_000), 0);
+    assert!(get_time() - start >= 10);
+
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]);
+        sleep(20);
+        assert_eq!(write(fds[1], b"ready"), 5);
+        close(fds[1]);
+        exit(0);
+    }
+    close(fds[1]);
+    // wakes up once the producer writes
+    assert_eq!(poll(&mut polls[..1], -1), 1);
+    assert_eq!(polls[0].revents, POLLIN);
+    let mut buf = [0u8; 8];
+    assert_eq!(read(fds[0], &mut buf), 5);
+    assert_eq!(&buf[..5], b"ready");
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    // empty but closed for writing: a read returns at once
+    assert_eq!(poll(&mut polls[..1], 0), 1);
+    assert_eq!(read(fds[0], &mut buf), 0);
+    close(fds[0]);
+
+    let fname = "fname_poll\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let mut polls = [
+        PollFd::new(fd as usize, POLLIN | POLLOUT),
+        PollFd::new(fds[0], POLLIN),
+    ];
+    // files are always ready, and the closed fd is reported whatever was asked
+    assert_eq!(poll(&mut polls, 0), 2);
+    assert_eq!(polls[0].revents, POLLIN | POLLOUT);
+    assert_eq!(polls[1].revents, POLLNVAL);
+    close(fd as usize);
+    unlink(fname);
+    println!("Test poll OK!");
+    0
+}
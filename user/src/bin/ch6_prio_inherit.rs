@@ -0,0 +1,89 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    exit, fork, get_time, mutex_blocking_create, mutex_lock, mutex_unlock, pipe, read,
+    set_priority, waitpid, write,
+};
+
+/// 测试互斥锁的优先级继承，低优先级进程持锁时高优先级进程等锁，中优先级进程一直占用 CPU，持锁进程继承等待者的优先级后很快放锁，高优先级进程的等待时间远小于中优先级进程运行的时间，输出 Test prio_inherit OK! 就算正确。
+
+/// The least time in ms the low priority task works with the lock held
+const WORK_MS: isize = 50;
+
+fn work(iterations: usize) {
+    let mut x = 0usize;
+    for i in 0..iterations {
+        x = unsafe { core::ptr::read_volatile(&x) }.wrapping_add(i);
+    }
+}
+
+/// How many iterations of `work` take at least `WORK_MS` with the CPU to itself,
+/// and how long they do take
+fn calibrate() -> (usize, isize) {
+    let mut iterations = 1 << 16;
+    loop {
+        let start = get_time();
+        work(iterations);
+        let elapsed = get_time() - start;
+        if elapsed >= WORK_MS {
+            return (iterations, elapsed);
+        }
+        iterations *= 2;
+    }
+}
+
+fn reap(pid: isize) -> i32 {
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let (iterations, work_ms) = calibrate();
+    let mutex_id = mutex_blocking_create();
+    assert!(mutex_id >= 0);
+    let mutex_id = mutex_id as usize;
+    let mut locked = [0usize; 2];
+    assert_eq!(pipe(&mut locked), 0);
+    let low = fork();
+    if low == 0 {
+        assert_eq!(set_priority(2), 2);
+        assert_eq!(mutex_lock(mutex_id), 0);
+        assert_eq!(write(locked[1], b"l"), 1);
+        work(iterations);
+        mutex_unlock(mutex_id);
+        exit(0);
+    }
+    let mut token = [0u8; 1];
+    assert_eq!(read(locked[0], &mut token), 1);
+    let high = fork();
+    if high == 0 {
+        assert_eq!(set_priority(64), 64);
+        let start = get_time();
+        assert_eq!(mutex_lock(mutex_id), 0);
+        let waited = get_time() - start;
+        mutex_unlock(mutex_id);
+        exit(waited as i32);
+    }
+    let medium = fork();
+    if medium == 0 {
+        // without inheritance this would keep the holder of the lock off the CPU all along
+        assert_eq!(set_priority(32), 32);
+        work(20 * iterations);
+        exit(0);
+    }
+    let waited = reap(high);
+    assert_eq!(reap(low), 0);
+    assert_eq!(reap(medium), 0);
+    println!(
+        "high priority task waited {}ms for a lock held {}ms",
+        waited, work_ms
+    );
+    assert!(waited < 5 * work_ms as i32);
+    println!("Test prio_inherit OK!");
+    0
+}
@@ -0,0 +1,68 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, exit, fork, pipe, process_vm_readv, read, waitpid, write, IoVec};
+
+const SECRET: u64 = 0x1234_5678_9abc_def0;
+
+/// 测试 process_vm_readv，父进程读出子进程 fork 之后写入的变量，输出 Test process_vm_readv OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut value: u64 = 0;
+    let addr = &mut value as *mut u64 as usize;
+    let mut ready = [0usize; 2];
+    let mut done = [0usize; 2];
+    assert_eq!(pipe(&mut ready), 0);
+    assert_eq!(pipe(&mut done), 0);
+    let pid = fork();
+    if pid == 0 {
+        // only the child's copy of the variable gets the secret
+        unsafe {
+            (addr as *mut u64).write_volatile(SECRET);
+        }
+        assert_eq!(write(ready[1], &[1]), 1);
+        // stay alive until the parent has read it
+        let mut byte = [0u8];
+        assert_eq!(read(done[0], &mut byte), 1);
+        exit(0);
+    }
+    let pid = pid as usize;
+    let mut byte = [0u8];
+    assert_eq!(read(ready[0], &mut byte), 1);
+    // read the variable into one buffer
+    let mut buf = [0u8; 8];
+    let local = [IoVec::from_slice(&mut buf)];
+    let remote = [IoVec::new(addr, 8)];
+    assert_eq!(process_vm_readv(pid, &local, &remote), 8);
+    assert_eq!(u64::from_ne_bytes(buf), SECRET);
+    assert_eq!(unsafe { (addr as *const u64).read_volatile() }, 0);
+    // scattered into two buffers, from two halves
+    let (mut low, mut high) = ([0u8; 3], [0u8; 5]);
+    let local = [IoVec::from_slice(&mut low), IoVec::from_slice(&mut high)];
+    let remote = [IoVec::new(addr, 4), IoVec::new(addr + 4, 4)];
+    assert_eq!(process_vm_readv(pid, &local, &remote), 8);
+    let mut joined = [0u8; 8];
+    joined[..3].copy_from_slice(&low);
+    joined[3..].copy_from_slice(&high);
+    assert_eq!(u64::from_ne_bytes(joined), SECRET);
+    // the copy stops at an unmapped remote buffer, and fails if nothing was copied
+    let mut buf = [0u8; 16];
+    let local = [IoVec::from_slice(&mut buf)];
+    assert_eq!(process_vm_readv(pid, &local, &[IoVec::new(0, 8)]), -1);
+    let remote = [IoVec::new(addr, 8), IoVec::new(0, 8)];
+    assert_eq!(process_vm_readv(pid, &local, &remote), 8);
+    assert_eq!(write(done[1], &[1]), 1);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    assert_eq!(exit_code, 0);
+    // only children can be read
+    assert_eq!(process_vm_readv(pid, &local, &remote), -1);
+    for fd in ready.iter().chain(done.iter()) {
+        close(*fd);
+    }
+    println!("Test process_vm_readv OK!");
+    0
+}
@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, getpid, ps, spawn, waitpid, yield_, ProcInfo, ProcStatus};
+
+const MAX_PROCS: usize = 16;
+
+/// 测试 ps，列出的进程中能找到本进程及其子进程，pid、父进程和名字都正确，输出 Test ps OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let me = getpid() as usize;
+    let spawned = [
+        spawn("ch5_exit0\0") as usize,
+        spawn("ch5_exit0\0") as usize,
+    ];
+    let forked = fork();
+    if forked == 0 {
+        for _ in 0..100 {
+            yield_();
+        }
+        exit(0);
+    }
+    let forked = forked as usize;
+    let mut infos = [ProcInfo::new(); MAX_PROCS];
+    let total = ps(&mut infos);
+    assert!(total >= 4 && total as usize <= MAX_PROCS);
+    let infos = &infos[..total as usize];
+    let find = |pid: usize| infos.iter().find(|info| info.pid == pid).unwrap();
+    let info = find(me);
+    assert_eq!(info.status, ProcStatus::Running);
+    assert_eq!(info.name(), "ch6_ps");
+    for pid in spawned {
+        let info = find(pid);
+        assert_eq!(info.ppid, me);
+        assert_eq!(info.name(), "ch5_exit0");
+    }
+    // a forked child keeps the name of its parent
+    let info = find(forked);
+    assert_eq!(info.ppid, me);
+    assert_eq!(info.name(), "ch6_ps");
+    // a short buffer still gets the total count
+    let mut one = [ProcInfo::new(); 1];
+    assert_eq!(ps(&mut one), total);
+    let mut exit_code: i32 = 0;
+    for pid in spawned {
+        assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    }
+    assert_eq!(waitpid(forked, &mut exit_code), forked as isize);
+    // reaped processes are gone
+    let mut infos = [ProcInfo::new(); MAX_PROCS];
+    let total = ps(&mut infos) as usize;
+    assert!(infos[..total]
+        .iter()
+        .all(|info| info.pid != forked && !spawned.contains(&info.pid)));
+    println!("Test ps OK!");
+    0
+}
@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, exit, extend_and_map, fork, get_reclaim_stats, mmap, munmap, open, pipe, read,
+    set_reclaim_watermark, waitpid, write, yield_, OpenFlags, ReclaimStats,
+};
+
+/// 测试 set_reclaim_watermark 与 get_reclaim_stats，空闲页帧低于水位线时回收文件映射的页，映射内容不丢失，系统调用读写被回收的页也能读回，输出 Test reclaim OK! 就算正确。
+
+const PAGES: usize = 32;
+const PAGE_SIZE: usize = 4096;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_reclaim\0";
+    let mut ready = [0usize; 2];
+    let mut resume = [0usize; 2];
+    assert_eq!(pipe(&mut ready), 0);
+    assert_eq!(pipe(&mut resume), 0);
+    let pid = fork();
+    if pid == 0 {
+        // the child owns the file mapping and sleeps while its pages are reclaimed
+        let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+        assert!(fd > 0);
+        let len = PAGES * PAGE_SIZE;
+        let addr = extend_and_map(fd as usize, len);
+        assert!(addr > 0);
+        let region = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) };
+        for (i, byte) in region.iter_mut().enumerate() {
+            *byte = (i % 251) as u8;
+        }
+        assert_eq!(write(ready[1], b"r"), 1);
+        // the kernel reads and writes reclaimed pages for a syscall as well
+        let at = 2 * PAGE_SIZE;
+        assert_eq!(read(resume[0], &mut region[at..at + 1]), 1);
+        assert_eq!(write(ready[1], &region[PAGE_SIZE..PAGE_SIZE + 8]), 8);
+        // reclaimed pages are read back from the file when touched
+        assert!(region
+            .iter()
+            .enumerate()
+            .all(|(i, b)| *b == (i % 251) as u8));
+        close(fd as usize);
+        exit(0);
+    }
+    let mut buf = [0u8; 1];
+    assert_eq!(read(ready[0], &mut buf), 1);
+    let mut before = ReclaimStats::new();
+    assert_eq!(get_reclaim_stats(&mut before), 0);
+    assert_eq!(set_reclaim_watermark(before.free_frames - 16), 0);
+    let mut stats = ReclaimStats::new();
+    assert_eq!(get_reclaim_stats(&mut stats), 0);
+    assert_eq!(stats.low_watermark, before.free_frames - 16);
+    assert!(stats.high_watermark > stats.low_watermark);
+    // take enough frames to drop below the low watermark
+    let start: usize = 0x10000000;
+    let len = (stats.free_frames - stats.low_watermark + 4) * PAGE_SIZE;
+    assert_eq!(mmap(start, len, 3), 0);
+    // the scheduler loop reclaims while switching tasks
+    yield_();
+    assert_eq!(get_reclaim_stats(&mut stats), 0);
+    assert!(stats.reclaimed > before.reclaimed);
+    assert!(stats.free_frames >= stats.high_watermark);
+    assert_eq!(set_reclaim_watermark(0), 0);
+    assert_eq!(munmap(start, len), 0);
+    let at = 2 * PAGE_SIZE;
+    assert_eq!(write(resume[1], &[(at % 251) as u8]), 1);
+    let mut bytes = [0u8; 8];
+    assert_eq!(read(ready[0], &mut bytes), 8);
+    assert!(bytes
+        .iter()
+        .enumerate()
+        .all(|(i, b)| *b == ((PAGE_SIZE + i) % 251) as u8));
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // the file holds what was written through the mapping
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut page = [0u8; PAGE_SIZE];
+    for i in 0..PAGES {
+        assert_eq!(read(fd as usize, &mut page), PAGE_SIZE as isize);
+        assert!(page
+            .iter()
+            .enumerate()
+            .all(|(j, b)| *b == ((i * PAGE_SIZE + j) % 251) as u8));
+    }
+    close(fd as usize);
+    println!("Test reclaim OK!");
+    0
+}
@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, fstat, open, read, rename, unlink, write, OpenFlags, Stat};
+
+/// 测试 rename，改名后旧名字消失、新名字指向同一个 inode，输出 Test rename OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let old_name = "fname_rename_old\0";
+    let new_name = "fname_rename_new\0";
+    let taken_name = "fname_rename_taken\0";
+    let fd = open(old_name, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let data = "rename me";
+    assert_eq!(write(fd, data.as_bytes()), data.len() as isize);
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(rename(old_name, new_name), 0);
+    // the old name is gone, the new one leads to the same inode
+    assert!(open(old_name, OpenFlags::RDONLY) < 0);
+    let fd2 = open(new_name, OpenFlags::RDONLY);
+    assert!(fd2 > 0);
+    let fd2 = fd2 as usize;
+    let stat2 = Stat::new();
+    assert_eq!(fstat(fd2, &stat2), 0);
+    assert_eq!(stat.ino, stat2.ino);
+    assert_eq!(stat2.nlink, 1);
+    let mut buf = [0u8; 32];
+    assert_eq!(read(fd2, &mut buf), data.len() as isize);
+    assert_eq!(&buf[..data.len()], data.as_bytes());
+    close(fd2);
+    // an existing name is not replaced, a missing one can not be moved
+    let fd3 = open(taken_name, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd3 > 0);
+    close(fd3 as usize);
+    assert_eq!(rename(new_name, taken_name), -1);
+    assert_eq!(rename(old_name, "fname_rename_none\0"), -1);
+    // renaming to itself is fine
+    assert_eq!(rename(new_name, new_name), 0);
+    close(fd);
+    unlink(new_name);
+    unlink(taken_name);
+    println!("Test rename OK!");
+    0
+}
@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, open, read, rename_replace, statfs, unlink, write, OpenFlags, StatFs};
+
+/// 测试 rename_replace，把文件改名覆盖另一个文件后新名字读出原文件的内容，被覆盖的文件在最后一个打开它的 fd 关闭后释放它的块，源不存在、目录非空、目录移到自己下面、文件与目录互相覆盖时返回对应的错误码，输出 Test rename replace OK! 就算正确。
+
+const ENOENT: isize = 2;
+const ENOTDIR: isize = 20;
+const EISDIR: isize = 21;
+const EINVAL: isize = 22;
+const ENOTEMPTY: isize = 39;
+
+/// Data blocks of the file replaced
+const TARGET_BLOCKS: usize = 8;
+
+fn free_blocks() -> u32 {
+    let mut buf = StatFs::default();
+    assert_eq!(statfs(&mut buf), 0);
+    buf.free_blocks
+}
+
+fn create(name: &str, data: &[u8]) {
+    let fd = open(name, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, data), data.len() as isize);
+    close(fd as usize);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let source = "fname_mv_source\0";
+    let target = "fname_mv_target\0";
+    create(source, b"moved over");
+    create(target, &[b't'; TARGET_BLOCKS * 512]);
+    // held open across the rename, so its blocks stay until it is closed
+    let held = open(target, OpenFlags::RDONLY);
+    assert!(held > 0);
+    let held = held as usize;
+    let free = free_blocks();
+    assert_eq!(rename_replace(source, target), 0);
+    assert!(open(source, OpenFlags::RDONLY) < 0);
+    let fd = open(target, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 32];
+    assert_eq!(read(fd as usize, &mut buf), 10);
+    assert_eq!(&buf[..10], b"moved over");
+    close(fd as usize);
+    assert_eq!(free_blocks(), free);
+    assert_eq!(read(held, &mut buf), 32);
+    assert!(buf.iter().all(|&b| b == b't'));
+    close(held);
+    assert_eq!(free_blocks(), free + TARGET_BLOCKS as u32);
+
+    assert_eq!(rename_replace(source, "fname_mv_none\0"), -ENOENT);
+    // a file and a directory never replace each other
+    assert_eq!(rename_replace(target, "home\0"), -EISDIR);
+    assert_eq!(rename_replace("home\0", target), -ENOTDIR);
+    // a directory moves only onto an empty one, and never under itself
+    create("home/fname_mv_entry\0", b"entry");
+    assert_eq!(rename_replace("mnt\0", "home\0"), -ENOTEMPTY);
+    assert_eq!(rename_replace("home\0", "home/inner\0"), -EINVAL);
+    unlink("home/fname_mv_entry\0");
+    // renaming onto itself is fine
+    assert_eq!(rename_replace(target, target), 0);
+    unlink(target);
+    println!("Test rename replace OK!");
+    0
+}
@@ -0,0 +1,81 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, fcntl, fstat, open, read, ringfile_create, unlink, write, OpenFlags, Stat, StatMode,
+    F_SETFL,
+};
+
+/// 测试 ringfile_create，写入超过容量的数据后只留下最新的 capacity 个字节且按写入顺序读出，读完后返回 0 或 EAGAIN，输出 Test ringfile OK! 就算正确。
+
+const CAPACITY: usize = 100;
+const EAGAIN: isize = 11;
+
+fn byte(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_ring\0";
+    assert_eq!(ringfile_create(fname, 0), -1);
+    let fd = ringfile_create(fname, CAPACITY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(ringfile_create(fname, CAPACITY), -1);
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(stat.mode, StatMode::FILE);
+    // 250 bytes in small pieces, the first 150 are pushed out
+    let mut written = 0usize;
+    while written < 250 {
+        let mut piece = [0u8; 7];
+        for (j, b) in piece.iter_mut().enumerate() {
+            *b = byte(written + j);
+        }
+        let len = piece.len().min(250 - written);
+        assert_eq!(write(fd, &piece[..len]), len as isize);
+        written += len;
+    }
+    let mut buf = [0u8; 2 * CAPACITY];
+    assert_eq!(read(fd, &mut buf[..30]), 30);
+    for (j, &b) in buf[..30].iter().enumerate() {
+        assert_eq!(b, byte(150 + j));
+    }
+    // the read cursor is kept by the ring, a new fd goes on where the last one stopped
+    close(fd);
+    let fd = open(fname, OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(read(fd, &mut buf), 70);
+    for (j, &b) in buf[..70].iter().enumerate() {
+        assert_eq!(b, byte(180 + j));
+    }
+    // caught up: end of file, or EAGAIN without blocking
+    assert_eq!(read(fd, &mut buf), 0);
+    assert_eq!(fcntl(fd, F_SETFL, OpenFlags::NONBLOCK.bits() as usize), 0);
+    assert_eq!(read(fd, &mut buf), -EAGAIN);
+    // a single write longer than the ring leaves only its tail
+    let mut data = [0u8; 3 * CAPACITY / 2];
+    for (j, b) in data.iter_mut().enumerate() {
+        *b = byte(1000 + j);
+    }
+    assert_eq!(write(fd, &data), data.len() as isize);
+    assert_eq!(read(fd, &mut buf), CAPACITY as isize);
+    assert_eq!(&buf[..CAPACITY], &data[data.len() - CAPACITY..]);
+    // truncating empties the ring but keeps its capacity
+    assert_eq!(write(fd, b"stale"), 5);
+    close(fd);
+    let fd = open(fname, OpenFlags::RDWR | OpenFlags::TRUNC);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(read(fd, &mut buf), 0);
+    assert_eq!(write(fd, &data), data.len() as isize);
+    assert_eq!(read(fd, &mut buf), CAPACITY as isize);
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    println!("Test ringfile OK!");
+    0
+}
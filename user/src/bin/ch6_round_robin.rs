@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, get_time, set_timeslice, waitpid, yield_};
+
+/// 测试时间片轮转，两个时间片为 3 个 tick 的忙等任务交替运行，每段连续运行和被换下的时间都接近一个时间片，输出 Test round_robin OK! 就算正确。
+
+/// 时间片 (tick)
+const QUANTUM: isize = 3;
+/// 一个 tick 的时长 (ms)
+const TICK_MS: isize = 10;
+/// 测量时长 (ms)
+const DURATION: isize = 400;
+/// 两次读到的时间相差这么多 (ms) 就算被换下过
+const GAP_MS: isize = 4;
+
+/// Busy loop from `start` for DURATION ms, checking each stretch it ran
+/// and each time it was switched out is about one quantum long
+fn child(start: isize) -> ! {
+    assert_eq!(set_timeslice(QUANTUM as usize), QUANTUM);
+    while get_time() < start {
+        yield_();
+    }
+    let mut stretch_start = get_time();
+    let mut last = stretch_start;
+    let mut stretches = 0;
+    while last < start + DURATION {
+        let now = get_time();
+        if now - last >= GAP_MS {
+            // the first stretch may have started anywhere in a quantum,
+            // and the other task may be done before the last one
+            if stretches > 0 && now < start + DURATION {
+                let ran = last - stretch_start;
+                assert!(
+                    ran >= (QUANTUM - 1) * TICK_MS - GAP_MS && ran <= QUANTUM * TICK_MS + GAP_MS,
+                    "ran {}ms in a row",
+                    ran
+                );
+                // the other task ran for its quantum in between
+                let away = now - last;
+                assert!(
+                    away >= (QUANTUM - 1) * TICK_MS - GAP_MS
+                        && away <= QUANTUM * TICK_MS + 2 * GAP_MS,
+                    "switched out for {}ms",
+                    away
+                );
+            }
+            stretches += 1;
+            stretch_start = now;
+        }
+        last = now;
+    }
+    // preempted about once a quantum all along
+    assert!(stretches >= DURATION / (2 * QUANTUM * TICK_MS) - 1);
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = get_time() + 50;
+    let mut pids = [0isize; 2];
+    for pid in pids.iter_mut() {
+        *pid = fork();
+        if *pid == 0 {
+            child(start);
+        }
+    }
+    for pid in pids {
+        let mut exit_code: i32 = 1;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        assert_eq!(exit_code, 0);
+    }
+    println!("Test round_robin OK!");
+    0
+}
@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, frame_stats, sbrk, waitpid, FrameStats, SIGSEGV};
+
+/// 测试 sbrk，堆增长后新区域可读写，收缩后页帧被释放、访问原区域的进程被杀死，超过上限或缩到堆底以下返回 -1，输出 Test sbrk OK! 就算正确。
+
+const PAGES: usize = 8;
+const PAGE_SIZE: usize = 4096;
+
+fn frees() -> usize {
+    let mut stats = FrameStats::default();
+    assert_eq!(frame_stats(&mut stats), 0);
+    stats.frees
+}
+
+fn byte_at(addr: usize) -> u8 {
+    unsafe { (addr as *const u8).read_volatile() }
+}
+
+/// Touch `addr` in a child, it has to be killed by a page fault
+fn gone(addr: usize) {
+    let pid = fork();
+    if pid == 0 {
+        unsafe { (addr as *mut u8).write_volatile(0xff) };
+        exit(0);
+    }
+    assert!(pid > 0);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -SIGSEGV);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let bottom = sbrk(0);
+    assert!(bottom > 0);
+    let bottom = bottom as usize;
+    // grow by whole pages and a bit more, the break lands where it was asked to
+    let len = PAGES * PAGE_SIZE + 100;
+    assert_eq!(sbrk(len as isize), bottom as isize);
+    assert_eq!(sbrk(0), (bottom + len) as isize);
+    let heap = unsafe { core::slice::from_raw_parts_mut(bottom as *mut u8, len) };
+    for (i, b) in heap.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    for (i, &b) in heap.iter().enumerate() {
+        assert_eq!(b, i as u8);
+    }
+    // a fork gets a heap of its own
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(sbrk(0), (bottom + len) as isize);
+        assert_eq!(byte_at(bottom + len - 1), (len - 1) as u8);
+        assert_eq!(sbrk(-(len as isize)), (bottom + len) as isize);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(sbrk(0), (bottom + len) as isize);
+    assert_eq!(heap[len - 1], (len - 1) as u8);
+    // shrinking frees the frames of the whole pages past the new break
+    let before = frees();
+    let kept = PAGE_SIZE / 2;
+    assert_eq!(sbrk(-((len - kept) as isize)), (bottom + len) as isize);
+    assert_eq!(sbrk(0), (bottom + kept) as isize);
+    assert_eq!(frees(), before + PAGES);
+    assert_eq!(heap[kept - 1], (kept - 1) as u8);
+    gone(bottom + PAGE_SIZE);
+    gone(bottom + len - 1);
+    // not below the bottom, nor past the limit
+    assert_eq!(sbrk(-(kept as isize) - 1), -1);
+    assert_eq!(sbrk(isize::MAX), -1);
+    assert_eq!(sbrk(0), (bottom + kept) as isize);
+    // grown again, the pages come back zeroed
+    assert_eq!(sbrk(PAGE_SIZE as isize), (bottom + kept) as isize);
+    assert_eq!(byte_at(bottom + PAGE_SIZE), 0);
+    let top = bottom + kept + PAGE_SIZE;
+    assert_eq!(sbrk(-((top - bottom) as isize)), top as isize);
+    assert_eq!(sbrk(0), bottom as isize);
+    println!("Test sbrk OK!");
+    0
+}
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, fallocate, lseek, open, read, seal_size, unlink, write, OpenFlags, SEEK_END, SEEK_SET,
+};
+
+const BLOCK: usize = 512;
+
+/// 测试 fallocate 与 seal_size，预分配 10 块只写前 3 块，封口后文件大小只剩写过的部分，输出 Test seal_size OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_seal_size\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(fallocate(fd, 0, 10 * BLOCK), 0);
+    assert_eq!(lseek(fd, 0, SEEK_END), (10 * BLOCK) as isize);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let data = [b's'; BLOCK];
+    for _ in 0..3 {
+        assert_eq!(write(fd, &data), BLOCK as isize);
+    }
+    assert_eq!(seal_size(fd), (3 * BLOCK) as isize);
+    assert_eq!(lseek(fd, 0, SEEK_END), (3 * BLOCK) as isize);
+    // sealing again changes nothing
+    assert_eq!(seal_size(fd), (3 * BLOCK) as isize);
+    close(fd);
+    // only the written bytes are left
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buf = [0u8; BLOCK];
+    let mut len = 0;
+    loop {
+        let n = read(fd, &mut buf);
+        if n <= 0 {
+            break;
+        }
+        assert!(buf[..n as usize].iter().all(|&b| b == b's'));
+        len += n as usize;
+    }
+    assert_eq!(len, 3 * BLOCK);
+    // a read-only file can be neither preallocated nor sealed
+    assert_eq!(fallocate(fd, 0, BLOCK), -1);
+    assert_eq!(seal_size(fd), -1);
+    close(fd);
+    unlink(fname);
+    println!("Test seal_size OK!");
+    0
+}
@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, lseek, open, read, sendfile, unlink, write, OpenFlags, SEEK_SET};
+
+/// 测试 sendfile，把 100KB 的文件整个拷到新文件，要拷的字节数多于文件长度时只拷到文件末尾，两个文件逐字节相同，输出 Test sendfile OK! 就算正确。
+
+const SIZE: usize = 100 * 1024;
+const CHUNK: usize = 1000;
+
+fn byte(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let src = "sendfile_src\0";
+    let dst = "sendfile_dst\0";
+    let fd = open(src, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buf = [0u8; CHUNK];
+    let mut pos = 0;
+    while pos < SIZE {
+        let len = CHUNK.min(SIZE - pos);
+        for (j, b) in buf[..len].iter_mut().enumerate() {
+            *b = byte(pos + j);
+        }
+        assert_eq!(write(fd, &buf[..len]), len as isize);
+        pos += len;
+    }
+    close(fd);
+    let in_fd = open(src, OpenFlags::RDONLY);
+    let out_fd = open(dst, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(in_fd > 0 && out_fd > 0);
+    let (in_fd, out_fd) = (in_fd as usize, out_fd as usize);
+    // part of the way, then past the end, which stops at the end of the input
+    assert_eq!(sendfile(out_fd, in_fd, 3000), 3000);
+    assert_eq!(sendfile(out_fd, in_fd, 2 * SIZE), (SIZE - 3000) as isize);
+    assert_eq!(sendfile(out_fd, in_fd, 100), 0);
+    // only a readable input and a writable output
+    assert_eq!(sendfile(in_fd, out_fd, 100), -1);
+    assert_eq!(lseek(out_fd, 0, SEEK_SET), 0);
+    let mut pos = 0;
+    loop {
+        let n = read(out_fd, &mut buf);
+        if n <= 0 {
+            break;
+        }
+        for (j, &b) in buf[..n as usize].iter().enumerate() {
+            assert_eq!(b, byte(pos + j));
+        }
+        pos += n as usize;
+    }
+    assert_eq!(pos, SIZE);
+    close(in_fd);
+    close(out_fd);
+    assert_eq!(unlink(src), 0);
+    assert_eq!(unlink(dst), 0);
+    println!("Test sendfile OK!");
+    0
+}
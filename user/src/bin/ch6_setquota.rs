@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, fallocate, ftruncate, lseek, open, setquota, unlink, write, OpenFlags, SEEK_END,
+};
+
+const QUOTA: usize = 1000;
+
+/// 测试 setquota，给文件设 1000 字节的上限后，越过上限的写只写到上限为止，之后的写、预分配与扩展都失败，低于当前大小的上限被拒绝，取消上限后又能继续写，输出 Test setquota OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_setquota\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let data = [b'q'; 600];
+    assert_eq!(write(fd, &data), 600);
+    // not below what the file holds already
+    assert_eq!(setquota(fd, 599), -1);
+    assert_eq!(setquota(fd, QUOTA), 0);
+    // cut short at the quota
+    assert_eq!(write(fd, &data), (QUOTA - 600) as isize);
+    assert_eq!(write(fd, &data), 0);
+    assert_eq!(lseek(fd, 0, SEEK_END), QUOTA as isize);
+    assert!(fallocate(fd, 0, QUOTA + 1) < 0);
+    assert!(ftruncate(fd, QUOTA + 1) < 0);
+    // shrinking is fine
+    assert_eq!(ftruncate(fd, 100), 0);
+    assert_eq!(lseek(fd, 0, SEEK_END), 100);
+    assert_eq!(write(fd, &data), 600);
+    // lifted
+    assert_eq!(setquota(fd, 0), 0);
+    assert_eq!(write(fd, &data), 600);
+    assert_eq!(lseek(fd, 0, SEEK_END), 1300);
+    close(fd);
+    // only on a file open for writing
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(setquota(fd as usize, QUOTA), -1);
+    close(fd as usize);
+    unlink(fname);
+    println!("Test setquota OK!");
+    0
+}
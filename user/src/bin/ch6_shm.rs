@@ -0,0 +1,74 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, shmat, shmdt, shmget, waitpid};
+
+/// 测试共享内存，父进程写入共享段后 fork，子进程从继承的映射和重新挂上的映射都能读到，子进程写入的内容父进程也能读到，子进程退出后共享段仍在，最后一个分离后共享段被释放，输出 Test shm OK! 就算正确。
+
+const KEY: usize = 0x5348;
+const SIZE: usize = 4096;
+
+/// Write `data` at `offset` of the segment at `addr`, the same frames may be at other addresses
+fn store(addr: isize, offset: usize, data: &[u8]) {
+    for (i, &byte) in data.iter().enumerate() {
+        unsafe { ((addr as usize + offset + i) as *mut u8).write_volatile(byte) };
+    }
+}
+
+fn load(addr: isize, offset: usize) -> u8 {
+    unsafe { ((addr as usize + offset) as *const u8).read_volatile() }
+}
+
+fn holds(addr: isize, offset: usize, data: &[u8]) -> bool {
+    data.iter()
+        .enumerate()
+        .all(|(i, &byte)| load(addr, offset + i) == byte)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(shmget(KEY, 0), -1);
+    let id = shmget(KEY, SIZE);
+    assert!(id > 0);
+    assert_eq!(shmget(KEY, SIZE), id);
+    assert_eq!(shmget(KEY, SIZE + 1), -1);
+    assert_eq!(shmat(id as usize + 1), -1);
+    let addr = shmat(id as usize);
+    assert!(addr > 0);
+    store(addr, 0, b"hello");
+
+    let pid = fork();
+    if pid == 0 {
+        // shared through the fork, not copied on write
+        assert!(holds(addr, 0, b"hello"));
+        // and once more by key, at another address over the same frames
+        let again = shmat(shmget(KEY, 0) as usize);
+        assert!(again > 0 && again != addr);
+        assert!(holds(again, 0, b"hello"));
+        store(again, 5, b"world");
+        assert!(holds(addr, 5, b"world"));
+        // exits attached twice
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // the child leaving did not take the segment with it
+    assert!(holds(addr, 0, b"helloworld"));
+    assert_eq!(shmget(KEY, 0), id);
+    assert_eq!(shmdt(addr as usize + SIZE), -1);
+    assert_eq!(shmdt(addr as usize), 0);
+    assert_eq!(shmdt(addr as usize), -1);
+    // freed with the last attachment, the key makes a new zeroed one
+    assert_eq!(shmget(KEY, 0), -1);
+    let id = shmget(KEY, SIZE);
+    assert!(id > 0);
+    let addr = shmat(id as usize);
+    assert!(addr > 0);
+    assert!((0..SIZE).all(|offset| load(addr, offset) == 0));
+    assert_eq!(shmdt(addr as usize), 0);
+    println!("Test shm OK!");
+    0
+}
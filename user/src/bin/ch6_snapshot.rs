@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, lseek, open, read, snapshot_file, write, OpenFlags, SEEK_END, SEEK_SET};
+
+/// 测试 snapshot_file，快照保留覆盖写之前的内容且不可写，输出 Test snapshot OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_snapshot\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let old = [b'o'; 1000];
+    assert_eq!(write(fd, &old), old.len() as isize);
+    let snap = snapshot_file(fd);
+    assert!(snap > 0);
+    let snap = snap as usize;
+    // overwrite and grow the original
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let new = [b'n'; 1500];
+    assert_eq!(write(fd, &new), new.len() as isize);
+    // the snapshot still shows what was there
+    let mut buf = [0u8; 2000];
+    assert_eq!(read(snap, &mut buf), old.len() as isize);
+    assert_eq!(buf[..old.len()], old);
+    assert_eq!(lseek(snap, -10, SEEK_END), old.len() as isize - 10);
+    assert_eq!(read(snap, &mut buf), 10);
+    assert_eq!(lseek(snap, -1, SEEK_SET), -1);
+    // and can not be written
+    assert_eq!(write(snap, &new), -1);
+    close(snap);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, &mut buf), new.len() as isize);
+    assert_eq!(buf[..new.len()], new);
+    close(fd);
+    println!("Test snapshot OK!");
+    0
+}
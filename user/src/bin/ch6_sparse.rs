@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, fstat, lseek, open, read, unlink, write, OpenFlags, Stat, SEEK_SET};
+
+/// 测试稀疏文件，在 1MB 处写入一个字节后文件大小为 1MB 加 1，但只占用了几个块，空洞读出来全是 0，输出 Test sparse OK! 就算正确。
+
+const FAR: usize = 1 << 20;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_sparse\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(lseek(fd, FAR as isize, SEEK_SET), FAR as isize);
+    assert_eq!(write(fd, b"x"), 1);
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(stat.size, FAR as u64 + 1);
+    // the block written and the indirect blocks above it, not the 2048 before it
+    assert!(stat.blocks > 0 && stat.blocks <= 3);
+    let mut buf = [1u8; 512];
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, &mut buf), 512);
+    assert!(buf.iter().all(|&b| b == 0));
+    assert_eq!(
+        lseek(fd, (FAR - 511) as isize, SEEK_SET),
+        (FAR - 511) as isize
+    );
+    assert_eq!(read(fd, &mut buf), 512);
+    assert!(buf[..511].iter().all(|&b| b == 0));
+    assert_eq!(buf[511], b'x');
+    close(fd);
+    unlink(fname);
+    println!("Test sparse OK!");
+    0
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, fs_info, open, statfs, unlink, write, FsInfo, OpenFlags, StatFs};
+
+const BLOCK: usize = 512;
+
+/// 测试 statfs，写入 20 块后空闲数据块恰好少 21 块（含一个索引块），空闲 inode 少 1，删除文件后全部恢复，输出 Test statfs OK! 就算正确。
+
+fn usage() -> StatFs {
+    let mut stat = StatFs::default();
+    assert_eq!(statfs(&mut stat), 0);
+    stat
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let info = FsInfo::default();
+    assert_eq!(fs_info(&info), 0);
+    let before = usage();
+    assert_eq!(before.block_size as usize, BLOCK);
+    assert_eq!(before.total_blocks, info.data_area_blocks);
+    assert_eq!(before.total_inodes, info.inode_num);
+    assert!(before.free_blocks <= before.total_blocks);
+    assert!(before.free_inodes < before.total_inodes);
+    let fname = "fname_statfs\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let created = usage();
+    assert_eq!(created.free_inodes, before.free_inodes - 1);
+    // past the direct blocks, so an indirect block comes along
+    let buf = [b's'; BLOCK];
+    for _ in 0..20 {
+        assert_eq!(write(fd, &buf), BLOCK as isize);
+    }
+    close(fd);
+    assert_eq!(usage().free_blocks, created.free_blocks - 21);
+    assert_eq!(unlink(fname), 0);
+    let after = usage();
+    assert_eq!(after.free_blocks, before.free_blocks);
+    assert_eq!(after.free_inodes, before.free_inodes);
+    println!("Test statfs OK!");
+    0
+}
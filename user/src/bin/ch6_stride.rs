@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, get_time, getpid, ps, set_priority, waitpid, yield_, ProcInfo};
+
+/// 测量时长 (ms)
+const DURATION: usize = 600;
+/// 两个任务的优先级
+const PRIO_LOW: isize = 2;
+const PRIO_HIGH: isize = 8;
+
+/// Busy loop from `start` for DURATION ms, then exit with the CPU time we got in ms
+fn child(start: usize, prio: isize) -> ! {
+    assert_eq!(set_priority(prio), prio);
+    while (get_time() as usize) < start {
+        yield_();
+    }
+    while (get_time() as usize) < start + DURATION {}
+    let mut infos = [ProcInfo::new(); 16];
+    let total = ps(&mut infos) as usize;
+    let pid = getpid() as usize;
+    let me = infos[..total.min(infos.len())]
+        .iter()
+        .find(|info| info.pid == pid)
+        .unwrap();
+    exit(me.cpu_time as i32)
+}
+
+/// 测试 stride 调度，两个忙等任务的优先级为 1:4，得到的 CPU 时间之比也接近 1:4，输出 Test stride OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(set_priority(1), -1);
+    let start = get_time() as usize + 50;
+    let mut cpu_times = [0i32; 2];
+    let mut pids = [0usize; 2];
+    for (i, &prio) in [PRIO_LOW, PRIO_HIGH].iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            child(start, prio);
+        }
+        pids[i] = pid as usize;
+    }
+    for (pid, cpu_time) in pids.iter().zip(cpu_times.iter_mut()) {
+        assert_eq!(waitpid(*pid, cpu_time), *pid as isize);
+    }
+    let (low, high) = (cpu_times[0], cpu_times[1]);
+    println!(
+        "cpu time with priority {}: {}ms, with priority {}: {}ms",
+        PRIO_LOW, low, PRIO_HIGH, high
+    );
+    // the low priority task still gets its share
+    assert!(low > 0);
+    // the ratio should be 4, leave some room for the ticks lost around the edges
+    assert!(high * 2 >= low * 5 && high * 2 <= low * 11);
+    println!("Test stride OK!");
+    0
+}
@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::mem::size_of;
+use user_lib::{close, getdents, open, read, rename, swap_dir, unlink, write, Dirent, OpenFlags};
+
+/// 测试 swap_dir，交换两个子目录后各自列出对方原来的文件，只能交换两个目录，文件、不存在的路径和目录与其子目录都会失败且内容不变，输出 Test swap_dir OK! 就算正确。
+
+/// Check that the directory at `dir` holds just `expected`, besides `.` and `..`
+fn check_entries(dir: &str, expected: &[&str]) {
+    let fd = open(dir, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut dirents: [Dirent; 4] = Default::default();
+    let mut found = 0;
+    loop {
+        let n = getdents(fd as usize, &mut dirents);
+        assert!(n >= 0);
+        if n == 0 {
+            break;
+        }
+        for d in &dirents[..n as usize / size_of::<Dirent>()] {
+            let name = d.name();
+            if name == "." || name == ".." {
+                continue;
+            }
+            assert!(expected.contains(&name));
+            found += 1;
+        }
+    }
+    assert_eq!(found, expected.len());
+    close(fd as usize);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let names = ["fname_swap_dir_a\0", "fname_swap_dir_b\0"];
+    for name in names.iter() {
+        let fd = open(name, OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(fd > 0);
+        assert_eq!(write(fd as usize, name.as_bytes()), name.len() as isize);
+        close(fd as usize);
+    }
+    // regular files are not directories
+    assert_eq!(swap_dir(names[0], names[1]), -1);
+    assert_eq!(swap_dir(names[0], "no_such_dir\0"), -1);
+    assert_eq!(swap_dir("no_such_dir\0", names[0]), -1);
+    // nothing moved
+    for name in names.iter() {
+        let fd = open(name, OpenFlags::RDONLY);
+        assert!(fd > 0);
+        let mut buf = [0u8; 32];
+        let len = read(fd as usize, &mut buf) as usize;
+        assert_eq!(&buf[..len], name.as_bytes());
+        close(fd as usize);
+        assert_eq!(unlink(name), 0);
+    }
+    // filled directories swap their entries, with the data of the files
+    let paths = ["/swap/a/a_file0\0", "/swap/a/a_file1\0", "/swap/b/b_file\0"];
+    for path in paths.iter() {
+        let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(fd > 0);
+        assert_eq!(write(fd as usize, path.as_bytes()), path.len() as isize);
+        close(fd as usize);
+    }
+    assert_eq!(swap_dir("/swap/a\0", "swap/b\0"), 0);
+    check_entries("/swap/a\0", &["b_file"]);
+    check_entries("/swap/b\0", &["a_file0", "a_file1"]);
+    let fd = open("/swap/a/b_file\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 32];
+    let len = read(fd as usize, &mut buf) as usize;
+    assert_eq!(&buf[..len], paths[2].as_bytes());
+    close(fd as usize);
+    // a directory and one under it do not swap
+    assert_eq!(swap_dir("/swap\0", "/swap/a\0"), -1);
+    check_entries("/swap\0", &["a", "b"]);
+    // unlink takes a name in the root only
+    for path in ["/swap/a/b_file\0", "/swap/b/a_file0\0", "/swap/b/a_file1\0"].iter() {
+        assert_eq!(rename(path, "fname_swap_dir\0"), 0);
+        assert_eq!(unlink("fname_swap_dir\0"), 0);
+    }
+    println!("Test swap_dir OK!");
+    0
+}
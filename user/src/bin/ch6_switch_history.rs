@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, exit, fork, get_time, getpid, pipe, read, sleep, switch_history, waitpid, write,
+    SwitchReason, SwitchRecord,
+};
+
+/// 测试 switch_history，在管道上阻塞、被时钟中断抢占的原因都会被记录，输出 Test switch_history OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(pipe_fd[0]);
+        // let the parent wait on the empty pipe for a while
+        sleep(20);
+        assert_eq!(write(pipe_fd[1], b"x"), 1);
+        close(pipe_fd[1]);
+        exit(0);
+    }
+    close(pipe_fd[1]);
+    let mut buf = [0u8; 1];
+    assert_eq!(read(pipe_fd[0], &mut buf), 1);
+    close(pipe_fd[0]);
+    // busy loop without yielding until the time slice runs out
+    let start = get_time();
+    while get_time() < start + 50 {}
+    let mut records = [SwitchRecord::new(); 16];
+    let n = switch_history(getpid() as usize, &mut records);
+    assert!(n > 0);
+    let records = &records[..n as usize];
+    let pipe_pos = records
+        .iter()
+        .rposition(|r| r.reason == SwitchReason::BlockOnPipe)
+        .unwrap();
+    let preempt_pos = records
+        .iter()
+        .rposition(|r| r.reason == SwitchReason::Preempt)
+        .unwrap();
+    assert!(pipe_pos < preempt_pos);
+    assert!(records.windows(2).all(|w| w[0].time_us <= w[1].time_us));
+    // the child sleeps with sys_yield, which can be seen from its parent
+    let mut child_records = [SwitchRecord::new(); 4];
+    let n = switch_history(pid as usize, &mut child_records);
+    assert!(n > 0);
+    assert!(child_records[..n as usize]
+        .iter()
+        .any(|r| r.reason == SwitchReason::Yield));
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    // no longer a child
+    assert_eq!(switch_history(pid as usize, &mut child_records), -1);
+    println!("Test switch_history OK!");
+    0
+}
@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, open, read, symlink, unlink, write, OpenFlags};
+
+/// 测试 symlink，经有效的符号链接打开的是目标文件，带 NOFOLLOW 时打开链接本身失败，悬空链接和指向自身的链接打开失败但可以删除，输出 Test symlink OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let target = "symlink_target\0";
+    let fd = open(target, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"through the link"), 16);
+    close(fd as usize);
+    // a valid link opens its target, for reading and writing alike
+    assert_eq!(symlink(target, "symlink_valid\0"), 0);
+    assert_eq!(symlink("other\0", "symlink_valid\0"), -1);
+    let fd = open("symlink_valid\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 32];
+    assert_eq!(read(fd as usize, &mut buf), 16);
+    assert_eq!(&buf[..16], b"through the link");
+    close(fd as usize);
+    let fd = open("symlink_valid\0", OpenFlags::WRONLY | OpenFlags::APPEND);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"!"), 1);
+    close(fd as usize);
+    let fd = open(target, OpenFlags::RDONLY);
+    assert_eq!(read(fd as usize, &mut buf), 17);
+    close(fd as usize);
+    // the link itself is not opened
+    assert_eq!(
+        open("symlink_valid\0", OpenFlags::RDONLY | OpenFlags::NOFOLLOW),
+        -1
+    );
+    let fd = open(target, OpenFlags::RDONLY | OpenFlags::NOFOLLOW);
+    assert!(fd > 0);
+    close(fd as usize);
+    // a dangling link and a cycle lead nowhere
+    assert_eq!(symlink("symlink_missing\0", "symlink_dangling\0"), 0);
+    assert_eq!(open("symlink_dangling\0", OpenFlags::RDONLY), -1);
+    assert_eq!(symlink("symlink_cycle\0", "symlink_cycle\0"), 0);
+    assert_eq!(open("symlink_cycle\0", OpenFlags::RDONLY), -1);
+    // removing a link leaves its target alone
+    for link in ["symlink_valid\0", "symlink_dangling\0", "symlink_cycle\0"] {
+        assert_eq!(unlink(link), 0);
+    }
+    let fd = open(target, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(unlink(target), 0);
+    println!("Test symlink OK!");
+    0
+}
@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    get_time, getpid, reset_syscall_stats, task_info, yield_, TaskInfo, SYSCALL_GETPID,
+    SYSCALL_RESET_SYSCALL_STATS, SYSCALL_TASK_INFO, SYSCALL_YIELD,
+};
+
+/// 测试 reset_syscall_stats，先调用 5 次 getpid，清零后再调用 2 次，之后的 task_info 只计入清零之后的调用与时间，调度次数不清零并随 yield 增加，输出 Test syscall stats OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let info = TaskInfo::new();
+    for _ in 0..5 {
+        getpid();
+    }
+    assert_eq!(task_info(&info), 0);
+    assert_eq!(info.syscall_times[SYSCALL_GETPID], 5);
+    assert_eq!(info.syscall_times[SYSCALL_TASK_INFO], 1);
+    // switched in at least once to get here
+    assert!(info.schedule_count >= 1);
+    let scheduled = info.schedule_count;
+
+    let t1 = get_time() as usize;
+    assert_eq!(reset_syscall_stats(), 0);
+    for _ in 0..2 {
+        getpid();
+    }
+    yield_();
+    assert_eq!(task_info(&info), 0);
+    let t2 = get_time() as usize;
+    // the reset counts itself out along with the first interval
+    assert_eq!(info.syscall_times[SYSCALL_RESET_SYSCALL_STATS], 0);
+    assert_eq!(info.syscall_times[SYSCALL_GETPID], 2);
+    assert_eq!(info.syscall_times[SYSCALL_TASK_INFO], 1);
+    assert_eq!(info.syscall_times[SYSCALL_YIELD], 1);
+    assert!(info.time <= t2 - t1 + 1);
+    // the yield switched it out and in again
+    assert!(info.schedule_count > scheduled);
+    println!("Test syscall stats OK!");
+    0
+}
@@ -0,0 +1,78 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    close, exit, fork, getpid, gettid, pipe, read, sleep, thread_create, waitpid, waittid, write,
+    yield_,
+};
+
+/// 测试线程，两个线程在同一地址空间中各给共享计数加 1000 次，等两者退出后计数为 2000，线程号各不相同，第一个线程退出后进程的另一个线程仍能使用地址空间与文件，输出 Test threads OK! 就算正确。
+
+const ADDS: usize = 1000;
+
+/// Shared by all threads of the process
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// The tid each adder sees itself
+static TIDS: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+
+fn adder(index: usize) -> ! {
+    TIDS[index].store(gettid() as usize, Ordering::Relaxed);
+    for i in 0..ADDS {
+        COUNTER.fetch_add(1, Ordering::Relaxed);
+        // give the other one a turn in the middle
+        if i % 100 == 0 {
+            yield_();
+        }
+    }
+    exit(index as i32 + 1)
+}
+
+fn survivor(fd: usize) -> ! {
+    // the first thread of the process has exited by now
+    sleep(20);
+    assert_eq!(write(fd, b"alive"), 5);
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid();
+    assert_eq!(gettid(), pid);
+    let tids = [
+        thread_create(adder as usize, 0),
+        thread_create(adder as usize, 1),
+    ];
+    assert!(tids[0] > 0 && tids[1] > 0 && tids[0] != tids[1]);
+    assert!(tids[0] != pid && tids[1] != pid);
+    assert_eq!(waittid(pid as usize), -1);
+    for (index, &tid) in tids.iter().enumerate() {
+        assert_eq!(waittid(tid as usize), index as isize + 1);
+        assert_eq!(TIDS[index].load(Ordering::Relaxed), tid as usize);
+    }
+    assert_eq!(COUNTER.load(Ordering::Relaxed), 2 * ADDS);
+    // reaped already
+    assert_eq!(waittid(tids[0] as usize), -1);
+
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let child = fork();
+    if child == 0 {
+        close(fds[0]);
+        assert!(thread_create(survivor as usize, fds[1]) > 0);
+        exit(7);
+    }
+    close(fds[1]);
+    let mut buf = [0u8; 8];
+    assert_eq!(read(fds[0], &mut buf), 5);
+    assert_eq!(&buf[..5], b"alive");
+    // the write end went with the last thread of the child
+    assert_eq!(read(fds[0], &mut buf), 0);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 7);
+    println!("Test threads OK!");
+    0
+}
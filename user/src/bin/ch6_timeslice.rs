@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, get_time, getpid, ps, set_timeslice, waitpid, yield_, ProcInfo};
+
+/// 测量时长 (ms)
+const DURATION: usize = 600;
+/// 两个任务的时间片 (tick)
+const SLICE_SHORT: usize = 1;
+const SLICE_LONG: usize = 4;
+
+/// Busy loop from `start` for DURATION ms, then exit with the CPU time we got in ms
+fn child(start: usize, ticks: usize) -> ! {
+    assert_eq!(set_timeslice(ticks), ticks as isize);
+    while (get_time() as usize) < start {
+        yield_();
+    }
+    while (get_time() as usize) < start + DURATION {}
+    let mut infos = [ProcInfo::new(); 16];
+    let total = ps(&mut infos) as usize;
+    let pid = getpid() as usize;
+    let me = infos[..total.min(infos.len())]
+        .iter()
+        .find(|info| info.pid == pid)
+        .unwrap();
+    exit(me.cpu_time as i32)
+}
+
+/// 测试 set_timeslice，两个忙等任务的时间片为 1:4，得到的 CPU 时间之比也接近 1:4，输出 Test timeslice OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // out of range slices are clamped
+    assert_eq!(set_timeslice(0), 1);
+    assert_eq!(set_timeslice(100000), 100);
+    assert_eq!(set_timeslice(1), 1);
+    let start = get_time() as usize + 50;
+    let mut cpu_times = [0i32; 2];
+    let mut pids = [0usize; 2];
+    for (i, &ticks) in [SLICE_SHORT, SLICE_LONG].iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            child(start, ticks);
+        }
+        pids[i] = pid as usize;
+    }
+    for (pid, cpu_time) in pids.iter().zip(cpu_times.iter_mut()) {
+        assert_eq!(waitpid(*pid, cpu_time), *pid as isize);
+    }
+    let (short, long) = (cpu_times[0], cpu_times[1]);
+    println!(
+        "cpu time with slice {}: {}ms, with slice {}: {}ms",
+        SLICE_SHORT, short, SLICE_LONG, long
+    );
+    assert!(short > 0);
+    // the ratio should be 4, leave some room for the ticks lost around the edges
+    assert!(long * 2 >= short * 5 && long * 2 <= short * 11);
+    println!("Test timeslice OK!");
+    0
+}
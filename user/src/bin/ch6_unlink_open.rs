@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, dup, get_dir_quota, lseek, open, read, set_dir_quota, unlink, write, DirQuota,
+    OpenFlags, SEEK_SET,
+};
+
+/// 测试删除仍打开着的文件，unlink 后文件名消失但数据块仍被占用，已打开的 fd 照常读写，最后一个 fd 关闭后数据块才回收，输出 Test unlink_open OK! 就算正确。
+
+const BLOCK_SIZE: usize = 512;
+const BLOCKS: usize = 24;
+
+fn used() -> usize {
+    let mut quota = DirQuota::default();
+    assert_eq!(get_dir_quota("/\0", &mut quota), 0);
+    quota.used as usize
+}
+
+fn byte(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_unlink_open\0";
+    // a quota with no limit to count the blocks in use
+    assert_eq!(set_dir_quota("/\0", u32::MAX as usize), 0);
+    let before = used();
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buf = [0u8; BLOCK_SIZE];
+    for i in 0..BLOCKS {
+        for (j, b) in buf.iter_mut().enumerate() {
+            *b = byte(i * BLOCK_SIZE + j);
+        }
+        assert_eq!(write(fd, &buf), BLOCK_SIZE as isize);
+    }
+    let held = used();
+    assert!(held >= before + BLOCKS);
+    let other = dup(fd);
+    assert!(other > 0);
+    // the name is gone at once, the blocks stay while the file is open
+    assert_eq!(unlink(fname), 0);
+    assert!(open(fname, OpenFlags::RDONLY) < 0);
+    assert_eq!(used(), held);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    for i in 0..BLOCKS {
+        assert_eq!(read(fd, &mut buf), BLOCK_SIZE as isize);
+        for (j, &b) in buf.iter().enumerate() {
+            assert_eq!(b, byte(i * BLOCK_SIZE + j));
+        }
+    }
+    // and it can still be written
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(write(fd, b"still here"), 10);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, &mut buf[..10]), 10);
+    assert_eq!(&buf[..10], b"still here");
+    // a new file of the same name is another file
+    let new = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(new > 0);
+    assert_eq!(read(new as usize, &mut buf), 0);
+    close(new as usize);
+    assert_eq!(unlink(fname), 0);
+    // the blocks come back only with the last fd
+    close(fd);
+    assert_eq!(used(), held);
+    close(other as usize);
+    assert_eq!(used(), before);
+    assert_eq!(set_dir_quota("/\0", 0), 0);
+    println!("Test unlink_open OK!");
+    0
+}
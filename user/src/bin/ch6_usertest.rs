@@ -27,6 +27,90 @@ static TESTS: &[&str] = &[
     "ch6_file1\0",
     "ch6_file2\0",
     "ch6_file3\0",
+    "ch6_fs_info\0",
+    "ch6_wait_size\0",
+    "ch6_drain\0",
+    "ch6_fd_path\0",
+    "ch6_create_with_data\0",
+    "ch6_ps\0",
+    "ch6_deadline_sched\0",
+    "ch6_vdso\0",
+    "ch6_pin_inode\0",
+    "ch6_close_range\0",
+    "ch6_rename\0",
+    "ch6_write_would_block\0",
+    "ch6_nonblock\0",
+    "ch6_snapshot\0",
+    "ch6_switch_history\0",
+    "ch6_extend_and_map\0",
+    "ch6_checkcycles\0",
+    "ch6_lseek_swap\0",
+    "ch6_timeslice\0",
+    "ch6_process_vm_readv\0",
+    "ch6_seal_size\0",
+    "ch6_eventfd\0",
+    "ch6_mempolicy\0",
+    "ch6_swap_dir\0",
+    "ch6_reclaim\0",
+    "ch6_link_replace\0",
+    "ch6_lock_stats\0",
+    "ch6_append\0",
+    "ch6_ringfile\0",
+    "ch6_cas_rename\0",
+    "ch6_frame_stats\0",
+    "ch6_huge_mmap\0",
+    "ch6_msync\0",
+    "ch6_dir_quota\0",
+    "ch6_o_append\0",
+    "ch6_stride\0",
+    "ch6_file_times\0",
+    "ch6_cow\0",
+    "ch6_ftruncate\0",
+    "ch6_condvar\0",
+    "ch6_lseek\0",
+    "ch6_lazy_mmap\0",
+    "ch6_munmap_split\0",
+    "ch6_getdents\0",
+    "ch6_max_rss\0",
+    "ch6_fsync\0",
+    "ch6_round_robin\0",
+    "ch6_symlink\0",
+    "ch6_dup\0",
+    "ch6_pipe\0",
+    "ch6_unlink_open\0",
+    "ch6_sbrk\0",
+    "ch6_prio_inherit\0",
+    "ch6_checkpoint\0",
+    "ch6_access\0",
+    "ch6_iovec\0",
+    "ch6_statfs\0",
+    "ch6_wait_stats\0",
+    "ch6_open_flags\0",
+    "ch6_concurrent_write\0",
+    "ch6_sendfile\0",
+    "ch6_nanosleep\0",
+    "ch6_threads\0",
+    "ch6_setquota\0",
+    "ch6_syscall_stats\0",
+    "ch6_mount\0",
+    "ch6_mount_twins\0",
+    "ch6_shm\0",
+    "ch6_poll\0",
+    "ch6_futex\0",
+    "ch6_defrag\0",
+    "ch6_cwd\0",
+    "ch6_wait_status\0",
+    "ch6_sparse\0",
+    "ch6_fcntl\0",
+    "ch6_rename_replace\0",
+    "ch6_mmap_file\0",
+    "ch6_msync_range\0",
+    "ch6_killpg\0",
+    "ch6_bad_buffer\0",
+    "ch6_vfork\0",
+    "ch6_getdents_unlink\0",
+    "ch6_chroot\0",
+    "ch6_lock_order\0",
 ];
 
 use user_lib::{spawn, waitpid};
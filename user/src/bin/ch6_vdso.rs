@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, map_vdso, task_info, waitpid, TaskInfo, SYSCALL_GETTIMEOFDAY};
+
+/// 测试 map_vdso，不经过系统调用直接读内存就能看到时间前进，输出 Test vdso OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let clock = map_vdso().unwrap();
+    // mapping twice gives the same page
+    assert!(core::ptr::eq(clock, map_vdso().unwrap()));
+    let ticks = clock.ticks();
+    let time_us = clock.time_us();
+    // spin without any syscall, timer interrupts keep the page fresh
+    while clock.ticks() < ticks + 3 {}
+    assert!(clock.time_us() > time_us);
+    let info = TaskInfo::new();
+    assert_eq!(task_info(&info), 0);
+    assert_eq!(info.syscall_times[SYSCALL_GETTIMEOFDAY], 0);
+    // a forked child sees the same page
+    let pid = fork();
+    if pid == 0 {
+        let ticks = clock.ticks();
+        while clock.ticks() == ticks {}
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("Test vdso OK!");
+    0
+}
@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::hint::black_box;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{exec, exit, getpid, vfork, waitpid};
+
+/// 测试 vfork，子进程在父进程的地址空间里运行，父进程一直等到子进程 exec 或 exit 才继续，能看到子进程写下的数据，子进程调用函数用过的栈不会破坏父进程返回时用的栈，输出 Test vfork OK! 就算正确。
+
+/// Written by the child, read by the parent
+static SHARED: AtomicUsize = AtomicUsize::new(0);
+
+/// Use a good deal of stack where the frames of the parent in `vfork` were
+#[inline(never)]
+fn scribble(depth: usize) -> usize {
+    let buf = black_box([depth as u8; 512]);
+    if depth == 0 {
+        buf[0] as usize
+    } else {
+        scribble(depth - 1) + buf[511] as usize
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid();
+    let locals = black_box([0x5au8; 64]);
+
+    // exec right away
+    let child = vfork();
+    if child == 0 {
+        SHARED.store(1, Ordering::SeqCst);
+        let args = ["ch5_exit0\0".as_ptr(), core::ptr::null::<u8>()];
+        exec("ch5_exit0\0", &args);
+        exit(-1);
+    }
+    assert!(child > 0);
+    // not a copy, and the child went on first
+    assert_eq!(SHARED.load(Ordering::SeqCst), 1);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 66778);
+
+    // exit without exec, after calls all over the stack
+    let child = vfork();
+    if child == 0 {
+        assert_ne!(getpid(), pid);
+        SHARED.store(scribble(8), Ordering::SeqCst);
+        exit(7);
+    }
+    assert!(child > 0);
+    assert_eq!(SHARED.load(Ordering::SeqCst), 36);
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 7);
+    assert_eq!(getpid(), pid);
+    assert_eq!(locals, [0x5au8; 64]);
+    println!("Test vfork OK!");
+    0
+}
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, exit, fork, open, sys_waitpid, unlink, wait_size, waitpid, write, yield_, OpenFlags,
+};
+
+/// 测试 wait_size，子进程阻塞直到文件被父进程写到足够长，输出 Test wait_size OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_wait_size\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // nobody writes, times out
+    assert_eq!(wait_size(fd, 1000, 10), -11);
+    // already large enough, returns at once
+    assert_eq!(wait_size(fd, 0, 0), 0);
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(wait_size(fd, 100, 5000), 100);
+        exit(0);
+    }
+    let buf = [b'x'; 50];
+    assert_eq!(write(fd, &buf), 50);
+    for _ in 0..10 {
+        yield_();
+    }
+    // 50 bytes are not enough, child is still blocked
+    let mut exit_code: i32 = 0;
+    assert_eq!(sys_waitpid(pid, &mut exit_code as *mut _), -2);
+    assert_eq!(write(fd, &buf), 50);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    close(fd);
+    unlink(fname);
+    println!("Test wait_size OK!");
+    0
+}
@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, get_time, sleep_blocking, wait_stats, Rusage};
+
+/// 测试 wait_stats，子进程空转约 100ms 后退出，父进程回收时读到的 CPU 时间不少于其一半且不超过经过的时间；睡眠 100ms 的子进程几乎不占 CPU，输出 Test wait_stats OK! 就算正确。
+
+const BURN_MS: isize = 100;
+
+/// Fork a child running `f`, and reap it, return its CPU time and the time it took in ms
+fn run_child(f: fn()) -> (Rusage, isize) {
+    let start = get_time();
+    let pid = fork();
+    if pid == 0 {
+        f();
+        exit(7);
+    }
+    let mut exit_code = 0;
+    let mut rusage = Rusage::default();
+    assert_eq!(wait_stats(pid as usize, &mut exit_code, &mut rusage), pid);
+    assert_eq!(exit_code, 7);
+    (rusage, get_time() - start)
+}
+
+fn burn() {
+    let start = get_time();
+    let mut x: usize = 1;
+    while get_time() - start < BURN_MS {
+        for _ in 0..1000 {
+            x = x.wrapping_mul(6364136223846793005).wrapping_add(1);
+        }
+    }
+    assert_ne!(x, 0);
+}
+
+fn doze() {
+    sleep_blocking(BURN_MS as usize);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let (busy, elapsed) = run_child(burn);
+    let busy_ms = ((busy.utime_us + busy.stime_us) / 1000) as isize;
+    println!(
+        "busy child: user {}us, system {}us in {}ms",
+        busy.utime_us, busy.stime_us, elapsed
+    );
+    // the parent only yields while waiting, the child gets most of the CPU
+    assert!(busy_ms >= BURN_MS / 2);
+    assert!(busy_ms <= elapsed + 1);
+    // spinning in user mode, the kernel only answers get_time
+    assert!(busy.utime_us > busy.stime_us);
+    let (idle, elapsed) = run_child(doze);
+    assert!(elapsed >= BURN_MS);
+    // blocked, it is not charged for the time it slept
+    assert!((idle.utime_us + idle.stime_us) / 1000 < (BURN_MS / 4) as usize);
+    println!("Test wait_stats OK!");
+    0
+}
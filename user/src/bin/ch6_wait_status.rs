@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    exit, fork, waitpid, waitpid_status, wexitstatus, wifexited, wifsignaled, wtermsig, SIGSEGV,
+};
+
+/// 测试 wait 的状态字，一个子进程以 42 退出，另一个访问空指针被杀死，父进程从状态字中分辨出正常退出及其退出码、被信号杀死及其信号，输出 Test wait_status OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let exited = fork();
+    if exited == 0 {
+        exit(42);
+    }
+    let crashed = fork();
+    if crashed == 0 {
+        unsafe { (0x0 as *mut u8).write_volatile(0) };
+        exit(0);
+    }
+    assert!(exited > 0 && crashed > 0);
+
+    let mut status = 0;
+    assert_eq!(waitpid_status(exited, &mut status), exited);
+    assert_eq!(status, 42 << 8);
+    assert!(wifexited(status) && !wifsignaled(status));
+    assert_eq!(wexitstatus(status), 42);
+
+    assert_eq!(waitpid_status(crashed, &mut status), crashed);
+    assert!(wifsignaled(status) && !wifexited(status));
+    assert_eq!(wtermsig(status), SIGSEGV);
+
+    // the exit code alone, negated signal for a child killed
+    let pid = fork();
+    if pid == 0 {
+        unsafe { (0x0 as *const u8).read_volatile() };
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -SIGSEGV);
+    // none left
+    assert_eq!(waitpid_status(-1, &mut status), -1);
+    println!("Test wait_status OK!");
+    0
+}
@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, open, pipe, read, write, write_would_block, OpenFlags};
+
+/// 测试 write_would_block，管道写满时返回 1，读空后返回 0，输出 Test write_would_block OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let (read_end, write_end) = (pipe_fd[0], pipe_fd[1]);
+    assert_eq!(write_would_block(write_end), 0);
+    // the read end can not be written at all
+    assert_eq!(write_would_block(read_end), -1);
+    // fill the pipe one byte at a time, until a write would block
+    let mut filled = 0;
+    while write_would_block(write_end) == 0 {
+        assert_eq!(write(write_end, &[filled as u8]), 1);
+        filled += 1;
+        assert!(filled <= 4096);
+    }
+    assert!(filled > 0);
+    assert_eq!(write_would_block(write_end), 1);
+    // drain it
+    let mut buf = [0u8; 1];
+    for i in 0..filled {
+        assert_eq!(read(read_end, &mut buf), 1);
+        assert_eq!(buf[0], i as u8);
+    }
+    assert_eq!(write_would_block(write_end), 0);
+    close(read_end);
+    close(write_end);
+    // a regular file never blocks
+    let fd = open("fname_write_would_block\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write_would_block(fd as usize), 0);
+    close(fd as usize);
+    println!("Test write_would_block OK!");
+    0
+}
@@ -80,8 +80,102 @@ bitflags! {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
+        /// with `CREATE`, fail if the path exists already, even as a symbolic link
+        const EXCL = 1 << 7;
+        /// create the file if it does not exist, an existing one is truncated
         const CREATE = 1 << 9;
+        /// truncate an existing file to zero length
         const TRUNC = 1 << 10;
+        const NONBLOCK = 1 << 11;
+        const APPEND = 1 << 12;
+        /// fail if the last component of the path is a symbolic link
+        const NOFOLLOW = 1 << 17;
+    }
+}
+
+pub const F_GETFD: usize = 1;
+pub const F_SETFD: usize = 2;
+pub const F_GETFL: usize = 3;
+pub const F_SETFL: usize = 4;
+/// The fd flag of `F_GETFD` and `F_SETFD`: close the fd on exec
+pub const FD_CLOEXEC: usize = 1;
+
+/// Flags of `mmap` next to the permission bits: map with 2MiB megapages
+/// if start and len allow it, falling back to pages or failing if not
+pub const MMAP_HUGE: usize = 1 << 3;
+pub const MMAP_HUGE_ONLY: usize = 1 << 4;
+/// Flag of `sys_mmap`: map a file, pages read from it on their first touch and
+/// written back to it on `msync` or `munmap`, past its end they read as zeros
+pub const MAP_SHARED: usize = 1;
+
+/// Flags of `msync`
+pub const MS_ASYNC: usize = 1;
+pub const MS_INVALIDATE: usize = 2;
+pub const MS_SYNC: usize = 4;
+
+/// Accesses `access` checks for, or `F_OK` only to check that the path exists
+pub const F_OK: usize = 0;
+pub const X_OK: usize = 1;
+pub const W_OK: usize = 2;
+pub const R_OK: usize = 4;
+
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+/// Signals a child killed by a fault has in its wait status
+pub const SIGILL: i32 = 4;
+pub const SIGSEGV: i32 = 11;
+/// A signal to kill with by `killpg`
+pub const SIGKILL: i32 = 9;
+
+/// Whether the child of the wait status exited by itself
+pub fn wifexited(status: i32) -> bool {
+    status & 0x7f == 0
+}
+
+/// The exit code of a child that exited by itself
+pub fn wexitstatus(status: i32) -> i32 {
+    status >> 8
+}
+
+/// Whether the child of the wait status was killed by a signal
+pub fn wifsignaled(status: i32) -> bool {
+    !wifexited(status)
+}
+
+/// The signal that killed the child
+pub fn wtermsig(status: i32) -> i32 {
+    status & 0x7f
+}
+
+/// The exit code of the wait status, or the negated signal for a child killed
+fn exit_code_of(status: i32) -> i32 {
+    if wifexited(status) {
+        wexitstatus(status)
+    } else {
+        -wtermsig(status)
+    }
+}
+
+bitflags! {
+    pub struct CloseRangeFlags: u32 {
+        const CLOEXEC = 1 << 2;
+    }
+}
+
+bitflags! {
+    pub struct LinkFlags: u32 {
+        /// point the new name at the inode even if it exists
+        const REPLACE = 1;
+    }
+}
+
+bitflags! {
+    pub struct EventFdFlags: u32 {
+        const SEMAPHORE = 1;
+        const NONBLOCK = 1 << 11;
+        const CLOEXEC = 1 << 19;
     }
 }
 
@@ -98,6 +192,16 @@ impl TimeVal {
     }
 }
 
+/// CPU time of a reaped child, filled by `wait_stats`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Rusage {
+    /// time spent in user mode, in microseconds
+    pub utime_us: usize,
+    /// time spent in the kernel, in microseconds
+    pub stime_us: usize,
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum TaskStatus {
     UnInit,
@@ -119,6 +223,8 @@ pub struct TaskInfo {
     pub status: TaskStatus,
     pub syscall_times: [u32; MAX_SYSCALL_NUM],
     pub time: usize,
+    /// Times the task was switched in to run
+    pub schedule_count: usize,
 }
 
 impl TaskInfo {
@@ -127,10 +233,192 @@ impl TaskInfo {
             status: TaskStatus::UnInit,
             syscall_times: [0; MAX_SYSCALL_NUM],
             time: 0,
+            schedule_count: 0,
+        }
+    }
+}
+
+#[repr(usize)]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ProcStatus {
+    Ready,
+    Running,
+    Blocking,
+    Zombie,
+}
+
+/// Why a task was switched out
+#[repr(usize)]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SwitchReason {
+    Yield,
+    Preempt,
+    BlockOnPipe,
+    BlockOnFile,
+    BlockOnSync,
+    Sleep,
+    BlockOnVfork,
+}
+
+/// A context switch of a task, filled by `switch_history`
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SwitchRecord {
+    pub reason: SwitchReason,
+    /// in microseconds
+    pub time_us: usize,
+}
+
+impl SwitchRecord {
+    pub fn new() -> Self {
+        SwitchRecord {
+            reason: SwitchReason::Yield,
+            time_us: 0,
         }
     }
 }
 
+/// A buffer for `process_vm_readv`, `readv` and `writev`, as `struct iovec`
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct IoVec {
+    pub base: usize,
+    pub len: usize,
+}
+
+impl IoVec {
+    pub fn new(base: usize, len: usize) -> Self {
+        IoVec { base, len }
+    }
+    pub fn from_slice(buf: &mut [u8]) -> Self {
+        IoVec::new(buf.as_mut_ptr() as usize, buf.len())
+    }
+    /// Only to be read from, as by `writev`
+    pub fn from_bytes(buf: &[u8]) -> Self {
+        IoVec::new(buf.as_ptr() as usize, buf.len())
+    }
+}
+
+/// An fd for `poll` to watch, as `struct pollfd`
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct PollFd {
+    pub fd: i32,
+    /// events of interest
+    pub events: i16,
+    /// events that happened, filled by `poll`
+    pub revents: i16,
+}
+
+/// Events of `PollFd`: it can be read, it can be written, it is not open
+pub const POLLIN: i16 = 0x1;
+pub const POLLOUT: i16 = 0x4;
+pub const POLLNVAL: i16 = 0x20;
+
+impl PollFd {
+    pub fn new(fd: usize, events: i16) -> Self {
+        PollFd {
+            fd: fd as i32,
+            events,
+            revents: 0,
+        }
+    }
+}
+
+/// Frames reclaimed from file mappings, filled by `get_reclaim_stats`
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct ReclaimStats {
+    pub reclaimed: usize,
+    pub free_frames: usize,
+    /// 0 if reclaim is off
+    pub low_watermark: usize,
+    pub high_watermark: usize,
+}
+
+impl ReclaimStats {
+    pub fn new() -> Self {
+        ReclaimStats {
+            reclaimed: 0,
+            free_frames: 0,
+            low_watermark: 0,
+            high_watermark: 0,
+        }
+    }
+}
+
+/// Frame allocator counters, filled by `frame_stats`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FrameStats {
+    pub total: usize,
+    pub free: usize,
+    /// the most physically contiguous free frames
+    pub largest_free_run: usize,
+    /// allocations and frees since boot
+    pub allocs: usize,
+    pub frees: usize,
+}
+
+/// Quota of a directory, filled by `get_dir_quota`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DirQuota {
+    /// max number of blocks, 0 for no quota
+    pub limit: u32,
+    /// blocks the files under the directory use
+    pub used: u32,
+}
+
+/// The clock page mapped by `map_vdso`, refreshed by the kernel on every timer interrupt
+#[repr(C)]
+#[derive(Debug)]
+pub struct ClockPage {
+    /// number of timer interrupts so far
+    pub ticks: usize,
+    /// time of the last timer interrupt, in microseconds
+    pub time_us: usize,
+}
+
+impl ClockPage {
+    pub fn ticks(&self) -> usize {
+        unsafe { core::ptr::read_volatile(&self.ticks) }
+    }
+    pub fn time_us(&self) -> usize {
+        unsafe { core::ptr::read_volatile(&self.time_us) }
+    }
+}
+
+const PROC_NAME_LEN: usize = 32;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct ProcInfo {
+    pub pid: usize,
+    /// 0 if the process has no parent
+    pub ppid: usize,
+    pub status: ProcStatus,
+    /// in milliseconds
+    pub cpu_time: usize,
+    pub name: [u8; PROC_NAME_LEN],
+}
+
+impl ProcInfo {
+    pub fn new() -> Self {
+        ProcInfo {
+            pid: 0,
+            ppid: 0,
+            status: ProcStatus::Ready,
+            cpu_time: 0,
+            name: [0; PROC_NAME_LEN],
+        }
+    }
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|c| *c == 0).unwrap();
+        core::str::from_utf8(&self.name[..len]).unwrap()
+    }
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct Stat {
@@ -142,8 +430,18 @@ pub struct Stat {
     pub mode: StatMode,
     /// number of hard links
     pub nlink: u32,
+    /// time of last access, in microseconds since boot
+    pub atime: u64,
+    /// time of last data modification
+    pub mtime: u64,
+    /// time of last status change
+    pub ctime: u64,
+    /// size in bytes, holes included
+    pub size: u64,
+    /// number of blocks in use, fewer than the size takes if the file has holes
+    pub blocks: u64,
     /// unused pad
-    pad: [u64; 7],
+    pad: [u64; 2],
 }
 
 impl Stat {
@@ -153,7 +451,12 @@ impl Stat {
             ino: 0,
             mode: StatMode::NULL,
             nlink: 0,
-            pad: [0; 7],
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+            size: 0,
+            blocks: 0,
+            pad: [0; 2],
         }
     }
 }
@@ -164,6 +467,40 @@ impl Default for Stat {
     }
 }
 
+const DIRENT_NAME_LEN: usize = 28;
+
+/// A directory entry, filled by `getdents`
+#[repr(C)]
+#[derive(Debug)]
+pub struct Dirent {
+    /// inode number
+    pub ino: u64,
+    /// whether a directory or a file
+    pub mode: StatMode,
+    /// name, ending with a NUL
+    pub name: [u8; DIRENT_NAME_LEN],
+}
+
+impl Dirent {
+    pub fn new() -> Self {
+        Dirent {
+            ino: 0,
+            mode: StatMode::NULL,
+            name: [0; DIRENT_NAME_LEN],
+        }
+    }
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|c| *c == 0).unwrap();
+        core::str::from_utf8(&self.name[..len]).unwrap()
+    }
+}
+
+impl Default for Dirent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 bitflags! {
     pub struct StatMode: u32 {
         const NULL  = 0;
@@ -174,6 +511,57 @@ bitflags! {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct FsInfo {
+    /// size of a block in bytes
+    pub block_size: u32,
+    /// number of blocks on the device
+    pub total_blocks: u32,
+    /// max number of inodes
+    pub inode_num: u32,
+    /// first block of the inode bitmap
+    pub inode_bitmap_start: u32,
+    pub inode_bitmap_blocks: u32,
+    /// first block of the inode area
+    pub inode_area_start: u32,
+    pub inode_area_blocks: u32,
+    /// first block of the data bitmap
+    pub data_bitmap_start: u32,
+    pub data_bitmap_blocks: u32,
+    /// first block of the data area
+    pub data_area_start: u32,
+    pub data_area_blocks: u32,
+    /// first block of the journal
+    pub journal_start: u32,
+    pub journal_blocks: u32,
+}
+
+/// Usage of the filesystem, filled by `statfs`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StatFs {
+    /// size of a block in bytes
+    pub block_size: u32,
+    /// number of data blocks
+    pub total_blocks: u32,
+    pub free_blocks: u32,
+    /// max number of inodes
+    pub total_inodes: u32,
+    pub free_inodes: u32,
+}
+
+/// Waits on the filesystem locks since boot, filled by `lock_stats`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LockStats {
+    pub fs_waits: usize,
+    /// the wait is counted in spins
+    pub fs_spins: usize,
+    pub block_cache_waits: usize,
+    pub block_cache_spins: usize,
+}
+
 const AT_FDCWD: isize = -100;
 
 pub fn open(path: &str, flags: OpenFlags) -> isize {
@@ -187,6 +575,13 @@ pub fn close(fd: usize) -> isize {
     sys_close(fd)
 }
 
+pub fn close_range(first: usize, last: usize, flags: CloseRangeFlags) -> isize {
+    if (first..=last).contains(&STDOUT) && !flags.contains(CloseRangeFlags::CLOEXEC) {
+        console::flush();
+    }
+    sys_close_range(first, last, flags.bits)
+}
+
 pub fn read(fd: usize, buf: &mut [u8]) -> isize {
     sys_read(fd, buf)
 }
@@ -195,18 +590,181 @@ pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
 
+/// Fill the buffers of `iov` in order with a single read
+pub fn readv(fd: usize, iov: &[IoVec]) -> isize {
+    sys_readv(fd, iov)
+}
+
+/// Write the buffers of `iov` in order with a single write
+pub fn writev(fd: usize, iov: &[IoVec]) -> isize {
+    sys_writev(fd, iov)
+}
+
+pub fn append(fd: usize, buf: &[u8]) -> isize {
+    sys_append(fd, buf)
+}
+
+pub fn ringfile_create(path: &str, capacity: usize) -> isize {
+    sys_ringfile_create(path, capacity)
+}
+
 pub fn link(old_path: &str, new_path: &str) -> isize {
     sys_linkat(AT_FDCWD as usize, old_path, AT_FDCWD as usize, new_path, 0)
 }
 
+pub fn linkat(old_path: &str, new_path: &str, flags: LinkFlags) -> isize {
+    sys_linkat(
+        AT_FDCWD as usize,
+        old_path,
+        AT_FDCWD as usize,
+        new_path,
+        flags.bits as usize,
+    )
+}
+
+pub fn rename(old_path: &str, new_path: &str) -> isize {
+    sys_renameat2(AT_FDCWD as usize, old_path, AT_FDCWD as usize, new_path, 0)
+}
+
+/// Move `old_path` to `new_path` as `mv` does, replacing a file or an empty directory there
+pub fn rename_replace(old_path: &str, new_path: &str) -> isize {
+    sys_rename(old_path, new_path)
+}
+
+pub fn cas_rename(path: &str, expected: &[u8], new_path: &str) -> isize {
+    sys_cas_rename(path, expected, new_path)
+}
+
+pub fn set_dir_quota(path: &str, max_blocks: usize) -> isize {
+    sys_set_dir_quota(path, max_blocks)
+}
+
+pub fn get_dir_quota(path: &str, quota: &mut DirQuota) -> isize {
+    sys_get_dir_quota(path, quota)
+}
+
+pub fn swap_dir(dir_a: &str, dir_b: &str) -> isize {
+    sys_swap_dir(dir_a, dir_b)
+}
+
+/// Create a symbolic link at `path` pointing to `target`, which need not exist
+pub fn symlink(target: &str, path: &str) -> isize {
+    sys_symlinkat(target, AT_FDCWD as usize, path)
+}
+
+/// Mount the easy-fs image in the file at `source` on the directory at `target`
+pub fn mount(source: &str, target: &str) -> isize {
+    sys_mount(source, target)
+}
+
+pub fn umount(target: &str) -> isize {
+    sys_umount(target)
+}
+
+/// Write the absolute path of the working directory and a `\0` to `buf`,
+/// return the length of the path
+pub fn getcwd(buf: &mut [u8]) -> isize {
+    sys_getcwd(buf)
+}
+
+/// Make `path` the directory relative paths start at
+pub fn chdir(path: &str) -> isize {
+    sys_chdir(path)
+}
+
+/// Make `path` the directory this process and its children take as `/`,
+/// the working directory moves there. Only once per process.
+pub fn chroot(path: &str) -> isize {
+    sys_chroot(path)
+}
+
 pub fn unlink(path: &str) -> isize {
     sys_unlinkat(AT_FDCWD as usize, path, 0)
 }
 
+pub fn access(path: &str, mode: usize) -> isize {
+    sys_faccessat(AT_FDCWD as usize, path, mode)
+}
+
+/// Set the permission bits of `path`, only those of the owner are checked
+pub fn chmod(path: &str, mode: usize) -> isize {
+    sys_fchmodat(AT_FDCWD as usize, path, mode)
+}
+
 pub fn fstat(fd: usize, st: &Stat) -> isize {
     sys_fstat(fd, st)
 }
 
+/// Read entries of the directory `fd` into `dirents` from where the last call stopped,
+/// return the number of bytes filled, 0 at the end
+pub fn getdents(fd: usize, dirents: &mut [Dirent]) -> isize {
+    sys_getdents(fd, dirents)
+}
+
+pub fn fs_info(info: &FsInfo) -> isize {
+    sys_fs_info(info)
+}
+
+pub fn statfs(buf: &mut StatFs) -> isize {
+    sys_statfs(buf)
+}
+
+/// Copy up to `count` bytes from `in_fd` to `out_fd` without passing through user space
+pub fn sendfile(out_fd: usize, in_fd: usize, count: usize) -> isize {
+    sys_sendfile(out_fd, in_fd, count)
+}
+
+pub fn lock_stats(stats: &mut LockStats) -> isize {
+    sys_lock_stats(stats)
+}
+
+pub fn fs_defrag() -> isize {
+    sys_fs_defrag()
+}
+
+/// Move the blocks of the file behind `fd` to one run, return how many moved
+pub fn defrag(fd: usize) -> isize {
+    sys_defrag(fd)
+}
+
+pub fn fs_checkcycles(remove: bool) -> isize {
+    sys_fs_checkcycles(remove as usize)
+}
+
+pub fn pin_inode(fd: usize) -> isize {
+    sys_pin_inode(fd)
+}
+
+pub fn unpin_inode(fd: usize) -> isize {
+    sys_unpin_inode(fd)
+}
+
+pub fn write_would_block(fd: usize) -> isize {
+    sys_write_would_block(fd)
+}
+
+/// Wait for at most `timeout_us`, forever if negative, until one of `fds` can be read or
+/// written as asked, and return how many can, 0 if time ran out
+pub fn poll(fds: &mut [PollFd], timeout_us: isize) -> isize {
+    sys_poll(fds, timeout_us)
+}
+
+pub fn wait_size(fd: usize, min_size: usize, timeout_ms: usize) -> isize {
+    sys_wait_size(fd, min_size, timeout_ms)
+}
+
+pub fn drain(fd: usize, buf: &mut [u8]) -> isize {
+    sys_drain(fd, buf)
+}
+
+pub fn fd_path(fd: usize, buf: &mut [u8]) -> isize {
+    sys_fd_path(fd, buf)
+}
+
+pub fn create_with_data(path: &str, data: &[u8]) -> isize {
+    sys_create_with_data(path, data, 0o644)
+}
+
 pub fn mail_read(buf: &mut [u8]) -> isize {
     sys_mail_read(buf)
 }
@@ -240,6 +798,12 @@ pub fn fork() -> isize {
     sys_fork()
 }
 
+/// Like [`fork`], but the child runs in the memory of the parent, which waits
+/// till the child calls [`exec`] or [`exit`]. The child should do little else.
+pub fn vfork() -> isize {
+    sys_vfork()
+}
+
 pub fn exec(path: &str, args: &[*const u8]) -> isize {
     sys_exec(path, args)
 }
@@ -248,9 +812,37 @@ pub fn set_priority(prio: isize) -> isize {
     sys_set_priority(prio)
 }
 
+pub fn set_deadline_sched(period_ms: usize, runtime_ms: usize) -> isize {
+    sys_set_deadline_sched(period_ms, runtime_ms)
+}
+
+pub fn set_timeslice(ticks: usize) -> isize {
+    sys_set_timeslice(ticks)
+}
+
 pub fn wait(exit_code: &mut i32) -> isize {
+    let mut status = 0;
+    let pid = waitpid_status(-1, &mut status);
+    if pid > 0 {
+        *exit_code = exit_code_of(status);
+    }
+    pid
+}
+
+pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
+    let mut status = 0;
+    let pid = waitpid_status(pid as isize, &mut status);
+    if pid > 0 {
+        *exit_code = exit_code_of(status);
+    }
+    pid
+}
+
+/// Like `waitpid` with -1 for any child, but get the whole wait status
+/// to tell a child that exited from one killed by a fault
+pub fn waitpid_status(pid: isize, status: &mut i32) -> isize {
     loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
+        match sys_waitpid(pid, status as *mut _) {
             -2 => {
                 sys_yield();
             }
@@ -261,13 +853,18 @@ pub fn wait(exit_code: &mut i32) -> isize {
     }
 }
 
-pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
+/// Like `waitpid`, and also get the CPU time of the child
+pub fn wait_stats(pid: usize, exit_code: &mut i32, rusage: &mut Rusage) -> isize {
+    let mut status = 0;
     loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
+        match sys_wait_stats(pid as isize, &mut status as *mut _, rusage) {
             -2 => {
                 sys_yield();
             }
             n => {
+                if n > 0 {
+                    *exit_code = exit_code_of(status);
+                }
                 return n;
             }
         }
@@ -278,6 +875,11 @@ pub fn sleep_blocking(sleep_ms: usize) {
     sys_sleep(sleep_ms);
 }
 
+/// Block for `duration_us` microseconds, which may be shorter than a tick
+pub fn nanosleep(duration_us: usize) -> isize {
+    sys_nanosleep(duration_us)
+}
+
 pub fn sleep(period_ms: usize) {
     let start = get_time();
     while get_time() < start + period_ms as isize {
@@ -285,13 +887,74 @@ pub fn sleep(period_ms: usize) {
     }
 }
 pub fn mmap(start: usize, len: usize, prot: usize) -> isize {
-    sys_mmap(start, len, prot)
+    sys_mmap(start, len, prot, 0, 0, 0)
+}
+
+/// Map `len` bytes of the file behind `fd` from `offset` on at `start`,
+/// see [`MAP_SHARED`]
+pub fn mmap_file(start: usize, len: usize, prot: usize, fd: usize, offset: usize) -> isize {
+    sys_mmap(start, len, prot, MAP_SHARED, fd, offset)
 }
 
 pub fn munmap(start: usize, len: usize) -> isize {
     sys_munmap(start, len)
 }
 
+/// Move the program break by `increment` bytes, return the old one or -1
+pub fn sbrk(increment: isize) -> isize {
+    sys_sbrk(increment)
+}
+
+/// The id of the shared memory segment named `key`, made with `size` bytes if new
+pub fn shmget(key: usize, size: usize) -> isize {
+    sys_shmget(key, size)
+}
+
+/// Map the shared memory segment `id`, return the address or -1
+pub fn shmat(id: usize) -> isize {
+    sys_shmat(id)
+}
+
+pub fn shmdt(addr: usize) -> isize {
+    sys_shmdt(addr)
+}
+
+/// Save the registers and writable memory to the file at `path`,
+/// return 0, 1 when resumed by `restore`, or -1
+pub fn checkpoint(path: &str) -> isize {
+    sys_checkpoint(path)
+}
+
+/// Resume from the checkpoint at `path`, only return -1 if it can not be restored
+pub fn restore(path: &str) -> isize {
+    sys_restore(path)
+}
+
+pub fn set_mempolicy(start: usize, len: usize, node: usize) -> isize {
+    sys_set_mempolicy(start, len, node)
+}
+
+pub fn get_mempolicy(addr: usize) -> isize {
+    sys_get_mempolicy(addr)
+}
+
+pub fn set_reclaim_watermark(free_frames: usize) -> isize {
+    sys_set_reclaim_watermark(free_frames)
+}
+
+pub fn get_reclaim_stats(stats: &mut ReclaimStats) -> isize {
+    sys_get_reclaim_stats(stats)
+}
+
+pub fn frame_stats(stats: &mut FrameStats) -> isize {
+    sys_frame_stats(stats)
+}
+
+/// The most pages the process has had resident at once, shared ones included
+pub fn get_rss() -> isize {
+    sys_get_rss()
+}
+
 pub fn spawn(path: &str) -> isize {
     sys_spawn(path)
 }
@@ -299,20 +962,119 @@ pub fn spawn(path: &str) -> isize {
 pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
+/// Open `old_fd` again at `new_fd`, closing what `new_fd` had open first
+pub fn dup2(old_fd: usize, new_fd: usize) -> isize {
+    sys_dup2(old_fd, new_fd)
+}
 pub fn pipe(pipe_fd: &mut [usize]) -> isize {
     sys_pipe(pipe_fd)
 }
 
+pub fn eventfd(initval: u32, flags: EventFdFlags) -> isize {
+    sys_eventfd(initval, flags.bits)
+}
+
+pub fn eventfd_read(fd: usize, value: &mut u64) -> isize {
+    let mut buf = [0u8; 8];
+    let ret = sys_read(fd, &mut buf);
+    if ret == 8 {
+        *value = u64::from_ne_bytes(buf);
+    }
+    ret
+}
+
+pub fn eventfd_write(fd: usize, value: u64) -> isize {
+    sys_write(fd, &value.to_ne_bytes())
+}
+
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset, whence)
+}
+
+pub fn lseek_swap(fd: usize, offset: usize) -> isize {
+    sys_lseek_swap(fd, offset)
+}
+
+pub fn snapshot_file(fd: usize) -> isize {
+    sys_snapshot_file(fd)
+}
+
+pub fn extend_and_map(fd: usize, len: usize) -> isize {
+    sys_extend_and_map(fd, len)
+}
+
+pub fn fsync(fd: usize) -> isize {
+    sys_fsync(fd)
+}
+
+pub fn msync(start: usize, len: usize, flags: usize) -> isize {
+    sys_msync(start, len, flags)
+}
+
+pub fn ftruncate(fd: usize, len: usize) -> isize {
+    sys_ftruncate(fd, len)
+}
+
+pub fn fallocate(fd: usize, offset: usize, len: usize) -> isize {
+    sys_fallocate(fd, 0, offset, len)
+}
+
+pub fn seal_size(fd: usize) -> isize {
+    sys_seal_size(fd)
+}
+
+pub fn setquota(fd: usize, bytes: usize) -> isize {
+    sys_setquota(fd, bytes)
+}
+
+pub fn fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    sys_fcntl(fd, cmd, arg)
+}
+
 pub fn task_info(info: &TaskInfo) -> isize {
     sys_task_info(info)
 }
 
+pub fn reset_syscall_stats() -> isize {
+    sys_reset_syscall_stats()
+}
+
+pub fn ps(infos: &mut [ProcInfo]) -> isize {
+    sys_ps(infos)
+}
+
+pub fn switch_history(pid: usize, records: &mut [SwitchRecord]) -> isize {
+    sys_switch_history(pid, records)
+}
+
+pub fn process_vm_readv(pid: usize, local: &[IoVec], remote: &[IoVec]) -> isize {
+    sys_process_vm_readv(pid, local, remote, 0)
+}
+
+pub fn map_vdso() -> Option<&'static ClockPage> {
+    match sys_map_vdso() {
+        -1 => None,
+        addr => Some(unsafe { &*(addr as usize as *const ClockPage) }),
+    }
+}
+
 pub fn thread_create(entry: usize, arg: usize) -> isize {
     sys_thread_create(entry, arg)
 }
 pub fn gettid() -> isize {
     sys_gettid()
 }
+/// Move process `pid`, 0 for current process, into group `pgid`, 0 for a new one of its own
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    sys_setpgid(pid, pgid)
+}
+pub fn getpgid(pid: usize) -> isize {
+    sys_getpgid(pid)
+}
+/// Kill every process in group `pgid`, 0 for that of current process, by `signal`
+pub fn killpg(pgid: usize, signal: i32) -> isize {
+    sys_killpg(pgid, signal as usize)
+}
 pub fn waittid(tid: usize) -> isize {
     loop {
         match sys_waittid(tid) {
@@ -357,3 +1119,12 @@ pub fn condvar_signal(condvar_id: usize) {
 pub fn condvar_wait(condvar_id: usize, mutex_id: usize) {
     sys_condvar_wait(condvar_id, mutex_id);
 }
+/// Sleep until `futex_wake` on the same word, wherever it is mapped, if `*addr` is still
+/// `expected`, else return -1 at once
+pub fn futex_wait(addr: *const u32, expected: u32) -> isize {
+    sys_futex_wait(addr, expected)
+}
+/// Wake up to `n` tasks sleeping on the word at `addr`, return how many
+pub fn futex_wake(addr: *const u32, n: usize) -> isize {
+    sys_futex_wake(addr, n)
+}
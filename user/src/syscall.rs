@@ -1,32 +1,112 @@
 use crate::TaskInfo;
 
-use super::{Stat, TimeVal};
+use core::mem::size_of_val;
+
+use super::{
+    DirQuota, Dirent, FrameStats, FsInfo, IoVec, LockStats, PollFd, ProcInfo, ReclaimStats, Rusage,
+    Stat, StatFs, SwitchRecord, TimeVal,
+};
 
 pub const SYSCALL_OPENAT: usize = 56;
 pub const SYSCALL_CLOSE: usize = 57;
+pub const SYSCALL_CLOSE_RANGE: usize = 436;
 pub const SYSCALL_READ: usize = 63;
 pub const SYSCALL_WRITE: usize = 64;
+pub const SYSCALL_READV: usize = 65;
+pub const SYSCALL_WRITEV: usize = 66;
 pub const SYSCALL_UNLINKAT: usize = 35;
+pub const SYSCALL_FACCESSAT: usize = 48;
+pub const SYSCALL_FCHMODAT: usize = 53;
+pub const SYSCALL_SYMLINKAT: usize = 36;
 pub const SYSCALL_LINKAT: usize = 37;
+pub const SYSCALL_UMOUNT: usize = 39;
+pub const SYSCALL_MOUNT: usize = 40;
+pub const SYSCALL_GETCWD: usize = 17;
+pub const SYSCALL_CHDIR: usize = 49;
+pub const SYSCALL_CHROOT: usize = 51;
 pub const SYSCALL_FSTAT: usize = 80;
+pub const SYSCALL_GETDENTS: usize = 61;
+pub const SYSCALL_FSYNC: usize = 82;
+pub const SYSCALL_MSYNC: usize = 227;
+pub const SYSCALL_RENAMEAT2: usize = 276;
+pub const SYSCALL_PROCESS_VM_READV: usize = 270;
+pub const SYSCALL_FTRUNCATE: usize = 46;
+pub const SYSCALL_FALLOCATE: usize = 47;
+pub const SYSCALL_SEAL_SIZE: usize = 429;
+pub const SYSCALL_SET_MEMPOLICY: usize = 430;
+pub const SYSCALL_GET_MEMPOLICY: usize = 431;
+pub const SYSCALL_SWAP_DIR: usize = 432;
+pub const SYSCALL_SET_RECLAIM_WATERMARK: usize = 433;
+pub const SYSCALL_GET_RECLAIM_STATS: usize = 434;
+pub const SYSCALL_LOCK_STATS: usize = 435;
+pub const SYSCALL_APPEND: usize = 437;
+pub const SYSCALL_RINGFILE_CREATE: usize = 438;
+pub const SYSCALL_CAS_RENAME: usize = 439;
+pub const SYSCALL_FRAME_STATS: usize = 440;
+pub const SYSCALL_SET_DIR_QUOTA: usize = 441;
+pub const SYSCALL_GET_DIR_QUOTA: usize = 442;
+pub const SYSCALL_GET_RSS: usize = 443;
 pub const SYSCALL_EXIT: usize = 93;
 pub const SYSCALL_SLEEP: usize = 101;
 pub const SYSCALL_YIELD: usize = 124;
 pub const SYSCALL_GETTIMEOFDAY: usize = 169;
 pub const SYSCALL_GETPID: usize = 172;
 pub const SYSCALL_GETTID: usize = 178;
+pub const SYSCALL_SETPGID: usize = 154;
+pub const SYSCALL_GETPGID: usize = 155;
+pub const SYSCALL_KILLPG: usize = 457;
+pub const SYSCALL_VFORK: usize = 458;
 pub const SYSCALL_FORK: usize = 220;
 pub const SYSCALL_EXEC: usize = 221;
 pub const SYSCALL_WAITPID: usize = 260;
 pub const SYSCALL_SET_PRIORITY: usize = 140;
 pub const SYSCALL_MUNMAP: usize = 215;
 pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_SBRK: usize = 214;
+pub const SYSCALL_SHMGET: usize = 194;
+pub const SYSCALL_SHMAT: usize = 196;
+pub const SYSCALL_SHMDT: usize = 197;
+pub const SYSCALL_CHECKPOINT: usize = 445;
+pub const SYSCALL_RESTORE: usize = 446;
+pub const SYSCALL_STATFS: usize = 447;
+pub const SYSCALL_WAIT_STATS: usize = 448;
+pub const SYSCALL_SENDFILE: usize = 449;
+pub const SYSCALL_NANOSLEEP: usize = 450;
+pub const SYSCALL_SETQUOTA: usize = 451;
+pub const SYSCALL_RESET_SYSCALL_STATS: usize = 452;
+pub const SYSCALL_FUTEX_WAIT: usize = 453;
+pub const SYSCALL_FUTEX_WAKE: usize = 454;
+pub const SYSCALL_DEFRAG: usize = 455;
+pub const SYSCALL_RENAME: usize = 456;
 pub const SYSCALL_SPAWN: usize = 400;
 pub const SYSCALL_MAIL_READ: usize = 401;
 pub const SYSCALL_MAIL_WRITE: usize = 402;
 pub const SYSCALL_DUP: usize = 24;
+pub const SYSCALL_DUP2: usize = 444;
 pub const SYSCALL_PIPE: usize = 59;
+pub const SYSCALL_EVENTFD: usize = 19;
+pub const SYSCALL_FCNTL: usize = 25;
+pub const SYSCALL_LSEEK: usize = 62;
 pub const SYSCALL_TASK_INFO: usize = 410;
+pub const SYSCALL_FS_INFO: usize = 411;
+pub const SYSCALL_WAIT_SIZE: usize = 412;
+pub const SYSCALL_DRAIN: usize = 413;
+pub const SYSCALL_FD_PATH: usize = 414;
+pub const SYSCALL_CREATE_WITH_DATA: usize = 415;
+pub const SYSCALL_PS: usize = 416;
+pub const SYSCALL_SET_DEADLINE_SCHED: usize = 417;
+pub const SYSCALL_MAP_VDSO: usize = 418;
+pub const SYSCALL_FS_DEFRAG: usize = 419;
+pub const SYSCALL_PIN_INODE: usize = 420;
+pub const SYSCALL_UNPIN_INODE: usize = 421;
+pub const SYSCALL_WRITE_WOULD_BLOCK: usize = 422;
+pub const SYSCALL_POLL: usize = 73;
+pub const SYSCALL_SNAPSHOT_FILE: usize = 423;
+pub const SYSCALL_SWITCH_HISTORY: usize = 424;
+pub const SYSCALL_EXTEND_AND_MAP: usize = 425;
+pub const SYSCALL_FS_CHECKCYCLES: usize = 426;
+pub const SYSCALL_LSEEK_SWAP: usize = 427;
+pub const SYSCALL_SET_TIMESLICE: usize = 428;
 pub const SYSCALL_THREAD_CREATE: usize = 460;
 pub const SYSCALL_WAITTID: usize = 462;
 pub const SYSCALL_MUTEX_CREATE: usize = 463;
@@ -88,6 +168,10 @@ pub fn sys_close(fd: usize) -> isize {
     syscall(SYSCALL_CLOSE, [fd, 0, 0])
 }
 
+pub fn sys_close_range(first: usize, last: usize, flags: u32) -> isize {
+    syscall(SYSCALL_CLOSE_RANGE, [first, last, flags as usize])
+}
+
 pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
     syscall(
         SYSCALL_READ,
@@ -99,6 +183,25 @@ pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
 
+pub fn sys_readv(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_READV, [fd, iov.as_ptr() as usize, iov.len()])
+}
+
+pub fn sys_writev(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_WRITEV, [fd, iov.as_ptr() as usize, iov.len()])
+}
+
+pub fn sys_append(fd: usize, buffer: &[u8]) -> isize {
+    syscall(SYSCALL_APPEND, [fd, buffer.as_ptr() as usize, buffer.len()])
+}
+
+pub fn sys_ringfile_create(path: &str, capacity: usize) -> isize {
+    syscall(
+        SYSCALL_RINGFILE_CREATE,
+        [path.as_ptr() as usize, capacity, 0],
+    )
+}
+
 pub fn sys_linkat(
     old_dirfd: usize,
     old_path: &str,
@@ -119,14 +222,117 @@ pub fn sys_linkat(
     )
 }
 
+pub fn sys_cas_rename(path: &str, expected: &[u8], new_path: &str) -> isize {
+    syscall6(
+        SYSCALL_CAS_RENAME,
+        [
+            path.as_ptr() as usize,
+            expected.as_ptr() as usize,
+            expected.len(),
+            new_path.as_ptr() as usize,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_renameat2(
+    old_dirfd: usize,
+    old_path: &str,
+    new_dirfd: usize,
+    new_path: &str,
+    flags: usize,
+) -> isize {
+    syscall6(
+        SYSCALL_RENAMEAT2,
+        [
+            old_dirfd,
+            old_path.as_ptr() as usize,
+            new_dirfd,
+            new_path.as_ptr() as usize,
+            flags,
+            0,
+        ],
+    )
+}
+
+pub fn sys_set_dir_quota(path: &str, max_blocks: usize) -> isize {
+    syscall(
+        SYSCALL_SET_DIR_QUOTA,
+        [path.as_ptr() as usize, max_blocks, 0],
+    )
+}
+
+pub fn sys_get_dir_quota(path: &str, quota: &mut DirQuota) -> isize {
+    syscall(
+        SYSCALL_GET_DIR_QUOTA,
+        [path.as_ptr() as usize, quota as *mut DirQuota as usize, 0],
+    )
+}
+
+pub fn sys_swap_dir(dir_a: &str, dir_b: &str) -> isize {
+    syscall(
+        SYSCALL_SWAP_DIR,
+        [dir_a.as_ptr() as usize, dir_b.as_ptr() as usize, 0],
+    )
+}
+
+pub fn sys_symlinkat(target: &str, dirfd: usize, path: &str) -> isize {
+    syscall(
+        SYSCALL_SYMLINKAT,
+        [target.as_ptr() as usize, dirfd, path.as_ptr() as usize],
+    )
+}
+
 pub fn sys_unlinkat(dirfd: usize, path: &str, flags: usize) -> isize {
     syscall(SYSCALL_UNLINKAT, [dirfd, path.as_ptr() as usize, flags])
 }
 
+pub fn sys_mount(source: &str, target: &str) -> isize {
+    syscall(
+        SYSCALL_MOUNT,
+        [source.as_ptr() as usize, target.as_ptr() as usize, 0],
+    )
+}
+
+pub fn sys_umount(target: &str) -> isize {
+    syscall(SYSCALL_UMOUNT, [target.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_getcwd(buffer: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_GETCWD,
+        [buffer.as_mut_ptr() as usize, buffer.len(), 0],
+    )
+}
+
+pub fn sys_chdir(path: &str) -> isize {
+    syscall(SYSCALL_CHDIR, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_chroot(path: &str) -> isize {
+    syscall(SYSCALL_CHROOT, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_faccessat(dirfd: usize, path: &str, mode: usize) -> isize {
+    syscall(SYSCALL_FACCESSAT, [dirfd, path.as_ptr() as usize, mode])
+}
+
+pub fn sys_fchmodat(dirfd: usize, path: &str, mode: usize) -> isize {
+    syscall(SYSCALL_FCHMODAT, [dirfd, path.as_ptr() as usize, mode])
+}
+
 pub fn sys_fstat(fd: usize, st: &Stat) -> isize {
     syscall(SYSCALL_FSTAT, [fd, st as *const _ as usize, 0])
 }
 
+pub fn sys_getdents(fd: usize, dirents: &mut [Dirent]) -> isize {
+    syscall(
+        SYSCALL_GETDENTS,
+        [fd, dirents.as_mut_ptr() as usize, size_of_val(dirents)],
+    )
+}
+
 pub fn sys_mail_read(buffer: &mut [u8]) -> isize {
     syscall(
         SYSCALL_MAIL_READ,
@@ -150,6 +356,10 @@ pub fn sys_sleep(sleep_ms: usize) -> isize {
     syscall(SYSCALL_SLEEP, [sleep_ms, 0, 0])
 }
 
+pub fn sys_nanosleep(duration_us: usize) -> isize {
+    syscall(SYSCALL_NANOSLEEP, [duration_us, 0, 0])
+}
+
 pub fn sys_yield() -> isize {
     syscall(SYSCALL_YIELD, [0, 0, 0])
 }
@@ -166,6 +376,10 @@ pub fn sys_fork() -> isize {
     syscall(SYSCALL_FORK, [0, 0, 0])
 }
 
+pub fn sys_vfork() -> isize {
+    syscall(SYSCALL_VFORK, [0, 0, 0])
+}
+
 pub fn sys_exec(path: &str, args: &[*const u8]) -> isize {
     syscall(
         SYSCALL_EXEC,
@@ -177,18 +391,94 @@ pub fn sys_waitpid(pid: isize, xstatus: *mut i32) -> isize {
     syscall(SYSCALL_WAITPID, [pid as usize, xstatus as usize, 0])
 }
 
+pub fn sys_wait_stats(pid: isize, xstatus: *mut i32, rusage: &mut Rusage) -> isize {
+    syscall(
+        SYSCALL_WAIT_STATS,
+        [pid as usize, xstatus as usize, rusage as *mut _ as usize],
+    )
+}
+
 pub fn sys_set_priority(prio: isize) -> isize {
     syscall(SYSCALL_SET_PRIORITY, [prio as usize, 0, 0])
 }
 
-pub fn sys_mmap(start: usize, len: usize, prot: usize) -> isize {
-    syscall(SYSCALL_MMAP, [start, len, prot])
+pub fn sys_set_deadline_sched(period_ms: usize, runtime_ms: usize) -> isize {
+    syscall(SYSCALL_SET_DEADLINE_SCHED, [period_ms, runtime_ms, 0])
+}
+
+pub fn sys_set_timeslice(ticks: usize) -> isize {
+    syscall(SYSCALL_SET_TIMESLICE, [ticks, 0, 0])
+}
+
+pub fn sys_mmap(
+    start: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    syscall6(SYSCALL_MMAP, [start, len, prot, flags, fd, offset])
 }
 
 pub fn sys_munmap(start: usize, len: usize) -> isize {
     syscall(SYSCALL_MUNMAP, [start, len, 0])
 }
 
+pub fn sys_sbrk(increment: isize) -> isize {
+    syscall(SYSCALL_SBRK, [increment as usize, 0, 0])
+}
+
+pub fn sys_shmget(key: usize, size: usize) -> isize {
+    syscall(SYSCALL_SHMGET, [key, size, 0])
+}
+
+pub fn sys_shmat(id: usize) -> isize {
+    syscall(SYSCALL_SHMAT, [id, 0, 0])
+}
+
+pub fn sys_shmdt(addr: usize) -> isize {
+    syscall(SYSCALL_SHMDT, [addr, 0, 0])
+}
+
+pub fn sys_checkpoint(path: &str) -> isize {
+    syscall(SYSCALL_CHECKPOINT, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_restore(path: &str) -> isize {
+    syscall(SYSCALL_RESTORE, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_set_mempolicy(start: usize, len: usize, node: usize) -> isize {
+    syscall(SYSCALL_SET_MEMPOLICY, [start, len, node])
+}
+
+pub fn sys_get_mempolicy(addr: usize) -> isize {
+    syscall(SYSCALL_GET_MEMPOLICY, [addr, 0, 0])
+}
+
+pub fn sys_set_reclaim_watermark(free_frames: usize) -> isize {
+    syscall(SYSCALL_SET_RECLAIM_WATERMARK, [free_frames, 0, 0])
+}
+
+pub fn sys_get_reclaim_stats(stats: &mut ReclaimStats) -> isize {
+    syscall(
+        SYSCALL_GET_RECLAIM_STATS,
+        [stats as *mut ReclaimStats as usize, 0, 0],
+    )
+}
+
+pub fn sys_frame_stats(stats: &mut FrameStats) -> isize {
+    syscall(
+        SYSCALL_FRAME_STATS,
+        [stats as *mut FrameStats as usize, 0, 0],
+    )
+}
+
+pub fn sys_get_rss() -> isize {
+    syscall(SYSCALL_GET_RSS, [0, 0, 0])
+}
+
 pub fn sys_spawn(path: &str) -> isize {
     syscall(SYSCALL_SPAWN, [path.as_ptr() as usize, 0, 0])
 }
@@ -197,14 +487,185 @@ pub fn sys_dup(fd: usize) -> isize {
     syscall(SYSCALL_DUP, [fd, 0, 0])
 }
 
+pub fn sys_dup2(old_fd: usize, new_fd: usize) -> isize {
+    syscall(SYSCALL_DUP2, [old_fd, new_fd, 0])
+}
+
 pub fn sys_pipe(pipe: &mut [usize]) -> isize {
     syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
 }
 
+pub fn sys_eventfd(initval: u32, flags: u32) -> isize {
+    syscall(SYSCALL_EVENTFD, [initval as usize, flags as usize, 0])
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    syscall(SYSCALL_LSEEK, [fd, offset as usize, whence])
+}
+
+pub fn sys_snapshot_file(fd: usize) -> isize {
+    syscall(SYSCALL_SNAPSHOT_FILE, [fd, 0, 0])
+}
+
+pub fn sys_lseek_swap(fd: usize, offset: usize) -> isize {
+    syscall(SYSCALL_LSEEK_SWAP, [fd, offset, 0])
+}
+
+pub fn sys_extend_and_map(fd: usize, len: usize) -> isize {
+    syscall(SYSCALL_EXTEND_AND_MAP, [fd, len, 0])
+}
+
+pub fn sys_fsync(fd: usize) -> isize {
+    syscall(SYSCALL_FSYNC, [fd, 0, 0])
+}
+
+pub fn sys_msync(start: usize, len: usize, flags: usize) -> isize {
+    syscall(SYSCALL_MSYNC, [start, len, flags])
+}
+
+pub fn sys_ftruncate(fd: usize, len: usize) -> isize {
+    syscall(SYSCALL_FTRUNCATE, [fd, len, 0])
+}
+
+pub fn sys_fallocate(fd: usize, mode: usize, offset: usize, len: usize) -> isize {
+    syscall6(SYSCALL_FALLOCATE, [fd, mode, offset, len, 0, 0])
+}
+
+pub fn sys_seal_size(fd: usize) -> isize {
+    syscall(SYSCALL_SEAL_SIZE, [fd, 0, 0])
+}
+
+pub fn sys_setquota(fd: usize, bytes: usize) -> isize {
+    syscall(SYSCALL_SETQUOTA, [fd, bytes, 0])
+}
+
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall(SYSCALL_FCNTL, [fd, cmd, arg])
+}
+
 pub fn sys_task_info(info: &TaskInfo) -> isize {
     syscall(SYSCALL_TASK_INFO, [info as *const _ as usize, 0, 0])
 }
 
+pub fn sys_reset_syscall_stats() -> isize {
+    syscall(SYSCALL_RESET_SYSCALL_STATS, [0; 3])
+}
+
+pub fn sys_map_vdso() -> isize {
+    syscall(SYSCALL_MAP_VDSO, [0, 0, 0])
+}
+
+pub fn sys_ps(infos: &mut [ProcInfo]) -> isize {
+    syscall(SYSCALL_PS, [infos.as_mut_ptr() as usize, infos.len(), 0])
+}
+
+pub fn sys_process_vm_readv(pid: usize, local: &[IoVec], remote: &[IoVec], flags: usize) -> isize {
+    syscall6(
+        SYSCALL_PROCESS_VM_READV,
+        [
+            pid,
+            local.as_ptr() as usize,
+            local.len(),
+            remote.as_ptr() as usize,
+            remote.len(),
+            flags,
+        ],
+    )
+}
+
+pub fn sys_switch_history(pid: usize, records: &mut [SwitchRecord]) -> isize {
+    syscall(
+        SYSCALL_SWITCH_HISTORY,
+        [pid, records.as_mut_ptr() as usize, records.len()],
+    )
+}
+
+pub fn sys_fs_info(info: &FsInfo) -> isize {
+    syscall(SYSCALL_FS_INFO, [info as *const _ as usize, 0, 0])
+}
+
+pub fn sys_statfs(buf: &mut StatFs) -> isize {
+    syscall(SYSCALL_STATFS, [buf as *mut _ as usize, 0, 0])
+}
+
+pub fn sys_sendfile(out_fd: usize, in_fd: usize, count: usize) -> isize {
+    syscall(SYSCALL_SENDFILE, [out_fd, in_fd, count])
+}
+
+pub fn sys_lock_stats(stats: &mut LockStats) -> isize {
+    syscall(SYSCALL_LOCK_STATS, [stats as *mut _ as usize, 0, 0])
+}
+
+pub fn sys_fs_defrag() -> isize {
+    syscall(SYSCALL_FS_DEFRAG, [0, 0, 0])
+}
+
+pub fn sys_defrag(fd: usize) -> isize {
+    syscall(SYSCALL_DEFRAG, [fd, 0, 0])
+}
+
+pub fn sys_rename(old_path: &str, new_path: &str) -> isize {
+    syscall(
+        SYSCALL_RENAME,
+        [old_path.as_ptr() as usize, new_path.as_ptr() as usize, 0],
+    )
+}
+
+pub fn sys_fs_checkcycles(remove: usize) -> isize {
+    syscall(SYSCALL_FS_CHECKCYCLES, [remove, 0, 0])
+}
+
+pub fn sys_pin_inode(fd: usize) -> isize {
+    syscall(SYSCALL_PIN_INODE, [fd, 0, 0])
+}
+
+pub fn sys_unpin_inode(fd: usize) -> isize {
+    syscall(SYSCALL_UNPIN_INODE, [fd, 0, 0])
+}
+
+pub fn sys_write_would_block(fd: usize) -> isize {
+    syscall(SYSCALL_WRITE_WOULD_BLOCK, [fd, 0, 0])
+}
+
+pub fn sys_poll(fds: &mut [PollFd], timeout_us: isize) -> isize {
+    syscall(
+        SYSCALL_POLL,
+        [fds.as_mut_ptr() as usize, fds.len(), timeout_us as usize],
+    )
+}
+
+pub fn sys_wait_size(fd: usize, min_size: usize, timeout_ms: usize) -> isize {
+    syscall(SYSCALL_WAIT_SIZE, [fd, min_size, timeout_ms])
+}
+
+pub fn sys_drain(fd: usize, buffer: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_DRAIN,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len()],
+    )
+}
+
+pub fn sys_fd_path(fd: usize, buffer: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_FD_PATH,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len()],
+    )
+}
+
+pub fn sys_create_with_data(path: &str, data: &[u8], mode: u32) -> isize {
+    syscall6(
+        SYSCALL_CREATE_WITH_DATA,
+        [
+            path.as_ptr() as usize,
+            data.as_ptr() as usize,
+            data.len(),
+            mode as usize,
+            0,
+            0,
+        ],
+    )
+}
+
 pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
     syscall(SYSCALL_THREAD_CREATE, [entry, arg, 0])
 }
@@ -213,6 +674,18 @@ pub fn sys_gettid() -> isize {
     syscall(SYSCALL_GETTID, [0; 3])
 }
 
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    syscall(SYSCALL_SETPGID, [pid, pgid, 0])
+}
+
+pub fn sys_getpgid(pid: usize) -> isize {
+    syscall(SYSCALL_GETPGID, [pid, 0, 0])
+}
+
+pub fn sys_killpg(pgid: usize, signal: usize) -> isize {
+    syscall(SYSCALL_KILLPG, [pgid, signal, 0])
+}
+
 pub fn sys_waittid(tid: usize) -> isize {
     syscall(SYSCALL_WAITTID, [tid, 0, 0])
 }
@@ -256,3 +729,11 @@ pub fn sys_condvar_signal(condvar_id: usize) -> isize {
 pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
     syscall(SYSCALL_CONDVAR_WAIT, [condvar_id, mutex_id, 0])
 }
+
+pub fn sys_futex_wait(addr: *const u32, expected: u32) -> isize {
+    syscall(SYSCALL_FUTEX_WAIT, [addr as usize, expected as usize, 0])
+}
+
+pub fn sys_futex_wake(addr: *const u32, n: usize) -> isize {
+    syscall(SYSCALL_FUTEX_WAKE, [addr as usize, n, 0])
+}